@@ -0,0 +1,941 @@
+// Alarm protocol machine, sends alarms over the acyclic real-time channel (RTA)
+
+use byteorder::{ByteOrder, NetworkEndian};
+use smoltcp::wire::EthernetAddress;
+
+use crate::cmdev::Arep;
+use crate::cmrpc::{AlarmCrRequest, CmRpc, DEVICE_ALARM_REFERENCE, MAX_ALARM_DATA_LENGTH};
+use crate::constants::{MAX_FRAME_LENGTH, MIN_FRAME_LENGTH};
+use crate::ethernet::EthType;
+use crate::field::{Field, Rest, SmallField};
+use crate::fspm::app::{AlarmArgument, AlarmSpecifier, App};
+use crate::scheduler::{TaskCallback, TaskKind};
+use crate::time::Ticks;
+use crate::PNet;
+
+pub const FRAME_ID_ALARM_HIGH: u16 = 0xfc01;
+pub const FRAME_ID_ALARM_LOW: u16 = 0xfe01;
+
+pub const BLOCK_TYPE_ALARM_NOTIFICATION_HIGH: u16 = 0x0001;
+pub const BLOCK_TYPE_ALARM_NOTIFICATION_LOW: u16 = 0x0002;
+
+/// PDU type DATA in the low nibble, RTA version 1 in the high nibble
+pub const PDU_TYPE_DATA: u8 = 0x11;
+pub const PDU_TYPE_ACK: u8 = 0x13;
+/// Window size 1, the receiver has to acknowledge every DATA PDU
+pub const ADD_FLAGS_TACK: u8 = 0x11;
+
+/// The RTA timeout factor counts in units of 100 ms
+pub const RTA_TIMEOUT_BASE_US: usize = 100_000;
+
+/// Alarm type of a process alarm
+pub const ALARM_TYPE_PROCESS: u16 = 0x0002;
+
+/// Alarms per priority that wait for the outstanding alarm to be acknowledged.
+/// Each entry holds the full alarm data, so the queue is kept short.
+pub const ALARM_QUEUE_LENGTH: usize = 2;
+
+/// The sequence number in the alarm specifier is 11 bits wide
+const SEQUENCE_NUMBER_MASK: u16 = 0x07ff;
+const CHANNEL_DIAGNOSIS_BIT: u16 = 1 << 11;
+const MANUFACTURER_DIAGNOSIS_BIT: u16 = 1 << 12;
+const SUBMODULE_DIAGNOSIS_BIT: u16 = 1 << 13;
+const AR_DIAGNOSIS_BIT: u16 = 1 << 15;
+
+const DESTINATION_FIELD: Field = 0..6;
+const SOURCE_FIELD: Field = 6..12;
+const VLAN_TYPE_FIELD: Field = 12..14;
+const VLAN_TCI_FIELD: Field = 14..16;
+const TYPE_FIELD: Field = 16..18;
+const FRAME_ID_FIELD: Field = 18..20;
+const RTA_PDU_FIELD: Rest = 20..;
+
+// RTA header, relative to the end of the frame id
+const ALARM_DST_ENDPOINT_FIELD: Field = 0..2;
+const ALARM_SRC_ENDPOINT_FIELD: Field = 2..4;
+const PDU_TYPE_FIELD: SmallField = 4;
+const ADD_FLAGS_FIELD: SmallField = 5;
+const SEND_SEQ_NUM_FIELD: Field = 6..8;
+const ACK_SEQ_NUM_FIELD: Field = 8..10;
+const VAR_PART_LEN_FIELD: Field = 10..12;
+const VAR_PART_FIELD: Rest = 12..;
+
+// Alarm notification content, relative to the end of the block header
+const ALARM_TYPE_FIELD: Field = 0..2;
+const API_FIELD: Field = 2..6;
+const SLOT_FIELD: Field = 6..8;
+const SUBSLOT_FIELD: Field = 8..10;
+const MODULE_IDENT_FIELD: Field = 10..14;
+const SUBMODULE_IDENT_FIELD: Field = 14..18;
+const ALARM_SPECIFIER_FIELD: Field = 18..20;
+const USI_FIELD: Field = 20..22;
+const ALARM_DATA_FIELD: Rest = 22..;
+const BLOCK_HEADER_LENGTH: usize = 6;
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AlarmError {
+    NotConnected,
+    DataTooLong,
+    /// The queue for alarms waiting on an acknowledgement is full
+    QueueFull,
+    TooShort,
+    InvalidPduType,
+}
+
+/// The acknowledgement part of a received RTA PDU
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AlarmAck {
+    pub alarm_dst_endpoint: u16,
+    pub alarm_src_endpoint: u16,
+    pub pdu_type: u8,
+    /// Sequence number of the last DATA PDU the controller received
+    pub ack_seq_num: u16,
+}
+
+/// Parses the RTA header following the frame id of an alarm frame.
+///
+/// Both ACK PDUs and DATA PDUs acknowledge our alarms through their
+/// `ack_seq_num`.
+pub fn parse_ack(buffer: &[u8]) -> Result<AlarmAck, AlarmError> {
+    if buffer.len() < VAR_PART_FIELD.start {
+        return Err(AlarmError::TooShort);
+    }
+
+    let pdu_type = buffer[PDU_TYPE_FIELD];
+    if pdu_type != PDU_TYPE_ACK && pdu_type != PDU_TYPE_DATA {
+        return Err(AlarmError::InvalidPduType);
+    }
+
+    Ok(AlarmAck {
+        alarm_dst_endpoint: NetworkEndian::read_u16(&buffer[ALARM_DST_ENDPOINT_FIELD]),
+        alarm_src_endpoint: NetworkEndian::read_u16(&buffer[ALARM_SRC_ENDPOINT_FIELD]),
+        pdu_type,
+        ack_seq_num: NetworkEndian::read_u16(&buffer[ACK_SEQ_NUM_FIELD]),
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AlarmPriority {
+    High = 0,
+    Low = 1,
+}
+
+impl AlarmPriority {
+    fn frame_id(self) -> u16 {
+        match self {
+            Self::High => FRAME_ID_ALARM_HIGH,
+            Self::Low => FRAME_ID_ALARM_LOW,
+        }
+    }
+
+    fn block_type(self) -> u16 {
+        match self {
+            Self::High => BLOCK_TYPE_ALARM_NOTIFICATION_HIGH,
+            Self::Low => BLOCK_TYPE_ALARM_NOTIFICATION_LOW,
+        }
+    }
+
+    fn vlan_priority(self) -> u16 {
+        match self {
+            Self::High => 6,
+            Self::Low => 5,
+        }
+    }
+}
+
+impl AlarmSpecifier {
+    /// Encodes the specifier bits together with the 11 bit sequence number
+    pub fn encode(&self, sequence_number: u16) -> u16 {
+        let mut specifier = sequence_number & SEQUENCE_NUMBER_MASK;
+
+        if self.channel_diagnosis {
+            specifier |= CHANNEL_DIAGNOSIS_BIT;
+        }
+        if self.manufacturer_diagnosis {
+            specifier |= MANUFACTURER_DIAGNOSIS_BIT;
+        }
+        if self.submodule_diagnosis {
+            specifier |= SUBMODULE_DIAGNOSIS_BIT;
+        }
+        if self.ar_diagnosis {
+            specifier |= AR_DIAGNOSIS_BIT;
+        }
+
+        specifier
+    }
+}
+
+/// An alarm that was sent and waits for its acknowledgement
+#[derive(Clone, Copy)]
+struct PendingAlarm {
+    frame: [u8; MAX_FRAME_LENGTH],
+    length: usize,
+    send_seq_num: u16,
+    retries: u16,
+}
+
+/// The RTA state of one alarm priority
+#[derive(Clone, Copy)]
+struct AlarmChannel {
+    send_seq_num: u16,
+    sequence_number: u16,
+    pending: Option<PendingAlarm>,
+}
+
+impl AlarmChannel {
+    const fn new() -> Self {
+        Self {
+            // The first DATA PDU carries sequence number 0
+            send_seq_num: 0xffff,
+            sequence_number: 0,
+            pending: None,
+        }
+    }
+}
+
+/// An alarm that waits until the outstanding alarm of its priority is acknowledged
+#[derive(Clone, Copy)]
+pub struct QueuedAlarm {
+    pub argument: AlarmArgument,
+    pub usi: u16,
+    data: [u8; MAX_ALARM_DATA_LENGTH as usize],
+    data_length: usize,
+}
+
+impl QueuedAlarm {
+    pub fn data(&self) -> &[u8] {
+        &self.data[..self.data_length]
+    }
+}
+
+/// The alarms waiting to be sent, in separate queues for the high and the
+/// low priority
+pub struct AlarmQueue {
+    alarms: [[Option<QueuedAlarm>; ALARM_QUEUE_LENGTH]; 2],
+}
+
+impl Default for AlarmQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AlarmQueue {
+    pub const fn new() -> Self {
+        Self {
+            alarms: [[None; ALARM_QUEUE_LENGTH]; 2],
+        }
+    }
+
+    /// Appends an alarm to the queue of `priority`
+    pub fn push(
+        &mut self,
+        priority: AlarmPriority,
+        argument: AlarmArgument,
+        usi: u16,
+        data: &[u8],
+    ) -> Result<(), AlarmError> {
+        let mut alarm = QueuedAlarm {
+            argument,
+            usi,
+            data: [0; MAX_ALARM_DATA_LENGTH as usize],
+            data_length: data.len(),
+        };
+        alarm
+            .data
+            .get_mut(..data.len())
+            .ok_or(AlarmError::DataTooLong)?
+            .copy_from_slice(data);
+
+        let slot = self.alarms[priority as usize]
+            .iter_mut()
+            .find(|slot| slot.is_none())
+            .ok_or(AlarmError::QueueFull)?;
+        *slot = Some(alarm);
+
+        Ok(())
+    }
+
+    /// Removes the oldest alarm of `priority`
+    pub fn pop(&mut self, priority: AlarmPriority) -> Option<QueuedAlarm> {
+        let queue = &mut self.alarms[priority as usize];
+        let alarm = queue[0].take()?;
+        queue.rotate_left(1);
+
+        Some(alarm)
+    }
+
+    pub fn len(&self, priority: AlarmPriority) -> usize {
+        self.alarms[priority as usize].iter().flatten().count()
+    }
+
+    pub fn is_empty(&self, priority: AlarmPriority) -> bool {
+        self.alarms[priority as usize][0].is_none()
+    }
+}
+
+/// The alarm CR negotiated with the controller at connect
+#[derive(Clone, Copy)]
+struct AlarmCr {
+    /// The AR the channel belongs to
+    arep: Arep,
+    destination: EthernetAddress,
+    remote_alarm_reference: u16,
+    max_data_length: u16,
+    /// Time between retransmissions in scheduler ticks
+    retransmit_interval: Ticks,
+    max_retries: u16,
+}
+
+pub struct Alarm {
+    cr: Option<AlarmCr>,
+    channels: [AlarmChannel; 2],
+    queue: AlarmQueue,
+}
+
+impl Default for Alarm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Alarm {
+    pub const fn new() -> Self {
+        Self {
+            cr: None,
+            channels: [AlarmChannel::new(); 2],
+            queue: AlarmQueue::new(),
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.cr.is_some()
+    }
+
+    /// Whether an alarm of `priority` waits for its acknowledgement
+    pub fn is_pending(&self, priority: AlarmPriority) -> bool {
+        self.channels[priority as usize].pending.is_some()
+    }
+
+    /// Number of alarms of `priority` waiting to be sent
+    pub fn queued(&self, priority: AlarmPriority) -> usize {
+        self.queue.len(priority)
+    }
+
+    /// Opens the alarm channel to the controller of the AR of `arep`.
+    ///
+    /// There is one channel, an AR can't take it over while another AR has it
+    /// open. Returns whether the channel was opened.
+    pub fn start<T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &mut PNet<T, U>,
+        arep: Arep,
+        destination: EthernetAddress,
+        alarm_cr: &AlarmCrRequest,
+    ) -> bool {
+        if pnet.alarm.cr.is_some_and(|cr| cr.arep != arep) {
+            warn!("Alarm channel is in use, AR {} gets none", arep);
+            return false;
+        }
+
+        let retransmit_interval = Ticks::from_micros(
+            alarm_cr.rta_timeout_factor as usize * RTA_TIMEOUT_BASE_US,
+            pnet.fspm_user_config.tick_us,
+        );
+
+        Self::stop(pnet, arep);
+        pnet.alarm.cr = Some(AlarmCr {
+            arep,
+            destination,
+            remote_alarm_reference: alarm_cr.local_alarm_reference,
+            max_data_length: alarm_cr.max_alarm_data_length.min(MAX_ALARM_DATA_LENGTH),
+            retransmit_interval: retransmit_interval.max(Ticks(1)),
+            max_retries: alarm_cr.rta_retries,
+        });
+
+        true
+    }
+
+    /// Closes the alarm channel if the AR of `arep` has it open, pending
+    /// retransmissions are cancelled and queued alarms are discarded
+    pub fn stop<T: App + Copy, U: TaskCallback + Copy>(pnet: &mut PNet<T, U>, arep: Arep) {
+        if pnet.alarm.cr.is_some_and(|cr| cr.arep != arep) {
+            return;
+        }
+
+        pnet.alarm = Self::new();
+        pnet.scheduler
+            .cancel(|task| matches!(task, TaskKind::AlarmRetransmit(..)));
+    }
+
+    /// Confirms the pending alarm of `priority` that `ack` acknowledges and
+    /// sends the next queued alarm of that priority.
+    ///
+    /// Acknowledgements for any other sequence number are ignored, so a late
+    /// or duplicated ACK cannot confirm an unrelated alarm.
+    pub(crate) fn handle_ack<T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &mut PNet<T, U>,
+        priority: AlarmPriority,
+        ack: AlarmAck,
+        current_time: Ticks,
+    ) {
+        let Some(cr) = pnet.alarm.cr else {
+            return;
+        };
+
+        if ack.alarm_dst_endpoint != DEVICE_ALARM_REFERENCE
+            || ack.alarm_src_endpoint != cr.remote_alarm_reference
+        {
+            return;
+        }
+
+        let channel = &mut pnet.alarm.channels[priority as usize];
+        if channel.pending.as_ref().map(|p| p.send_seq_num) != Some(ack.ack_seq_num) {
+            debug!("Ignoring alarm ACK for sequence {}", ack.ack_seq_num);
+            return;
+        }
+
+        // The retransmission task finds nothing pending and stops
+        channel.pending = None;
+
+        let mut app = pnet.fspm_user_config.app;
+        app.alarm_ack_cnf_callback(pnet, 0, ack.ack_seq_num as usize);
+        pnet.fspm_user_config.app = app;
+
+        Self::send_next(pnet, priority, current_time);
+    }
+
+    /// Sends the oldest queued alarm of `priority`, if any
+    fn send_next<T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &mut PNet<T, U>,
+        priority: AlarmPriority,
+        current_time: Ticks,
+    ) {
+        let Some(alarm) = pnet.alarm.queue.pop(priority) else {
+            return;
+        };
+
+        if let Err(e) = send_alarm(
+            pnet,
+            priority,
+            alarm.argument,
+            alarm.usi,
+            alarm.data(),
+            current_time,
+        ) {
+            error!("Failed to send queued alarm: {}", e);
+        }
+    }
+
+    /// Scheduler callback, resends an unacknowledged alarm until the retries run out
+    pub(crate) fn handle_retransmit<T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &mut PNet<T, U>,
+        priority: AlarmPriority,
+        send_seq_num: u16,
+        current_time: Ticks,
+    ) {
+        let Some(cr) = pnet.alarm.cr else {
+            return;
+        };
+
+        let channel = &mut pnet.alarm.channels[priority as usize];
+        let Some(pending) = &mut channel.pending else {
+            return;
+        };
+
+        // The alarm this task was armed for was acknowledged already
+        if pending.send_seq_num != send_seq_num {
+            return;
+        }
+
+        if pending.retries >= cr.max_retries {
+            error!("Alarm {} was not acknowledged", send_seq_num);
+            channel.pending = None;
+            Self::send_next(pnet, priority, current_time);
+            return;
+        }
+
+        pending.retries += 1;
+        let pending = *pending;
+
+        if let Err(e) = pnet.queue_packet(&pending.frame[..pending.length], current_time) {
+            error!("Failed to queue alarm retransmission: {}", e);
+        }
+
+        pnet.scheduler.add_task(
+            "alarm_retransmit",
+            cr.retransmit_interval,
+            TaskKind::AlarmRetransmit(priority, send_seq_num),
+            current_time,
+        );
+    }
+}
+
+/// Sends a high priority process alarm with `data` as its alarm item.
+///
+/// The alarm is retransmitted until the controller acknowledges it. The
+/// sequence number in `argument` is ignored, the alarm channel assigns its own.
+pub fn send_process_alarm<T: App + Copy, U: TaskCallback + Copy>(
+    pnet: &mut PNet<T, U>,
+    argument: AlarmArgument,
+    usi: u16,
+    data: &[u8],
+    current_time: Ticks,
+) -> Result<(), AlarmError> {
+    send_alarm(pnet, AlarmPriority::High, argument, usi, data, current_time)
+}
+
+/// Sends an alarm of `priority` and arms its retransmission timer.
+///
+/// Only one alarm per priority may wait for its acknowledgement. While one
+/// does, the new alarm is queued and sent once the outstanding one is
+/// acknowledged or runs out of retries.
+pub fn send_alarm<T: App + Copy, U: TaskCallback + Copy>(
+    pnet: &mut PNet<T, U>,
+    priority: AlarmPriority,
+    argument: AlarmArgument,
+    usi: u16,
+    data: &[u8],
+    current_time: Ticks,
+) -> Result<(), AlarmError> {
+    let cr = pnet.alarm.cr.ok_or(AlarmError::NotConnected)?;
+
+    // The USI is part of the alarm data
+    if data.len() + USI_FIELD.len() > cr.max_data_length as usize {
+        return Err(AlarmError::DataTooLong);
+    }
+
+    let channel = &pnet.alarm.channels[priority as usize];
+    if channel.pending.is_some() {
+        return pnet.alarm.queue.push(priority, argument, usi, data);
+    }
+
+    let send_seq_num = channel.send_seq_num.wrapping_add(1) & 0x7fff;
+    let sequence_number = channel.sequence_number;
+
+    let (module_ident, submodule_ident) = pnet
+        .fspm_user_config
+        .submodules
+        .submodule(
+            argument.api_id as u32,
+            argument.slot_number as u16,
+            argument.subslot_number as u16,
+        )
+        .map(|s| (s.module_ident, s.submodule_ident))
+        .unwrap_or((0, 0));
+
+    let notification = AlarmNotification {
+        priority,
+        alarm_type: argument.alarm_type as u16,
+        api: argument.api_id as u32,
+        slot_number: argument.slot_number as u16,
+        subslot_number: argument.subslot_number as u16,
+        module_ident,
+        submodule_ident,
+        alarm_specifier: argument.alarm_specifier.encode(sequence_number),
+        usi,
+    };
+
+    let mut frame = [0; MAX_FRAME_LENGTH];
+    let length = notification.build_frame(
+        cr.destination,
+        pnet.fspm_user_config.interface_config.ip_config.mac_address,
+        cr.remote_alarm_reference,
+        send_seq_num,
+        data,
+        &mut frame,
+    );
+
+    if let Err(e) = pnet.queue_packet(&frame[..length], current_time) {
+        error!("Failed to queue alarm: {}", e);
+    }
+
+    let channel = &mut pnet.alarm.channels[priority as usize];
+    channel.send_seq_num = send_seq_num;
+    channel.sequence_number = (sequence_number + 1) & SEQUENCE_NUMBER_MASK;
+    channel.pending = Some(PendingAlarm {
+        frame,
+        length,
+        send_seq_num,
+        retries: 0,
+    });
+
+    pnet.scheduler.add_task(
+        "alarm_retransmit",
+        cr.retransmit_interval,
+        TaskKind::AlarmRetransmit(priority, send_seq_num),
+        current_time,
+    );
+
+    Ok(())
+}
+
+/// The content of an alarm notification block
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AlarmNotification {
+    pub priority: AlarmPriority,
+    pub alarm_type: u16,
+    pub api: u32,
+    pub slot_number: u16,
+    pub subslot_number: u16,
+    pub module_ident: u32,
+    pub submodule_ident: u32,
+    pub alarm_specifier: u16,
+    pub usi: u16,
+}
+
+impl AlarmNotification {
+    /// Builds the RTA DATA frame carrying this notification and returns the written length
+    pub fn build_frame(
+        &self,
+        destination: EthernetAddress,
+        source: EthernetAddress,
+        remote_alarm_reference: u16,
+        send_seq_num: u16,
+        data: &[u8],
+        buffer: &mut [u8],
+    ) -> usize {
+        buffer[DESTINATION_FIELD].copy_from_slice(destination.as_bytes());
+        buffer[SOURCE_FIELD].copy_from_slice(source.as_bytes());
+        NetworkEndian::write_u16(&mut buffer[VLAN_TYPE_FIELD], EthType::Vlan as u16);
+        NetworkEndian::write_u16(
+            &mut buffer[VLAN_TCI_FIELD],
+            self.priority.vlan_priority() << 13,
+        );
+        NetworkEndian::write_u16(&mut buffer[TYPE_FIELD], EthType::Profinet as u16);
+        NetworkEndian::write_u16(&mut buffer[FRAME_ID_FIELD], self.priority.frame_id());
+
+        let pdu = &mut buffer[RTA_PDU_FIELD];
+        NetworkEndian::write_u16(&mut pdu[ALARM_DST_ENDPOINT_FIELD], remote_alarm_reference);
+        NetworkEndian::write_u16(&mut pdu[ALARM_SRC_ENDPOINT_FIELD], DEVICE_ALARM_REFERENCE);
+        pdu[PDU_TYPE_FIELD] = PDU_TYPE_DATA;
+        pdu[ADD_FLAGS_FIELD] = ADD_FLAGS_TACK;
+        NetworkEndian::write_u16(&mut pdu[SEND_SEQ_NUM_FIELD], send_seq_num);
+        // Nothing was received from the controller that needs acknowledging
+        NetworkEndian::write_u16(&mut pdu[ACK_SEQ_NUM_FIELD], 0xfffe);
+
+        let var_part = &mut pdu[VAR_PART_FIELD];
+        let content_length = ALARM_DATA_FIELD.start + data.len();
+        let header_length =
+            CmRpc::encode_block_header(var_part, self.priority.block_type(), content_length);
+
+        let content = &mut var_part[header_length..header_length + content_length];
+        NetworkEndian::write_u16(&mut content[ALARM_TYPE_FIELD], self.alarm_type);
+        NetworkEndian::write_u32(&mut content[API_FIELD], self.api);
+        NetworkEndian::write_u16(&mut content[SLOT_FIELD], self.slot_number);
+        NetworkEndian::write_u16(&mut content[SUBSLOT_FIELD], self.subslot_number);
+        NetworkEndian::write_u32(&mut content[MODULE_IDENT_FIELD], self.module_ident);
+        NetworkEndian::write_u32(&mut content[SUBMODULE_IDENT_FIELD], self.submodule_ident);
+        NetworkEndian::write_u16(&mut content[ALARM_SPECIFIER_FIELD], self.alarm_specifier);
+        NetworkEndian::write_u16(&mut content[USI_FIELD], self.usi);
+        content[ALARM_DATA_FIELD].copy_from_slice(data);
+
+        let var_part_length = BLOCK_HEADER_LENGTH + content_length;
+        NetworkEndian::write_u16(&mut pdu[VAR_PART_LEN_FIELD], var_part_length as u16);
+
+        let length = RTA_PDU_FIELD.start + VAR_PART_FIELD.start + var_part_length;
+        if length < MIN_FRAME_LENGTH {
+            buffer[length..MIN_FRAME_LENGTH].fill(0);
+        }
+
+        length.max(MIN_FRAME_LENGTH)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::{test_config, test_connect_request, TestTask};
+
+    use super::*;
+
+    const CONTROLLER: EthernetAddress = EthernetAddress([0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5]);
+
+    const ALARM_CR: AlarmCrRequest = AlarmCrRequest {
+        alarm_cr_type: 1,
+        rta_timeout_factor: 1,
+        rta_retries: 3,
+        local_alarm_reference: 3,
+        max_alarm_data_length: 200,
+    };
+
+    fn process_alarm() -> AlarmArgument {
+        AlarmArgument {
+            api_id: 0,
+            slot_number: 1,
+            subslot_number: 1,
+            alarm_type: ALARM_TYPE_PROCESS as usize,
+            sequence_number: 0,
+            alarm_specifier: AlarmSpecifier {
+                channel_diagnosis: false,
+                manufacturer_diagnosis: false,
+                submodule_diagnosis: false,
+                ar_diagnosis: true,
+            },
+        }
+    }
+
+    fn sent_alarms<T: App + Copy>(pnet: &PNet<T, TestTask>) -> usize {
+        pnet.outgoing_packets
+            .iter()
+            .flatten()
+            .filter(|p| p.frame()[FRAME_ID_FIELD] == FRAME_ID_ALARM_HIGH.to_be_bytes())
+            .count()
+    }
+
+    fn ack_frame(ack_seq_num: u16) -> [u8; 60] {
+        let mut buffer = [0; 60];
+        buffer[DESTINATION_FIELD].copy_from_slice(&[0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe]);
+        buffer[SOURCE_FIELD].copy_from_slice(CONTROLLER.as_bytes());
+        NetworkEndian::write_u16(&mut buffer[12..14], EthType::Profinet as u16);
+        NetworkEndian::write_u16(&mut buffer[14..16], FRAME_ID_ALARM_HIGH);
+
+        let pdu = &mut buffer[16..];
+        NetworkEndian::write_u16(&mut pdu[ALARM_DST_ENDPOINT_FIELD], DEVICE_ALARM_REFERENCE);
+        NetworkEndian::write_u16(&mut pdu[ALARM_SRC_ENDPOINT_FIELD], 3);
+        pdu[PDU_TYPE_FIELD] = PDU_TYPE_ACK;
+        pdu[ADD_FLAGS_FIELD] = 0x11;
+        NetworkEndian::write_u16(&mut pdu[SEND_SEQ_NUM_FIELD], 0xffff);
+        NetworkEndian::write_u16(&mut pdu[ACK_SEQ_NUM_FIELD], ack_seq_num);
+
+        buffer
+    }
+
+    #[test]
+    fn test_parse_ack() {
+        let frame = ack_frame(5);
+        let ack = parse_ack(&frame[16..]).unwrap();
+
+        assert_eq!(ack.alarm_dst_endpoint, DEVICE_ALARM_REFERENCE);
+        assert_eq!(ack.alarm_src_endpoint, 3);
+        assert_eq!(ack.pdu_type, PDU_TYPE_ACK);
+        assert_eq!(ack.ack_seq_num, 5);
+
+        assert_eq!(parse_ack(&frame[16..24]), Err(AlarmError::TooShort));
+
+        let mut frame = ack_frame(5);
+        frame[16 + PDU_TYPE_FIELD] = 0x14;
+        assert_eq!(parse_ack(&frame[16..]), Err(AlarmError::InvalidPduType));
+    }
+
+    #[test]
+    fn test_connect_opens_alarm_channel() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        let mut buffer = [0; 1024];
+        let (arep, _) = pnet.connect(&test_connect_request(), &mut buffer).unwrap();
+
+        send_process_alarm(&mut pnet, process_alarm(), 0x1234, &[0xaa], Ticks(0)).unwrap();
+
+        // Sent to the controller on the alarm reference of its alarm CR
+        let frame = pnet.outgoing_packets[0].unwrap();
+        let frame = frame.frame();
+        assert_eq!(frame[DESTINATION_FIELD], *CONTROLLER.as_bytes());
+        let rta = &frame[RTA_PDU_FIELD];
+        assert_eq!(NetworkEndian::read_u16(&rta[ALARM_DST_ENDPOINT_FIELD]), 3);
+        assert_eq!(
+            NetworkEndian::read_u16(&rta[ALARM_SRC_ENDPOINT_FIELD]),
+            DEVICE_ALARM_REFERENCE
+        );
+
+        // The release closes the channel and cancels the retransmission
+        pnet.release(arep).unwrap();
+        assert!(!pnet.alarm.is_connected());
+        pnet.outgoing_packets = [None; 8];
+        pnet.run_scheduled_tasks(Ticks(1000));
+        assert_eq!(sent_alarms(&pnet), 0);
+        assert_eq!(
+            send_process_alarm(&mut pnet, process_alarm(), 0x1234, &[0xaa], Ticks(1000)),
+            Err(AlarmError::NotConnected)
+        );
+    }
+
+    #[test]
+    fn test_ack_confirms_alarm() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        Alarm::start(&mut pnet, 0, CONTROLLER, &ALARM_CR);
+
+        send_process_alarm(&mut pnet, process_alarm(), 0x1234, &[0xaa], Ticks(0)).unwrap();
+        pnet.handle_frame(&ack_frame(0), Ticks(50)).unwrap();
+        assert!(!pnet.alarm.is_pending(AlarmPriority::High));
+
+        // The retransmission timer was cancelled
+        pnet.run_scheduled_tasks(Ticks(100));
+        assert_eq!(sent_alarms(&pnet), 1);
+    }
+
+    #[test]
+    fn test_ack_unknown_sequence_ignored() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        Alarm::start(&mut pnet, 0, CONTROLLER, &ALARM_CR);
+
+        send_process_alarm(&mut pnet, process_alarm(), 0x1234, &[0xaa], Ticks(0)).unwrap();
+        pnet.handle_frame(&ack_frame(7), Ticks(50)).unwrap();
+        assert!(pnet.alarm.is_pending(AlarmPriority::High));
+
+        pnet.run_scheduled_tasks(Ticks(100));
+        assert_eq!(sent_alarms(&pnet), 2);
+    }
+
+    #[test]
+    fn test_encode_specifier() {
+        let specifier = AlarmSpecifier {
+            channel_diagnosis: true,
+            manufacturer_diagnosis: false,
+            submodule_diagnosis: true,
+            ar_diagnosis: true,
+        };
+
+        assert_eq!(specifier.encode(5), 0xa805);
+        assert_eq!(specifier.encode(0x0fff), 0xafff);
+    }
+
+    #[test]
+    fn test_build_frame() {
+        let notification = AlarmNotification {
+            priority: AlarmPriority::High,
+            alarm_type: ALARM_TYPE_PROCESS,
+            api: 0,
+            slot_number: 1,
+            subslot_number: 0x8001,
+            module_ident: 0x32,
+            submodule_ident: 0x01,
+            alarm_specifier: 0x8001,
+            usi: 0x1234,
+        };
+
+        let mut buffer = [0; MAX_FRAME_LENGTH];
+        let length = notification.build_frame(
+            CONTROLLER,
+            EthernetAddress([0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe]),
+            3,
+            0,
+            &[0xaa, 0xbb],
+            &mut buffer,
+        );
+
+        assert_eq!(length, 62);
+        assert_eq!(
+            buffer[12..62],
+            [
+                0x81, 0x00, 0xc0, 0x00, 0x88, 0x92, 0xfc, 0x01, 0x00, 0x03, 0x00, 0x01, 0x11, 0x11,
+                0x00, 0x00, 0xff, 0xfe, 0x00, 0x1e, 0x00, 0x01, 0x00, 0x1a, 0x01, 0x00, 0x00, 0x02,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x80, 0x01, 0x00, 0x00, 0x00, 0x32, 0x00, 0x00,
+                0x00, 0x01, 0x80, 0x01, 0x12, 0x34, 0xaa, 0xbb
+            ]
+        );
+    }
+
+    #[test]
+    fn test_send_not_connected() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        let result = send_process_alarm(&mut pnet, process_alarm(), 0x1234, &[0xaa], Ticks(0));
+
+        assert_eq!(result, Err(AlarmError::NotConnected));
+    }
+
+    #[test]
+    fn test_send_too_long() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        Alarm::start(&mut pnet, 0, CONTROLLER, &ALARM_CR);
+
+        let result = send_process_alarm(&mut pnet, process_alarm(), 0x1234, &[0; 199], Ticks(0));
+
+        assert_eq!(result, Err(AlarmError::DataTooLong));
+    }
+
+    #[test]
+    fn test_send_and_retransmit() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        Alarm::start(&mut pnet, 0, CONTROLLER, &ALARM_CR);
+
+        send_process_alarm(&mut pnet, process_alarm(), 0x1234, &[0xaa], Ticks(0)).unwrap();
+        assert_eq!(sent_alarms(&pnet), 1);
+        assert!(pnet.alarm.is_pending(AlarmPriority::High));
+
+        // The timeout factor of 1 is 100 ms, or 100 ticks
+        pnet.run_scheduled_tasks(Ticks(99));
+        assert_eq!(sent_alarms(&pnet), 1);
+
+        pnet.run_scheduled_tasks(Ticks(100));
+        pnet.run_scheduled_tasks(Ticks(200));
+        pnet.run_scheduled_tasks(Ticks(300));
+        assert_eq!(sent_alarms(&pnet), 4);
+
+        // Out of retries, the alarm is dropped
+        pnet.run_scheduled_tasks(Ticks(400));
+        assert_eq!(sent_alarms(&pnet), 4);
+        assert!(!pnet.alarm.is_pending(AlarmPriority::High));
+    }
+
+    #[test]
+    fn test_second_alarm_waits_for_ack() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        Alarm::start(&mut pnet, 0, CONTROLLER, &ALARM_CR);
+
+        send_process_alarm(&mut pnet, process_alarm(), 0x1234, &[0xaa], Ticks(0)).unwrap();
+        send_process_alarm(&mut pnet, process_alarm(), 0x1234, &[0xbb], Ticks(0)).unwrap();
+
+        // Only one alarm per priority may be outstanding
+        assert_eq!(sent_alarms(&pnet), 1);
+        assert_eq!(pnet.alarm.queued(AlarmPriority::High), 1);
+        assert_eq!(pnet.alarm.queued(AlarmPriority::Low), 0);
+
+        // A low priority alarm doesn't wait for the high priority one
+        send_alarm(
+            &mut pnet,
+            AlarmPriority::Low,
+            process_alarm(),
+            0x1234,
+            &[0xcc],
+            Ticks(0),
+        )
+        .unwrap();
+        assert!(pnet.alarm.is_pending(AlarmPriority::Low));
+        assert_eq!(pnet.alarm.queued(AlarmPriority::Low), 0);
+
+        pnet.handle_frame(&ack_frame(0), Ticks(50)).unwrap();
+        assert_eq!(sent_alarms(&pnet), 2);
+        assert_eq!(pnet.alarm.queued(AlarmPriority::High), 0);
+        assert!(pnet.alarm.is_pending(AlarmPriority::High));
+
+        let second = pnet
+            .outgoing_packets
+            .iter()
+            .flatten()
+            .filter(|p| p.frame()[FRAME_ID_FIELD] == FRAME_ID_ALARM_HIGH.to_be_bytes())
+            .max_by_key(|p| p.sequence)
+            .unwrap();
+        assert_eq!(
+            second.frame()[RTA_PDU_FIELD.start + SEND_SEQ_NUM_FIELD.end - 1],
+            1
+        );
+
+        // The second alarm has its own retransmission timer
+        pnet.run_scheduled_tasks(Ticks(150));
+        assert_eq!(sent_alarms(&pnet), 3);
+    }
+
+    #[test]
+    fn test_alarm_queue_full() {
+        let mut queue = AlarmQueue::new();
+
+        queue
+            .push(AlarmPriority::High, process_alarm(), 1, &[0xaa])
+            .unwrap();
+        queue
+            .push(AlarmPriority::High, process_alarm(), 2, &[0xbb])
+            .unwrap();
+        assert_eq!(
+            queue.push(AlarmPriority::High, process_alarm(), 3, &[0xcc]),
+            Err(AlarmError::QueueFull)
+        );
+        assert!(queue.is_empty(AlarmPriority::Low));
+
+        let first = queue.pop(AlarmPriority::High).unwrap();
+        assert_eq!((first.usi, first.data()), (1, &[0xaa][..]));
+        let second = queue.pop(AlarmPriority::High).unwrap();
+        assert_eq!((second.usi, second.data()), (2, &[0xbb][..]));
+        assert!(queue.pop(AlarmPriority::High).is_none());
+    }
+}
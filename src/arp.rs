@@ -0,0 +1,573 @@
+// Address conflict detection using ARP probes, and replies to ARP requests
+// for the device's address
+
+use byteorder::{ByteOrder, NetworkEndian};
+use smoltcp::wire::{ArpOperation, ArpPacket, ArpRepr, EthernetAddress, Ipv4Address, Ipv4Cidr};
+
+use crate::dcp::Dcp;
+use crate::ethernet::{EthType, EthernetFrame};
+use crate::field::{Field, Rest};
+use crate::fspm::{app::App, subnet_mask_to_prefix};
+use crate::scheduler::{TaskCallback, TaskKind};
+use crate::time::Ticks;
+use crate::PNet;
+
+/// Number of probes sent before a new address is committed
+pub const PROBE_COUNT: u8 = 2;
+/// Time between two probes in microseconds
+pub const PROBE_INTERVAL_US: usize = 1_000_000;
+/// Time to wait for replies after the last probe in microseconds
+pub const ANNOUNCE_WAIT_US: usize = 2_000_000;
+
+/// An ARP frame padded to the minimum Ethernet frame length
+pub const ARP_FRAME_LENGTH: usize = 60;
+
+const DESTINATION_FIELD: Field = 0..6;
+const SOURCE_FIELD: Field = 6..12;
+const TYPE_FIELD: Field = 12..14;
+const PAYLOAD_FIELD: Rest = 14..;
+
+/// IP settings waiting for the conflict check before they are applied
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IpSuite {
+    pub ip_address: Ipv4Address,
+    pub subnet_mask: Ipv4Address,
+    pub gateway: Ipv4Address,
+    pub dns: Option<Ipv4Address>,
+}
+
+impl IpSuite {
+    /// Whether the settings can be used by a device. The unspecified address
+    /// is valid, it removes the address.
+    pub fn is_valid(&self) -> bool {
+        if self.ip_address.is_unspecified() {
+            return true;
+        }
+
+        let Ok(prefix) = subnet_mask_to_prefix(self.subnet_mask) else {
+            return false;
+        };
+        let network = Ipv4Cidr::new(self.ip_address, prefix);
+
+        self.ip_address.is_unicast()
+            && !self.ip_address.is_loopback()
+            && network.broadcast() != Some(self.ip_address)
+            && (self.gateway.is_unspecified() || network.contains_addr(&self.gateway))
+    }
+}
+
+pub struct Acd {
+    candidate: Option<IpSuite>,
+    probes_sent: u8,
+    conflict: bool,
+    /// A DCP Set committed while probing, signalled once the address is
+    /// applied or rejected. Holds whether the commit is permanent.
+    pending_commit: Option<bool>,
+}
+
+impl Default for Acd {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Acd {
+    pub const fn new() -> Self {
+        Self {
+            candidate: None,
+            probes_sent: 0,
+            conflict: false,
+            pending_commit: None,
+        }
+    }
+
+    /// Whether the last checked address was already in use
+    pub fn conflict(&self) -> bool {
+        self.conflict
+    }
+
+    pub fn is_probing(&self) -> bool {
+        self.candidate.is_some()
+    }
+
+    /// Holds back the commit of a DCP Set until the probed address is applied
+    /// or rejected
+    pub(crate) fn defer_commit(&mut self, permanent: bool) {
+        self.pending_commit = Some(self.pending_commit.unwrap_or(false) | permanent);
+    }
+
+    /// Starts probing for `candidate`, the address is only applied if no other
+    /// host claims it. Addresses that need no check are applied right away.
+    pub fn start<T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &mut PNet<T, U>,
+        candidate: IpSuite,
+        current_time: Ticks,
+    ) {
+        let ip_config = &pnet.fspm_user_config.interface_config.ip_config;
+
+        pnet.acd.conflict = false;
+
+        if candidate.ip_address.is_unspecified() || candidate.ip_address == ip_config.ip_address {
+            pnet.acd.candidate = None;
+            Self::apply(pnet, candidate);
+            return;
+        }
+
+        pnet.acd.candidate = Some(candidate);
+        pnet.acd.probes_sent = 0;
+
+        Self::handle_probe(pnet, current_time);
+    }
+
+    /// Scheduler callback, sends the next probe or commits the address once
+    /// the wait after the last probe passed without a reply
+    pub(crate) fn handle_probe<T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &mut PNet<T, U>,
+        current_time: Ticks,
+    ) {
+        let Some(candidate) = pnet.acd.candidate else {
+            return;
+        };
+
+        if pnet.acd.probes_sent >= PROBE_COUNT {
+            pnet.acd.candidate = None;
+            Self::apply(pnet, candidate);
+            Self::resolve_commit(pnet);
+            return;
+        }
+
+        let mac_address = pnet.fspm_user_config.interface_config.ip_config.mac_address;
+        let mut buffer = [0; ARP_FRAME_LENGTH];
+        let length = build_probe(mac_address, candidate.ip_address, &mut buffer);
+
+        if let Err(e) = pnet.queue_packet(&buffer[..length], current_time) {
+            error!("Failed to queue ARP probe: {}", e);
+        }
+
+        pnet.acd.probes_sent += 1;
+
+        let delay_us = if pnet.acd.probes_sent < PROBE_COUNT {
+            PROBE_INTERVAL_US
+        } else {
+            ANNOUNCE_WAIT_US
+        };
+
+        pnet.scheduler.add_task(
+            "acd_probe",
+            Ticks::from_micros(delay_us, pnet.fspm_user_config.tick_us),
+            TaskKind::AcdProbe,
+            current_time,
+        );
+    }
+
+    /// Checks a received ARP frame against the address being probed
+    pub(crate) fn handle_frame<F: AsRef<[u8]>, T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &mut PNet<T, U>,
+        frame: &EthernetFrame<F>,
+    ) {
+        let Some(candidate) = pnet.acd.candidate else {
+            return;
+        };
+
+        let Ok(packet) = ArpPacket::new_checked(frame.eth_payload()) else {
+            return;
+        };
+
+        let Ok(ArpRepr::EthernetIpv4 {
+            operation,
+            source_hardware_addr,
+            source_protocol_addr,
+            target_protocol_addr,
+            ..
+        }) = ArpRepr::parse(&packet)
+        else {
+            return;
+        };
+
+        let mac_address = pnet.fspm_user_config.interface_config.ip_config.mac_address;
+
+        if source_hardware_addr == mac_address {
+            return;
+        }
+
+        // Either the address is in use, or another host is probing for it
+        let in_use = source_protocol_addr == candidate.ip_address;
+        let probed = operation == ArpOperation::Request
+            && source_protocol_addr.is_unspecified()
+            && target_protocol_addr == candidate.ip_address;
+
+        if in_use || probed {
+            warn!("IP address {} is already in use", candidate.ip_address);
+            pnet.acd.candidate = None;
+            pnet.acd.conflict = true;
+            Self::resolve_commit(pnet);
+        }
+    }
+
+    /// Signals a commit held back by `defer_commit`
+    fn resolve_commit<T: App + Copy, U: TaskCallback + Copy>(pnet: &mut PNet<T, U>) {
+        if let Some(permanent) = pnet.acd.pending_commit.take() {
+            Dcp::commit(pnet, permanent);
+        }
+    }
+
+    fn apply<T: App + Copy, U: TaskCallback + Copy>(pnet: &mut PNet<T, U>, suite: IpSuite) {
+        let ip_config = &mut pnet.fspm_user_config.interface_config.ip_config;
+        let old = ip_config.clone();
+
+        ip_config.ip_address = suite.ip_address;
+        ip_config.subnet_mask = suite.subnet_mask;
+        ip_config.gateway = suite.gateway;
+        ip_config.dns = suite.dns;
+
+        // Setting the same address again is not a change
+        if *ip_config == old {
+            return;
+        }
+
+        let new = ip_config.clone();
+        pnet.update_interface();
+
+        let mut app = pnet.fspm_user_config.app;
+        app.ip_changed_ind(pnet, old, new);
+        pnet.fspm_user_config.app = app;
+    }
+}
+
+/// Answers an ARP request for the device's IP address, so controllers can
+/// reach the device. Requests for other addresses are ignored.
+pub(crate) fn handle_request<F: AsRef<[u8]>, T: App + Copy, U: TaskCallback + Copy>(
+    pnet: &mut PNet<T, U>,
+    frame: &EthernetFrame<F>,
+    current_time: Ticks,
+) {
+    let ip_config = &pnet.fspm_user_config.interface_config.ip_config;
+    let (mac_address, ip_address) = (ip_config.mac_address, ip_config.ip_address);
+
+    if ip_address.is_unspecified() {
+        return;
+    }
+
+    let Ok(packet) = ArpPacket::new_checked(frame.eth_payload()) else {
+        return;
+    };
+
+    let Ok(ArpRepr::EthernetIpv4 {
+        operation: ArpOperation::Request,
+        source_hardware_addr,
+        source_protocol_addr,
+        target_protocol_addr,
+        ..
+    }) = ArpRepr::parse(&packet)
+    else {
+        return;
+    };
+
+    if target_protocol_addr != ip_address {
+        return;
+    }
+
+    let mut buffer = [0; ARP_FRAME_LENGTH];
+    let length = build_reply(
+        mac_address,
+        ip_address,
+        source_hardware_addr,
+        source_protocol_addr,
+        &mut buffer,
+    );
+
+    if let Err(e) = pnet.queue_packet(&buffer[..length], current_time) {
+        error!("Failed to queue ARP reply: {}", e);
+    }
+}
+
+/// Builds the reply to an ARP request from `target_mac`/`target_ip` and returns
+/// the written length
+pub fn build_reply(
+    mac_address: EthernetAddress,
+    ip_address: Ipv4Address,
+    target_mac: EthernetAddress,
+    target_ip: Ipv4Address,
+    buffer: &mut [u8],
+) -> usize {
+    buffer[..ARP_FRAME_LENGTH].fill(0);
+    buffer[DESTINATION_FIELD].copy_from_slice(target_mac.as_bytes());
+    buffer[SOURCE_FIELD].copy_from_slice(mac_address.as_bytes());
+    NetworkEndian::write_u16(&mut buffer[TYPE_FIELD], EthType::Arp as u16);
+
+    let repr = ArpRepr::EthernetIpv4 {
+        operation: ArpOperation::Reply,
+        source_hardware_addr: mac_address,
+        source_protocol_addr: ip_address,
+        target_hardware_addr: target_mac,
+        target_protocol_addr: target_ip,
+    };
+    repr.emit(&mut ArpPacket::new_unchecked(&mut buffer[PAYLOAD_FIELD]));
+
+    ARP_FRAME_LENGTH
+}
+
+/// Builds an ARP probe asking who uses `ip_address` and returns the written length
+pub fn build_probe(
+    mac_address: EthernetAddress,
+    ip_address: Ipv4Address,
+    buffer: &mut [u8],
+) -> usize {
+    buffer[..ARP_FRAME_LENGTH].fill(0);
+    buffer[DESTINATION_FIELD].copy_from_slice(EthernetAddress::BROADCAST.as_bytes());
+    buffer[SOURCE_FIELD].copy_from_slice(mac_address.as_bytes());
+    NetworkEndian::write_u16(&mut buffer[TYPE_FIELD], EthType::Arp as u16);
+
+    let repr = ArpRepr::EthernetIpv4 {
+        operation: ArpOperation::Request,
+        source_hardware_addr: mac_address,
+        source_protocol_addr: Ipv4Address::UNSPECIFIED,
+        target_hardware_addr: EthernetAddress([0; 6]),
+        target_protocol_addr: ip_address,
+    };
+    repr.emit(&mut ArpPacket::new_unchecked(&mut buffer[PAYLOAD_FIELD]));
+
+    ARP_FRAME_LENGTH
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::{test_config, TestTask};
+
+    use super::*;
+
+    const CANDIDATE: IpSuite = IpSuite {
+        ip_address: Ipv4Address::new(192, 168, 0, 2),
+        subnet_mask: Ipv4Address::new(255, 255, 255, 0),
+        gateway: Ipv4Address::new(192, 168, 0, 1),
+        dns: None,
+    };
+
+    fn arp_reply(ip_address: Ipv4Address) -> [u8; ARP_FRAME_LENGTH] {
+        let mut buffer = [0; ARP_FRAME_LENGTH];
+        let other_mac = EthernetAddress([0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5]);
+
+        buffer[DESTINATION_FIELD].copy_from_slice(&[0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe]);
+        buffer[SOURCE_FIELD].copy_from_slice(other_mac.as_bytes());
+        NetworkEndian::write_u16(&mut buffer[TYPE_FIELD], EthType::Arp as u16);
+
+        let repr = ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Reply,
+            source_hardware_addr: other_mac,
+            source_protocol_addr: ip_address,
+            target_hardware_addr: EthernetAddress([0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe]),
+            target_protocol_addr: Ipv4Address::UNSPECIFIED,
+        };
+        repr.emit(&mut ArpPacket::new_unchecked(&mut buffer[PAYLOAD_FIELD]));
+
+        buffer
+    }
+
+    fn arp_request(target_ip: Ipv4Address) -> [u8; ARP_FRAME_LENGTH] {
+        let mut buffer = [0; ARP_FRAME_LENGTH];
+        let other_mac = EthernetAddress([0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5]);
+
+        buffer[DESTINATION_FIELD].copy_from_slice(EthernetAddress::BROADCAST.as_bytes());
+        buffer[SOURCE_FIELD].copy_from_slice(other_mac.as_bytes());
+        NetworkEndian::write_u16(&mut buffer[TYPE_FIELD], EthType::Arp as u16);
+
+        let repr = ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Request,
+            source_hardware_addr: other_mac,
+            source_protocol_addr: Ipv4Address::new(192, 168, 0, 1),
+            target_hardware_addr: EthernetAddress([0; 6]),
+            target_protocol_addr: target_ip,
+        };
+        repr.emit(&mut ArpPacket::new_unchecked(&mut buffer[PAYLOAD_FIELD]));
+
+        buffer
+    }
+
+    #[test]
+    fn test_ip_suite_is_valid() {
+        assert!(CANDIDATE.is_valid());
+
+        let unspecified = IpSuite {
+            ip_address: Ipv4Address::UNSPECIFIED,
+            subnet_mask: Ipv4Address::UNSPECIFIED,
+            gateway: Ipv4Address::UNSPECIFIED,
+            dns: None,
+        };
+        assert!(unspecified.is_valid());
+
+        // No gateway, and the device being its own gateway
+        for gateway in [Ipv4Address::UNSPECIFIED, CANDIDATE.ip_address] {
+            assert!(IpSuite {
+                gateway,
+                ..CANDIDATE
+            }
+            .is_valid());
+        }
+
+        let invalid = [
+            IpSuite {
+                subnet_mask: Ipv4Address::new(255, 0, 255, 0),
+                ..CANDIDATE
+            },
+            IpSuite {
+                ip_address: Ipv4Address::new(224, 0, 0, 1),
+                ..CANDIDATE
+            },
+            IpSuite {
+                ip_address: Ipv4Address::new(127, 0, 0, 1),
+                ..CANDIDATE
+            },
+            IpSuite {
+                ip_address: Ipv4Address::new(192, 168, 0, 255),
+                ..CANDIDATE
+            },
+            IpSuite {
+                gateway: Ipv4Address::new(10, 0, 0, 1),
+                ..CANDIDATE
+            },
+        ];
+        for suite in invalid {
+            assert!(!suite.is_valid());
+        }
+    }
+
+    #[test]
+    fn test_build_probe() {
+        let mut buffer = [0; ARP_FRAME_LENGTH];
+        let mac = EthernetAddress([0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe]);
+        let length = build_probe(mac, Ipv4Address::new(192, 168, 0, 2), &mut buffer);
+
+        assert_eq!(length, ARP_FRAME_LENGTH);
+        assert_eq!(
+            buffer[..42],
+            [
+                0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe, 0x08, 0x06,
+                0x00, 0x01, 0x08, 0x00, 0x06, 0x04, 0x00, 0x01, 0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xc0, 0xa8, 0x00, 0x02
+            ]
+        );
+    }
+
+    #[test]
+    fn test_commit_without_conflict() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        Acd::start(&mut pnet, CANDIDATE, Ticks(0));
+        assert!(pnet.acd.is_probing());
+        assert!(pnet.outgoing_packets[0].is_some());
+
+        pnet.run_scheduled_tasks(Ticks(1000));
+        assert!(pnet.outgoing_packets[1].is_some());
+        assert!(pnet
+            .fspm_user_config
+            .interface_config
+            .ip_config
+            .ip_address
+            .is_unspecified());
+
+        pnet.run_scheduled_tasks(Ticks(3000));
+        assert!(!pnet.acd.is_probing());
+        assert!(!pnet.acd.conflict());
+        assert_eq!(
+            pnet.fspm_user_config.interface_config.ip_config.ip_address,
+            CANDIDATE.ip_address
+        );
+    }
+
+    #[test]
+    fn test_ip_changed_callback() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        Acd::start(&mut pnet, CANDIDATE, Ticks(0));
+        pnet.run_scheduled_tasks(Ticks(1000));
+        assert_eq!(pnet.fspm_user_config.app.ip_changes, 0);
+
+        pnet.run_scheduled_tasks(Ticks(3000));
+        assert_eq!(pnet.fspm_user_config.app.ip_changes, 1);
+        assert_eq!(pnet.ip_config().ip_address, CANDIDATE.ip_address);
+
+        // Setting the same address again does not call back
+        Acd::start(&mut pnet, CANDIDATE, Ticks(4000));
+        assert_eq!(pnet.fspm_user_config.app.ip_changes, 1);
+    }
+
+    #[test]
+    fn test_conflict_keeps_old_address() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        Acd::start(&mut pnet, CANDIDATE, Ticks(0));
+        pnet.handle_frame(&arp_reply(CANDIDATE.ip_address), Ticks(500))
+            .unwrap();
+
+        assert!(pnet.acd.conflict());
+
+        pnet.run_scheduled_tasks(Ticks(3000));
+        assert!(pnet
+            .fspm_user_config
+            .interface_config
+            .ip_config
+            .ip_address
+            .is_unspecified());
+    }
+
+    #[test]
+    fn test_commit_waits_for_conflict_check() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        Acd::start(&mut pnet, CANDIDATE, Ticks(0));
+        pnet.acd.defer_commit(true);
+        assert_eq!(pnet.fspm_user_config.app.commits, 0);
+
+        // The rejected address isn't part of the commit
+        pnet.handle_frame(&arp_reply(CANDIDATE.ip_address), Ticks(500))
+            .unwrap();
+
+        let app = pnet.fspm_user_config.app;
+        assert_eq!(app.commits, 1);
+        assert!(app.committed_ip.is_unspecified());
+        assert!(app.commit_permanent);
+    }
+
+    #[test]
+    fn test_unrelated_reply_is_ignored() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        Acd::start(&mut pnet, CANDIDATE, Ticks(0));
+        pnet.handle_frame(&arp_reply(Ipv4Address::new(192, 168, 0, 3)), Ticks(500))
+            .unwrap();
+
+        assert!(!pnet.acd.conflict());
+        assert!(pnet.acd.is_probing());
+    }
+
+    #[test]
+    fn test_reply_to_request() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        pnet.fspm_user_config.interface_config.ip_config.ip_address = CANDIDATE.ip_address;
+
+        pnet.handle_frame(&arp_request(CANDIDATE.ip_address), Ticks(0))
+            .unwrap();
+
+        let reply = pnet.outgoing_packets[0].as_ref().unwrap();
+        assert_eq!(reply.length, ARP_FRAME_LENGTH);
+        assert_eq!(
+            reply.data[..42],
+            [
+                0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5, 0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe, 0x08, 0x06,
+                0x00, 0x01, 0x08, 0x00, 0x06, 0x04, 0x00, 0x02, 0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe,
+                0xc0, 0xa8, 0x00, 0x02, 0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5, 0xc0, 0xa8, 0x00, 0x01
+            ]
+        );
+    }
+
+    #[test]
+    fn test_request_for_other_ip_is_ignored() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        pnet.fspm_user_config.interface_config.ip_config.ip_address = CANDIDATE.ip_address;
+
+        pnet.handle_frame(&arp_request(Ipv4Address::new(192, 168, 0, 3)), Ticks(0))
+            .unwrap();
+
+        assert!(pnet.outgoing_packets.iter().all(Option::is_none));
+    }
+}
@@ -0,0 +1,110 @@
+// Application relations set up by a controller
+
+use smoltcp::wire::EthernetAddress;
+
+use super::CmdevState;
+use crate::cmrpc::ArRequest;
+use crate::constants::MAX_AR;
+
+/// Identifies an AR in the callbacks, the index of its slot in the `ArStore`
+pub type Arep = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ar {
+    pub ar_uuid: [u8; 16],
+    pub session_key: u16,
+    pub initiator_mac: EthernetAddress,
+    pub state: CmdevState,
+}
+
+impl Ar {
+    /// A new AR for a connect request that was just received
+    pub fn new(request: &ArRequest) -> Self {
+        Self {
+            ar_uuid: request.ar_uuid,
+            session_key: request.session_key,
+            initiator_mac: request.cm_initiator_mac,
+            state: CmdevState::ConnectInd,
+        }
+    }
+}
+
+/// The active ARs, at most `MAX_AR` of them
+pub struct ArStore {
+    ars: [Option<Ar>; MAX_AR],
+}
+
+impl Default for ArStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ArStore {
+    pub const fn new() -> Self {
+        Self {
+            ars: [None; MAX_AR],
+        }
+    }
+
+    /// Stores `ar` in a free slot, `None` if all slots are taken
+    pub fn allocate(&mut self, ar: Ar) -> Option<Arep> {
+        let arep = self.ars.iter().position(Option::is_none)?;
+        self.ars[arep] = Some(ar);
+
+        Some(arep)
+    }
+
+    /// Frees the slot of `arep` and returns the AR it held
+    pub fn release(&mut self, arep: Arep) -> Option<Ar> {
+        self.ars.get_mut(arep)?.take()
+    }
+
+    pub fn get(&self, arep: Arep) -> Option<&Ar> {
+        self.ars.get(arep)?.as_ref()
+    }
+
+    pub fn get_mut(&mut self, arep: Arep) -> Option<&mut Ar> {
+        self.ars.get_mut(arep)?.as_mut()
+    }
+
+    /// The AR with the given UUID
+    pub fn find(&self, ar_uuid: &[u8; 16]) -> Option<Arep> {
+        self.ars
+            .iter()
+            .position(|ar| ar.is_some_and(|ar| ar.ar_uuid == *ar_uuid))
+    }
+
+    pub fn active(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Ar> {
+        self.ars.iter().flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_connect_request;
+
+    #[test]
+    fn test_allocate_and_release() {
+        let mut store = ArStore::new();
+        let ar = Ar::new(&test_connect_request().ar);
+
+        let arep = store.allocate(ar).unwrap();
+        assert_eq!(store.get(arep), Some(&ar));
+        assert_eq!(store.find(&ar.ar_uuid), Some(arep));
+        assert_eq!(store.active(), 1);
+
+        // All slots are taken
+        assert_eq!(store.allocate(ar), None);
+
+        assert_eq!(store.release(arep), Some(ar));
+        assert_eq!(store.release(arep), None);
+        assert_eq!(store.get(arep), None);
+        assert_eq!(store.allocate(ar), Some(arep));
+    }
+}
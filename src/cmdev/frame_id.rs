@@ -0,0 +1,95 @@
+// Frame ids of the cyclic IO data of the ARs
+
+use super::Arep;
+use crate::cmrpc::IocrType;
+use crate::constants::{MAX_AR, MAX_CR};
+
+/// RT_CLASS_1 frame ids of unicast IOCRs
+pub const RT_CLASS_1_UNICAST_FRAME_IDS: (u16, u16) = (0xc000, 0xf7ff);
+/// RT_CLASS_1 frame ids of multicast IOCRs
+pub const RT_CLASS_1_MULTICAST_FRAME_IDS: (u16, u16) = (0xf800, 0xfbff);
+
+const MAX_FRAME_IDS: usize = MAX_AR * MAX_CR;
+
+/// Hands out the frame ids of the provider and consumer CRs, each id is used
+/// by at most one CR at a time
+#[derive(Debug)]
+pub struct FrameIdAllocator {
+    frame_ids: [Option<(u16, Arep)>; MAX_FRAME_IDS],
+}
+
+impl Default for FrameIdAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameIdAllocator {
+    pub const fn new() -> Self {
+        Self {
+            frame_ids: [None; MAX_FRAME_IDS],
+        }
+    }
+
+    /// The range the frame id of an IOCR of `iocr_type` is taken from
+    pub fn range(iocr_type: IocrType) -> (u16, u16) {
+        match iocr_type {
+            IocrType::Input | IocrType::Output => RT_CLASS_1_UNICAST_FRAME_IDS,
+            IocrType::MulticastProvider | IocrType::MulticastConsumer => {
+                RT_CLASS_1_MULTICAST_FRAME_IDS
+            }
+        }
+    }
+
+    /// Allocates the lowest free frame id for an IOCR of `arep`, `None` if
+    /// `MAX_CR` ids per AR are in use or the range is exhausted
+    pub fn allocate(&mut self, arep: Arep, iocr_type: IocrType) -> Option<u16> {
+        let slot = self.frame_ids.iter().position(Option::is_none)?;
+        let (first, last) = Self::range(iocr_type);
+        let frame_id = (first..=last).find(|&id| !self.in_use(id))?;
+        self.frame_ids[slot] = Some((frame_id, arep));
+
+        Some(frame_id)
+    }
+
+    /// Frees all frame ids of `arep`
+    pub fn release(&mut self, arep: Arep) {
+        for entry in self.frame_ids.iter_mut() {
+            if entry.is_some_and(|(_, owner)| owner == arep) {
+                *entry = None;
+            }
+        }
+    }
+
+    pub fn in_use(&self, frame_id: u16) -> bool {
+        self.frame_ids
+            .iter()
+            .flatten()
+            .any(|&(id, _)| id == frame_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allocate_frame_ids() {
+        let mut allocator = FrameIdAllocator::new();
+
+        assert_eq!(allocator.allocate(0, IocrType::Input), Some(0xc000));
+        assert_eq!(allocator.allocate(0, IocrType::Output), Some(0xc001));
+        assert!(allocator.in_use(0xc001));
+
+        // All ids are handed out
+        assert_eq!(allocator.allocate(0, IocrType::Input), None);
+
+        allocator.release(0);
+        assert!(!allocator.in_use(0xc000));
+        assert_eq!(
+            allocator.allocate(0, IocrType::MulticastProvider),
+            Some(0xf800)
+        );
+        assert_eq!(allocator.allocate(0, IocrType::Input), Some(0xc000));
+    }
+}
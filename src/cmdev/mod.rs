@@ -1,6 +1,12 @@
-/// Context Management protocol machine Device
-///
+mod ar;
+mod frame_id;
+
+pub use ar::*;
+pub use frame_id::*;
 
+/// Context Management protocol machine Device
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum CmdevState {
     PowerOn,
     ConnectInd,
@@ -16,3 +22,13 @@ pub enum CmdevState {
 }
 
 impl CmdevState {}
+
+/// Why an AR was ended
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AbortReason {
+    /// The controller released the AR with an RPC release
+    Released,
+    /// No cyclic frame of the controller arrived within the data hold time
+    WatchdogExpired,
+}
@@ -0,0 +1,105 @@
+use smoltcp::wire::EthernetAddress;
+
+use crate::constants::{
+    MAX_CR, MAX_DATA_HOLD_FACTOR, MAX_DATA_HOLD_TIME_US, MAX_SLOTS, MAX_SUBSLOTS,
+    SEND_CLOCK_BASE_NS,
+};
+use crate::fspm::app::PnioStatus;
+
+pub const MAX_EXPECTED_SUBMODULES: usize = MAX_SLOTS * MAX_SUBSLOTS;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u16)]
+pub enum IocrType {
+    Input = 1,
+    Output = 2,
+    MulticastProvider = 3,
+    MulticastConsumer = 4,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArRequest {
+    pub ar_type: u16,
+    pub ar_uuid: [u8; 16],
+    pub session_key: u16,
+    pub cm_initiator_mac: EthernetAddress,
+    pub cm_initiator_object_uuid: [u8; 16],
+    pub ar_properties: u32,
+    pub cm_initiator_activity_timeout_factor: u16,
+    pub cm_initiator_udp_rt_port: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IocrRequest {
+    pub iocr_type: IocrType,
+    pub iocr_reference: u16,
+    pub frame_id: u16,
+    pub data_length: u16,
+    pub send_clock_factor: u16,
+    pub reduction_ratio: u16,
+    pub phase: u16,
+    pub watchdog_factor: u16,
+    pub data_hold_factor: u16,
+}
+
+impl IocrRequest {
+    /// ErrorCode2 of a faulty WatchdogFactor, the index of the field in the block
+    pub const WATCHDOG_FACTOR_FIELD: u8 = 15;
+    /// ErrorCode2 of a faulty DataHoldFactor
+    pub const DATA_HOLD_FACTOR_FIELD: u8 = 16;
+
+    /// How long the consumer waits for a frame before it gives up on the
+    /// provider, `data_hold_factor` send cycles
+    pub fn data_hold_time_us(&self) -> usize {
+        let send_clocks = self.send_clock_factor as usize
+            * self.reduction_ratio as usize
+            * self.data_hold_factor as usize;
+
+        send_clocks * SEND_CLOCK_BASE_NS / 1000
+    }
+
+    /// Checks the watchdog parameters, the error names the faulty field
+    pub fn check_watchdog(&self) -> Result<(), PnioStatus> {
+        if !(1..=MAX_DATA_HOLD_FACTOR).contains(&self.watchdog_factor) {
+            return Err(PnioStatus::faulty_iocr_block(Self::WATCHDOG_FACTOR_FIELD));
+        }
+
+        if !(self.watchdog_factor..=MAX_DATA_HOLD_FACTOR).contains(&self.data_hold_factor)
+            || self.data_hold_time_us() > MAX_DATA_HOLD_TIME_US
+        {
+            return Err(PnioStatus::faulty_iocr_block(Self::DATA_HOLD_FACTOR_FIELD));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AlarmCrRequest {
+    pub alarm_cr_type: u16,
+    pub rta_timeout_factor: u16,
+    pub rta_retries: u16,
+    pub local_alarm_reference: u16,
+    pub max_alarm_data_length: u16,
+}
+
+/// A submodule the controller expects to find in the device
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExpectedSubmodule {
+    pub api: u32,
+    pub slot_number: u16,
+    pub module_ident: u32,
+    pub subslot_number: u16,
+    pub submodule_ident: u32,
+}
+
+/// The content of an RPC connect request
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConnectRequest {
+    /// Maximum size of the response body the controller accepts
+    pub args_maximum: u32,
+    pub ar: ArRequest,
+    pub iocrs: [Option<IocrRequest>; MAX_CR],
+    pub alarm_cr: Option<AlarmCrRequest>,
+    pub expected_submodules: [Option<ExpectedSubmodule>; MAX_EXPECTED_SUBMODULES],
+}
@@ -1 +1,431 @@
 // Context Management RPC device protocol machine
+
+use byteorder::{ByteOrder, LittleEndian, NetworkEndian};
+
+use crate::error::Error;
+use crate::field::Field;
+use crate::fspm::{app::App, Config};
+
+mod connect;
+mod pd;
+mod read;
+
+pub use connect::*;
+pub use pd::*;
+pub use read::*;
+
+const PNIO_STATUS_FIELD: Field = 0..4;
+const ARGS_LENGTH_FIELD: Field = 4..8;
+const MAXIMUM_COUNT_FIELD: Field = 8..12;
+const OFFSET_FIELD: Field = 12..16;
+const ACTUAL_COUNT_FIELD: Field = 16..20;
+const NDR_HEADER_LENGTH: usize = ACTUAL_COUNT_FIELD.end;
+
+const BLOCK_TYPE_FIELD: Field = 0..2;
+const BLOCK_LENGTH_FIELD: Field = 2..4;
+const BLOCK_VERSION_HIGH_FIELD: usize = 4;
+const BLOCK_VERSION_LOW_FIELD: usize = 5;
+const BLOCK_HEADER_LENGTH: usize = 6;
+
+const AR_BLOCK_RES_LENGTH: usize = BLOCK_HEADER_LENGTH + 28;
+const IOCR_BLOCK_RES_LENGTH: usize = BLOCK_HEADER_LENGTH + 6;
+const ALARM_CR_BLOCK_RES_LENGTH: usize = BLOCK_HEADER_LENGTH + 6;
+
+pub const BLOCK_TYPE_AR_BLOCK_RES: u16 = 0x8101;
+pub const BLOCK_TYPE_IOCR_BLOCK_RES: u16 = 0x8102;
+pub const BLOCK_TYPE_ALARM_CR_BLOCK_RES: u16 = 0x8103;
+pub const BLOCK_TYPE_MODULE_DIFF_BLOCK: u16 = 0x8104;
+
+/// UDP port announced in the AR block response, RT frames are sent directly over Ethernet
+pub const RESPONDER_UDP_RT_PORT: u16 = 0x8892;
+pub const DEVICE_ALARM_REFERENCE: u16 = 0x0001;
+pub const MAX_ALARM_DATA_LENGTH: u16 = 1432;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u16)]
+pub enum ModuleState {
+    NoModule = 0,
+    WrongModule = 1,
+    ProperModule = 2,
+    Substitute = 3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u16)]
+pub enum SubmoduleIdentInfo {
+    Ok = 0,
+    Substitute = 1,
+    Wrong = 2,
+    NoSubmodule = 3,
+}
+
+impl SubmoduleIdentInfo {
+    /// SubmoduleState with the format indicator set and only the ident info filled in
+    fn submodule_state(self) -> u16 {
+        (1 << 15) | ((self as u16) << 11)
+    }
+}
+
+pub struct CmRpc;
+
+impl CmRpc {
+    /// Serializes the NDR body of the response to `request` into `buffer` and
+    /// returns the number of bytes written.
+    ///
+    /// The body consists of the AR, IOCR and alarm CR block responses, followed by
+    /// a module diff block if any expected submodule doesn't match the submodules
+    /// in `config`. A mismatching module doesn't fail the connect, the controller
+    /// learns about it from the module diff block.
+    ///
+    /// Fails with `Error::PacketTooLarge` if the body doesn't fit `buffer` or its
+    /// blocks are longer than the `args_maximum` the controller accepts,
+    /// nothing is written then.
+    pub fn build_connect_response<T: App>(
+        request: &ConnectRequest,
+        config: &Config<T>,
+        buffer: &mut [u8],
+    ) -> Result<usize, Error> {
+        let args_length = Self::connect_response_args_length(request, config);
+        let length = NDR_HEADER_LENGTH + args_length;
+        if length > buffer.len() || args_length > request.args_maximum as usize {
+            return Err(Error::PacketTooLarge);
+        }
+
+        let mut offset = NDR_HEADER_LENGTH;
+
+        offset += Self::encode_ar_block_res(request, config, &mut buffer[offset..]);
+
+        for iocr in request.iocrs.iter().flatten() {
+            offset += Self::encode_iocr_block_res(iocr, &mut buffer[offset..]);
+        }
+
+        if let Some(alarm_cr) = &request.alarm_cr {
+            offset += Self::encode_alarm_cr_block_res(alarm_cr, &mut buffer[offset..]);
+        }
+
+        offset += Self::encode_module_diff_block(request, config, &mut buffer[offset..]);
+
+        let args_length = (offset - NDR_HEADER_LENGTH) as u32;
+        buffer[PNIO_STATUS_FIELD].fill(0);
+        LittleEndian::write_u32(&mut buffer[ARGS_LENGTH_FIELD], args_length);
+        LittleEndian::write_u32(&mut buffer[MAXIMUM_COUNT_FIELD], request.args_maximum);
+        LittleEndian::write_u32(&mut buffer[OFFSET_FIELD], 0);
+        LittleEndian::write_u32(&mut buffer[ACTUAL_COUNT_FIELD], args_length);
+
+        Ok(offset)
+    }
+
+    /// Length of the blocks `build_connect_response` writes after the NDR header
+    fn connect_response_args_length<T: App>(request: &ConnectRequest, config: &Config<T>) -> usize {
+        let iocrs = request.iocrs.iter().flatten().count();
+        let alarm_crs = usize::from(request.alarm_cr.is_some());
+
+        AR_BLOCK_RES_LENGTH
+            + iocrs * IOCR_BLOCK_RES_LENGTH
+            + alarm_crs * ALARM_CR_BLOCK_RES_LENGTH
+            + Self::module_diff_block_length(request, config)
+    }
+
+    pub(crate) fn encode_block_header(
+        buffer: &mut [u8],
+        block_type: u16,
+        content_length: usize,
+    ) -> usize {
+        NetworkEndian::write_u16(&mut buffer[BLOCK_TYPE_FIELD], block_type);
+        // The block length counts everything after the length field, including the version
+        NetworkEndian::write_u16(&mut buffer[BLOCK_LENGTH_FIELD], content_length as u16 + 2);
+        buffer[BLOCK_VERSION_HIGH_FIELD] = 1;
+        buffer[BLOCK_VERSION_LOW_FIELD] = 0;
+
+        BLOCK_HEADER_LENGTH
+    }
+
+    fn encode_ar_block_res<T: App>(
+        request: &ConnectRequest,
+        config: &Config<T>,
+        buffer: &mut [u8],
+    ) -> usize {
+        let content = &mut buffer[BLOCK_HEADER_LENGTH..];
+        NetworkEndian::write_u16(&mut content[0..2], request.ar.ar_type);
+        content[2..18].copy_from_slice(&request.ar.ar_uuid);
+        NetworkEndian::write_u16(&mut content[18..20], request.ar.session_key);
+        content[20..26].copy_from_slice(config.interface_config.ip_config.mac_address.as_bytes());
+        NetworkEndian::write_u16(&mut content[26..28], RESPONDER_UDP_RT_PORT);
+
+        Self::encode_block_header(buffer, BLOCK_TYPE_AR_BLOCK_RES, 28) + 28
+    }
+
+    fn encode_iocr_block_res(iocr: &IocrRequest, buffer: &mut [u8]) -> usize {
+        let content = &mut buffer[BLOCK_HEADER_LENGTH..];
+        NetworkEndian::write_u16(&mut content[0..2], iocr.iocr_type as u16);
+        NetworkEndian::write_u16(&mut content[2..4], iocr.iocr_reference);
+        NetworkEndian::write_u16(&mut content[4..6], iocr.frame_id);
+
+        Self::encode_block_header(buffer, BLOCK_TYPE_IOCR_BLOCK_RES, 6) + 6
+    }
+
+    fn encode_alarm_cr_block_res(alarm_cr: &AlarmCrRequest, buffer: &mut [u8]) -> usize {
+        let content = &mut buffer[BLOCK_HEADER_LENGTH..];
+        NetworkEndian::write_u16(&mut content[0..2], alarm_cr.alarm_cr_type);
+        NetworkEndian::write_u16(&mut content[2..4], DEVICE_ALARM_REFERENCE);
+        NetworkEndian::write_u16(
+            &mut content[4..6],
+            alarm_cr.max_alarm_data_length.min(MAX_ALARM_DATA_LENGTH),
+        );
+
+        Self::encode_block_header(buffer, BLOCK_TYPE_ALARM_CR_BLOCK_RES, 6) + 6
+    }
+
+    /// The expected submodules that don't match the plugged ones, with how they differ
+    fn module_diffs<'r, T: App>(
+        request: &'r ConnectRequest,
+        config: &'r Config<T>,
+    ) -> impl Iterator<Item = (&'r ExpectedSubmodule, (ModuleState, SubmoduleIdentInfo))> + Clone
+    {
+        request
+            .expected_submodules
+            .iter()
+            .flatten()
+            .filter_map(|e| config.submodules.diff(e).map(|d| (e, d)))
+    }
+
+    /// Length of the module diff block, zero if every expected submodule matches
+    fn module_diff_block_length<T: App>(request: &ConnectRequest, config: &Config<T>) -> usize {
+        let diffs = Self::module_diffs(request, config);
+        if diffs.clone().next().is_none() {
+            return 0;
+        }
+
+        let (mut apis, mut modules, mut submodules) = (0, 0, 0);
+        for (i, (expected, _)) in diffs.clone().enumerate() {
+            let mut earlier = diffs.clone().take(i).map(|(e, _)| e);
+            if !earlier.clone().any(|e| e.api == expected.api) {
+                apis += 1;
+            }
+            if !earlier.any(|e| e.api == expected.api && e.slot_number == expected.slot_number) {
+                modules += 1;
+            }
+            submodules += 1;
+        }
+
+        BLOCK_HEADER_LENGTH + 2 + apis * 6 + modules * 10 + submodules * 8
+    }
+
+    fn encode_module_diff_block<T: App>(
+        request: &ConnectRequest,
+        config: &Config<T>,
+        buffer: &mut [u8],
+    ) -> usize {
+        let diffs = || Self::module_diffs(request, config);
+
+        if diffs().next().is_none() {
+            return 0;
+        }
+
+        let mut offset = BLOCK_HEADER_LENGTH + 2;
+        let mut number_of_apis = 0u16;
+
+        for (i, (expected, _)) in diffs().enumerate() {
+            if diffs().take(i).any(|(e, _)| e.api == expected.api) {
+                continue;
+            }
+
+            number_of_apis += 1;
+            NetworkEndian::write_u32(&mut buffer[offset..offset + 4], expected.api);
+            let number_of_modules_offset = offset + 4;
+            offset += 6;
+
+            let in_api = || diffs().filter(|(e, _)| e.api == expected.api);
+            let mut number_of_modules = 0u16;
+
+            for (j, (module, (module_state, _))) in in_api().enumerate() {
+                if in_api()
+                    .take(j)
+                    .any(|(e, _)| e.slot_number == module.slot_number)
+                {
+                    continue;
+                }
+
+                number_of_modules += 1;
+                NetworkEndian::write_u16(&mut buffer[offset..offset + 2], module.slot_number);
+                NetworkEndian::write_u32(&mut buffer[offset + 2..offset + 6], module.module_ident);
+                NetworkEndian::write_u16(&mut buffer[offset + 6..offset + 8], module_state as u16);
+                let number_of_submodules_offset = offset + 8;
+                offset += 10;
+
+                let mut number_of_submodules = 0u16;
+                for (submodule, (_, ident_info)) in
+                    in_api().filter(|(e, _)| e.slot_number == module.slot_number)
+                {
+                    number_of_submodules += 1;
+                    NetworkEndian::write_u16(
+                        &mut buffer[offset..offset + 2],
+                        submodule.subslot_number,
+                    );
+                    NetworkEndian::write_u32(
+                        &mut buffer[offset + 2..offset + 6],
+                        submodule.submodule_ident,
+                    );
+                    NetworkEndian::write_u16(
+                        &mut buffer[offset + 6..offset + 8],
+                        ident_info.submodule_state(),
+                    );
+                    offset += 8;
+                }
+
+                NetworkEndian::write_u16(
+                    &mut buffer[number_of_submodules_offset..number_of_submodules_offset + 2],
+                    number_of_submodules,
+                );
+            }
+
+            NetworkEndian::write_u16(
+                &mut buffer[number_of_modules_offset..number_of_modules_offset + 2],
+                number_of_modules,
+            );
+        }
+
+        NetworkEndian::write_u16(
+            &mut buffer[BLOCK_HEADER_LENGTH..BLOCK_HEADER_LENGTH + 2],
+            number_of_apis,
+        );
+        Self::encode_block_header(
+            buffer,
+            BLOCK_TYPE_MODULE_DIFF_BLOCK,
+            offset - BLOCK_HEADER_LENGTH,
+        );
+
+        offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::{test_config, test_connect_request};
+
+    use super::*;
+    use crate::fspm::SubmoduleConfig;
+
+    #[test]
+    fn test_connect_response_blocks() {
+        let mut config = test_config();
+        config
+            .submodules
+            .plug(SubmoduleConfig {
+                api: 0,
+                slot_number: 0,
+                subslot_number: 1,
+                module_ident: 0x01,
+                submodule_ident: 0x01,
+            })
+            .unwrap();
+        config
+            .submodules
+            .plug(SubmoduleConfig {
+                api: 0,
+                slot_number: 1,
+                subslot_number: 1,
+                module_ident: 0x30,
+                submodule_ident: 0x01,
+            })
+            .unwrap();
+
+        let mut buffer = [0; 256];
+        let length =
+            CmRpc::build_connect_response(&test_connect_request(), &config, &mut buffer).unwrap();
+
+        // NDR header + AR block + 2 IOCR blocks + alarm CR block, no module diff block
+        assert_eq!(length, 20 + 34 + 2 * 12 + 12);
+        assert_eq!(LittleEndian::read_u32(&buffer[ARGS_LENGTH_FIELD]), 70);
+        assert_eq!(LittleEndian::read_u32(&buffer[MAXIMUM_COUNT_FIELD]), 1024);
+
+        let ar_block = &buffer[20..54];
+        assert_eq!(ar_block[0..6], [0x81, 0x01, 0x00, 0x1e, 0x01, 0x00]);
+        assert_eq!(ar_block[8..24], [0xaa; 16]);
+        assert_eq!(ar_block[24..26], [0x12, 0x34]);
+        assert_eq!(ar_block[26..32], [0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe]);
+        assert_eq!(ar_block[32..34], [0x88, 0x92]);
+
+        assert_eq!(
+            buffer[54..66],
+            [0x81, 0x02, 0x00, 0x08, 0x01, 0x00, 0x00, 0x01, 0x00, 0x01, 0xc0, 0x01]
+        );
+        assert_eq!(
+            buffer[66..78],
+            [0x81, 0x02, 0x00, 0x08, 0x01, 0x00, 0x00, 0x02, 0x00, 0x02, 0xc0, 0x02]
+        );
+        assert_eq!(
+            buffer[78..90],
+            [0x81, 0x03, 0x00, 0x08, 0x01, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0xc8]
+        );
+    }
+
+    #[test]
+    fn test_connect_response_too_long() {
+        let config = test_config();
+        let mut request = test_connect_request();
+
+        // Two module diff entries of missing modules after the 70 bytes of blocks
+        let length = 20 + 70 + 6 + 2 + 6 + 2 * (10 + 8);
+        let mut buffer = [0; 256];
+        assert_eq!(
+            CmRpc::build_connect_response(&request, &config, &mut buffer).unwrap(),
+            length
+        );
+
+        let mut buffer = [0; 256];
+        assert!(matches!(
+            CmRpc::build_connect_response(&request, &config, &mut buffer[..length - 1]),
+            Err(Error::PacketTooLarge)
+        ));
+        assert!(buffer.iter().all(|b| *b == 0));
+
+        request.args_maximum = (length - 20 - 1) as u32;
+        assert!(matches!(
+            CmRpc::build_connect_response(&request, &config, &mut buffer),
+            Err(Error::PacketTooLarge)
+        ));
+    }
+
+    #[test]
+    fn test_connect_response_module_diff() {
+        let mut config = test_config();
+        config
+            .submodules
+            .plug(SubmoduleConfig {
+                api: 0,
+                slot_number: 0,
+                subslot_number: 1,
+                module_ident: 0x01,
+                submodule_ident: 0x01,
+            })
+            .unwrap();
+        config
+            .submodules
+            .plug(SubmoduleConfig {
+                api: 0,
+                slot_number: 1,
+                subslot_number: 1,
+                module_ident: 0x31,
+                submodule_ident: 0x01,
+            })
+            .unwrap();
+
+        let mut buffer = [0; 256];
+        let length =
+            CmRpc::build_connect_response(&test_connect_request(), &config, &mut buffer).unwrap();
+
+        let module_diff = &buffer[90..length];
+        assert_eq!(
+            module_diff,
+            [
+                0x81, 0x04, 0x00, 0x1c, 0x01, 0x00, // block header
+                0x00, 0x01, // number of APIs
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x01, // API 0, one module
+                0x00, 0x01, 0x00, 0x00, 0x00, 0x30, 0x00, 0x01, 0x00,
+                0x01, // slot 1, wrong module
+                0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x90, 0x00, // subslot 1, wrong submodule
+            ]
+        );
+    }
+}
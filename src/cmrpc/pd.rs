@@ -0,0 +1,81 @@
+// Physical device records of the interface submodule
+
+use crate::fspm::{app::App, Config};
+
+use super::{CmRpc, BLOCK_HEADER_LENGTH};
+
+pub const PD_INTERFACE_REAL_DATA_INDEX: u16 = 0x8080;
+pub const BLOCK_TYPE_PD_INTERFACE_REAL_DATA: u16 = 0x0240;
+
+const MAC_ADDRESS_LENGTH: usize = 6;
+
+/// Offset of the next 32 bit boundary, counted from the start of the block
+fn align(offset: usize) -> usize {
+    offset.next_multiple_of(4)
+}
+
+/// Encodes PDInterfaceDataReal: the chassis id (the NameOfStation), the MAC
+/// address and the IP settings of the interface.
+pub fn encode_pd_interface_real_data<T: App + Copy>(
+    config: &Config<T>,
+    buffer: &mut [u8],
+) -> usize {
+    let ip_config = &config.interface_config.ip_config;
+    let name = &config.station_name[..config.station_name_len()];
+
+    let mut offset = BLOCK_HEADER_LENGTH;
+    buffer[offset] = name.len() as u8;
+    offset += 1;
+    buffer[offset..offset + name.len()].copy_from_slice(name);
+    offset += name.len();
+
+    let mac_offset = align(offset);
+    buffer[offset..mac_offset].fill(0);
+    buffer[mac_offset..mac_offset + MAC_ADDRESS_LENGTH]
+        .copy_from_slice(ip_config.mac_address.as_bytes());
+    offset = mac_offset + MAC_ADDRESS_LENGTH;
+
+    let ip_offset = align(offset);
+    buffer[offset..ip_offset].fill(0);
+    offset = ip_offset;
+    for address in [
+        ip_config.ip_address,
+        ip_config.subnet_mask,
+        ip_config.gateway,
+    ] {
+        buffer[offset..offset + 4].copy_from_slice(address.as_bytes());
+        offset += 4;
+    }
+
+    let content_length = offset - BLOCK_HEADER_LENGTH;
+    CmRpc::encode_block_header(buffer, BLOCK_TYPE_PD_INTERFACE_REAL_DATA, content_length)
+        + content_length
+}
+
+#[cfg(test)]
+mod tests {
+    use smoltcp::wire::Ipv4Address;
+
+    use super::*;
+    use crate::util::test_config;
+
+    #[test]
+    fn test_encode_pd_interface_real_data() {
+        let mut config = test_config();
+        config.station_name[..3].copy_from_slice(b"dev");
+        config.interface_config.ip_config.ip_address = Ipv4Address::new(192, 168, 0, 2);
+        config.interface_config.ip_config.subnet_mask = Ipv4Address::new(255, 255, 255, 0);
+
+        let mut buffer = [0xff; 64];
+        let length = encode_pd_interface_real_data(&config, &mut buffer);
+
+        assert_eq!(
+            buffer[..length],
+            [
+                0x02, 0x40, 0x00, 0x1c, 0x01, 0x00, 0x03, b'd', b'e', b'v', 0x00, 0x00, 0x00, 0x00,
+                0x23, 0x53, 0x4e, 0xfe, 0x00, 0x00, 0xc0, 0xa8, 0x00, 0x02, 0xff, 0xff, 0xff, 0x00,
+                0x00, 0x00, 0x00, 0x00,
+            ]
+        );
+    }
+}
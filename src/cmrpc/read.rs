@@ -0,0 +1,134 @@
+use crate::fspm::app::{App, PnioStatus};
+use crate::fspm::im::{self, ImError, IM0_INDEX, IM1_INDEX, IM2_INDEX, IM3_INDEX, IM4_INDEX};
+use crate::fspm::Config;
+
+use super::{pd, CmRpc, PD_INTERFACE_REAL_DATA_INDEX};
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RecordError {
+    /// The index isn't handled by the stack and has to be read from the application
+    InvalidIndex,
+    /// The written I&M record is malformed
+    Im(ImError),
+    /// The record can only be read
+    ReadOnly,
+    /// The application failed the request
+    Application(PnioStatus),
+}
+
+impl RecordError {
+    /// The status reported to the controller, `error_code` tells whether
+    /// reading or writing failed
+    pub fn pnio_status(&self, error_code: u8) -> PnioStatus {
+        match self {
+            RecordError::InvalidIndex => PnioStatus::invalid_index(error_code),
+            RecordError::Im(ImError::InvalidLength) => PnioStatus::write_length_error(),
+            RecordError::Im(_) => PnioStatus::invalid_parameter(),
+            RecordError::ReadOnly => PnioStatus::access_denied(error_code),
+            RecordError::Application(status) => *status,
+        }
+    }
+}
+
+impl CmRpc {
+    /// Serializes the record with the given index into `buffer` and returns its length.
+    ///
+    /// I&M1 to I&M4 are only readable when the device announces them in the
+    /// I&M supported field of I&M0.
+    pub fn read_record<T: App + Copy>(
+        config: &Config<T>,
+        index: u16,
+        buffer: &mut [u8],
+    ) -> Result<usize, RecordError> {
+        let supported = |im: u16| config.im0.supported & (1 << im) != 0;
+
+        match index {
+            IM0_INDEX => Ok(im::encode_im0(&config.im0, buffer)),
+            IM1_INDEX if supported(1) => Ok(im::encode_im1(&config.im1, buffer)),
+            IM2_INDEX if supported(2) => Ok(im::encode_im2(&config.im2, buffer)),
+            IM3_INDEX if supported(3) => Ok(im::encode_im3(&config.im3, buffer)),
+            IM4_INDEX if supported(4) => Ok(im::encode_im4(&config.im4, buffer)),
+            PD_INTERFACE_REAL_DATA_INDEX => Ok(pd::encode_pd_interface_real_data(config, buffer)),
+            _ => Err(RecordError::InvalidIndex),
+        }
+    }
+
+    /// Applies a written record to `config`.
+    ///
+    /// I&M0 and PDInterfaceDataReal are read-only, I&M1 to I&M4 are writable
+    /// when the device announces them.
+    pub fn write_record<T: App>(
+        config: &mut Config<T>,
+        index: u16,
+        buffer: &[u8],
+    ) -> Result<(), RecordError> {
+        let supported = config.im0.supported;
+        let supported = |im: u16| supported & (1 << im) != 0;
+
+        match index {
+            IM1_INDEX if supported(1) => im::parse_im1_write(buffer, &mut config.im1),
+            IM2_INDEX if supported(2) => im::parse_im2_write(buffer, &mut config.im2),
+            IM3_INDEX if supported(3) => im::parse_im3_write(buffer, &mut config.im3),
+            IM4_INDEX if supported(4) => im::parse_im4_write(buffer, &mut config.im4),
+            IM0_INDEX | PD_INTERFACE_REAL_DATA_INDEX => return Err(RecordError::ReadOnly),
+            _ => return Err(RecordError::InvalidIndex),
+        }
+        .map_err(RecordError::Im)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::test_config;
+
+    use super::*;
+
+    #[test]
+    fn test_read_im0() {
+        let config = test_config();
+
+        let mut buffer = [0; 64];
+        let length = CmRpc::read_record(&config, IM0_INDEX, &mut buffer).unwrap();
+
+        assert_eq!(length, 60);
+        assert_eq!(buffer[..2], [0x00, 0x20]);
+    }
+
+    #[test]
+    fn test_read_unsupported_im() {
+        let mut config = test_config();
+
+        let mut buffer = [0; 64];
+        assert_eq!(
+            CmRpc::read_record(&config, IM1_INDEX, &mut buffer),
+            Err(RecordError::InvalidIndex)
+        );
+
+        config.im0.supported = 1 << 1;
+        assert_eq!(CmRpc::read_record(&config, IM1_INDEX, &mut buffer), Ok(60));
+    }
+
+    #[test]
+    fn test_write_im1() {
+        let mut config = test_config();
+        config.im0.supported = 1 << 1;
+
+        let mut buffer = [0; 64];
+        let mut im1 = config.im1.clone();
+        im1.tag_function[..4].copy_from_slice(b"pump");
+        let length = im::encode_im1(&im1, &mut buffer);
+
+        CmRpc::write_record(&mut config, IM1_INDEX, &buffer[..length]).unwrap();
+        assert_eq!(&config.im1.tag_function[..4], b"pump");
+
+        assert_eq!(
+            CmRpc::write_record(&mut config, IM1_INDEX, &buffer[..length - 2]),
+            Err(RecordError::Im(ImError::InvalidLength))
+        );
+        assert_eq!(
+            CmRpc::write_record(&mut config, IM0_INDEX, &buffer[..length]),
+            Err(RecordError::ReadOnly)
+        );
+    }
+}
@@ -1,6 +1,32 @@
 pub const MAX_AR: usize = 1;
 pub const MAX_CR: usize = 2;
 pub const MAX_PHYSICAL_PORTS: usize = 1;
+/// Longest Ethernet frame we send, a VLAN tagged frame without the FCS
+pub const MAX_FRAME_LENGTH: usize = 1518;
+/// Shortest Ethernet frame without the FCS, shorter frames are padded with zeros
+pub const MIN_FRAME_LENGTH: usize = 60;
+/// Queued frames that are overdue by more than this are dropped by default
+pub const DEFAULT_MAX_SEND_LATENCY_US: usize = 2_000_000;
+/// Received frames handled by one `PNet::poll`, so a flood can't starve the other work
+pub const MAX_RX_FRAMES_PER_POLL: usize = 16;
+/// Hello requests sent at startup when `send_dcp_hello` is set
+pub const DEFAULT_DCP_HELLO_COUNT: usize = 3;
+pub const DEFAULT_DCP_HELLO_INTERVAL_US: usize = 1_000_000;
+/// The send clock counts in units of 31.25 µs
+pub const SEND_CLOCK_BASE_NS: usize = 31_250;
+/// A send clock of 1 ms
+pub const DEFAULT_SEND_CLOCK_FACTOR: u16 = 32;
+pub const MAX_SEND_CLOCK_FACTOR: u16 = 128;
+pub const MAX_REDUCTION_RATIO: u16 = 512;
+/// Largest watchdog and data hold factor of an IOCR
+pub const MAX_DATA_HOLD_FACTOR: u16 = 0x1e00;
+/// Longest data hold time of an IOCR, 1.92 s
+pub const MAX_DATA_HOLD_TIME_US: usize = 1_920_000;
+
+pub const MAX_SLOTS: usize = 5;
+pub const MAX_SUBSLOTS: usize = 3;
+/// Multicast groups frames are accepted from, besides the device's own MAC address
+pub const MAX_MULTICAST_GROUPS: usize = 4;
 pub const MAX_SCHEDULER_TASKS: usize = 2 * (MAX_AR) * (MAX_CR) + 2 * (MAX_PHYSICAL_PORTS) + 9;
 
 pub const MAX_ORDER_ID_LENGTH: usize = 20;
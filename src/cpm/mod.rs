@@ -1,7 +1,132 @@
 // Consumer protocol machine
 
+use crate::cmdev::{AbortReason, Arep};
+use crate::cmrpc::IocrRequest;
+use crate::fspm::app::App;
+use crate::scheduler::{TaskCallback, TaskKind};
+use crate::time::Ticks;
+use crate::PNet;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum CPMState {
     WStart,
     FRun,
     Run,
 }
+
+/// Consumer of the cyclic frames of one IOCR the controller provides
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Cpm {
+    pub arep: Arep,
+    pub frame_id: u16,
+    pub state: CPMState,
+    /// The AR is aborted when no frame arrived for this long
+    pub data_hold_time: Ticks,
+    last_received: Ticks,
+}
+
+impl Cpm {
+    pub fn new(arep: Arep, frame_id: u16, data_hold_time: Ticks) -> Self {
+        Self {
+            arep,
+            frame_id,
+            state: CPMState::WStart,
+            data_hold_time,
+            last_received: Ticks::ZERO,
+        }
+    }
+
+    /// A CPM for `iocr`, its data hold time is `data_hold_factor` send cycles
+    /// but at least one tick
+    pub fn for_iocr(arep: Arep, iocr: &IocrRequest, tick_us: usize) -> Self {
+        let data_hold_time = Ticks::from_micros(iocr.data_hold_time_us(), tick_us);
+
+        Self::new(arep, iocr.frame_id, data_hold_time.max(Ticks(1)))
+    }
+
+    /// Activates the CPM in the first free CR slot, the watchdog starts with
+    /// the first received frame. Returns the CR index or `None` if all are in use.
+    pub fn start<T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &mut PNet<T, U>,
+        cpm: Cpm,
+    ) -> Option<usize> {
+        let cr = pnet.cpm.iter().position(Option::is_none)?;
+        pnet.cpm[cr] = Some(cpm);
+        pnet.cpm_instance_count += 1;
+
+        Some(cr)
+    }
+
+    /// Stops all CPMs of `arep` and cancels their watchdogs
+    pub fn stop<T: App + Copy, U: TaskCallback + Copy>(pnet: &mut PNet<T, U>, arep: Arep) {
+        for cr in 0..pnet.cpm.len() {
+            if pnet.cpm[cr].is_some_and(|cpm| cpm.arep == arep) {
+                pnet.cpm[cr] = None;
+                pnet.cpm_instance_count -= 1;
+                pnet.scheduler
+                    .cancel(|task| matches!(task, TaskKind::CpmWatchdog(c) if *c == cr));
+            }
+        }
+    }
+
+    /// Feeds the watchdog of the CPM consuming `frame_id`, the first frame
+    /// starts it. Returns false if no CPM consumes the frame.
+    pub(crate) fn handle_frame<T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &mut PNet<T, U>,
+        frame_id: u16,
+        current_time: Ticks,
+    ) -> bool {
+        let Some(cr) = pnet
+            .cpm
+            .iter()
+            .position(|cpm| cpm.is_some_and(|cpm| cpm.frame_id == frame_id))
+        else {
+            return false;
+        };
+        let Some(cpm) = &mut pnet.cpm[cr] else {
+            return false;
+        };
+
+        cpm.last_received = current_time;
+        if cpm.state == CPMState::WStart {
+            cpm.state = CPMState::Run;
+            let data_hold_time = cpm.data_hold_time;
+            pnet.scheduler.add_task(
+                "cpm_watchdog",
+                data_hold_time,
+                TaskKind::CpmWatchdog(cr),
+                current_time,
+            );
+        }
+
+        true
+    }
+
+    /// Scheduler callback, aborts the AR if the data hold time passed without
+    /// a frame, otherwise checks again once it would have
+    pub(crate) fn handle_watchdog<T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &mut PNet<T, U>,
+        cr: usize,
+        current_time: Ticks,
+    ) {
+        let Some(cpm) = pnet.cpm[cr] else {
+            return;
+        };
+
+        let expires_at = cpm.last_received + cpm.data_hold_time;
+        if expires_at.is_reached(current_time) {
+            warn!("CPM watchdog of frame id {:x} expired", cpm.frame_id);
+            pnet.abort_ar(cpm.arep, AbortReason::WatchdogExpired);
+            return;
+        }
+
+        pnet.scheduler.add_task(
+            "cpm_watchdog",
+            expires_at.since(current_time),
+            TaskKind::CpmWatchdog(cr),
+            current_time,
+        );
+    }
+}
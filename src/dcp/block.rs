@@ -1,15 +1,30 @@
-use core::mem;
+use core::{fmt, mem, ops::BitOr, str::Utf8Error};
 
 use byteorder::{ByteOrder, NetworkEndian};
 use num_enum::TryFromPrimitive;
 use smoltcp::wire::{EthernetAddress, Ipv4Address};
 
 use crate::dcp::block_options::*;
-use crate::dcp::error::ParseDcpBlockError;
+use crate::dcp::error::{BlockStringError, EncodeError, ParseDcpBlockError};
 use crate::field::{Field, Rest, SmallField};
+use crate::util::ByteWriter;
 
 pub const MAX_DEVICE_VENDOR_LENGTH: usize = 255;
 pub const MAX_NAME_OF_STATION_LENGTH: usize = 240;
+/// Length of the vendor id and device id on the wire
+pub const DEVICE_ID_LENGTH: usize = 4;
+/// Length of the high and low device instance bytes on the wire
+pub const DEVICE_INSTANCE_LENGTH: usize = 2;
+/// Length of the role bits and the reserved byte on the wire
+pub const DEVICE_ROLE_LENGTH: usize = 2;
+/// Number of (option, suboption) pairs a DeviceOptions block can hold
+pub const MAX_DEVICE_OPTIONS: usize = 24;
+/// Length of the UUIDs in NME domain blocks
+pub const NME_UUID_LENGTH: usize = 16;
+
+// The structs mirror their wire layout, catch it if that ever stops being true
+const _: () = assert!(mem::size_of::<DeviceId>() == DEVICE_ID_LENGTH);
+const _: () = assert!(mem::size_of::<DeviceInstance>() == DEVICE_INSTANCE_LENGTH);
 
 const OPTION_FIELD: SmallField = 0;
 const SUBOPTION_FIELD: SmallField = 1;
@@ -48,18 +63,58 @@ impl<T: AsRef<[u8]>> DCPBlockFrame<T> {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
-pub struct DcpBlock {
-    pub block: Block,
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DcpBlock<'a> {
+    pub block: Block<'a>,
+    /// Bytes the block takes in an encoded frame, with its header and pad
+    /// byte. Parsed blocks keep the length field of the block header instead.
     pub block_length: u16,
+    /// Only meaningful for blocks of a Set request, other frames carry the
+    /// block info in its place
+    pub qualifier: BlockQualifier,
 }
 
-impl DcpBlock {
-    pub fn new(block: Block) -> Self {
+/// Precedes the data of every block in a Set request
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BlockQualifier(pub u16);
+
+impl BlockQualifier {
+    const PERMANENT: u16 = 0x0001;
+
+    /// Whether the value should be stored permanently instead of only until
+    /// the next power cycle
+    pub fn is_permanent(&self) -> bool {
+        self.0 & Self::PERMANENT != 0
+    }
+
+    /// Whether any bit besides the permanent bit is set
+    pub fn has_reserved_bits(&self) -> bool {
+        self.0 & !Self::PERMANENT != 0
+    }
+}
+
+impl<'a> DcpBlock<'a> {
+    pub fn new(block: Block<'a>) -> Self {
         let mut block_length = match block {
             Block::Ip(ip) => ip.block_length(),
             Block::DeviceProperties(dp) => dp.block_length(),
+            Block::Dhcp(dhcp) => dhcp.block_length(),
+            Block::NmeDomain(nme) => nme.block_length(),
+            Block::ManufacturerSpecific { data, .. } => data.len() as u16 + 2,
             Block::All => 0,
-            Block::Control => 5,
+            // The block qualifier, Signal adds the signal value
+            Block::Control(ControlSuboption::Signal) => 4,
+            Block::Control(_) => 2,
+            Block::ControlResponse(response) => response.block_length(),
+            // Get requests only carry the option and suboption
+            Block::GetRequest(_) => {
+                return Self {
+                    block,
+                    block_length: 2,
+                    qualifier: BlockQualifier::default(),
+                }
+            }
         };
 
         // Account for block header
@@ -72,10 +127,14 @@ impl DcpBlock {
         Self {
             block,
             block_length,
+            qualifier: BlockQualifier::default(),
         }
     }
 
-    pub fn parse_block(buffer: &[u8]) -> Result<Self, ParseDcpBlockError> {
+    pub fn parse_block(buffer: &'a [u8]) -> Result<Self, ParseDcpBlockError> {
+        if buffer.len() < BLOCK_INFO_FIELD.start {
+            return Err(ParseDcpBlockError::InvalidBlockLength);
+        }
         let frame = DCPBlockFrame::new_unchecked(buffer);
 
         let option = BlockOption::try_from(frame.option())
@@ -88,11 +147,18 @@ impl DcpBlock {
             return Ok(Self {
                 block: Block::All,
                 block_length,
+                qualifier: BlockQualifier::default(),
             });
         }
 
-        let payload = frame.payload();
-        let payload_length = (block_length - 2) as usize;
+        // Nothing past the block belongs to it
+        let buffer = buffer
+            .get(..BLOCK_INFO_FIELD.start + block_length as usize)
+            .ok_or(ParseDcpBlockError::InvalidBlockLength)?;
+
+        // Borrowed from `buffer` rather than `frame`, the block keeps it. Empty
+        // if the block is too short for a block info.
+        let payload = buffer.get(PAYLOAD_FIELD).unwrap_or_default();
 
         let block = match option {
             BlockOption::IP => {
@@ -100,15 +166,21 @@ impl DcpBlock {
                     .map_err(|_| ParseDcpBlockError::InvalidIPSuboption)?;
 
                 let ip_block = match ip_suboption {
-                    IpSuboption::MacAddress => IpBlock::MacAddress(MacAddress::new(payload)),
+                    IpSuboption::MacAddress => IpBlock::MacAddress(MacAddress::new(check_payload(
+                        payload,
+                        MacAddress::ADDRESS.end,
+                    )?)),
                     IpSuboption::IpParameter => {
+                        let payload = check_payload(payload, IpParameter::GATEWAY.end)?;
                         let block_info_raw = NetworkEndian::read_u16(&buffer[BLOCK_INFO_FIELD]);
                         let block_info =
-                            IpParameterBlockInfo::try_from_primitive(block_info_raw as u8)
+                            IpParameterBlockInfo::try_from_primitive(block_info_raw)
                                 .map_err(|_| ParseDcpBlockError::InvalidIpParameterBlockInfo)?;
                         IpBlock::IpParameter(IpParameter::new(payload, block_info))
                     }
-                    IpSuboption::FullIpSuite => IpBlock::FullIpSuite(FullIpSuite::new(payload)),
+                    IpSuboption::FullIpSuite => IpBlock::FullIpSuite(FullIpSuite::new(
+                        check_payload(payload, FullIpSuite::DNS.end)?,
+                    )),
                 };
 
                 Block::Ip(ip_block)
@@ -119,28 +191,29 @@ impl DcpBlock {
                         .map_err(|_| ParseDcpBlockError::InvalidDevicePropertySuboption)?;
 
                 let device_block = match device_prop_suboption {
-                    DevicePropertiesSuboption::DeviceVendor => DevicePropertiesBlock::DeviceVendor(
-                        DeviceVendor::parse_bytes(payload, payload_length),
-                    ),
-                    DevicePropertiesSuboption::NameOfStation => {
-                        DevicePropertiesBlock::NameOfStation(NameOfStation::parse_bytes(
-                            payload,
-                            payload_length,
-                        ))
+                    DevicePropertiesSuboption::DeviceVendor => {
+                        DevicePropertiesBlock::DeviceVendor(DeviceVendor::new(payload))
                     }
-                    DevicePropertiesSuboption::DeviceId => {
-                        DevicePropertiesBlock::DeviceId(DeviceId::parse_bytes(payload))
+                    DevicePropertiesSuboption::NameOfStation => {
+                        DevicePropertiesBlock::NameOfStation(NameOfStation::new(payload))
                     }
+                    DevicePropertiesSuboption::DeviceId => DevicePropertiesBlock::DeviceId(
+                        DeviceId::parse_bytes(check_payload(payload, DEVICE_ID_LENGTH)?),
+                    ),
                     DevicePropertiesSuboption::DeviceRole => DevicePropertiesBlock::DeviceRole(
-                        DeviceRole::try_from_primitive(payload[0])
-                            .map_err(|_| ParseDcpBlockError::InvalidDeviceRole)?,
+                        DeviceRole(check_payload(payload, DEVICE_ROLE_LENGTH)?[0]),
                     ),
                     DevicePropertiesSuboption::DeviceOptions => {
-                        DevicePropertiesBlock::DeviceOptions
+                        DevicePropertiesBlock::DeviceOptions(DeviceOptions::parse_bytes(
+                            payload,
+                            payload.len(),
+                        ))
                     }
                     DevicePropertiesSuboption::AliasName => DevicePropertiesBlock::AliasName,
                     DevicePropertiesSuboption::DeviceInstance => {
-                        DevicePropertiesBlock::DeviceInstance(DeviceInstance::parse_bytes(payload))
+                        DevicePropertiesBlock::DeviceInstance(DeviceInstance::parse_bytes(
+                            check_payload(payload, DEVICE_INSTANCE_LENGTH)?,
+                        ))
                     }
                     DevicePropertiesSuboption::OemDeviceId => DevicePropertiesBlock::OemDeviceId,
                     DevicePropertiesSuboption::StandardGateway => {
@@ -153,13 +226,44 @@ impl DcpBlock {
 
                 Block::DeviceProperties(device_block)
             }
-            BlockOption::Control => Block::Control,
+            BlockOption::DHCP => match DhcpSuboptions::try_from_primitive(suboption) {
+                Ok(DhcpSuboptions::Control) => {
+                    let value = payload
+                        .first()
+                        .ok_or(ParseDcpBlockError::InvalidBlockLength)?;
+                    Block::Dhcp(DhcpBlock::Control(*value == DhcpBlock::USE_DHCP))
+                }
+                _ => return Err(ParseDcpBlockError::InvalidDHCPPropertySuboption),
+            },
+            BlockOption::NMEDomain => {
+                let nme_suboption = NMEDomainSuboption::try_from_primitive(suboption)
+                    .map_err(|_| ParseDcpBlockError::InvalidNMEDomainSuboption)?;
+                Block::NmeDomain(NmeDomainBlock::parse(nme_suboption, payload)?)
+            }
+            BlockOption::ManufacturerSpecific => Block::ManufacturerSpecific {
+                option: frame.option(),
+                suboption,
+                data: payload,
+            },
+            BlockOption::Control if suboption == ControlSuboption::Response as u8 => {
+                Block::ControlResponse(ControlResponse::new(&buffer[BLOCK_INFO_FIELD.start..])?)
+            }
+            BlockOption::Control => Block::Control(
+                ControlSuboption::try_from_primitive(suboption)
+                    .map_err(|_| ParseDcpBlockError::InvalidControlSuboption)?,
+            ),
             _ => return Err(ParseDcpBlockError::BlockNotSupported),
         };
 
+        let qualifier = buffer
+            .get(BLOCK_INFO_FIELD)
+            .map(NetworkEndian::read_u16)
+            .unwrap_or_default();
+
         Ok(Self {
             block,
             block_length,
+            qualifier: BlockQualifier(qualifier),
         })
     }
 
@@ -169,14 +273,48 @@ impl DcpBlock {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
-pub enum Block {
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Block<'a> {
     Ip(IpBlock),
-    DeviceProperties(DevicePropertiesBlock),
-    Control,
+    DeviceProperties(DevicePropertiesBlock<'a>),
+    Dhcp(DhcpBlock),
+    NmeDomain(NmeDomainBlock<'a>),
+    /// A vendor block of option 0x80 to 0xfe, kept as sent
+    ManufacturerSpecific {
+        option: u8,
+        suboption: u8,
+        /// Everything after the block info
+        data: &'a [u8],
+    },
+    /// A Control block of a Set request, without its block qualifier
+    Control(ControlSuboption),
+    ControlResponse(ControlResponse),
+    GetRequest(GetRequest),
     All,
 }
 
-impl Block {
+impl Block<'_> {
+    /// The raw option and suboption identifying this block
+    pub fn option_suboption(&self) -> (u8, u8) {
+        match self {
+            Block::Ip(ip) => (BlockOption::IP as u8, ip.suboption() as u8),
+            Block::DeviceProperties(dp) => {
+                (BlockOption::DeviceProperties as u8, dp.suboption() as u8)
+            }
+            Block::Dhcp(dhcp) => (BlockOption::DHCP as u8, dhcp.suboption() as u8),
+            Block::NmeDomain(nme) => (BlockOption::NMEDomain as u8, nme.suboption() as u8),
+            Block::ManufacturerSpecific {
+                option, suboption, ..
+            } => (*option, *suboption),
+            Block::Control(suboption) => (BlockOption::Control as u8, *suboption as u8),
+            Block::ControlResponse(_) => {
+                (BlockOption::Control as u8, ControlSuboption::Response as u8)
+            }
+            Block::GetRequest(request) => (request.option, request.suboption),
+            Block::All => (BlockOption::All as u8, AllSuboption::All as u8),
+        }
+    }
+
     fn encode_into(&self, buffer: &mut [u8]) {
         match self {
             Block::Ip(ip) => {
@@ -187,16 +325,123 @@ impl Block {
                 buffer[OPTION_FIELD] = BlockOption::DeviceProperties as u8;
                 dp.encode_into(buffer);
             }
+            Block::Dhcp(dhcp) => {
+                buffer[OPTION_FIELD] = BlockOption::DHCP as u8;
+                dhcp.encode_into(buffer);
+            }
+            Block::NmeDomain(nme) => {
+                buffer[OPTION_FIELD] = BlockOption::NMEDomain as u8;
+                nme.encode_into(buffer);
+            }
+            Block::ManufacturerSpecific {
+                option,
+                suboption,
+                data,
+            } => {
+                buffer[OPTION_FIELD] = *option;
+                buffer[SUBOPTION_FIELD] = *suboption;
+                NetworkEndian::write_u16(&mut buffer[BLOCK_LENGTH_FIELD], data.len() as u16 + 2);
+                buffer[PAYLOAD_FIELD][..data.len()].copy_from_slice(data);
+            }
             Block::All => {
                 buffer[OPTION_FIELD] = BlockOption::All as u8;
                 buffer[SUBOPTION_FIELD] = BlockOption::All as u8;
             }
-            Block::Control => (),
+            Block::Control(suboption) => {
+                buffer[OPTION_FIELD] = BlockOption::Control as u8;
+                buffer[SUBOPTION_FIELD] = *suboption as u8;
+                let length = if *suboption == ControlSuboption::Signal {
+                    // Flash once
+                    NetworkEndian::write_u16(&mut buffer[PAYLOAD_FIELD], 0x0100);
+                    4
+                } else {
+                    2
+                };
+                NetworkEndian::write_u16(&mut buffer[BLOCK_LENGTH_FIELD], length);
+            }
+            Block::ControlResponse(response) => {
+                buffer[OPTION_FIELD] = BlockOption::Control as u8;
+                buffer[SUBOPTION_FIELD] = ControlSuboption::Response as u8;
+                NetworkEndian::write_u16(&mut buffer[BLOCK_LENGTH_FIELD], response.block_length());
+                response.encode_into(&mut buffer[BLOCK_INFO_FIELD.start..]);
+            }
+            Block::GetRequest(request) => {
+                buffer[OPTION_FIELD] = request.option;
+                buffer[SUBOPTION_FIELD] = request.suboption;
+            }
         }
     }
 }
 
+/// Response to one block of a Set request
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ControlResponse {
+    pub option: u8,
+    pub suboption: u8,
+    pub error: DcpBlockError,
+}
+
+impl ControlResponse {
+    const OPTION: SmallField = 0;
+    const SUBOPTION: SmallField = 1;
+    const BLOCK_ERROR: SmallField = 2;
+
+    /// Builds the response for the block of a Set request
+    pub fn for_block(block: &Block, error: DcpBlockError) -> Self {
+        let (option, suboption) = block.option_suboption();
+
+        Self {
+            option,
+            suboption,
+            error,
+        }
+    }
+
+    /// Parses the echoed option, suboption and block error of a response block
+    fn new(buffer: &[u8]) -> Result<Self, ParseDcpBlockError> {
+        if buffer.len() <= Self::BLOCK_ERROR {
+            return Err(ParseDcpBlockError::InvalidBlockLength);
+        }
+
+        Ok(Self {
+            option: buffer[Self::OPTION],
+            suboption: buffer[Self::SUBOPTION],
+            error: DcpBlockError::try_from_primitive(buffer[Self::BLOCK_ERROR])
+                .map_err(|_| ParseDcpBlockError::InvalidBlockError)?,
+        })
+    }
+
+    fn encode_into(&self, buffer: &mut [u8]) {
+        buffer[Self::OPTION] = self.option;
+        buffer[Self::SUBOPTION] = self.suboption;
+        buffer[Self::BLOCK_ERROR] = self.error as u8;
+    }
+
+    fn block_length(&self) -> u16 {
+        Self::BLOCK_ERROR as u16 + 1
+    }
+}
+
+/// One option and suboption pair of a Get request
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct GetRequest {
+    pub option: u8,
+    pub suboption: u8,
+}
+
+impl GetRequest {
+    pub fn new(buffer: &[u8]) -> Self {
+        Self {
+            option: buffer[OPTION_FIELD],
+            suboption: buffer[SUBOPTION_FIELD],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum IpBlock {
     MacAddress(MacAddress),
     IpParameter(IpParameter),
@@ -204,6 +449,14 @@ pub enum IpBlock {
 }
 
 impl IpBlock {
+    fn suboption(&self) -> IpSuboption {
+        match self {
+            IpBlock::MacAddress(_) => IpSuboption::MacAddress,
+            IpBlock::IpParameter(_) => IpSuboption::IpParameter,
+            IpBlock::FullIpSuite(_) => IpSuboption::FullIpSuite,
+        }
+    }
+
     fn encode_into(&self, buffer: &mut [u8]) {
         #[cfg(test)]
         println!("buffer: {:?}", buffer);
@@ -224,7 +477,7 @@ impl IpBlock {
             }
             IpBlock::FullIpSuite(suite) => {
                 buffer[SUBOPTION_FIELD] = IpSuboption::FullIpSuite as u8;
-                NetworkEndian::write_u16(&mut buffer[BLOCK_LENGTH_FIELD], 20);
+                NetworkEndian::write_u16(&mut buffer[BLOCK_LENGTH_FIELD], suite.block_length());
                 suite.encode_into(&mut buffer[PAYLOAD_FIELD]);
             }
         }
@@ -240,6 +493,118 @@ impl IpBlock {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DhcpBlock {
+    /// Whether the device gets its address by DHCP instead of a static one
+    Control(bool),
+}
+
+impl DhcpBlock {
+    const USE_DHCP: u8 = 0x01;
+
+    fn suboption(&self) -> DhcpSuboptions {
+        match self {
+            DhcpBlock::Control(_) => DhcpSuboptions::Control,
+        }
+    }
+
+    fn encode_into(&self, buffer: &mut [u8]) {
+        buffer[SUBOPTION_FIELD] = self.suboption() as u8;
+        NetworkEndian::write_u16(&mut buffer[BLOCK_LENGTH_FIELD], self.block_length());
+
+        match self {
+            DhcpBlock::Control(use_dhcp) => buffer[PAYLOAD_FIELD][0] = *use_dhcp as u8,
+        }
+    }
+
+    fn block_length(&self) -> u16 {
+        match self {
+            // Block info and the control value
+            DhcpBlock::Control(_) => 3,
+        }
+    }
+}
+
+/// Network management entity blocks of TSN domains. They are only parsed so
+/// they can be answered, the device doesn't take part in a TSN domain.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum NmeDomainBlock<'a> {
+    /// UUID of the TSN domain
+    Domain([u8; NME_UUID_LENGTH]),
+    Prio(u16),
+    ParameterUuid([u8; NME_UUID_LENGTH]),
+    Name(&'a [u8]),
+    CimInterface,
+}
+
+impl<'a> NmeDomainBlock<'a> {
+    fn parse(suboption: NMEDomainSuboption, payload: &'a [u8]) -> Result<Self, ParseDcpBlockError> {
+        let uuid = || {
+            payload
+                .get(..NME_UUID_LENGTH)
+                .and_then(|uuid| uuid.try_into().ok())
+                .ok_or(ParseDcpBlockError::InvalidBlockLength)
+        };
+
+        Ok(match suboption {
+            NMEDomainSuboption::NMEDomain => NmeDomainBlock::Domain(uuid()?),
+            NMEDomainSuboption::NMEPrio => NmeDomainBlock::Prio(
+                payload
+                    .get(..2)
+                    .map(NetworkEndian::read_u16)
+                    .ok_or(ParseDcpBlockError::InvalidBlockLength)?,
+            ),
+            NMEDomainSuboption::NMEParameterUUID => NmeDomainBlock::ParameterUuid(uuid()?),
+            NMEDomainSuboption::NMEName => NmeDomainBlock::Name(payload),
+            NMEDomainSuboption::CIMInterface => NmeDomainBlock::CimInterface,
+        })
+    }
+
+    fn suboption(&self) -> NMEDomainSuboption {
+        match self {
+            NmeDomainBlock::Domain(_) => NMEDomainSuboption::NMEDomain,
+            NmeDomainBlock::Prio(_) => NMEDomainSuboption::NMEPrio,
+            NmeDomainBlock::ParameterUuid(_) => NMEDomainSuboption::NMEParameterUUID,
+            NmeDomainBlock::Name(_) => NMEDomainSuboption::NMEName,
+            NmeDomainBlock::CimInterface => NMEDomainSuboption::CIMInterface,
+        }
+    }
+
+    fn encode_into(&self, buffer: &mut [u8]) {
+        buffer[SUBOPTION_FIELD] = self.suboption() as u8;
+        NetworkEndian::write_u16(&mut buffer[BLOCK_LENGTH_FIELD], self.block_length());
+
+        let mut writer = ByteWriter::new(&mut buffer[PAYLOAD_FIELD]);
+        match self {
+            NmeDomainBlock::Domain(uuid) | NmeDomainBlock::ParameterUuid(uuid) => {
+                writer.put_slice(uuid);
+            }
+            NmeDomainBlock::Prio(prio) => {
+                writer.put_u16_be(*prio);
+            }
+            NmeDomainBlock::Name(name) => {
+                writer.put_slice(name);
+            }
+            NmeDomainBlock::CimInterface => (),
+        }
+    }
+
+    fn block_length(&self) -> u16 {
+        let data_length = match self {
+            NmeDomainBlock::Domain(_) | NmeDomainBlock::ParameterUuid(_) => NME_UUID_LENGTH,
+            NmeDomainBlock::Prio(_) => 2,
+            NmeDomainBlock::Name(name) => name.len(),
+            NmeDomainBlock::CimInterface => 0,
+        };
+
+        // Block info and the data
+        data_length as u16 + 2
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct MacAddress {
     pub address: EthernetAddress,
 }
@@ -262,17 +627,57 @@ impl MacAddress {
     }
 }
 
+/// Block info of the IP parameter block, bit 0 marks the IP as set, bit 1
+/// marks it as set via DHCP and bit 7 reports an address conflict
 #[derive(Debug, Clone, Copy, PartialEq, TryFromPrimitive)]
-#[repr(u8)]
+#[repr(u16)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum IpParameterBlockInfo {
-    IpNotSet,
-    IpSetViaSetRequest,
-    IpSetViaSetRequestDuplicate,
-    IpSetViaDhcp,
-    IpSetViaDhcpDuplicate,
+    IpNotSet = 0x0000,
+    IpSetViaSetRequest = 0x0001,
+    IpSetViaDhcp = 0x0002,
+    IpNotSetDuplicate = 0x0080,
+    IpSetViaSetRequestDuplicate = 0x0081,
+    IpSetViaDhcpDuplicate = 0x0082,
+}
+
+impl IpParameterBlockInfo {
+    const IP_SET: u16 = 1 << 0;
+    const SET_VIA_DHCP: u16 = 1 << 1;
+    const ADDRESS_CONFLICT: u16 = 1 << 7;
+
+    pub fn new(ip_set: bool, set_via_dhcp: bool, address_conflict: bool) -> Self {
+        let mut raw = 0;
+
+        if set_via_dhcp {
+            raw |= Self::SET_VIA_DHCP;
+        } else if ip_set {
+            raw |= Self::IP_SET;
+        }
+
+        if address_conflict {
+            raw |= Self::ADDRESS_CONFLICT;
+        }
+
+        // Every combination of the bits above is a variant
+        Self::try_from_primitive(raw).unwrap()
+    }
+
+    pub fn ip_set(&self) -> bool {
+        *self as u16 & (Self::IP_SET | Self::SET_VIA_DHCP) != 0
+    }
+
+    pub fn set_via_dhcp(&self) -> bool {
+        *self as u16 & Self::SET_VIA_DHCP != 0
+    }
+
+    pub fn address_conflict(&self) -> bool {
+        *self as u16 & Self::ADDRESS_CONFLICT != 0
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct IpParameter {
     pub ip_address: Ipv4Address,
     pub subnet_mask: Ipv4Address,
@@ -306,6 +711,7 @@ impl IpParameter {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct FullIpSuite {
     pub ip_address: Ipv4Address,
     pub subnet_mask: Ipv4Address,
@@ -341,12 +747,13 @@ impl FullIpSuite {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
-pub enum DevicePropertiesBlock {
-    DeviceVendor(DeviceVendor),
-    NameOfStation(NameOfStation),
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DevicePropertiesBlock<'a> {
+    DeviceVendor(DeviceVendor<'a>),
+    NameOfStation(NameOfStation<'a>),
     DeviceId(DeviceId),
     DeviceRole(DeviceRole),
-    DeviceOptions,
+    DeviceOptions(DeviceOptions),
     AliasName,
     DeviceInstance(DeviceInstance),
     OemDeviceId,
@@ -354,71 +761,62 @@ pub enum DevicePropertiesBlock {
     RsiProperties,
 }
 
-impl DevicePropertiesBlock {
+impl DevicePropertiesBlock<'_> {
+    fn suboption(&self) -> DevicePropertiesSuboption {
+        match self {
+            DevicePropertiesBlock::DeviceVendor(_) => DevicePropertiesSuboption::DeviceVendor,
+            DevicePropertiesBlock::NameOfStation(_) => DevicePropertiesSuboption::NameOfStation,
+            DevicePropertiesBlock::DeviceId(_) => DevicePropertiesSuboption::DeviceId,
+            DevicePropertiesBlock::DeviceRole(_) => DevicePropertiesSuboption::DeviceRole,
+            DevicePropertiesBlock::DeviceOptions(_) => DevicePropertiesSuboption::DeviceOptions,
+            DevicePropertiesBlock::AliasName => DevicePropertiesSuboption::AliasName,
+            DevicePropertiesBlock::DeviceInstance(_) => DevicePropertiesSuboption::DeviceInstance,
+            DevicePropertiesBlock::OemDeviceId => DevicePropertiesSuboption::OemDeviceId,
+            DevicePropertiesBlock::StandardGateway => DevicePropertiesSuboption::StandardGateway,
+            DevicePropertiesBlock::RsiProperties => DevicePropertiesSuboption::RsiProperties,
+        }
+    }
+
     fn encode_into(&self, buffer: &mut [u8]) {
-        NetworkEndian::write_u16(&mut buffer[BLOCK_INFO_FIELD], 0);
+        // The option is written by the caller
+        let mut writer = ByteWriter::new(&mut buffer[SUBOPTION_FIELD..]);
+        writer.put_u8(self.suboption() as u8);
 
         match self {
             DevicePropertiesBlock::DeviceVendor(dv) => {
-                buffer[SUBOPTION_FIELD] = DevicePropertiesSuboption::DeviceVendor as u8;
-                NetworkEndian::write_u16(&mut buffer[BLOCK_LENGTH_FIELD], dv.length as u16 + 2);
-                dv.encode_into(&mut buffer[PAYLOAD_FIELD]);
+                writer.put_u16_be(dv.block_length()).put_u16_be(0);
+                dv.encode_into(&mut writer);
             }
             DevicePropertiesBlock::NameOfStation(nos) => {
-                buffer[SUBOPTION_FIELD] = DevicePropertiesSuboption::NameOfStation as u8;
-                NetworkEndian::write_u16(&mut buffer[BLOCK_LENGTH_FIELD], nos.length as u16 + 2);
-                nos.encode_into(&mut buffer[PAYLOAD_FIELD]);
+                writer.put_u16_be(nos.block_length()).put_u16_be(0);
+                nos.encode_into(&mut writer);
             }
             DevicePropertiesBlock::DeviceId(id) => {
-                buffer[SUBOPTION_FIELD] = DevicePropertiesSuboption::DeviceId as u8;
-                NetworkEndian::write_u16(
-                    &mut buffer[BLOCK_LENGTH_FIELD],
-                    mem::size_of::<DeviceId>() as u16 + 2,
-                );
-                id.encode_into(&mut buffer[PAYLOAD_FIELD]);
+                writer.put_u16_be(id.block_length()).put_u16_be(0);
+                id.encode_into(&mut writer);
             }
             DevicePropertiesBlock::DeviceRole(dr) => {
-                buffer[SUBOPTION_FIELD] = DevicePropertiesSuboption::DeviceRole as u8;
-                NetworkEndian::write_u16(&mut buffer[BLOCK_LENGTH_FIELD], 4);
-                buffer[PAYLOAD_FIELD.start] = *dr as u8;
-            }
-            DevicePropertiesBlock::DeviceOptions => {
-                buffer[SUBOPTION_FIELD] = DevicePropertiesSuboption::DeviceOptions as u8;
-                NetworkEndian::write_u16(&mut buffer[BLOCK_LENGTH_FIELD], 4);
-                buffer[PAYLOAD_FIELD.start] = 2;
-                buffer[PAYLOAD_FIELD.start + 1] = 7;
+                writer
+                    .put_u16_be(DEVICE_ROLE_LENGTH as u16 + 2)
+                    .put_u16_be(0)
+                    .put_u8(dr.0)
+                    .put_u8(0);
             }
-            DevicePropertiesBlock::AliasName => {
-                buffer[SUBOPTION_FIELD] = DevicePropertiesSuboption::AliasName as u8;
-                NetworkEndian::write_u16(&mut buffer[BLOCK_LENGTH_FIELD], 3);
-                buffer[PAYLOAD_FIELD.start] = 0;
+            DevicePropertiesBlock::DeviceOptions(options) => {
+                writer.put_u16_be(options.block_length()).put_u16_be(0);
+                options.encode_into(&mut writer);
             }
             DevicePropertiesBlock::DeviceInstance(di) => {
-                buffer[SUBOPTION_FIELD] = DevicePropertiesSuboption::DeviceInstance as u8;
-                NetworkEndian::write_u16(
-                    &mut buffer[BLOCK_LENGTH_FIELD],
-                    mem::size_of::<DeviceInstance>() as u16 + 2,
-                );
-                di.encode_into(&mut buffer[PAYLOAD_FIELD]);
-            }
-            DevicePropertiesBlock::OemDeviceId => {
-                buffer[SUBOPTION_FIELD] = DevicePropertiesSuboption::OemDeviceId as u8;
-                NetworkEndian::write_u16(&mut buffer[BLOCK_LENGTH_FIELD], 3);
-                buffer[PAYLOAD_FIELD.start] = 0;
+                writer.put_u16_be(di.block_length()).put_u16_be(0);
+                di.encode_into(&mut writer);
             }
-            DevicePropertiesBlock::StandardGateway => {
-                buffer[SUBOPTION_FIELD] = DevicePropertiesSuboption::StandardGateway as u8;
-                NetworkEndian::write_u16(&mut buffer[BLOCK_LENGTH_FIELD], 3);
-                buffer[PAYLOAD_FIELD.start] = 0;
-            }
-            DevicePropertiesBlock::RsiProperties => {
-                buffer[SUBOPTION_FIELD] = DevicePropertiesSuboption::RsiProperties as u8;
-                NetworkEndian::write_u16(&mut buffer[BLOCK_LENGTH_FIELD], 3);
-                buffer[PAYLOAD_FIELD.start] = 0;
+            DevicePropertiesBlock::AliasName
+            | DevicePropertiesBlock::OemDeviceId
+            | DevicePropertiesBlock::StandardGateway
+            | DevicePropertiesBlock::RsiProperties => {
+                writer.put_u16_be(3).put_u16_be(0).put_u8(0);
             }
         }
-
-        NetworkEndian::write_u16(&mut buffer[BLOCK_INFO_FIELD], 0);
     }
 
     fn block_length(&self) -> u16 {
@@ -427,108 +825,211 @@ impl DevicePropertiesBlock {
             DevicePropertiesBlock::NameOfStation(nos) => nos.block_length(),
             DevicePropertiesBlock::DeviceId(id) => id.block_length(),
             DevicePropertiesBlock::DeviceInstance(di) => di.block_length(),
-            DevicePropertiesBlock::DeviceOptions => 4,
+            DevicePropertiesBlock::DeviceRole(_) => DEVICE_ROLE_LENGTH as u16 + 2,
+            DevicePropertiesBlock::DeviceOptions(options) => options.block_length(),
             _ => mem::size_of::<u8>() as u16 + 2,
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub struct DeviceVendor {
-    pub vendor: [u8; MAX_DEVICE_VENDOR_LENGTH],
-    pub length: usize,
+/// `payload` if it is long enough for a block with `length` bytes of data
+fn check_payload(payload: &[u8], length: usize) -> Result<&[u8], ParseDcpBlockError> {
+    if payload.len() < length {
+        return Err(ParseDcpBlockError::InvalidBlockLength);
+    }
+
+    Ok(payload)
 }
 
-impl DeviceVendor {
-    pub fn new(vendor: [u8; MAX_DEVICE_VENDOR_LENGTH], length: usize) -> Self {
-        Self { vendor, length }
+fn check_block_string(str: &str, max_length: usize) -> Result<(), BlockStringError> {
+    if !str.is_ascii() {
+        return Err(BlockStringError::NotAscii);
     }
 
-    pub fn parse_bytes(buffer: &[u8], data_size: usize) -> Self {
-        let mut device_vendor = [0; MAX_DEVICE_VENDOR_LENGTH];
+    if str.len() > max_length {
+        return Err(BlockStringError::TooLong);
+    }
 
-        for i in 0..data_size {
-            device_vendor[i] = buffer[i];
-        }
+    Ok(())
+}
 
-        Self {
-            vendor: device_vendor,
-            length: data_size,
-        }
+/// The longest prefix of `str` that is ASCII and at most `max_length` long
+fn ascii_prefix(str: &str, max_length: usize) -> &[u8] {
+    let length = str
+        .bytes()
+        .take(max_length)
+        .take_while(u8::is_ascii)
+        .count();
+
+    &str.as_bytes()[..length]
+}
+
+/// Borrows the vendor from the received frame or the device configuration
+#[derive(Clone, Copy, PartialEq)]
+pub struct DeviceVendor<'a> {
+    vendor: &'a [u8],
+}
+
+impl<'a> DeviceVendor<'a> {
+    pub fn new(vendor: &'a [u8]) -> Self {
+        Self { vendor }
     }
 
-    pub fn from_str(str: &str) -> Self {
-        let mut device_vendor = [0; MAX_DEVICE_VENDOR_LENGTH];
-        let str_bytes = str.as_bytes();
+    pub fn parse_bytes(buffer: &'a [u8], data_size: usize) -> Self {
+        Self::new(&buffer[..data_size])
+    }
 
-        for i in 0..str.len() {
-            device_vendor[i] = str_bytes[i] as u8;
-        }
+    /// Like `try_from_str`, but cuts off anything after `MAX_DEVICE_VENDOR_LENGTH`
+    /// characters or from the first non-ASCII character on
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(str: &'a str) -> Self {
+        Self::new(ascii_prefix(str, MAX_DEVICE_VENDOR_LENGTH))
+    }
 
-        Self {
-            vendor: device_vendor,
-            length: str_bytes.len(),
-        }
+    pub fn try_from_str(str: &'a str) -> Result<Self, BlockStringError> {
+        check_block_string(str, MAX_DEVICE_VENDOR_LENGTH)?;
+
+        Ok(Self::new(str.as_bytes()))
     }
 
-    fn encode_into(&self, buffer: &mut [u8]) {
-        buffer[..self.length].copy_from_slice(&self.vendor[..self.length]);
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.vendor
+    }
+
+    pub fn len(&self) -> usize {
+        self.vendor.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vendor.is_empty()
+    }
+
+    fn encode_into(&self, writer: &mut ByteWriter) {
+        writer.put_slice(self.vendor);
     }
 
     fn block_length(&self) -> u16 {
-        self.length as u16 + 2
+        self.len() as u16 + 2
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub struct NameOfStation {
-    pub name: [u8; MAX_NAME_OF_STATION_LENGTH],
-    pub length: usize,
+/// Prints the string instead of the bytes
+impl fmt::Debug for DeviceVendor<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match core::str::from_utf8(self.vendor) {
+            Ok(str) => f.debug_tuple("DeviceVendor").field(&str).finish(),
+            Err(_) => f.debug_tuple("DeviceVendor").field(&self.vendor).finish(),
+        }
+    }
 }
 
-impl NameOfStation {
-    pub fn new(name: [u8; MAX_NAME_OF_STATION_LENGTH], length: usize) -> Self {
-        Self { name, length }
+#[cfg(feature = "defmt")]
+impl defmt::Format for DeviceVendor<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "DeviceVendor({=[u8]:a})", self.vendor);
     }
+}
 
-    pub fn parse_bytes(buffer: &[u8], data_size: usize) -> Self {
-        let mut name_of_station = [0; MAX_NAME_OF_STATION_LENGTH];
+/// Borrows the name from the received frame or the device configuration
+#[derive(Clone, Copy, PartialEq)]
+pub struct NameOfStation<'a> {
+    name: &'a [u8],
+}
 
-        for i in 0..data_size {
-            name_of_station[i] = buffer[i];
-        }
+impl<'a> NameOfStation<'a> {
+    pub fn new(name: &'a [u8]) -> Self {
+        Self { name }
+    }
 
-        Self {
-            name: name_of_station,
-            length: data_size,
-        }
+    pub fn parse_bytes(buffer: &'a [u8], data_size: usize) -> Self {
+        Self::new(&buffer[..data_size])
     }
 
-    pub fn from_str(str: &str) -> Self {
-        let mut name_of_station = [0; MAX_NAME_OF_STATION_LENGTH];
-        let name_of_station_bytes = str.as_bytes();
+    /// Like `try_from_str`, but cuts off anything after `MAX_NAME_OF_STATION_LENGTH`
+    /// characters or from the first non-ASCII character on
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(str: &'a str) -> Self {
+        Self::new(ascii_prefix(str, MAX_NAME_OF_STATION_LENGTH))
+    }
 
-        for i in 0..name_of_station_bytes.len() {
-            name_of_station[i] = name_of_station_bytes[i] as u8
-        }
+    pub fn try_from_str(str: &'a str) -> Result<Self, BlockStringError> {
+        check_block_string(str, MAX_NAME_OF_STATION_LENGTH)?;
 
-        Self {
-            name: name_of_station,
-            length: name_of_station_bytes.len(),
-        }
+        Ok(Self::new(str.as_bytes()))
     }
 
-    fn encode_into(&self, buffer: &mut [u8]) {
-        buffer[..self.length].copy_from_slice(&self.name[..self.length]);
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.name
+    }
+
+    /// The name as a string, fails for names that aren't valid UTF-8
+    pub fn as_str(&self) -> Result<&'a str, Utf8Error> {
+        core::str::from_utf8(self.name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.name.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.name.is_empty()
+    }
+
+    fn encode_into(&self, writer: &mut ByteWriter) {
+        writer.put_slice(self.name);
     }
 
     fn block_length(&self) -> u16 {
-        self.length as u16 + 2
+        self.len() as u16 + 2
+    }
+}
+
+/// Prints the string instead of the bytes
+impl fmt::Debug for NameOfStation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.as_str() {
+            Ok(str) => f.debug_tuple("NameOfStation").field(&str).finish(),
+            Err(_) => f.debug_tuple("NameOfStation").field(&self.name).finish(),
+        }
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for NameOfStation<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "NameOfStation({=[u8]:a})", self.name);
+    }
+}
+
+/// The roles of a device, one bit each. A device can have several roles,
+/// for example an IO-Device that is an IO-Supervisor as well.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DeviceRole(pub u8);
+
+impl DeviceRole {
+    pub const IO_DEVICE: Self = Self(0x01);
+    pub const IO_CONTROLLER: Self = Self(0x02);
+    pub const IO_MULTIDEVICE: Self = Self(0x04);
+    pub const IO_SUPERVISOR: Self = Self(0x08);
+
+    /// Whether all roles of `role` are set
+    pub fn contains(&self, role: DeviceRole) -> bool {
+        self.0 & role.0 == role.0
+    }
+}
+
+impl BitOr for DeviceRole {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
     }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 #[repr(C)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct DeviceId {
     pub vendor_id: u16,
     pub device_id: u16,
@@ -545,18 +1046,18 @@ impl DeviceId {
         }
     }
 
-    fn encode_into(&self, buffer: &mut [u8]) {
-        NetworkEndian::write_u16(&mut buffer[0..2], self.vendor_id);
-        NetworkEndian::write_u16(&mut buffer[2..4], self.device_id);
+    fn encode_into(&self, writer: &mut ByteWriter) {
+        writer.put_u16_be(self.vendor_id).put_u16_be(self.device_id);
     }
 
     fn block_length(&self) -> u16 {
-        mem::size_of::<Self>() as u16 + 2
+        DEVICE_ID_LENGTH as u16 + 2
     }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 #[repr(C)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct DeviceInstance {
     pub high: u8,
     pub low: u8,
@@ -570,13 +1071,87 @@ impl DeviceInstance {
         Self { high, low }
     }
 
-    fn encode_into(&self, buffer: &mut [u8]) {
-        buffer[0] = self.high;
-        buffer[1] = self.low;
+    fn encode_into(&self, writer: &mut ByteWriter) {
+        writer.put_u8(self.high).put_u8(self.low);
     }
 
     fn block_length(&self) -> u16 {
-        mem::size_of::<Self>() as u16 + 2
+        DEVICE_INSTANCE_LENGTH as u16 + 2
+    }
+}
+
+/// The options and suboptions a device supports
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DeviceOptions {
+    options: [(u8, u8); MAX_DEVICE_OPTIONS],
+    length: usize,
+}
+
+impl Default for DeviceOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeviceOptions {
+    pub const fn new() -> Self {
+        Self {
+            options: [(0, 0); MAX_DEVICE_OPTIONS],
+            length: 0,
+        }
+    }
+
+    /// Like `new` followed by `push` for each pair
+    pub fn from_pairs(pairs: &[(u8, u8)]) -> Result<Self, EncodeError> {
+        let mut options = Self::new();
+        for &(option, suboption) in pairs {
+            options.push(option, suboption)?;
+        }
+
+        Ok(options)
+    }
+
+    /// Adds a supported suboption, fails once `MAX_DEVICE_OPTIONS` are stored
+    pub fn push(&mut self, option: u8, suboption: u8) -> Result<(), EncodeError> {
+        let slot = self
+            .options
+            .get_mut(self.length)
+            .ok_or(EncodeError::TooManyDeviceOptions)?;
+        *slot = (option, suboption);
+        self.length += 1;
+
+        Ok(())
+    }
+
+    pub fn as_slice(&self) -> &[(u8, u8)] {
+        &self.options[..self.length]
+    }
+
+    pub fn contains(&self, option: u8, suboption: u8) -> bool {
+        self.as_slice().contains(&(option, suboption))
+    }
+
+    pub fn parse_bytes(buffer: &[u8], data_size: usize) -> Self {
+        let mut options = Self::new();
+        // A device announcing more options than fit keeps the first ones
+        for pair in buffer[..data_size].chunks_exact(2) {
+            if options.push(pair[0], pair[1]).is_err() {
+                break;
+            }
+        }
+
+        options
+    }
+
+    fn encode_into(&self, writer: &mut ByteWriter) {
+        for &(option, suboption) in self.as_slice() {
+            writer.put_u8(option).put_u8(suboption);
+        }
+    }
+
+    fn block_length(&self) -> u16 {
+        (self.length * 2) as u16 + 2
     }
 }
 
@@ -587,10 +1162,10 @@ mod tests {
     #[test]
     fn test_device_vendor_as_bytes() {
         let device_vendor = DeviceVendor::from_str("device vendor 123");
-        assert_eq!(device_vendor.length, 17);
+        assert_eq!(device_vendor.len(), 17);
 
         let mut buffer = [0; 17];
-        device_vendor.encode_into(&mut buffer);
+        device_vendor.encode_into(&mut ByteWriter::new(&mut buffer));
 
         assert_eq!(
             buffer,
@@ -601,25 +1176,304 @@ mod tests {
     #[test]
     fn test_device_instance_as_bytes() {
         let device_instance = DeviceInstance { high: 123, low: 42 };
-        let mut buffer = [0; mem::size_of::<DeviceInstance>()];
+        let mut buffer = [0; DEVICE_INSTANCE_LENGTH];
 
-        device_instance.encode_into(&mut buffer);
+        device_instance.encode_into(&mut ByteWriter::new(&mut buffer));
 
         assert_eq!(buffer, [123, 42])
     }
 
+    #[test]
+    fn test_ip_parameter_block_info_round_trip() {
+        let infos = [
+            IpParameterBlockInfo::IpNotSet,
+            IpParameterBlockInfo::IpSetViaSetRequest,
+            IpParameterBlockInfo::IpSetViaDhcp,
+            IpParameterBlockInfo::IpNotSetDuplicate,
+            IpParameterBlockInfo::IpSetViaSetRequestDuplicate,
+            IpParameterBlockInfo::IpSetViaDhcpDuplicate,
+        ];
+
+        for block_info in infos {
+            let block = Block::Ip(IpBlock::IpParameter(IpParameter {
+                ip_address: Ipv4Address::new(192, 168, 0, 2),
+                subnet_mask: Ipv4Address::new(255, 255, 255, 0),
+                gateway: Ipv4Address::new(192, 168, 0, 1),
+                block_info,
+            }));
+            let dcp_block = DcpBlock::new(block);
+
+            let mut buffer = [0; 18];
+            dcp_block.encode_into(&mut buffer);
+
+            assert_eq!(
+                NetworkEndian::read_u16(&buffer[BLOCK_INFO_FIELD]),
+                block_info as u16
+            );
+            assert_eq!(DcpBlock::parse_block(&buffer).unwrap().block, block);
+            assert_eq!(
+                IpParameterBlockInfo::new(
+                    block_info.ip_set(),
+                    block_info.set_via_dhcp(),
+                    block_info.address_conflict()
+                ),
+                block_info
+            );
+        }
+    }
+
     #[test]
     fn test_name_of_station_as_bytes() {
         let name_of_station = NameOfStation::from_str("test name");
-        assert_eq!(name_of_station.length, 9);
+        assert_eq!(name_of_station.len(), 9);
         assert_eq!(name_of_station.block_length(), 11);
 
         let mut buffer = [0; 20];
-        name_of_station.encode_into(&mut buffer);
+        name_of_station.encode_into(&mut ByteWriter::new(&mut buffer));
 
         assert_eq!(
             buffer,
             [116, 101, 115, 116, 32, 110, 97, 109, 101, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
         )
     }
+
+    #[test]
+    fn test_parse_block_qualifier() {
+        let raw_block = [
+            0x02, 0x02, 0x00, 0x08, 0x00, 0x01, b'd', b'e', b'v', b'i', b'c', b'e',
+        ];
+
+        let block = DcpBlock::parse_block(&raw_block).unwrap();
+
+        assert_eq!(block.qualifier, BlockQualifier(0x0001));
+        assert!(block.qualifier.is_permanent());
+        assert!(!block.qualifier.has_reserved_bits());
+        assert_eq!(
+            block.block,
+            Block::DeviceProperties(DevicePropertiesBlock::NameOfStation(
+                NameOfStation::from_str("device")
+            ))
+        );
+
+        let temporary = BlockQualifier(0x0000);
+        assert!(!temporary.is_permanent());
+        assert!(BlockQualifier(0x0002).has_reserved_bits());
+    }
+
+    #[test]
+    fn test_from_str_length() {
+        let vendor = "v".repeat(MAX_DEVICE_VENDOR_LENGTH);
+        assert_eq!(
+            DeviceVendor::try_from_str(&vendor).unwrap().len(),
+            MAX_DEVICE_VENDOR_LENGTH
+        );
+        let vendor = "v".repeat(MAX_DEVICE_VENDOR_LENGTH + 1);
+        assert_eq!(
+            DeviceVendor::try_from_str(&vendor),
+            Err(BlockStringError::TooLong)
+        );
+        assert_eq!(
+            DeviceVendor::from_str(&vendor).len(),
+            MAX_DEVICE_VENDOR_LENGTH
+        );
+
+        let name = "n".repeat(MAX_NAME_OF_STATION_LENGTH);
+        assert_eq!(
+            NameOfStation::try_from_str(&name).unwrap().len(),
+            MAX_NAME_OF_STATION_LENGTH
+        );
+        let name = "n".repeat(MAX_NAME_OF_STATION_LENGTH + 1);
+        assert_eq!(
+            NameOfStation::try_from_str(&name),
+            Err(BlockStringError::TooLong)
+        );
+        assert_eq!(
+            NameOfStation::from_str(&name).len(),
+            MAX_NAME_OF_STATION_LENGTH
+        );
+    }
+
+    #[test]
+    fn test_from_str_non_ascii() {
+        assert_eq!(
+            NameOfStation::try_from_str("gerät"),
+            Err(BlockStringError::NotAscii)
+        );
+        assert_eq!(
+            DeviceVendor::try_from_str("Müller"),
+            Err(BlockStringError::NotAscii)
+        );
+
+        let name = NameOfStation::from_str("gerät");
+        assert_eq!(name.as_bytes(), b"ger");
+    }
+
+    #[test]
+    fn test_parse_control_response() {
+        let raw_block = [0x05, 0x04, 0x00, 0x03, 0x02, 0x02, 0x00];
+
+        let block = DcpBlock::parse_block(&raw_block).unwrap();
+        assert_eq!(
+            block.block,
+            Block::ControlResponse(ControlResponse {
+                option: 0x02,
+                suboption: 0x02,
+                error: DcpBlockError::NoError,
+            })
+        );
+
+        let mut buffer = [0; 7];
+        block.block.encode_into(&mut buffer);
+        assert_eq!(buffer, raw_block);
+
+        let unknown_error = [0x05, 0x04, 0x00, 0x03, 0x01, 0x02, 0x42];
+        assert!(matches!(
+            DcpBlock::parse_block(&unknown_error),
+            Err(ParseDcpBlockError::InvalidBlockError)
+        ));
+
+        let truncated = [0x05, 0x04, 0x00, 0x02, 0x02, 0x02];
+        assert!(matches!(
+            DcpBlock::parse_block(&truncated),
+            Err(ParseDcpBlockError::InvalidBlockLength)
+        ));
+    }
+
+    #[test]
+    fn test_name_of_station_as_str() {
+        let name = NameOfStation::from_str("device");
+        assert_eq!(name.as_bytes(), b"device");
+        assert_eq!(name.as_str(), Ok("device"));
+
+        let garbage = NameOfStation::parse_bytes(&[b'd', 0xff, b'v'], 3);
+        assert!(garbage.as_str().is_err());
+    }
+
+    #[test]
+    fn test_parse_bytes_borrows_data_size() {
+        let buffer = *b"dev\xff";
+
+        assert_eq!(
+            NameOfStation::parse_bytes(&buffer, 3),
+            NameOfStation::from_str("dev")
+        );
+        assert_ne!(
+            NameOfStation::parse_bytes(&buffer, 4),
+            NameOfStation::from_str("dev")
+        );
+        assert_eq!(
+            DeviceVendor::parse_bytes(&buffer, 3),
+            DeviceVendor::from_str("dev")
+        );
+    }
+
+    #[test]
+    fn test_block_size() {
+        // Names and vendors are borrowed, a block used to hold both inline
+        assert!(mem::size_of::<DcpBlock>() <= 80);
+    }
+
+    #[test]
+    fn test_debug_strings() {
+        assert_eq!(
+            format!("{:?}", NameOfStation::from_str("device")),
+            "NameOfStation(\"device\")"
+        );
+        assert_eq!(
+            format!("{:?}", DeviceVendor::from_str("vendor")),
+            "DeviceVendor(\"vendor\")"
+        );
+    }
+
+    #[test]
+    fn test_parse_nme_domain_block() {
+        let mut raw_block = [0; 22];
+        raw_block[..6].copy_from_slice(&[0x07, 0x01, 0x00, 0x12, 0x00, 0x00]);
+        raw_block[6..].copy_from_slice(&[0xab; NME_UUID_LENGTH]);
+
+        let block = DcpBlock::parse_block(&raw_block).unwrap();
+        assert_eq!(
+            block.block,
+            Block::NmeDomain(NmeDomainBlock::Domain([0xab; NME_UUID_LENGTH]))
+        );
+
+        let mut buffer = [0; 22];
+        block.block.encode_into(&mut buffer);
+        assert_eq!(buffer, raw_block);
+
+        let name = [0x07, 0x04, 0x00, 0x05, 0x00, 0x00, b't', b's', b'n'];
+        assert_eq!(
+            DcpBlock::parse_block(&name).unwrap().block,
+            Block::NmeDomain(NmeDomainBlock::Name(b"tsn"))
+        );
+
+        // Too short for the UUID
+        let truncated = [0x07, 0x01, 0x00, 0x04, 0x00, 0x00, 0xab, 0xab];
+        assert!(matches!(
+            DcpBlock::parse_block(&truncated),
+            Err(ParseDcpBlockError::InvalidBlockLength)
+        ));
+
+        let unknown = [0x07, 0x06, 0x00, 0x02, 0x00, 0x00];
+        assert!(matches!(
+            DcpBlock::parse_block(&unknown),
+            Err(ParseDcpBlockError::InvalidNMEDomainSuboption)
+        ));
+    }
+
+    #[test]
+    fn test_device_role() {
+        let role = DeviceRole::IO_DEVICE | DeviceRole::IO_SUPERVISOR;
+        assert!(role.contains(DeviceRole::IO_DEVICE));
+        assert!(role.contains(DeviceRole::IO_SUPERVISOR));
+        assert!(!role.contains(DeviceRole::IO_CONTROLLER));
+
+        let block = Block::DeviceProperties(DevicePropertiesBlock::DeviceRole(role));
+        let mut buffer = [0; 8];
+        DcpBlock::new(block).encode_into(&mut buffer);
+        assert_eq!(buffer, [0x02, 0x04, 0x00, 0x04, 0x00, 0x00, 0x09, 0x00]);
+        assert_eq!(DcpBlock::parse_block(&buffer).unwrap().block, block);
+
+        // The reserved byte is part of the block
+        let without_reserved = [0x02, 0x04, 0x00, 0x03, 0x00, 0x00, 0x09];
+        assert!(matches!(
+            DcpBlock::parse_block(&without_reserved),
+            Err(ParseDcpBlockError::InvalidBlockLength)
+        ));
+    }
+
+    #[test]
+    fn test_parse_dhcp_block() {
+        let raw_block = [0x03, 0xff, 0x00, 0x03, 0x00, 0x01, 0x01];
+
+        let block = DcpBlock::parse_block(&raw_block).unwrap();
+        assert_eq!(block.block, Block::Dhcp(DhcpBlock::Control(true)));
+
+        let mut buffer = [0; 7];
+        block.block.encode_into(&mut buffer);
+        assert_eq!(buffer, [0x03, 0xff, 0x00, 0x03, 0x00, 0x00, 0x01]);
+
+        let unsupported = [0x03, 0x0c, 0x00, 0x03, 0x00, 0x01, 0x01];
+        assert!(matches!(
+            DcpBlock::parse_block(&unsupported),
+            Err(ParseDcpBlockError::InvalidDHCPPropertySuboption)
+        ));
+    }
+
+    #[test]
+    fn test_too_many_device_options() {
+        let pairs = [(0x02, 0x07); MAX_DEVICE_OPTIONS + 1];
+
+        let mut options = DeviceOptions::from_pairs(&pairs[..MAX_DEVICE_OPTIONS]).unwrap();
+        assert!(matches!(
+            options.push(0x01, 0x01),
+            Err(EncodeError::TooManyDeviceOptions)
+        ));
+        assert_eq!(options.as_slice().len(), MAX_DEVICE_OPTIONS);
+
+        assert!(matches!(
+            DeviceOptions::from_pairs(&pairs),
+            Err(EncodeError::TooManyDeviceOptions)
+        ));
+    }
 }
@@ -9,7 +9,7 @@ pub enum BlockOption {
     Control = 5,
     DeviceInitiative = 6,
     NMEDomain = 7,
-    #[num_enum(alternatives = [0x81..0xfe])] // 0x80..0xfe
+    #[num_enum(alternatives = [0x81..=0xfe])]
     ManufacturerSpecific = 0x80,
     All = 255,
 }
@@ -36,7 +36,8 @@ pub enum DevicePropertiesSuboption {
     RsiProperties = 10,
 }
 
-#[derive(TryFromPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, TryFromPrimitive)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u8)]
 pub enum ControlSuboption {
     Start = 1,
@@ -47,6 +48,20 @@ pub enum ControlSuboption {
     ResetToFactory = 6,
 }
 
+/// Result of a Set or Get reported in a control response block
+#[derive(Debug, Clone, Copy, TryFromPrimitive, PartialEq)]
+#[repr(u8)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DcpBlockError {
+    NoError = 0,
+    OptionNotSupported = 1,
+    SuboptionNotSupported = 2,
+    SuboptionNotSet = 3,
+    ResourceError = 4,
+    SetNotPossible = 5,
+    InOperation = 6,
+}
+
 #[derive(TryFromPrimitive)]
 #[repr(u8)]
 pub enum DeviceInitiativeSuboption {
@@ -89,15 +104,6 @@ pub enum DHCPSuboption {
     UUIDBasedClient = 97,
 }
 
-#[derive(Debug, Clone, Copy, TryFromPrimitive, PartialEq)]
-#[repr(u8)]
-pub enum DeviceRole {
-    IODevice,
-    IOController,
-    IOMultidevice,
-    IOSupervisor,
-}
-
 #[derive(TryFromPrimitive)]
 #[repr(u8)]
 pub enum DhcpSuboptions {
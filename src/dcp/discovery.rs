@@ -0,0 +1,61 @@
+// What an Identify response or Hello tells about the device that sent it
+
+use smoltcp::wire::{EthernetAddress, Ipv4Address};
+
+use super::block::*;
+use super::Dcp;
+
+/// A device found by an Identify request, the blocks it didn't send are `None`
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DiscoveredDevice<'a> {
+    pub mac_address: EthernetAddress,
+    pub name_of_station: Option<NameOfStation<'a>>,
+    pub ip_address: Option<Ipv4Address>,
+    pub subnet_mask: Option<Ipv4Address>,
+    pub gateway: Option<Ipv4Address>,
+    pub device_id: Option<DeviceId>,
+    pub device_role: Option<DeviceRole>,
+}
+
+impl<'a> DiscoveredDevice<'a> {
+    /// Collects the device blocks of `dcp`, later blocks replace earlier ones
+    pub fn from_dcp<const N: usize>(dcp: &Dcp<'a, N>) -> Self {
+        let mut device = Self {
+            mac_address: dcp.source,
+            name_of_station: None,
+            ip_address: None,
+            subnet_mask: None,
+            gateway: None,
+            device_id: None,
+            device_role: None,
+        };
+
+        for block in dcp.blocks_iter() {
+            match block.block {
+                Block::DeviceProperties(DevicePropertiesBlock::NameOfStation(name)) => {
+                    device.name_of_station = Some(name)
+                }
+                Block::DeviceProperties(DevicePropertiesBlock::DeviceId(id)) => {
+                    device.device_id = Some(id)
+                }
+                Block::DeviceProperties(DevicePropertiesBlock::DeviceRole(role)) => {
+                    device.device_role = Some(role)
+                }
+                Block::Ip(IpBlock::IpParameter(ip)) => {
+                    device.ip_address = Some(ip.ip_address);
+                    device.subnet_mask = Some(ip.subnet_mask);
+                    device.gateway = Some(ip.gateway);
+                }
+                Block::Ip(IpBlock::FullIpSuite(suite)) => {
+                    device.ip_address = Some(suite.ip_address);
+                    device.subnet_mask = Some(suite.subnet_mask);
+                    device.gateway = Some(suite.gateway);
+                }
+                _ => (),
+            }
+        }
+
+        device
+    }
+}
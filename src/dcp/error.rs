@@ -1,13 +1,22 @@
-use defmt::Format;
+use super::DcpBlockError;
+use crate::ethernet::EthernetError;
 
-#[derive(Debug, Format)]
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum ParseDcpError {
+    /// The bytes are not a complete Ethernet frame
+    EthernetError(EthernetError),
+    /// The EtherType is not PROFINET
+    NotProfinet,
     FrameIdError,
     HeaderError(ParseDcpHeaderError),
     BlockError(ParseDcpBlockError),
+    /// The frame has more blocks than the `Dcp` has room for
+    TooManyBlocks,
 }
 
-#[derive(Debug, Format)]
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum ParseDcpHeaderError {
     InvalidHeaderLength,
     InvalidFrameID,
@@ -15,7 +24,8 @@ pub enum ParseDcpHeaderError {
     InvalidServiceType,
 }
 
-#[derive(Debug, Format)]
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum ParseDcpBlockError {
     InvalidBlockOption,
     InvalidIPSuboption,
@@ -27,7 +37,63 @@ pub enum ParseDcpBlockError {
     InvalidNMEDomainSuboption,
     InvalidManufacturerSpecificSuboption,
     InvalidAllSuboption,
-    InvalidDeviceRole,
     InvalidIpParameterBlockInfo,
+    InvalidBlockError,
     BlockNotSupported,
+    /// The block length points past the end of the frame
+    InvalidBlockLength,
+}
+
+impl ParseDcpBlockError {
+    /// The error reported back for a Set block that failed to parse
+    pub fn block_error(&self) -> DcpBlockError {
+        match self {
+            Self::InvalidBlockOption | Self::BlockNotSupported => DcpBlockError::OptionNotSupported,
+            Self::InvalidIPSuboption
+            | Self::InvalidDevicePropertySuboption
+            | Self::InvalidDHCPPropertySuboption
+            | Self::InvalidControlSuboption
+            | Self::InvalidDeviceInitiativeSuboption
+            | Self::InvalidNMEDomainSuboption
+            | Self::InvalidManufacturerSpecificSuboption
+            | Self::InvalidAllSuboption => DcpBlockError::SuboptionNotSupported,
+            _ => DcpBlockError::SetNotPossible,
+        }
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EncodeError {
+    BufferTooSmall,
+    /// DCP frames can only be sent with the PROFINET EtherType
+    InvalidEthType,
+    /// The frame has no room for another block
+    TooManyBlocks,
+    /// The blocks are longer than the 16 bit data length of the header can count
+    DataTooLong,
+    /// A DeviceOptions block has no room for another option
+    TooManyDeviceOptions,
+}
+
+/// A string that can't be stored in a DeviceVendor or NameOfStation block
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BlockStringError {
+    /// Longer than the block allows
+    TooLong,
+    /// Only ASCII is allowed on the wire
+    NotAscii,
+}
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum NameError {
+    Empty,
+    TooLong,
+    InvalidLabelLength,
+    InvalidCharacter,
+    InvalidHyphen,
+    PortName,
+    IpAddress,
 }
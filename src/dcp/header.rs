@@ -2,6 +2,7 @@ use byteorder::{ByteOrder, NetworkEndian};
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 
 use crate::field::{Field, Rest, SmallField};
+use crate::util::ByteWriter;
 
 use super::error::ParseDcpHeaderError;
 
@@ -15,6 +16,7 @@ pub const DCP_HEADER_LENGTH_FIELD: usize = PAYLOAD_FIELD.start;
 
 #[derive(Debug, PartialEq, Clone, TryFromPrimitive, IntoPrimitive)]
 #[repr(u8)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum ServiceType {
     Request = 0,
     Success = 1,
@@ -23,6 +25,7 @@ pub enum ServiceType {
 
 #[derive(Debug, PartialEq, Clone, TryFromPrimitive, IntoPrimitive)]
 #[repr(u8)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum ServiceId {
     Get = 3,
     Set = 4,
@@ -47,10 +50,13 @@ impl<T: AsRef<[u8]>> DcpHeaderFrame<T> {
         }
     }
 
+    /// Whether the buffer holds the header and the `data_length` bytes of
+    /// blocks it announces, anything after them is padding
     pub fn check_len(&self) -> bool {
         let len = self.buffer.as_ref().len();
 
-        len > DCP_HEADER_LENGTH_FIELD
+        len >= DCP_HEADER_LENGTH_FIELD
+            && len - DCP_HEADER_LENGTH_FIELD >= self.data_length() as usize
     }
 
     pub fn service_id(&self) -> Result<ServiceId, ParseDcpHeaderError> {
@@ -86,6 +92,8 @@ impl<T: AsRef<[u8]>> DcpHeaderFrame<T> {
     }
 }
 
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct DcpHeader {
     pub service_id: ServiceId,
     pub service_type: ServiceType,
@@ -121,18 +129,30 @@ impl DcpHeader {
     }
 
     pub fn encode_into(&self, buffer: &mut [u8]) {
-        buffer[SERVICE_ID_FIELD] = self.service_id.clone().into();
-        buffer[SERVICE_TYPE_FIELD] = self.service_type.clone().into();
-        NetworkEndian::write_u32(&mut buffer[X_ID_FIELD], self.x_id);
-        NetworkEndian::write_u16(&mut buffer[RESPONSE_DELAY_FIELD], 0);
-        NetworkEndian::write_u16(&mut buffer[DATA_LENGTH_FIELD], self.data_length);
+        // Responses never ask for a delay
+        let response_delay_factor = match self.service_type {
+            ServiceType::Request => self.response_delay_factor,
+            _ => 0,
+        };
+
+        let mut writer = ByteWriter::new(buffer);
+        writer
+            .put_u8(self.service_id.clone().into())
+            .put_u8(self.service_type.clone().into())
+            .put_u32_be(self.x_id)
+            .put_u16_be(response_delay_factor)
+            .put_u16_be(self.data_length);
+
+        debug_assert_eq!(writer.written(), DCP_HEADER_LENGTH_FIELD);
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
-        dcp::header::{DcpHeaderFrame, ServiceId, ServiceType},
+        dcp::error::ParseDcpHeaderError,
+        dcp::header::{DcpHeaderFrame, ServiceId, ServiceType, DCP_HEADER_LENGTH_FIELD},
+        dcp::tests::IDENTIFY_RESPONSE,
         ethernet::EthernetFrame,
         DcpHeader,
     };
@@ -179,6 +199,50 @@ mod tests {
         // println!("Data Length: {}", dcp_header.data_length());
     }
 
+    #[test]
+    fn test_parse_identify_response_header() {
+        // A VLAN tagged Identify response of an S7-1200, the header starts
+        // with the ServiceID right after the FrameID
+        let packet = EthernetFrame::new_checked(IDENTIFY_RESPONSE).unwrap();
+        assert_eq!(packet.frame_id_u16(), 0xfeff);
+
+        let payload = packet.payload();
+        assert_eq!(payload[0], 0x05);
+
+        let dcp_header = DcpHeaderFrame::new_checked(payload).unwrap();
+        assert_eq!(dcp_header.service_id().unwrap(), ServiceId::Identify);
+        assert_eq!(dcp_header.service_type().unwrap(), ServiceType::Success);
+        assert_eq!(dcp_header.x_id(), 0x0166);
+        assert_eq!(dcp_header.response_delay(), 0);
+        assert_eq!(dcp_header.data_length(), 0x52);
+
+        // The blocks fill the rest of the frame
+        assert_eq!(
+            payload.len(),
+            DCP_HEADER_LENGTH_FIELD + dcp_header.data_length() as usize
+        );
+    }
+
+    #[test]
+    fn test_data_length_past_payload() {
+        let mut buffer = [0; DCP_HEADER_LENGTH_FIELD + 4];
+        buffer[..DCP_HEADER_LENGTH_FIELD]
+            .copy_from_slice(&[0x05, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 20]);
+
+        assert!(matches!(
+            DcpHeaderFrame::new_checked(&buffer[..]),
+            Err(ParseDcpHeaderError::InvalidHeaderLength)
+        ));
+        assert!(matches!(
+            DcpHeaderFrame::new_checked(&buffer[..DCP_HEADER_LENGTH_FIELD - 1]),
+            Err(ParseDcpHeaderError::InvalidHeaderLength)
+        ));
+
+        // A header without blocks
+        buffer[9] = 0;
+        assert!(DcpHeaderFrame::new_checked(&buffer[..DCP_HEADER_LENGTH_FIELD]).is_ok());
+    }
+
     #[test]
     fn test_encode_header() {
         let header = DcpHeader::new(ServiceId::Identify, ServiceType::Success, 1, 0);
@@ -0,0 +1,170 @@
+// Burst of Hello requests announcing the device at startup
+
+use super::Dcp;
+use crate::fspm::app::App;
+use crate::scheduler::{TaskCallback, TaskKind};
+use crate::time::Ticks;
+use crate::PNet;
+
+/// The delay between Hellos stops doubling at 16 times `dcp_hello_interval_us`
+const MAX_HELLO_BACKOFF_SHIFT: usize = 4;
+
+/// Progress of the current Hello burst
+#[derive(Clone, Copy, Default)]
+pub struct HelloState {
+    /// Identifies the current burst, scheduled Hellos of older bursts are ignored
+    burst: u16,
+    /// Hello requests sent in the current burst
+    count: usize,
+}
+
+impl HelloState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+impl Dcp<'_> {
+    /// Starts a burst of `dcp_hello_count` Hello requests.
+    ///
+    /// The first one is sent right away, the delay before each following one
+    /// starts at `dcp_hello_interval_us` and doubles every time. A running
+    /// burst is replaced.
+    pub fn start_hello<T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &mut PNet<T, U>,
+        current_time: Ticks,
+    ) {
+        let state = &mut pnet.dcp_hello;
+        state.burst = state.burst.wrapping_add(1);
+        state.count = 0;
+
+        let burst = state.burst;
+        Self::handle_hello(pnet, burst, current_time);
+    }
+
+    /// Scheduler callback, sends the next Hello of the burst and schedules the one after
+    pub(crate) fn handle_hello<T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &mut PNet<T, U>,
+        burst: u16,
+        current_time: Ticks,
+    ) {
+        let config = &pnet.fspm_user_config;
+        let (hello_count, interval) = (
+            config.dcp_hello_count,
+            Ticks::from_micros(config.dcp_hello_interval_us, config.tick_us),
+        );
+
+        let state = &pnet.dcp_hello;
+        if burst != state.burst || state.count >= hello_count {
+            return;
+        }
+
+        // The controller found us, no need to keep announcing
+        if pnet.is_connected() {
+            debug!("Connected, stopping DCP Hello");
+            return;
+        }
+
+        Self::send_hello(pnet, current_time);
+
+        pnet.dcp_hello.count += 1;
+        let count = pnet.dcp_hello.count;
+
+        if count < hello_count {
+            let delay = Ticks(
+                interval
+                    .0
+                    .saturating_mul(1 << (count - 1).min(MAX_HELLO_BACKOFF_SHIFT)),
+            );
+            pnet.scheduler
+                .add_task("dcp_hello", delay, TaskKind::DcpHello(burst), current_time);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmdev::CmdevState;
+    use crate::dcp::DCP_HELLO_MULTICAST;
+    use crate::util::{test_config, test_connect_request, TestApp, TestTask};
+
+    fn hello_pnet() -> PNet<'static, 'static, TestApp, TestTask> {
+        let mut config = test_config();
+        config.send_dcp_hello = true;
+        config.dcp_hello_count = 3;
+        config.dcp_hello_interval_us = 100_000;
+        PNet::new(config)
+    }
+
+    /// Takes the queued Hellos out of the outgoing queue
+    fn take_hellos(pnet: &mut PNet<TestApp, TestTask>) -> usize {
+        pnet.outgoing_packets
+            .iter_mut()
+            .filter(|p| p.is_some_and(|p| p.frame()[..6] == DCP_HELLO_MULTICAST))
+            .map(|p| p.take())
+            .count()
+    }
+
+    #[test]
+    fn test_hello_burst() {
+        let mut pnet = hello_pnet();
+
+        Dcp::start_hello(&mut pnet, Ticks(0));
+        assert_eq!(take_hellos(&mut pnet), 1);
+
+        pnet.run_scheduled_tasks(Ticks(99));
+        assert_eq!(take_hellos(&mut pnet), 0);
+        pnet.run_scheduled_tasks(Ticks(100));
+        assert_eq!(take_hellos(&mut pnet), 1);
+
+        // The delay doubles
+        pnet.run_scheduled_tasks(Ticks(299));
+        assert_eq!(take_hellos(&mut pnet), 0);
+        pnet.run_scheduled_tasks(Ticks(300));
+        assert_eq!(take_hellos(&mut pnet), 1);
+
+        pnet.run_scheduled_tasks(Ticks(10_000));
+        assert_eq!(take_hellos(&mut pnet), 0);
+        assert_eq!(pnet.dcp_hello.count(), 3);
+    }
+
+    #[test]
+    fn test_hello_burst_stops_when_connected() {
+        let mut pnet = hello_pnet();
+
+        Dcp::start_hello(&mut pnet, Ticks(0));
+        assert_eq!(take_hellos(&mut pnet), 1);
+
+        // An AR brought up by the controller, exchanging process data
+        let mut buffer = [0; 1024];
+        let (arep, _) = pnet.connect(&test_connect_request(), &mut buffer).unwrap();
+        pnet.run_scheduled_tasks(Ticks(100));
+        assert_eq!(take_hellos(&mut pnet), 1);
+
+        pnet.ars.get_mut(arep).unwrap().state = CmdevState::DataExchange;
+
+        pnet.run_scheduled_tasks(Ticks(10_000));
+        assert_eq!(take_hellos(&mut pnet), 0);
+        assert_eq!(pnet.dcp_hello.count(), 2);
+    }
+
+    #[test]
+    fn test_restarted_hello_burst() {
+        let mut pnet = hello_pnet();
+
+        Dcp::start_hello(&mut pnet, Ticks(0));
+        Dcp::start_hello(&mut pnet, Ticks(50));
+        assert_eq!(take_hellos(&mut pnet), 2);
+
+        // Only the second burst continues
+        pnet.run_scheduled_tasks(Ticks(100));
+        assert_eq!(take_hellos(&mut pnet), 0);
+        pnet.run_scheduled_tasks(Ticks(150));
+        assert_eq!(take_hellos(&mut pnet), 1);
+    }
+}
@@ -1,297 +1,426 @@
 // Discovery and basic configuration protocol
 
+use core::fmt;
+
 use byteorder::{ByteOrder, NetworkEndian};
 use num_enum::TryFromPrimitive;
 use smoltcp::wire::EthernetAddress;
 
+#[cfg(feature = "heapless")]
+use crate::constants::MAX_FRAME_LENGTH;
 use crate::ethernet::{EthType, EthernetFrame};
 use crate::field::{Field, Rest};
-use crate::PNet;
+use crate::time::Ticks;
 
 mod block;
 mod block_options;
+mod discovery;
 mod error;
 mod header;
+#[cfg(feature = "device")]
+mod hello;
+mod name;
+#[cfg(feature = "device")]
+mod service;
+#[cfg(feature = "device")]
+mod signal;
+mod view;
 
 pub use block::*;
 pub use block_options::*;
-pub use error::ParseDcpError;
+pub use discovery::*;
+pub use error::{BlockStringError, EncodeError, NameError, ParseDcpError};
 pub use header::*;
-
-pub const DCP_MAC_HELLO_ADDRESS: [u8; 6] = [0x01, 0x0e, 0xcf, 0x00, 0x00, 0x00];
+#[cfg(feature = "device")]
+pub use hello::*;
+pub use name::*;
+#[cfg(feature = "device")]
+pub use signal::*;
+pub use view::*;
+
+/// Multicast group of Identify requests
+pub const DCP_IDENTIFY_MULTICAST: [u8; 6] = [0x01, 0x0e, 0xcf, 0x00, 0x00, 0x00];
+/// Multicast group of the Hello announcements of devices
+pub const DCP_HELLO_MULTICAST: [u8; 6] = [0x01, 0x0e, 0xcf, 0x00, 0x00, 0x01];
 pub const MAX_DCP_BLOCK_NUMBER: usize = 32;
+/// Largest response delay factor allowed by the spec, spreading over 64s
+pub const MAX_RESPONSE_DELAY_FACTOR: u16 = 0x1900;
+/// Time one step of the response delay factor stands for in microseconds
+pub const RESPONSE_DELAY_STEP_US: usize = 10_000;
 
 const DESTINATION_FIELD: Field = 0..6;
 const SOURCE_FIELD: Field = 6..12;
 const TYPE_FIELD: Field = 12..14;
 const FRAME_ID_FIELD: Field = 14..16;
 const PAYLOAD_FIELD: Rest = 16..;
+const VLAN_TCI_FIELD: Field = 14..16;
+const VLAN_TAG_LENGTH: usize = 4;
 
 #[derive(Debug, PartialEq, Clone, TryFromPrimitive)]
 #[repr(u16)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum DcpFrameId {
     Hello = 0xfefc,
     GetSet = 0xfefd,
+    /// Identify request
     Request = 0xfefe,
+    /// Identify response, there is no DCP reset frame
     Response = 0xfeff,
 }
 
-pub struct Dcp {
+/// A DCP frame with room for `N` blocks. The default fits any frame the
+/// device sends or answers, use `with_capacity` for another size.
+pub struct Dcp<'a, const N: usize = MAX_DCP_BLOCK_NUMBER> {
     pub destination: EthernetAddress,
     pub source: EthernetAddress,
     pub eth_type: EthType,
+    /// Tag control information, the frame is VLAN tagged when set
+    pub vlan_tci: Option<u16>,
     pub frame_id: DcpFrameId,
     pub header: DcpHeader,
     pub number_of_blocks: usize,
-    pub blocks: [Option<DcpBlock>; MAX_DCP_BLOCK_NUMBER],
+    pub blocks: [Option<DcpBlock<'a>>; N],
 }
 
-impl Dcp {
+impl<'a> Dcp<'a> {
     pub fn new(
         destination: EthernetAddress,
         source: EthernetAddress,
         header: DcpHeader,
         frame_id: DcpFrameId,
+    ) -> Self {
+        Self::with_capacity(destination, source, header, frame_id)
+    }
+
+    /// Builds the Identify All request a controller sends to find the devices on the bus
+    pub fn new_identify_all(
+        source: EthernetAddress,
+        x_id: u32,
+        response_delay_factor: u16,
+    ) -> Self {
+        let mut dcp = Dcp::new(
+            EthernetAddress(DCP_IDENTIFY_MULTICAST),
+            source,
+            DcpHeader::new(
+                ServiceId::Identify,
+                ServiceType::Request,
+                x_id,
+                response_delay_factor,
+            ),
+            DcpFrameId::Request,
+        );
+        dcp.add_block(DcpBlock::new(Block::All));
+
+        dcp
+    }
+
+    /// Parses the frame including all of its blocks, see `DcpView` to parse
+    /// them on demand
+    pub fn parse<T: AsRef<[u8]>>(frame: &'a EthernetFrame<T>) -> Result<Self, ParseDcpError> {
+        Self::parse_with_capacity(frame)
+    }
+
+    /// Parses a raw Ethernet frame, for example one from a capture. The
+    /// frame may be VLAN tagged.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, ParseDcpError> {
+        Self::from_bytes_with_capacity(bytes)
+    }
+}
+
+impl<'a, const N: usize> Dcp<'a, N> {
+    /// Like `new`, but with room for `N` blocks
+    pub fn with_capacity(
+        destination: EthernetAddress,
+        source: EthernetAddress,
+        header: DcpHeader,
+        frame_id: DcpFrameId,
     ) -> Self {
         Self {
             destination,
             source,
             eth_type: EthType::Profinet,
+            vlan_tci: None,
             frame_id,
             header,
             number_of_blocks: 0,
-            blocks: [None; MAX_DCP_BLOCK_NUMBER],
+            blocks: [None; N],
         }
     }
 
-    pub fn new_hello_response(&self, pnet: &PNet) -> Self {
-        let response_dcp_header = DcpHeader::new(
-            ServiceId::Identify,
-            ServiceType::Success,
-            self.header.x_id,
-            1,
-        );
-        let mut response_dcp = Dcp::new(
-            self.source,
-            pnet.config.ip_config.mac_address,
-            response_dcp_header,
-            DcpFrameId::Response,
-        );
-
-        response_dcp.add_block(DcpBlock::new(Block::DeviceProperties(
-            DevicePropertiesBlock::DeviceOptions,
-        )));
-
-        response_dcp.add_block(DcpBlock::new(Block::DeviceProperties(
-            DevicePropertiesBlock::NameOfStation(NameOfStation::new(
-                pnet.config.name_of_station,
-                pnet.config.name_of_station_len,
-            )),
-        )));
-
-        response_dcp.add_block(DcpBlock::new(Block::DeviceProperties(
-            DevicePropertiesBlock::DeviceVendor(DeviceVendor::new(
-                pnet.config.device_vendor,
-                pnet.config.device_vendor_len,
-            )),
-        )));
+    /// The blocks of this frame, without the unused slots
+    pub fn blocks_iter(&self) -> impl Iterator<Item = &DcpBlock<'a>> {
+        self.blocks[..self.number_of_blocks].iter().flatten()
+    }
 
-        response_dcp.add_block(DcpBlock::new(Block::DeviceProperties(
-            DevicePropertiesBlock::DeviceRole(DeviceRole::IODevice),
-        )));
+    /// Appends `block`, a block that doesn't fit is dropped. See `try_add_block`.
+    pub fn add_block(&mut self, block: DcpBlock<'a>) -> &mut Self {
+        if let Err(e) = self.try_add_block(block) {
+            error!("Dropped DCP block: {}", e);
+        }
 
-        response_dcp.add_block(DcpBlock::new(Block::DeviceProperties(
-            DevicePropertiesBlock::DeviceId(DeviceId {
-                vendor_id: 0x1337,
-                device_id: 0x6969,
-            }),
-        )));
+        self
+    }
 
-        response_dcp.add_block(DcpBlock::new(Block::DeviceProperties(
-            DevicePropertiesBlock::DeviceInstance(DeviceInstance {
-                high: 0x42,
-                low: 0x69,
-            }),
-        )));
+    /// Appends `block` and counts its bytes in the data length of the header.
+    ///
+    /// Fails with `TooManyBlocks` if all `N` blocks are in use and with
+    /// `DataTooLong` if the data length would overflow, the frame is left
+    /// unchanged then.
+    pub fn try_add_block(&mut self, block: DcpBlock<'a>) -> Result<&mut Self, EncodeError> {
+        let slot = self
+            .blocks
+            .get_mut(self.number_of_blocks)
+            .ok_or(EncodeError::TooManyBlocks)?;
+        let data_length = self
+            .header
+            .data_length
+            .checked_add(block.block_length)
+            .ok_or(EncodeError::DataTooLong)?;
+
+        *slot = Some(block);
+        self.number_of_blocks += 1;
+        self.header.data_length = data_length;
 
-        response_dcp.add_block(DcpBlock::new(Block::Ip(IpBlock::IpParameter(
-            IpParameter {
-                ip_address: pnet.config.ip_config.ip_address,
-                subnet_mask: pnet.config.ip_config.subnet_mask,
-                gateway: pnet.config.ip_config.gateway,
-                block_info: IpParameterBlockInfo::IpNotSet,
-            },
-        ))));
-
-        response_dcp
-    }
-
-    pub fn handle_frame<T: AsRef<[u8]>>(
-        pnet: &mut PNet,
-        frame: EthernetFrame<T>,
-        current_timestamp: usize,
-    ) {
-        let Ok(request_dcp) = Dcp::parse(&frame) else {
-            defmt::debug!("Failed to parse DCP packet");
-            return;
-        };
+        Ok(self)
+    }
 
-        defmt::debug!("Successfully parsed frame to DCP packet");
-
-        match request_dcp.frame_id {
-            DcpFrameId::Request => {
-                if request_dcp.dst_is_hello() && request_dcp.number_of_blocks > 0 {
-                    let Some(hello_block) = request_dcp.blocks[0] else {
-                        defmt::debug!("DCP packet does not contain a Hello block");
-                        return;
-                    };
-                    if hello_block.block == Block::All {
-                        defmt::debug!("Recieved Hello DCP request, creating response");
-                        let response_dcp = request_dcp.new_hello_response(pnet);
-                        let mut response_buffer = [0; 255];
-                        response_dcp.encode_into(&mut response_buffer);
-
-                        let response_delay_time = request_dcp.response_delay_time();
-
-                        defmt::debug!("Adding response DCP request to outgoing buffer");
-                        pnet.queue_packet(response_buffer, current_timestamp + response_delay_time)
-                    }
-                }
-            }
-            DcpFrameId::GetSet => {
-                for block in request_dcp.blocks {
-                    if let Some(block) = block {
-                        match block.block {
-                            Block::DeviceProperties(dp) => match dp {
-                                DevicePropertiesBlock::NameOfStation(ns) => {
-                                    pnet.config.name_of_station = ns.name;
-                                    pnet.config.name_of_station_len = ns.length;
-                                }
-                                _ => (),
-                            },
-                            Block::Ip(ip) => match ip {
-                                IpBlock::IpParameter(ip) => {
-                                    pnet.config.ip_config.ip_address = ip.ip_address;
-                                    pnet.config.ip_config.subnet_mask = ip.subnet_mask;
-                                    pnet.config.ip_config.gateway = ip.gateway;
-                                    pnet.update_interface();
-                                }
-                                IpBlock::FullIpSuite(suite) => {
-                                    pnet.config.ip_config.ip_address = suite.ip_address;
-                                    pnet.config.ip_config.subnet_mask = suite.subnet_mask;
-                                    pnet.config.ip_config.gateway = suite.gateway;
-                                    pnet.update_interface();
-                                }
-                                _ => (),
-                            },
-                            _ => (),
-                        }
-                    }
-                }
-            }
-            _ => {
-                defmt::debug!("Recieved DCP packet is not a Hello packet");
-                defmt::debug!(
-                    "dst_is_hello = {}, num_of_blocks: {}, frame_id: {:x}",
-                    request_dcp.dst_is_hello(),
-                    request_dcp.number_of_blocks,
-                    request_dcp.frame_id as u16
-                );
-            }
-        }
+    /// Like `parse`, but fails with `TooManyBlocks` for frames of more than
+    /// `N` blocks
+    pub fn parse_with_capacity<T: AsRef<[u8]>>(
+        frame: &'a EthernetFrame<T>,
+    ) -> Result<Self, ParseDcpError> {
+        Self::from_view(DcpView::parse(frame)?)
     }
 
-    pub fn add_block(&mut self, block: DcpBlock) -> &mut Self {
-        self.blocks[self.number_of_blocks] = Some(block);
-        self.number_of_blocks += 1;
-        self.header.data_length += block.block_length;
+    fn from_view(view: DcpView<'a>) -> Result<Self, ParseDcpError> {
+        let mut blocks = [None; N];
+        let mut number_of_blocks = 0;
+
+        // Blocks that fail to parse keep their slot so the numbering matches the request
+        for block in view.blocks() {
+            let slot = blocks
+                .get_mut(number_of_blocks)
+                .ok_or(ParseDcpError::TooManyBlocks)?;
+            *slot = block.ok();
+            number_of_blocks += 1;
+        }
 
-        self
+        Ok(Self {
+            destination: view.destination,
+            source: view.source,
+            eth_type: view.eth_type,
+            vlan_tci: view.vlan_tci,
+            frame_id: view.frame_id,
+            header: view.header,
+            number_of_blocks,
+            blocks,
+        })
     }
 
-    pub fn parse<T: AsRef<[u8]>>(frame: &EthernetFrame<T>) -> Result<Self, ParseDcpError> {
-        let frame_id = DcpFrameId::try_from_primitive(frame.frame_id_u16())
-            .map_err(|_| ParseDcpError::FrameIdError)?;
-
-        let header_frame = DcpHeaderFrame::new_checked(frame.payload())
-            .map_err(|e| ParseDcpError::HeaderError(e))?;
+    /// Like `from_bytes`, but fails with `TooManyBlocks` for frames of more
+    /// than `N` blocks
+    pub fn from_bytes_with_capacity(bytes: &'a [u8]) -> Result<Self, ParseDcpError> {
+        let frame = EthernetFrame::new_checked(bytes).map_err(ParseDcpError::EthernetError)?;
 
-        let header = DcpHeader::parse(&header_frame).map_err(|e| ParseDcpError::HeaderError(e))?;
-        let payload = header_frame.payload();
+        if !frame.is_profinet() {
+            return Err(ParseDcpError::NotProfinet);
+        }
 
-        let mut blocks = [None; MAX_DCP_BLOCK_NUMBER];
-        let mut block_start_index = 0usize;
-        let mut block_end_index;
-        let mut block_number = 0;
+        Self::from_view(DcpView::with_payload(&frame, frame.buffer_payload())?)
+    }
 
-        while block_start_index < header.data_length as usize {
-            let block_frame = DCPBlockFrame::new_unchecked(&payload[block_start_index..]);
-            let block_length = (block_frame.block_length() + 4) as usize; // option + suboption + block length = 4 bytes
-            block_end_index = block_start_index + block_length;
+    /// Whether the frame was sent to the Identify multicast group
+    pub fn dst_is_identify(&self) -> bool {
+        self.destination.0 == DCP_IDENTIFY_MULTICAST
+    }
 
-            let dcp_block =
-                DcpBlock::parse_block(&payload[block_start_index..block_end_index]).ok();
+    /// Whether the frame was sent to the Hello multicast group
+    pub fn dst_is_hello(&self) -> bool {
+        self.destination.0 == DCP_HELLO_MULTICAST
+    }
 
-            blocks[block_number] = dcp_block;
-            block_number += 1;
-            block_start_index += block_length;
+    /// Whether a station with the address `mac` should act on the frame.
+    /// Get, Set and Identify responses are unicast, Identify requests and
+    /// Hellos may also be sent to their multicast group.
+    pub fn is_for(&self, mac: &EthernetAddress) -> bool {
+        self.destination == *mac
+            || match self.frame_id {
+                DcpFrameId::Request => self.dst_is_identify(),
+                DcpFrameId::Hello => self.dst_is_hello(),
+                DcpFrameId::GetSet | DcpFrameId::Response => false,
+            }
+    }
 
-            // Check if block_length is odd
-            if block_length % 2 != 0 {
-                block_start_index += 1;
+    /// Whether the service id and type fit the frame id. Requests carry
+    /// ServiceType Request, Identify responses Success or NotSupported.
+    pub fn is_valid_service(&self) -> bool {
+        match (&self.frame_id, &self.header.service_type) {
+            (DcpFrameId::Request, ServiceType::Request) => {
+                self.header.service_id == ServiceId::Identify
+            }
+            (DcpFrameId::Response, ServiceType::Success | ServiceType::NotSupported) => {
+                self.header.service_id == ServiceId::Identify
             }
+            // Other services than Get and Set are answered with NotSupported
+            (DcpFrameId::GetSet, ServiceType::Request) => true,
+            (DcpFrameId::Hello, ServiceType::Request) => self.header.service_id == ServiceId::Hello,
+            _ => false,
         }
-
-        Ok(Self {
-            destination: frame.dst_address(),
-            source: frame.src_address(),
-            eth_type: frame.eth_type(),
-            frame_id,
-            header: header,
-            number_of_blocks: block_number,
-            blocks: blocks,
-        })
     }
-    pub fn dst_is_hello(&self) -> bool {
-        self.destination.0 == DCP_MAC_HELLO_ADDRESS
+
+    /// Whether this is a Hello announcement of a device, not an Identify
+    pub fn is_hello(&self) -> bool {
+        self.dst_is_hello()
+            && self.frame_id == DcpFrameId::Hello
+            && self.header.service_id == ServiceId::Hello
+            && self.header.service_type == ServiceType::Request
     }
 
-    pub fn encode_into(&self, buffer: &mut [u8]) {
+    /// Writes the frame without checking the buffer length or the EtherType
+    fn encode_into(&self, buffer: &mut [u8]) {
         buffer[DESTINATION_FIELD].copy_from_slice(self.destination.as_bytes());
         buffer[SOURCE_FIELD].copy_from_slice(self.source.as_bytes());
+
+        // Everything after the source address moves back by the size of the tag
+        let buffer = match self.vlan_tci {
+            Some(tci) => {
+                NetworkEndian::write_u16(&mut buffer[TYPE_FIELD], EthType::Vlan as u16);
+                NetworkEndian::write_u16(&mut buffer[VLAN_TCI_FIELD], tci);
+                &mut buffer[VLAN_TAG_LENGTH..]
+            }
+            None => buffer,
+        };
+
         NetworkEndian::write_u16(&mut buffer[TYPE_FIELD], self.eth_type.clone() as u16);
         NetworkEndian::write_u16(&mut buffer[FRAME_ID_FIELD], self.frame_id.clone() as u16);
         self.header.encode_into(&mut buffer[PAYLOAD_FIELD]);
 
         let mut current_block_index = 0;
         let block_start = PAYLOAD_FIELD.start + header::DCP_HEADER_LENGTH_FIELD;
-        for block_opt in self.blocks {
-            if let Some(block) = block_opt {
-                block.encode_into(&mut buffer[block_start + current_block_index..]);
-                current_block_index += block.block_length as usize;
-            }
+        for block in self.blocks_iter() {
+            let start = block_start + current_block_index;
+            let block_buffer = &mut buffer[start..start + block.block_length as usize];
+
+            // Clears the pad byte that follows blocks with an odd length
+            block_buffer.fill(0);
+            block.encode_into(block_buffer);
+
+            current_block_index += block.block_length as usize;
+        }
+    }
+
+    /// Encodes the frame into `buffer` and returns the number of bytes written.
+    ///
+    /// DCP is always carried with the PROFINET EtherType, a VLAN tag is added
+    /// through `vlan_tci` instead. Any other `eth_type` is rejected rather than
+    /// written to the wire.
+    pub fn encode_checked(&self, buffer: &mut [u8]) -> Result<usize, EncodeError> {
+        if self.eth_type != EthType::Profinet {
+            return Err(EncodeError::InvalidEthType);
         }
+
+        let length = self.length();
+
+        if buffer.len() < length {
+            return Err(EncodeError::BufferTooSmall);
+        }
+
+        self.encode_into(&mut buffer[..length]);
+
+        Ok(length)
     }
 
+    /// Encodes the frame into a buffer holding exactly the encoded bytes
+    #[cfg(feature = "heapless")]
+    pub fn encode(&self) -> Result<heapless::Vec<u8, MAX_FRAME_LENGTH>, EncodeError> {
+        let mut buffer = heapless::Vec::new();
+        buffer
+            .resize_default(self.length())
+            .map_err(|_| EncodeError::BufferTooSmall)?;
+
+        self.encode_checked(&mut buffer)?;
+
+        Ok(buffer)
+    }
+
+    /// Number of bytes `encode_into` writes
     pub fn length(&self) -> usize {
-        self.blocks.iter().fold(26usize, |mut acc, block| {
-            if let Some(b) = block {
-                acc += b.block_length as usize;
-            }
+        let mut header_length = DESTINATION_FIELD.len()
+            + SOURCE_FIELD.len()
+            + TYPE_FIELD.len()
+            + FRAME_ID_FIELD.len()
+            + header::DCP_HEADER_LENGTH_FIELD;
+
+        if self.vlan_tci.is_some() {
+            header_length += VLAN_TAG_LENGTH;
+        }
 
-            acc
-        })
+        header_length
+            + self
+                .blocks_iter()
+                .map(|b| b.block_length as usize)
+                .sum::<usize>()
     }
 
-    pub fn response_delay_time(&self) -> usize {
-        match self.header.response_delay_factor {
-            0..=1 => 400,
-            2.. => {
-                let delay = 1 + self.header.response_delay_factor as usize * 10;
-                let rem = delay % 1000;
-                delay + (1000 - rem)
-            }
+    /// Delay in scheduler ticks before answering a multicast identify request.
+    ///
+    /// The controller asks devices to spread their responses over
+    /// `(ResponseDelayFactor - 1) * 10ms`. The slot inside that window is
+    /// derived from the device MAC, so devices on the same bus answer at
+    /// different times. A factor of 0 or 1 means answering without delay.
+    pub fn response_delay_time(&self, mac: &EthernetAddress, tick_us: usize) -> Ticks {
+        let factor = self
+            .header
+            .response_delay_factor
+            .min(MAX_RESPONSE_DELAY_FACTOR) as usize;
+
+        if factor <= 1 {
+            return Ticks::ZERO;
         }
+
+        let seed = u16::from_be_bytes([mac.0[4], mac.0[5]]) as usize;
+        let delay_us = (seed % factor) * RESPONSE_DELAY_STEP_US;
+
+        Ticks::from_micros(delay_us, tick_us)
+    }
+}
+
+/// Lists only the blocks of the frame, not the unused slots
+impl<const N: usize> fmt::Debug for Dcp<'_, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Dcp")
+            .field("destination", &self.destination)
+            .field("source", &self.source)
+            .field("vlan_tci", &self.vlan_tci)
+            .field("frame_id", &self.frame_id)
+            .field("header", &self.header)
+            .field("blocks", &&self.blocks[..self.number_of_blocks])
+            .finish()
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<const N: usize> defmt::Format for Dcp<'_, N> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "Dcp {{ {} -> {}, {}, {} {}, x_id: {}, blocks: {} }}",
+            self.source,
+            self.destination,
+            self.frame_id,
+            self.header.service_id,
+            self.header.service_type,
+            self.header.x_id,
+            &self.blocks[..self.number_of_blocks],
+        );
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for Dcp<'a> {
+    type Error = ParseDcpError;
+
+    fn try_from(bytes: &'a [u8]) -> Result<Self, Self::Error> {
+        Self::from_bytes(bytes)
     }
 }
 
@@ -303,9 +432,138 @@ mod tests {
         header::ServiceId,
     };
 
-    use crate::{Config, IpConfig};
-
     use super::*;
+    use crate::constants::MAX_STATION_NAME_SIZE;
+
+    /// A VLAN tagged Identify response of an S7-1200
+    pub(crate) const IDENTIFY_RESPONSE: [u8; 112] = [
+        0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5, 0x8c, 0xf3, 0x19, 0x45, 0x01, 0x63, 0x81, 0x00, 0x00,
+        0x00, 0x88, 0x92, 0xfe, 0xff, 0x05, 0x01, 0x00, 0x00, 0x01, 0x66, 0x00, 0x00, 0x00, 0x52,
+        0x02, 0x05, 0x00, 0x04, 0x00, 0x00, 0x02, 0x07, 0x02, 0x01, 0x00, 0x09, 0x00, 0x00, 0x53,
+        0x37, 0x2d, 0x31, 0x32, 0x30, 0x30, 0x00, 0x02, 0x02, 0x00, 0x0c, 0x00, 0x00, 0x70, 0x6c,
+        0x63, 0x78, 0x62, 0x31, 0x64, 0x30, 0x65, 0x64, 0x02, 0x03, 0x00, 0x06, 0x00, 0x00, 0x00,
+        0x2a, 0x01, 0x0d, 0x02, 0x04, 0x00, 0x04, 0x00, 0x00, 0x02, 0x00, 0x02, 0x07, 0x00, 0x04,
+        0x00, 0x00, 0x00, 0x64, 0x01, 0x02, 0x00, 0x0e, 0x00, 0x01, 0xc0, 0xa8, 0x00, 0x01, 0xff,
+        0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn test_discovered_device() {
+        let frame = EthernetFrame::new_checked(&IDENTIFY_RESPONSE[..]).unwrap();
+        let dcp = Dcp::parse(&frame).unwrap();
+        let device = DiscoveredDevice::from_dcp(&dcp);
+
+        assert_eq!(
+            device.mac_address,
+            EthernetAddress([0x8c, 0xf3, 0x19, 0x45, 0x01, 0x63])
+        );
+        assert_eq!(device.name_of_station.unwrap().as_bytes(), b"plcxb1d0ed");
+        assert_eq!(device.ip_address, Some(Ipv4Address::new(192, 168, 0, 1)));
+        assert_eq!(device.subnet_mask, Some(Ipv4Address::new(255, 255, 255, 0)));
+        assert_eq!(device.gateway, Some(Ipv4Address::UNSPECIFIED));
+        assert_eq!(
+            device.device_id,
+            Some(DeviceId {
+                vendor_id: 0x002a,
+                device_id: 0x010d
+            })
+        );
+        assert!(device
+            .device_role
+            .unwrap()
+            .contains(DeviceRole::IO_CONTROLLER));
+    }
+
+    /// Pseudo random numbers for the parser tests, reproducible from the seed
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+    }
+
+    /// Parses `bytes` both ways, neither may panic whatever the bytes are
+    fn parse_any(bytes: &[u8]) {
+        let _ = Dcp::from_bytes(bytes);
+
+        if let Ok(frame) = EthernetFrame::new_checked(bytes) {
+            if let Ok(view) = DcpView::parse(&frame) {
+                view.raw_blocks()
+                    .map_while(Result::ok)
+                    .for_each(|block| drop(DcpBlock::parse_block(block)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_random_bytes() {
+        let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+        let frame_ids = [0xfefc, 0xfefd, 0xfefe, 0xfeff];
+
+        for _ in 0..20_000 {
+            let mut buffer = [0; 128];
+            let length = rng.below(buffer.len() + 1);
+            buffer.iter_mut().for_each(|b| *b = rng.next() as u8);
+
+            // Mostly DCP frames, or the parser gives up before the blocks
+            if rng.below(8) != 0 {
+                NetworkEndian::write_u16(&mut buffer[TYPE_FIELD], EthType::Profinet as u16);
+                let frame_id = frame_ids[rng.below(frame_ids.len())];
+                NetworkEndian::write_u16(&mut buffer[FRAME_ID_FIELD], frame_id);
+            }
+
+            parse_any(&buffer[..length]);
+        }
+    }
+
+    #[test]
+    fn test_parse_mutated_capture() {
+        let mut rng = XorShift(0x9e37_79b9_7f4a_7c15);
+
+        for _ in 0..20_000 {
+            let mut buffer = IDENTIFY_RESPONSE;
+            for _ in 0..1 + rng.below(8) {
+                buffer[rng.below(buffer.len())] = rng.next() as u8;
+            }
+
+            // The header and blocks follow the VLAN tag and frame id
+            let length = 20 + rng.below(buffer.len() - 20 + 1);
+            parse_any(&buffer[..length]);
+            // And as a Get request, which has no block headers
+            buffer[20] = ServiceId::Get as u8;
+            buffer[21] = ServiceType::Request as u8;
+            parse_any(&buffer[..length]);
+        }
+    }
+
+    #[test]
+    fn test_frame_id_values() {
+        assert_eq!(
+            DcpFrameId::try_from_primitive(0xfefc).unwrap(),
+            DcpFrameId::Hello
+        );
+        assert_eq!(
+            DcpFrameId::try_from_primitive(0xfefd).unwrap(),
+            DcpFrameId::GetSet
+        );
+        assert_eq!(
+            DcpFrameId::try_from_primitive(0xfefe).unwrap(),
+            DcpFrameId::Request
+        );
+        assert_eq!(
+            DcpFrameId::try_from_primitive(0xfeff).unwrap(),
+            DcpFrameId::Response
+        );
+        assert!(DcpFrameId::try_from_primitive(0xfefb).is_err());
+    }
 
     #[test]
     fn test_non_vlan() {
@@ -351,6 +609,33 @@ mod tests {
         assert_eq!(frame.eth_type(), EthType::Profinet);
     }
 
+    #[test]
+    fn test_from_bytes() {
+        let raw_packet = [
+            0x01, 0x0e, 0xcf, 0x00, 0x00, 0x00, 0xa8, 0x5e, 0x45, 0x15, 0x85, 0x46, 0x81, 0x00,
+            0x00, 0x05, 0x88, 0x92, 0xfe, 0xfe, 0x05, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01,
+            0x00, 0x04, 0xff, 0xff, 0x00, 0x00,
+        ];
+
+        let dcp = Dcp::try_from(&raw_packet[..]).unwrap();
+        assert_eq!(dcp.vlan_tci, Some(0x0005));
+        assert_eq!(dcp.frame_id, DcpFrameId::Request);
+        assert_eq!(dcp.header.service_id, ServiceId::Identify);
+        assert_eq!(dcp.blocks[0].unwrap().block, Block::All);
+
+        // An LLDP frame
+        let mut raw_packet = raw_packet;
+        raw_packet[16..18].copy_from_slice(&[0x88, 0xcc]);
+        assert!(matches!(
+            Dcp::from_bytes(&raw_packet),
+            Err(ParseDcpError::NotProfinet)
+        ));
+        assert!(matches!(
+            Dcp::from_bytes(&raw_packet[..10]),
+            Err(ParseDcpError::EthernetError(_))
+        ));
+    }
+
     #[test]
     fn test_dcp_hello() {
         let raw_packet: [u8; 64] = [
@@ -381,24 +666,15 @@ mod tests {
         assert_eq!(dcp.header.service_id, ServiceId::Identify);
         assert_eq!(dcp.number_of_blocks, 1);
 
-        let block = dcp.blocks[0].clone().unwrap();
+        let block = dcp.blocks[0].unwrap();
 
         assert_eq!(block.block, Block::All);
+        assert!(!dcp.is_hello());
     }
 
     #[test]
     fn test_dcp_response() {
-        let raw_packet: [u8; 112] = [
-            0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5, 0x8c, 0xf3, 0x19, 0x45, 0x01, 0x63, 0x81, 0x00,
-            0x00, 0x00, 0x88, 0x92, 0xfe, 0xff, 0x05, 0x01, 0x00, 0x00, 0x01, 0x66, 0x00, 0x00,
-            0x00, 0x52, 0x02, 0x05, 0x00, 0x04, 0x00, 0x00, 0x02, 0x07, 0x02, 0x01, 0x00, 0x09,
-            0x00, 0x00, 0x53, 0x37, 0x2d, 0x31, 0x32, 0x30, 0x30, 0x00, 0x02, 0x02, 0x00, 0x0c,
-            0x00, 0x00, 0x70, 0x6c, 0x63, 0x78, 0x62, 0x31, 0x64, 0x30, 0x65, 0x64, 0x02, 0x03,
-            0x00, 0x06, 0x00, 0x00, 0x00, 0x2a, 0x01, 0x0d, 0x02, 0x04, 0x00, 0x04, 0x00, 0x00,
-            0x02, 0x00, 0x02, 0x07, 0x00, 0x04, 0x00, 0x00, 0x00, 0x64, 0x01, 0x02, 0x00, 0x0e,
-            0x00, 0x01, 0xc0, 0xa8, 0x00, 0x01, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00,
-        ];
-
+        let raw_packet = IDENTIFY_RESPONSE;
         let frame = EthernetFrame::new_checked(raw_packet).unwrap();
         let dcp = Dcp::parse(&frame);
 
@@ -409,7 +685,7 @@ mod tests {
         assert!(dcp.is_ok());
         let dcp = dcp.unwrap();
 
-        let name_of_station = dcp.blocks[2].clone().unwrap().block;
+        let name_of_station = dcp.blocks[2].unwrap().block;
 
         assert_eq!(
             name_of_station,
@@ -418,7 +694,7 @@ mod tests {
             ))
         );
 
-        let ip = dcp.blocks[6].clone().unwrap().block;
+        let ip = dcp.blocks[6].unwrap().block;
 
         assert_eq!(
             ip,
@@ -431,6 +707,162 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_dcp_response_blocks_round_trip() {
+        let frame = EthernetFrame::new_checked(IDENTIFY_RESPONSE).unwrap();
+        let dcp = Dcp::parse(&frame).unwrap();
+
+        // Blocks start after the VLAN tag, frame id and DCP header
+        let mut offset = 30;
+
+        for block in dcp.blocks_iter() {
+            let block = DcpBlock::new(block.block);
+            let length = block.block_length as usize;
+
+            let mut buffer = [0; 64];
+            block.encode_into(&mut buffer[..length]);
+
+            assert_eq!(
+                buffer[..length],
+                IDENTIFY_RESPONSE[offset..offset + length],
+                "block {:?}",
+                block.block
+            );
+
+            offset += length;
+        }
+
+        assert_eq!(offset, IDENTIFY_RESPONSE.len());
+    }
+
+    #[test]
+    fn test_manufacturer_specific_block() {
+        let raw_packet = [
+            0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe, 0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5, 0x88, 0x92,
+            0xfe, 0xff, 0x05, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x1c, 0x02, 0x02,
+            0x00, 0x05, 0x00, 0x00, b'd', b'e', b'v', 0x00, 0x90, 0x01, 0x00, 0x05, 0x00, 0x00,
+            0xaa, 0xbb, 0xcc, 0x00, 0x02, 0x04, 0x00, 0x04, 0x00, 0x00, 0x01, 0x00,
+        ];
+
+        let dcp = Dcp::from_bytes(&raw_packet).unwrap();
+        let blocks: [Block; 3] = core::array::from_fn(|i| dcp.blocks[i].unwrap().block);
+
+        assert_eq!(dcp.number_of_blocks, 3);
+        assert_eq!(
+            blocks,
+            [
+                Block::DeviceProperties(DevicePropertiesBlock::NameOfStation(
+                    NameOfStation::from_str("dev")
+                )),
+                Block::ManufacturerSpecific {
+                    option: 0x90,
+                    suboption: 0x01,
+                    data: &[0xaa, 0xbb, 0xcc],
+                },
+                Block::DeviceProperties(DevicePropertiesBlock::DeviceRole(DeviceRole::IO_DEVICE)),
+            ]
+        );
+
+        // Encodes back to the bytes it was parsed from
+        let mut response = Dcp::new(
+            dcp.destination,
+            dcp.source,
+            DcpHeader::new(ServiceId::Identify, ServiceType::Success, 1, 0),
+            DcpFrameId::Response,
+        );
+        for block in blocks {
+            response.add_block(DcpBlock::new(block));
+        }
+        let mut buffer = [0; 64];
+        let length = response.encode_checked(&mut buffer).unwrap();
+        assert_eq!(buffer[..length], raw_packet);
+    }
+
+    #[test]
+    fn test_blocks_iter_stops_at_number_of_blocks() {
+        let mut dcp = Dcp::new(
+            EthernetAddress::from_bytes(&[0x01, 0x0e, 0xcf, 0x00, 0x00, 0x00]),
+            EthernetAddress::from_bytes(&[0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe]),
+            DcpHeader::new(ServiceId::Identify, ServiceType::Success, 1, 0),
+            DcpFrameId::Response,
+        );
+        dcp.add_block(DcpBlock::new(Block::DeviceProperties(
+            DevicePropertiesBlock::DeviceOptions(DeviceOptions::from_pairs(&[(2, 7)]).unwrap()),
+        )));
+
+        // A stale entry past the end is not a block of this frame
+        dcp.blocks[1] = Some(DcpBlock::new(Block::All));
+
+        assert_eq!(dcp.blocks_iter().count(), 1);
+    }
+
+    #[test]
+    fn test_encode_invalid_eth_type() {
+        let mut dcp = Dcp::new(
+            EthernetAddress::from_bytes(&[0x01, 0x0e, 0xcf, 0x00, 0x00, 0x00]),
+            EthernetAddress::from_bytes(&[0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe]),
+            DcpHeader::new(ServiceId::Identify, ServiceType::Success, 1, 0),
+            DcpFrameId::Response,
+        );
+        dcp.eth_type = EthType::Other;
+
+        let mut buffer = [0; 64];
+        assert!(matches!(
+            dcp.encode_checked(&mut buffer),
+            Err(EncodeError::InvalidEthType)
+        ));
+        assert_eq!(buffer, [0; 64]);
+
+        dcp.eth_type = EthType::Vlan;
+        assert!(matches!(
+            dcp.encode_checked(&mut buffer),
+            Err(EncodeError::InvalidEthType)
+        ));
+    }
+
+    #[test]
+    fn test_encode_odd_length_block() {
+        let mut dcp = Dcp::new(
+            EthernetAddress::from_bytes(&[0x01, 0x0e, 0xcf, 0x00, 0x00, 0x00]),
+            EthernetAddress::from_bytes(&[0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe]),
+            DcpHeader::new(ServiceId::Identify, ServiceType::Success, 1, 0),
+            DcpFrameId::Response,
+        );
+
+        let name_of_station =
+            DevicePropertiesBlock::NameOfStation(NameOfStation::from_str("device-name"));
+        let device_id = DevicePropertiesBlock::DeviceId(DeviceId {
+            vendor_id: 0x1337,
+            device_id: 0x6969,
+        });
+        dcp.add_block(DcpBlock::new(Block::DeviceProperties(name_of_station)));
+        dcp.add_block(DcpBlock::new(Block::DeviceProperties(device_id)));
+
+        // Stale data in the buffer must not end up in the pad byte
+        let mut buffer = [0xff; 64];
+        let length = dcp.encode_checked(&mut buffer).unwrap();
+
+        // The 11 character name is followed by a pad byte, then the next block
+        let blocks = &buffer[26..length];
+        assert_eq!(blocks[2..4], [0x00, 0x0d]);
+        assert_eq!(blocks[6..17], *b"device-name");
+        assert_eq!(blocks[17], 0x00);
+        assert_eq!(blocks[18..20], [0x02, 0x03]);
+
+        let frame = EthernetFrame::new_checked(&buffer[..length]).unwrap();
+        let parsed = Dcp::parse(&frame).unwrap();
+
+        assert_eq!(parsed.number_of_blocks, 2);
+        assert_eq!(
+            parsed.blocks[0].unwrap().block,
+            Block::DeviceProperties(name_of_station)
+        );
+        assert_eq!(
+            parsed.blocks[1].unwrap().block,
+            Block::DeviceProperties(device_id)
+        );
+    }
+
     #[test]
     fn test_dcp_encoding() {
         let mut dcp = Dcp::new(
@@ -441,7 +873,7 @@ mod tests {
         );
 
         dcp.add_block(DcpBlock::new(Block::DeviceProperties(
-            DevicePropertiesBlock::DeviceOptions,
+            DevicePropertiesBlock::DeviceOptions(DeviceOptions::from_pairs(&[(2, 7)]).unwrap()),
         )));
 
         dcp.add_block(DcpBlock::new(Block::DeviceProperties(
@@ -469,53 +901,229 @@ mod tests {
     }
 
     #[test]
-    fn test_hello_response() {
-        let ip_config = IpConfig::new_not_set(EthernetAddress::from_bytes(&[
-            0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe,
-        ]));
+    fn test_dcp_size() {
+        // Every frame reserves room for `MAX_DCP_BLOCK_NUMBER` blocks
+        assert!(core::mem::size_of::<Dcp>() <= 3 * 1024);
+        assert!(core::mem::size_of::<Dcp<'_, 4>>() < core::mem::size_of::<Dcp>());
+    }
 
-        let config = Config::new("test", "asd", ip_config);
-        let pnet = PNet::new(config);
+    #[test]
+    fn test_parse_with_capacity() {
+        let mut buffer = [0; 128];
+        let length = test_length_dcp().encode_checked(&mut buffer).unwrap();
+
+        let dcp = Dcp::<2>::from_bytes_with_capacity(&buffer[..length]).unwrap();
+        assert_eq!(dcp.blocks_iter().count(), 2);
+
+        assert!(matches!(
+            Dcp::<1>::from_bytes_with_capacity(&buffer[..length]),
+            Err(ParseDcpError::TooManyBlocks)
+        ));
+
+        let mut small = Dcp::<1>::with_capacity(
+            dcp.destination,
+            dcp.source,
+            DcpHeader::new(ServiceId::Identify, ServiceType::Success, 1, 0),
+            DcpFrameId::Hello,
+        );
+        small.add_block(DcpBlock::new(dcp.blocks[0].unwrap().block));
+        // Without the DeviceOptions block
+        assert_eq!(small.length(), length - 8);
+    }
 
-        let dcp_hello = Dcp::new(
-            EthernetAddress::from_bytes(&DCP_MAC_HELLO_ADDRESS),
-            EthernetAddress::from_bytes(&[0x02, 0x12, 0x23, 0x53, 0x4e, 0xfa]),
+    fn test_length_dcp() -> Dcp<'static> {
+        let mut dcp = Dcp::new(
+            EthernetAddress::from_bytes(&[0x01, 0x0e, 0xcf, 0x00, 0x00, 0x00]),
+            EthernetAddress::from_bytes(&[0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe]),
             DcpHeader::new(ServiceId::Identify, ServiceType::Success, 1, 0),
             DcpFrameId::Hello,
         );
 
-        let dcp_response = dcp_hello.new_hello_response(&pnet);
+        dcp.add_block(DcpBlock::new(Block::DeviceProperties(
+            DevicePropertiesBlock::NameOfStation(NameOfStation::from_str("my cool device")),
+        )));
+
+        // Ends in a non-zero byte, so the end of the written data is visible
+        dcp.add_block(DcpBlock::new(Block::DeviceProperties(
+            DevicePropertiesBlock::DeviceOptions(DeviceOptions::from_pairs(&[(2, 7)]).unwrap()),
+        )));
+
+        dcp
+    }
+
+    #[test]
+    fn test_data_length_of_large_blocks() {
+        let name = "n".repeat(MAX_STATION_NAME_SIZE);
+        let vendor = "v".repeat(MAX_DEVICE_VENDOR_LENGTH);
+        let mut dcp = test_length_dcp();
+
+        dcp.add_block(DcpBlock::new(Block::DeviceProperties(
+            DevicePropertiesBlock::NameOfStation(NameOfStation::from_str(&name)),
+        )));
+        for _ in 0..3 {
+            dcp.add_block(DcpBlock::new(Block::DeviceProperties(
+                DevicePropertiesBlock::DeviceVendor(DeviceVendor::from_str(&vendor)),
+            )));
+        }
+
+        let mut buffer = [0; 2048];
+        let length = dcp.encode_checked(&mut buffer).unwrap();
+        // The blocks follow the destination, source, EtherType, frame id and DCP header
+        let blocks_start = 6 + 6 + 2 + 2 + 10;
+        assert_eq!(dcp.header.data_length as usize, length - blocks_start);
+
+        let frame = EthernetFrame::new_checked(&buffer[..length]).unwrap();
+        let parsed = Dcp::parse(&frame).unwrap();
+        assert_eq!(parsed.header.data_length, dcp.header.data_length);
+        assert_eq!(parsed.blocks_iter().count(), 6);
+    }
+
+    #[test]
+    fn test_add_block_limits() {
+        let data = [0; 30_000];
+        let block = DcpBlock::new(Block::ManufacturerSpecific {
+            option: 0x81,
+            suboption: 1,
+            data: &data,
+        });
+        let mut dcp = test_length_dcp();
+        let data_length = dcp.header.data_length + 2 * block.block_length;
+
+        dcp.add_block(block).add_block(block);
+        assert!(matches!(
+            dcp.try_add_block(block),
+            Err(EncodeError::DataTooLong)
+        ));
+        dcp.add_block(block);
+        assert_eq!(dcp.header.data_length, data_length);
+        assert_eq!(dcp.blocks_iter().count(), 4);
+
+        let mut dcp =
+            Dcp::<1>::with_capacity(dcp.destination, dcp.source, dcp.header, DcpFrameId::Hello);
+        dcp.add_block(DcpBlock::new(Block::All));
+        assert!(matches!(
+            dcp.try_add_block(DcpBlock::new(Block::All)),
+            Err(EncodeError::TooManyBlocks)
+        ));
+    }
+
+    fn assert_length_matches_encoding(dcp: &Dcp) {
+        let mut buffer = [0xaa; 128];
+        dcp.encode_into(&mut buffer);
+
+        let length = dcp.length();
+        assert_ne!(buffer[length - 1], 0xaa);
+        assert!(buffer[length..].iter().all(|b| *b == 0xaa));
+    }
+
+    #[test]
+    fn test_length() {
+        let dcp = test_length_dcp();
+
+        assert_eq!(dcp.length(), 54);
+        assert_length_matches_encoding(&dcp);
+    }
+
+    #[test]
+    fn test_length_vlan() {
+        let mut dcp = test_length_dcp();
+        dcp.vlan_tci = Some(0xc000);
+
+        assert_eq!(dcp.length(), 58);
+        assert_length_matches_encoding(&dcp);
 
+        let mut buffer = [0; 128];
+        dcp.encode_into(&mut buffer);
+        let frame = EthernetFrame::new_checked(&buffer[..dcp.length()]).unwrap();
+
+        assert_eq!(frame.vlan_priority(), Some(6));
+        assert_eq!(frame.eth_type(), EthType::Profinet);
+        assert_eq!(frame.frame_id_u16(), DcpFrameId::Hello as u16);
+    }
+
+    #[test]
+    fn test_encode_checked() {
+        let dcp = test_length_dcp();
+
+        let mut buffer = [0; 128];
+        assert_eq!(dcp.encode_checked(&mut buffer).unwrap(), dcp.length());
+
+        let mut expected = [0; 128];
+        dcp.encode_into(&mut expected);
+        assert_eq!(buffer, expected);
+    }
+
+    #[test]
+    fn test_encode_checked_short_buffer() {
+        let dcp = test_length_dcp();
+
+        let mut buffer = [0; 128];
+        let result = dcp.encode_checked(&mut buffer[..dcp.length() - 1]);
+
+        assert!(matches!(result, Err(EncodeError::BufferTooSmall)));
+    }
+
+    #[cfg(feature = "heapless")]
+    #[test]
+    fn test_encode() {
+        let dcp = test_length_dcp();
+
+        let encoded = dcp.encode().unwrap();
+        assert_eq!(encoded.len(), dcp.length());
+
+        let mut expected = [0; 128];
+        dcp.encode_into(&mut expected);
+        assert_eq!(encoded[..], expected[..dcp.length()]);
+    }
+
+    fn test_delay_dcp(response_delay_factor: u16) -> Dcp<'static> {
+        Dcp::new(
+            EthernetAddress::from_bytes(&DCP_IDENTIFY_MULTICAST),
+            EthernetAddress::from_bytes(&[0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5]),
+            DcpHeader::new(
+                ServiceId::Identify,
+                ServiceType::Request,
+                1,
+                response_delay_factor,
+            ),
+            DcpFrameId::Request,
+        )
+    }
+
+    #[test]
+    fn test_response_delay_time() {
+        let mac = EthernetAddress::from_bytes(&[0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe]);
+        let other_mac = EthernetAddress::from_bytes(&[0x00, 0x00, 0x23, 0x53, 0x4e, 0xff]);
+
+        assert_eq!(test_delay_dcp(0).response_delay_time(&mac, 1000), Ticks(0));
+        assert_eq!(test_delay_dcp(1).response_delay_time(&mac, 1000), Ticks(0));
+
+        assert_eq!(test_delay_dcp(2).response_delay_time(&mac, 1000), Ticks(0));
         assert_eq!(
-            dcp_response.destination,
-            EthernetAddress::from_bytes(&[0x02, 0x12, 0x23, 0x53, 0x4e, 0xfa])
+            test_delay_dcp(2).response_delay_time(&other_mac, 1000),
+            Ticks(10)
         );
+
+        // 0x4efe % 255 = 77 steps of 10ms
         assert_eq!(
-            dcp_response.source,
-            EthernetAddress::from_bytes(&[0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe])
+            test_delay_dcp(255).response_delay_time(&mac, 1000),
+            Ticks(770)
         );
+        assert_eq!(
+            test_delay_dcp(255).response_delay_time(&mac, 1),
+            Ticks(770_000)
+        );
+        assert_eq!(
+            test_delay_dcp(255).response_delay_time(&other_mac, 1000),
+            Ticks(780)
+        );
+    }
 
-        assert_eq!(dcp_response.eth_type, EthType::Profinet);
-        assert_eq!(dcp_response.frame_id, DcpFrameId::Response);
-        assert_eq!(dcp_response.header.service_id, ServiceId::Identify);
-        assert_eq!(dcp_response.header.service_type, ServiceType::Success);
+    #[test]
+    fn test_response_delay_time_clamped() {
+        let mac = EthernetAddress::from_bytes(&[0x00, 0x00, 0x23, 0x53, 0xff, 0xff]);
+        let max_delay = (MAX_RESPONSE_DELAY_FACTOR as usize - 1) * RESPONSE_DELAY_STEP_US / 1000;
 
-        dcp_response
-            .blocks
-            .iter()
-            .filter_map(|b| *b)
-            .for_each(|b| match b.block {
-                Block::Ip(ip) => match ip {
-                    IpBlock::IpParameter(ip) => {
-                        assert_eq!(ip.block_info, IpParameterBlockInfo::IpNotSet);
-                        assert_eq!(ip.ip_address.0, [0, 0, 0, 0]);
-                        assert_eq!(ip.subnet_mask.0, [0, 0, 0, 0]);
-                        assert_eq!(ip.gateway.0, [0, 0, 0, 0])
-                    }
-                    _ => panic!("Response shouldn't contain anything but 'IpParameter' block"),
-                },
-                Block::All => panic!("Response shouldn't contain an 'ALL' block"),
-                _ => (),
-            })
+        assert!(test_delay_dcp(u16::MAX).response_delay_time(&mac, 1000) <= Ticks(max_delay));
     }
 }
@@ -0,0 +1,133 @@
+// Rules for a valid NameOfStation
+
+use super::block::MAX_NAME_OF_STATION_LENGTH;
+use super::error::NameError;
+
+/// Longest label between two dots
+pub const MAX_LABEL_LENGTH: usize = 63;
+
+/// Checks `name` against the NameOfStation rules.
+///
+/// A name consists of dot separated labels of lowercase letters, digits and
+/// hyphens, where no label starts or ends with a hyphen. It must not look like
+/// an IP address or start with a port name like `port-001`.
+pub fn validate_station_name(name: &[u8]) -> Result<(), NameError> {
+    if name.is_empty() {
+        return Err(NameError::Empty);
+    }
+
+    if name.len() > MAX_NAME_OF_STATION_LENGTH {
+        return Err(NameError::TooLong);
+    }
+
+    for label in name.split(|c| *c == b'.') {
+        if label.is_empty() || label.len() > MAX_LABEL_LENGTH {
+            return Err(NameError::InvalidLabelLength);
+        }
+
+        if !label
+            .iter()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || *c == b'-')
+        {
+            return Err(NameError::InvalidCharacter);
+        }
+
+        if label.starts_with(b"-") || label.ends_with(b"-") {
+            return Err(NameError::InvalidHyphen);
+        }
+    }
+
+    let first_label = name.split(|c| *c == b'.').next().unwrap_or_default();
+    if is_port_name(first_label) {
+        return Err(NameError::PortName);
+    }
+
+    if is_ip_address(name) {
+        return Err(NameError::IpAddress);
+    }
+
+    Ok(())
+}
+
+/// Matches `port-xyz` and `port-xyz-abcde` where the letters stand for digits
+fn is_port_name(label: &[u8]) -> bool {
+    let digits = |s: &[u8]| s.iter().all(u8::is_ascii_digit);
+
+    match label.len() {
+        8 => label.starts_with(b"port-") && digits(&label[5..8]),
+        14 => {
+            label.starts_with(b"port-")
+                && digits(&label[5..8])
+                && label[8] == b'-'
+                && digits(&label[9..14])
+        }
+        _ => false,
+    }
+}
+
+/// Matches names of the form `n.n.n.n` where each `n` is a number
+fn is_ip_address(name: &[u8]) -> bool {
+    let mut labels = 0;
+
+    for label in name.split(|c| *c == b'.') {
+        if !label.iter().all(u8::is_ascii_digit) {
+            return false;
+        }
+
+        labels += 1;
+    }
+
+    labels == 4
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_names() {
+        assert!(validate_station_name(b"plcxb1d0ed").is_ok());
+        assert!(validate_station_name(b"device-1.line-2").is_ok());
+        assert!(validate_station_name(b"port-1").is_ok());
+        assert!(validate_station_name(b"1.2.3").is_ok());
+        assert!(validate_station_name(&[b'a'; 63]).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_names() {
+        assert_eq!(validate_station_name(b""), Err(NameError::Empty));
+        assert_eq!(
+            validate_station_name(&[b'a'; MAX_NAME_OF_STATION_LENGTH + 1]),
+            Err(NameError::TooLong)
+        );
+        assert_eq!(
+            validate_station_name(&[b'a'; 64]),
+            Err(NameError::InvalidLabelLength)
+        );
+        assert_eq!(
+            validate_station_name(b"device..name"),
+            Err(NameError::InvalidLabelLength)
+        );
+        assert_eq!(
+            validate_station_name(b"Device"),
+            Err(NameError::InvalidCharacter)
+        );
+        assert_eq!(
+            validate_station_name(b"device_1"),
+            Err(NameError::InvalidCharacter)
+        );
+        assert_eq!(
+            validate_station_name(b"device-.name"),
+            Err(NameError::InvalidHyphen)
+        );
+        assert_eq!(validate_station_name(b"port-001"), Err(NameError::PortName));
+        assert_eq!(
+            validate_station_name(b"port-001-00002.device"),
+            Err(NameError::PortName)
+        );
+        assert_eq!(
+            validate_station_name(b"192.168.0.1"),
+            Err(NameError::IpAddress)
+        );
+    }
+}
@@ -0,0 +1,1339 @@
+// The DCP services the device answers: Identify, Get and Set, and Hello
+
+use num_enum::TryFromPrimitive;
+use smoltcp::wire::{EthernetAddress, Ipv4Address};
+
+use super::*;
+use crate::arp::{Acd, IpSuite};
+use crate::dhcp::{Dhcp, DhcpState};
+use crate::ethernet::EthernetFrame;
+use crate::fspm::app::App;
+use crate::scheduler::TaskCallback;
+use crate::stats::increment;
+use crate::time::Ticks;
+use crate::PNet;
+
+impl<'a> Dcp<'a> {
+    pub fn new_hello_response<'b, T: App + Copy, U: TaskCallback + Copy>(
+        &self,
+        pnet: &'b PNet<T, U>,
+    ) -> Dcp<'b> {
+        let config = &pnet.fspm_user_config;
+        let ip_config = &config.interface_config.ip_config;
+
+        let response_dcp_header = DcpHeader::new(
+            ServiceId::Identify,
+            ServiceType::Success,
+            self.header.x_id,
+            1,
+        );
+        let mut response_dcp = Dcp::new(
+            self.source,
+            ip_config.mac_address,
+            response_dcp_header,
+            DcpFrameId::Response,
+        );
+
+        for suboption in [
+            DevicePropertiesSuboption::DeviceOptions,
+            DevicePropertiesSuboption::NameOfStation,
+            DevicePropertiesSuboption::DeviceVendor,
+            DevicePropertiesSuboption::DeviceRole,
+            DevicePropertiesSuboption::DeviceId,
+            DevicePropertiesSuboption::DeviceInstance,
+        ] {
+            if let Some(block) = Self::device_property(pnet, suboption) {
+                response_dcp.add_block(DcpBlock::new(Block::DeviceProperties(block)));
+            }
+        }
+
+        response_dcp.add_block(DcpBlock::new(Block::Ip(Self::ip_block(pnet))));
+
+        response_dcp
+    }
+
+    /// Builds the Hello request a device multicasts to announce its name
+    pub fn new_hello<T: App + Copy, U: TaskCallback + Copy>(pnet: &'a PNet<T, U>) -> Self {
+        let config = &pnet.fspm_user_config;
+
+        let mut hello = Dcp::new(
+            EthernetAddress(DCP_HELLO_MULTICAST),
+            config.interface_config.ip_config.mac_address,
+            DcpHeader::new(ServiceId::Hello, ServiceType::Request, 1, 0),
+            DcpFrameId::Hello,
+        );
+
+        hello.add_block(DcpBlock::new(Block::DeviceProperties(
+            DevicePropertiesBlock::NameOfStation(NameOfStation::parse_bytes(
+                &config.station_name,
+                config.station_name_len(),
+            )),
+        )));
+        hello.add_block(DcpBlock::new(Block::Ip(Self::ip_block(pnet))));
+        hello.add_block(DcpBlock::new(Block::DeviceProperties(
+            DevicePropertiesBlock::DeviceId(Self::device_id(pnet)),
+        )));
+
+        hello
+    }
+
+    /// Queues a Hello request to be sent at `send_at`
+    pub fn send_hello<T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &mut PNet<T, U>,
+        send_at: Ticks,
+    ) {
+        let hello = match pnet.response.build_dcp(&Dcp::new_hello(pnet)) {
+            Ok(hello) => hello,
+            Err(e) => {
+                error!("Failed to encode DCP hello: {}", e);
+                return;
+            }
+        };
+
+        if let Err(e) = pnet.queue_response(hello, send_at) {
+            error!("Failed to queue DCP hello: {}", e);
+        }
+    }
+
+    fn device_id<T: App + Copy, U: TaskCallback + Copy>(pnet: &PNet<T, U>) -> DeviceId {
+        let device_id = &pnet.fspm_user_config.device_id;
+
+        DeviceId {
+            vendor_id: u16::from_be_bytes([device_id.vendor_id_hi, device_id.vendor_id_lo]),
+            device_id: u16::from_be_bytes([device_id.device_id_hi, device_id.device_id_lo]),
+        }
+    }
+
+    /// The options and suboptions this device implements
+    fn device_options<T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &PNet<T, U>,
+    ) -> Result<DeviceOptions, EncodeError> {
+        let config = &pnet.fspm_user_config;
+        let mut options = DeviceOptions::new();
+
+        for suboption in [
+            IpSuboption::MacAddress,
+            IpSuboption::IpParameter,
+            IpSuboption::FullIpSuite,
+        ] {
+            options.push(BlockOption::IP as u8, suboption as u8)?;
+        }
+
+        for suboption in [
+            DevicePropertiesSuboption::DeviceVendor,
+            DevicePropertiesSuboption::NameOfStation,
+            DevicePropertiesSuboption::DeviceId,
+            DevicePropertiesSuboption::DeviceRole,
+            DevicePropertiesSuboption::DeviceOptions,
+            DevicePropertiesSuboption::DeviceInstance,
+        ] {
+            options.push(BlockOption::DeviceProperties as u8, suboption as u8)?;
+        }
+
+        options.push(BlockOption::DHCP as u8, DhcpSuboptions::Control as u8)?;
+
+        for suboption in [
+            ControlSuboption::Start,
+            ControlSuboption::Stop,
+            ControlSuboption::Signal,
+        ] {
+            options.push(BlockOption::Control as u8, suboption as u8)?;
+        }
+
+        if config.send_dcp_hello {
+            options.push(
+                BlockOption::DeviceInitiative as u8,
+                DeviceInitiativeSuboption::DeviceInitiative as u8,
+            )?;
+        }
+
+        Ok(options)
+    }
+
+    /// The device property block of `suboption`, `None` for the properties
+    /// the device doesn't have
+    fn device_property<'b, T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &'b PNet<T, U>,
+        suboption: DevicePropertiesSuboption,
+    ) -> Option<DevicePropertiesBlock<'b>> {
+        let config = &pnet.fspm_user_config;
+
+        let block = match suboption {
+            DevicePropertiesSuboption::DeviceVendor => DevicePropertiesBlock::DeviceVendor(
+                DeviceVendor::parse_bytes(&config.product_name, config.product_name_len()),
+            ),
+            DevicePropertiesSuboption::NameOfStation => DevicePropertiesBlock::NameOfStation(
+                NameOfStation::parse_bytes(&config.station_name, config.station_name_len()),
+            ),
+            DevicePropertiesSuboption::DeviceId => {
+                DevicePropertiesBlock::DeviceId(Self::device_id(pnet))
+            }
+            DevicePropertiesSuboption::DeviceRole => {
+                DevicePropertiesBlock::DeviceRole(DeviceRole::IO_DEVICE)
+            }
+            DevicePropertiesSuboption::DeviceOptions => match Self::device_options(pnet) {
+                Ok(options) => DevicePropertiesBlock::DeviceOptions(options),
+                Err(e) => {
+                    error!("Failed to list the device options: {}", e);
+                    return None;
+                }
+            },
+            DevicePropertiesSuboption::DeviceInstance => {
+                DevicePropertiesBlock::DeviceInstance(DeviceInstance {
+                    high: 0x42,
+                    low: 0x69,
+                })
+            }
+            _ => return None,
+        };
+
+        Some(block)
+    }
+
+    /// The IP block of `suboption`, a FullIpSuite without a DNS server
+    /// reports it as zero
+    fn ip_option<T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &PNet<T, U>,
+        suboption: IpSuboption,
+    ) -> IpBlock {
+        let ip_config = &pnet.fspm_user_config.interface_config.ip_config;
+
+        match suboption {
+            IpSuboption::MacAddress => IpBlock::MacAddress(MacAddress {
+                address: ip_config.mac_address,
+            }),
+            IpSuboption::IpParameter => IpBlock::IpParameter(IpParameter {
+                ip_address: ip_config.ip_address,
+                subnet_mask: ip_config.subnet_mask,
+                gateway: ip_config.gateway,
+                block_info: IpParameterBlockInfo::new(
+                    !ip_config.ip_address.is_unspecified(),
+                    pnet.dhcp_state == DhcpState::Bound,
+                    pnet.acd.conflict(),
+                ),
+            }),
+            IpSuboption::FullIpSuite => IpBlock::FullIpSuite(FullIpSuite {
+                ip_address: ip_config.ip_address,
+                subnet_mask: ip_config.subnet_mask,
+                gateway: ip_config.gateway,
+                dns: ip_config.dns.unwrap_or(Ipv4Address::UNSPECIFIED),
+            }),
+        }
+    }
+
+    /// The IP settings as reported in Identify and Hello frames
+    fn ip_block<T: App + Copy, U: TaskCallback + Copy>(pnet: &PNet<T, U>) -> IpBlock {
+        let suboption = match pnet.fspm_user_config.interface_config.ip_config.dns {
+            Some(_) => IpSuboption::FullIpSuite,
+            None => IpSuboption::IpParameter,
+        };
+
+        Self::ip_option(pnet, suboption)
+    }
+
+    pub fn handle_frame<F: AsRef<[u8]>, T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &mut PNet<T, U>,
+        frame: EthernetFrame<F>,
+        current_timestamp: Ticks,
+    ) {
+        let Ok(request_dcp) = Dcp::parse(&frame) else {
+            debug!("Failed to parse DCP packet");
+            increment(&mut pnet.stats.dcp_parse_errors);
+            return;
+        };
+
+        debug!("Successfully parsed frame to DCP packet");
+        increment(&mut pnet.stats.dcp_parsed);
+
+        if !request_dcp.is_valid_service() {
+            debug!(
+                "Dropped DCP frame with service {} {} on frame id {:x}",
+                request_dcp.header.service_id,
+                request_dcp.header.service_type,
+                request_dcp.frame_id as u16
+            );
+            increment(&mut pnet.stats.dcp_invalid_service);
+            return;
+        }
+
+        let own_mac = pnet.fspm_user_config.interface_config.ip_config.mac_address;
+        if !request_dcp.is_for(&own_mac) {
+            debug!("Dropped DCP frame for {}", request_dcp.destination);
+            increment(&mut pnet.stats.frames_not_for_us);
+            return;
+        }
+
+        match request_dcp.frame_id {
+            DcpFrameId::Request => {
+                if request_dcp.dst_is_identify() && request_dcp.number_of_blocks > 0 {
+                    let Some(hello_block) = request_dcp.blocks[0] else {
+                        debug!("DCP packet does not contain a Hello block");
+                        return;
+                    };
+                    if hello_block.block == Block::All {
+                        debug!("Recieved Hello DCP request, creating response");
+                        let response_dcp = request_dcp.new_hello_response(pnet);
+                        let response = match pnet.response.build_dcp(&response_dcp) {
+                            Ok(response) => response,
+                            Err(e) => {
+                                error!("Failed to encode DCP response: {}", e);
+                                return;
+                            }
+                        };
+
+                        let response_delay_time = request_dcp.response_delay_time(
+                            &response_dcp.source,
+                            pnet.fspm_user_config.tick_us,
+                        );
+
+                        debug!("Adding response DCP request to outgoing buffer");
+                        if let Err(e) =
+                            pnet.queue_response(response, current_timestamp + response_delay_time)
+                        {
+                            error!("Failed to queue DCP response: {}", e);
+                        }
+                    }
+                }
+            }
+            DcpFrameId::GetSet => {
+                let mut response_dcp = Dcp::new(
+                    request_dcp.source,
+                    pnet.fspm_user_config.interface_config.ip_config.mac_address,
+                    DcpHeader::new(
+                        request_dcp.header.service_id.clone(),
+                        ServiceType::Success,
+                        request_dcp.header.x_id,
+                        0,
+                    ),
+                    DcpFrameId::GetSet,
+                );
+
+                match request_dcp.header.service_id {
+                    ServiceId::Get => {
+                        for block in request_dcp.blocks_iter() {
+                            response_dcp.add_block(Self::get_block(pnet, &block.block));
+                        }
+                    }
+                    ServiceId::Set => {
+                        // Walk the raw blocks so unsupported ones can be answered too
+                        let Ok(view) = DcpView::parse(&frame) else {
+                            return;
+                        };
+
+                        // Nothing is applied if any block has an unacceptable value,
+                        // unsupported options are only reported. A truncated block
+                        // ends the request.
+                        let commit = view.raw_blocks().map_while(Result::ok).all(|raw_block| {
+                            Self::check_set_block(pnet, raw_block).0.error
+                                != DcpBlockError::SetNotPossible
+                        });
+
+                        // The settings are applied first, Control blocks act on
+                        // the result wherever they are in the request
+                        let mut permanent = false;
+                        for raw_block in view.raw_blocks().map_while(Result::ok) {
+                            let (mut response, block) = Self::check_set_block(pnet, raw_block);
+
+                            let accepted = commit && response.error == DcpBlockError::NoError;
+                            match block {
+                                Some(DcpBlock {
+                                    block: Block::Control(_),
+                                    ..
+                                }) if accepted => (),
+                                Some(block) if accepted => {
+                                    permanent |= block.qualifier.is_permanent();
+                                    Self::set_block(pnet, &block.block, current_timestamp);
+                                }
+                                Some(_) if response.error == DcpBlockError::NoError => {
+                                    response.error = DcpBlockError::SetNotPossible
+                                }
+                                _ => (),
+                            }
+
+                            response_dcp.add_block(DcpBlock::new(Block::ControlResponse(response)));
+                        }
+
+                        if commit {
+                            let blocks = view.raw_blocks().map_while(Result::ok);
+                            for block in blocks.filter_map(|raw| DcpBlock::parse_block(raw).ok()) {
+                                if let Block::Control(suboption) = block.block {
+                                    Self::control(pnet, suboption, permanent, current_timestamp);
+                                }
+                            }
+                        }
+                    }
+                    // Identify and Hello are only valid on their own frame ids
+                    _ => {
+                        response_dcp.header.service_type = ServiceType::NotSupported;
+                    }
+                }
+
+                let response = match pnet.response.build_dcp(&response_dcp) {
+                    Ok(response) => response,
+                    Err(e) => {
+                        error!("Failed to encode DCP response: {}", e);
+                        return;
+                    }
+                };
+
+                if let Err(e) = pnet.queue_response(response, current_timestamp) {
+                    error!("Failed to queue DCP response: {}", e);
+                }
+            }
+            // Announcements of other devices are only of interest to controllers
+            DcpFrameId::Hello if request_dcp.is_hello() => {
+                debug!("Received DCP Hello from {}", request_dcp.source);
+            }
+            DcpFrameId::Response => {
+                if request_dcp.source == own_mac {
+                    debug!("Ignoring own DCP Identify response");
+                    return;
+                }
+
+                let device = DiscoveredDevice::from_dcp(&request_dcp);
+                debug!("Received DCP Identify response from {}", device.mac_address);
+
+                let mut app = pnet.fspm_user_config.app;
+                app.dcp_response_ind(pnet, &device);
+                pnet.fspm_user_config.app = app;
+            }
+            _ => {
+                debug!("Recieved DCP packet is not a Hello packet");
+                debug!(
+                    "dst_is_identify = {}, num_of_blocks: {}, frame_id: {:x}",
+                    request_dcp.dst_is_identify(),
+                    request_dcp.number_of_blocks,
+                    request_dcp.frame_id as u16
+                );
+            }
+        }
+    }
+
+    /// Answers one option of a Get request with the same blocks an Identify
+    /// response carries, for every option `device_options` advertises
+    fn get_block<'b, T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &'b PNet<T, U>,
+        request: &Block,
+    ) -> DcpBlock<'b> {
+        let (option, suboption) = request.option_suboption();
+
+        let block = match BlockOption::try_from_primitive(option) {
+            Ok(BlockOption::IP) => IpSuboption::try_from_primitive(suboption)
+                .ok()
+                .map(|suboption| Block::Ip(Self::ip_option(pnet, suboption))),
+            Ok(BlockOption::DeviceProperties) => {
+                DevicePropertiesSuboption::try_from_primitive(suboption)
+                    .ok()
+                    .and_then(|suboption| Self::device_property(pnet, suboption))
+                    .map(Block::DeviceProperties)
+            }
+            Ok(BlockOption::DHCP) if suboption == DhcpSuboptions::Control as u8 => Some(
+                Block::Dhcp(DhcpBlock::Control(pnet.dhcp_state != DhcpState::Disabled)),
+            ),
+            _ => None,
+        };
+
+        DcpBlock::new(block.unwrap_or_else(|| {
+            Block::ControlResponse(ControlResponse::for_block(
+                request,
+                DcpBlockError::SuboptionNotSupported,
+            ))
+        }))
+    }
+
+    /// Checks one unparsed block of a Set request without applying it.
+    ///
+    /// Returns the response for the block and the block if it parsed.
+    fn check_set_block<'b, T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &PNet<T, U>,
+        raw_block: &'b [u8],
+    ) -> (ControlResponse, Option<DcpBlock<'b>>) {
+        let block = match DcpBlock::parse_block(raw_block) {
+            Ok(block) => block,
+            Err(e) => {
+                let frame = DCPBlockFrame::new_unchecked(raw_block);
+                let response = ControlResponse {
+                    option: frame.option(),
+                    suboption: frame.suboption(),
+                    error: e.block_error(),
+                };
+                return (response, None);
+            }
+        };
+
+        let error = match &block.block {
+            // Only the permanent bit is defined for these
+            Block::Ip(_) | Block::DeviceProperties(_) if block.qualifier.has_reserved_bits() => {
+                DcpBlockError::SetNotPossible
+            }
+            // An empty name resets the name
+            Block::DeviceProperties(DevicePropertiesBlock::NameOfStation(ns))
+                if !ns.is_empty() && validate_station_name(ns.as_bytes()).is_err() =>
+            {
+                DcpBlockError::SetNotPossible
+            }
+            Block::DeviceProperties(DevicePropertiesBlock::NameOfStation(_)) => {
+                DcpBlockError::NoError
+            }
+            Block::Ip(IpBlock::IpParameter(_) | IpBlock::FullIpSuite(_)) => {
+                match Self::ip_suite(pnet, &block.block) {
+                    Some(suite) if suite.is_valid() => DcpBlockError::NoError,
+                    _ => DcpBlockError::SetNotPossible,
+                }
+            }
+            // The MAC address is read-only
+            Block::Ip(IpBlock::MacAddress(_)) => DcpBlockError::OptionNotSupported,
+            // The device isn't part of a TSN domain
+            Block::NmeDomain(_) => DcpBlockError::OptionNotSupported,
+            // No vendor blocks are defined for the device
+            Block::ManufacturerSpecific { .. } => DcpBlockError::OptionNotSupported,
+            Block::Dhcp(DhcpBlock::Control(_))
+            | Block::Control(
+                ControlSuboption::Start | ControlSuboption::Stop | ControlSuboption::Signal,
+            ) => DcpBlockError::NoError,
+            _ => DcpBlockError::SuboptionNotSupported,
+        };
+
+        (ControlResponse::for_block(&block.block, error), Some(block))
+    }
+
+    /// The IP settings requested by an IP block of a Set request
+    fn ip_suite<T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &PNet<T, U>,
+        block: &Block,
+    ) -> Option<IpSuite> {
+        match block {
+            Block::Ip(IpBlock::IpParameter(ip)) => Some(IpSuite {
+                ip_address: ip.ip_address,
+                subnet_mask: ip.subnet_mask,
+                gateway: ip.gateway,
+                dns: pnet.fspm_user_config.interface_config.ip_config.dns,
+            }),
+            Block::Ip(IpBlock::FullIpSuite(suite)) => Some(IpSuite {
+                ip_address: suite.ip_address,
+                subnet_mask: suite.subnet_mask,
+                gateway: suite.gateway,
+                // A zero DNS clears the stored server
+                dns: Some(suite.dns).filter(|dns| !dns.is_unspecified()),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Acts on a Control block of a Set request once its settings are applied.
+    /// Stop ends the transaction, the application stores the settings then.
+    fn control<T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &mut PNet<T, U>,
+        suboption: ControlSuboption,
+        permanent: bool,
+        current_timestamp: Ticks,
+    ) {
+        match suboption {
+            // A new address is only in place once its conflict check is done
+            ControlSuboption::Stop if pnet.acd.is_probing() => pnet.acd.defer_commit(permanent),
+            ControlSuboption::Stop => Self::commit(pnet, permanent),
+            ControlSuboption::Signal => Self::start_signal(pnet, current_timestamp),
+            _ => (),
+        }
+    }
+
+    /// Tells the application to store the settings of a Set request
+    pub(crate) fn commit<T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &mut PNet<T, U>,
+        permanent: bool,
+    ) {
+        let mut app = pnet.fspm_user_config.app;
+        app.dcp_commit_ind(pnet, permanent);
+        pnet.fspm_user_config.app = app;
+    }
+
+    /// Applies one block of a Set request that passed `check_set_block`
+    ///
+    /// New IP settings are only applied once the address conflict detection
+    /// found no other host using the address.
+    fn set_block<T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &mut PNet<T, U>,
+        block: &Block,
+        current_timestamp: Ticks,
+    ) {
+        match block {
+            Block::DeviceProperties(DevicePropertiesBlock::NameOfStation(ns)) => {
+                let station_name = &mut pnet.fspm_user_config.station_name;
+                station_name.fill(0);
+                station_name[..ns.len()].copy_from_slice(ns.as_bytes());
+            }
+            Block::Ip(_) => {
+                if let Some(candidate) = Self::ip_suite(pnet, block) {
+                    // A static address ends DHCP
+                    Dhcp::disable(pnet);
+                    Acd::start(pnet, candidate, current_timestamp);
+                }
+            }
+            Block::Dhcp(DhcpBlock::Control(true)) => Dhcp::enable(pnet, current_timestamp),
+            Block::Dhcp(DhcpBlock::Control(false)) => Dhcp::disable(pnet),
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use byteorder::{ByteOrder, NetworkEndian};
+    use smoltcp::wire::Ipv4Address;
+
+    use super::*;
+    use crate::constants::MAX_FRAME_LENGTH;
+    use crate::ethernet::EthType;
+    use crate::util::{test_config, TestApp, TestTask};
+    use crate::OutgoingPacket;
+
+    #[test]
+    fn test_new_hello_service() {
+        let pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        let mut buffer = [0; MAX_FRAME_LENGTH];
+        let length = Dcp::new_hello(&pnet).encode_checked(&mut buffer).unwrap();
+        let frame = EthernetFrame::new_checked(&buffer[..length]).unwrap();
+        let hello = Dcp::parse(&frame).unwrap();
+
+        assert_eq!(hello.destination.0, DCP_HELLO_MULTICAST);
+        assert_eq!(hello.frame_id, DcpFrameId::Hello);
+        assert_eq!(hello.header.service_id, ServiceId::Hello);
+        assert_eq!(hello.header.service_type, ServiceType::Request);
+        assert!(hello.is_hello());
+    }
+
+    #[test]
+    fn test_receive_hello() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        let mut hello = Dcp::new_hello(&pnet);
+        hello.source = EthernetAddress::from_bytes(&[0x00, 0x00, 0x23, 0x53, 0x4e, 0x01]);
+        let mut buffer = [0; MAX_FRAME_LENGTH];
+        let length = hello.encode_checked(&mut buffer).unwrap();
+
+        pnet.handle_frame(&buffer[..length], Ticks(0)).unwrap();
+
+        // Hellos of other devices aren't answered
+        assert!(pnet.outgoing_packets.iter().all(|p| p.is_none()));
+        assert_eq!(pnet.stats().dcp_parsed, 1);
+    }
+
+    #[test]
+    fn test_receive_identify_response() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        let responder: PNet<_, TestTask> = PNet::new(test_config());
+
+        // The device looks for others with an Identify request of its own
+        let own_mac = pnet.fspm_user_config.interface_config.ip_config.mac_address;
+        let request = Dcp::new_identify_all(own_mac, 1, 0);
+        let mut response = request.new_hello_response(&responder);
+        let mut own = [0; MAX_FRAME_LENGTH];
+        let own_length = response.encode_checked(&mut own).unwrap();
+        response.source = EthernetAddress::from_bytes(&[0x00, 0x00, 0x23, 0x53, 0x4e, 0x01]);
+        let mut other = [0; MAX_FRAME_LENGTH];
+        let other_length = response.encode_checked(&mut other).unwrap();
+
+        // A response with the device's own address is its own, looped back
+        pnet.handle_frame(&own[..own_length], Ticks(0)).unwrap();
+        assert_eq!(pnet.fspm_user_config.app.devices_discovered, 0);
+
+        pnet.handle_frame(&other[..other_length], Ticks(0)).unwrap();
+        assert_eq!(pnet.fspm_user_config.app.devices_discovered, 1);
+        assert!(pnet.outgoing_packets.iter().all(|p| p.is_none()));
+    }
+
+    fn get_set_request(service_id: u8, data: &[u8]) -> [u8; 64] {
+        let mut raw_packet = [0; 64];
+        raw_packet[..16].copy_from_slice(&[
+            0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe, 0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5, 0x88, 0x92,
+            0xfe, 0xfd,
+        ]);
+        raw_packet[16..24].copy_from_slice(&[service_id, 0x00, 0x00, 0x00, 0x00, 0x07, 0x00, 0x00]);
+        NetworkEndian::write_u16(&mut raw_packet[24..26], data.len() as u16);
+        raw_packet[26..26 + data.len()].copy_from_slice(data);
+
+        raw_packet
+    }
+
+    /// Handles the request and returns the DCP frame queued in response
+    fn handle_request(pnet: &mut PNet<TestApp, TestTask>, raw_packet: &[u8]) -> OutgoingPacket {
+        let frame = EthernetFrame::new_checked(raw_packet).unwrap();
+        Dcp::handle_frame(pnet, frame, Ticks(0));
+
+        // Skip ARP probes sent for new IP settings
+        pnet.outgoing_packets
+            .iter_mut()
+            .find(|p| p.is_some_and(|p| p.frame()[12..14] == [0x88, 0x92]))
+            .and_then(|p| p.take())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_unsupported_service() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        let raw_packet = get_set_request(ServiceId::Identify as u8, &[]);
+        let frame = EthernetFrame::new_checked(raw_packet).unwrap();
+        Dcp::handle_frame(&mut pnet, frame, Ticks(0));
+
+        let response = pnet.outgoing_packets[0].as_ref().unwrap().frame();
+        let header = DcpHeaderFrame::new_unchecked(&response[16..]);
+
+        assert_eq!(response[14..16], [0xfe, 0xfd]);
+        assert_eq!(header.service_id().unwrap(), ServiceId::Identify);
+        assert_eq!(header.service_type().unwrap(), ServiceType::NotSupported);
+        assert_eq!(header.x_id(), 7);
+        assert_eq!(header.data_length(), 0);
+
+        // The 26 byte response is padded to the minimum frame length
+        assert_eq!(response.len(), 60);
+        assert!(response[26..].iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn test_get_mac_address() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        let raw_packet = get_set_request(ServiceId::Get as u8, &[0x01, 0x01]);
+        let packet = handle_request(&mut pnet, &raw_packet);
+        let response = Dcp::from_bytes(packet.frame()).unwrap();
+
+        assert_eq!(
+            response.destination,
+            EthernetAddress::from_bytes(&[0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5])
+        );
+        assert_eq!(response.frame_id, DcpFrameId::GetSet);
+        assert_eq!(response.header.service_id, ServiceId::Get);
+        assert_eq!(response.header.service_type, ServiceType::Success);
+        assert_eq!(response.header.x_id, 7);
+        assert_eq!(response.number_of_blocks, 1);
+        assert_eq!(
+            response.blocks[0].unwrap().block,
+            Block::Ip(IpBlock::MacAddress(MacAddress {
+                address: EthernetAddress::from_bytes(&[0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe])
+            }))
+        );
+    }
+
+    #[test]
+    fn test_get_advertised_options() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        // Everything in DeviceOptions except the Set only Control and the
+        // Hello only DeviceInitiative options can be read
+        let options = Dcp::device_options(&pnet).unwrap();
+        let gettable = options.as_slice().iter().filter(|(option, _)| {
+            *option != BlockOption::Control as u8 && *option != BlockOption::DeviceInitiative as u8
+        });
+        let data: Vec<u8> = gettable.clone().flat_map(|(o, s)| [*o, *s]).collect();
+        assert_eq!(data.len(), 20);
+
+        let raw_packet = get_set_request(ServiceId::Get as u8, &data);
+        let packet = handle_request(&mut pnet, &raw_packet);
+        let response = Dcp::from_bytes(packet.frame()).unwrap();
+
+        assert_eq!(response.blocks_iter().count(), 10);
+        for (block, requested) in response.blocks_iter().zip(gettable) {
+            assert!(!matches!(block.block, Block::ControlResponse(_)));
+            assert_eq!(block.block.option_suboption(), *requested);
+        }
+    }
+
+    #[test]
+    fn test_set_mac_address_rejected() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        let raw_packet = get_set_request(
+            ServiceId::Set as u8,
+            &[
+                0x01, 0x01, 0x00, 0x08, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x01,
+            ],
+        );
+        let packet = handle_request(&mut pnet, &raw_packet);
+        let response = Dcp::from_bytes(packet.frame()).unwrap();
+
+        assert_eq!(response.header.service_id, ServiceId::Set);
+        assert_eq!(
+            response.blocks[0].unwrap().block,
+            Block::ControlResponse(ControlResponse {
+                option: 0x01,
+                suboption: 0x01,
+                error: DcpBlockError::OptionNotSupported,
+            })
+        );
+        assert_eq!(
+            pnet.fspm_user_config.interface_config.ip_config.mac_address,
+            EthernetAddress::from_bytes(&[0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe])
+        );
+    }
+
+    const FULL_IP_SUITE_SET: [u8; 22] = [
+        0x01, 0x03, 0x00, 0x12, 0x00, 0x01, 0xc0, 0xa8, 0x00, 0x02, 0xff, 0xff, 0xff, 0x00, 0xc0,
+        0xa8, 0x00, 0x01, 0x08, 0x08, 0x08, 0x08,
+    ];
+
+    #[test]
+    fn test_set_full_ip_suite() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        let raw_packet = get_set_request(ServiceId::Set as u8, &FULL_IP_SUITE_SET);
+        let packet = handle_request(&mut pnet, &raw_packet);
+        let response = Dcp::from_bytes(packet.frame()).unwrap();
+
+        assert_eq!(
+            response.blocks[0].unwrap().block,
+            Block::ControlResponse(ControlResponse {
+                option: 0x01,
+                suboption: 0x03,
+                error: DcpBlockError::NoError,
+            })
+        );
+
+        // Applied once the address conflict detection is done
+        pnet.run_scheduled_tasks(Ticks(1000));
+        pnet.run_scheduled_tasks(Ticks(3000));
+
+        let ip_config = &pnet.fspm_user_config.interface_config.ip_config;
+        assert_eq!(ip_config.ip_address, Ipv4Address::new(192, 168, 0, 2));
+        assert_eq!(ip_config.gateway, Ipv4Address::new(192, 168, 0, 1));
+        assert_eq!(ip_config.dns, Some(Ipv4Address::new(8, 8, 8, 8)));
+
+        let hello = Dcp::new(
+            EthernetAddress::from_bytes(&DCP_IDENTIFY_MULTICAST),
+            EthernetAddress::from_bytes(&[0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5]),
+            DcpHeader::new(ServiceId::Identify, ServiceType::Request, 1, 0),
+            DcpFrameId::Request,
+        );
+        let hello_response = hello.new_hello_response(&pnet);
+
+        assert!(hello_response.blocks_iter().any(|b| b.block
+            == Block::Ip(IpBlock::FullIpSuite(FullIpSuite {
+                ip_address: Ipv4Address::new(192, 168, 0, 2),
+                subnet_mask: Ipv4Address::new(255, 255, 255, 0),
+                gateway: Ipv4Address::new(192, 168, 0, 1),
+                dns: Ipv4Address::new(8, 8, 8, 8),
+            }))));
+    }
+
+    #[test]
+    fn test_set_unsupported_option() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        let raw_packet = get_set_request(
+            ServiceId::Set as u8,
+            &[
+                0x02, 0x02, 0x00, 0x08, 0x00, 0x01, b'd', b'e', b'v', b'i', b'c', b'e', 0x03, 0x0c,
+                0x00, 0x04, 0x00, 0x01, b'h', b'x',
+            ],
+        );
+        let packet = handle_request(&mut pnet, &raw_packet);
+        let response = Dcp::from_bytes(packet.frame()).unwrap();
+
+        assert_eq!(response.number_of_blocks, 2);
+        assert_eq!(
+            response.blocks[0].unwrap().block,
+            Block::ControlResponse(ControlResponse {
+                option: 0x02,
+                suboption: 0x02,
+                error: DcpBlockError::NoError,
+            })
+        );
+        assert_eq!(
+            response.blocks[1].unwrap().block,
+            Block::ControlResponse(ControlResponse {
+                option: 0x03,
+                suboption: 0x0c,
+                // Only the DHCP control suboption is supported
+                error: DcpBlockError::SuboptionNotSupported,
+            })
+        );
+    }
+
+    #[test]
+    fn test_set_nme_domain() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        let raw_packet = get_set_request(
+            ServiceId::Set as u8,
+            &[0x07, 0x04, 0x00, 0x05, 0x00, 0x01, b't', b's', b'n', 0x00],
+        );
+        let packet = handle_request(&mut pnet, &raw_packet);
+        let response = Dcp::from_bytes(packet.frame()).unwrap();
+
+        assert_eq!(
+            response.blocks[0].unwrap().block,
+            Block::ControlResponse(ControlResponse {
+                option: 0x07,
+                suboption: 0x04,
+                error: DcpBlockError::OptionNotSupported,
+            })
+        );
+    }
+
+    #[test]
+    fn test_set_is_atomic() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        // A valid name and an IP with a non-contiguous subnet mask
+        let raw_packet = get_set_request(
+            ServiceId::Set as u8,
+            &[
+                0x02, 0x02, 0x00, 0x08, 0x00, 0x01, b'd', b'e', b'v', b'i', b'c', b'e', 0x01, 0x02,
+                0x00, 0x0e, 0x00, 0x01, 0xc0, 0xa8, 0x00, 0x02, 0xff, 0x00, 0xff, 0x00, 0x00, 0x00,
+                0x00, 0x00,
+            ],
+        );
+        let packet = handle_request(&mut pnet, &raw_packet);
+        let response = Dcp::from_bytes(packet.frame()).unwrap();
+
+        assert_eq!(
+            response.blocks[0].unwrap().block,
+            Block::ControlResponse(ControlResponse {
+                option: 0x02,
+                suboption: 0x02,
+                error: DcpBlockError::SetNotPossible,
+            })
+        );
+        assert_eq!(
+            response.blocks[1].unwrap().block,
+            Block::ControlResponse(ControlResponse {
+                option: 0x01,
+                suboption: 0x02,
+                error: DcpBlockError::SetNotPossible,
+            })
+        );
+
+        assert_eq!(pnet.fspm_user_config.station_name_len(), 0);
+        assert!(!pnet.acd.is_probing());
+    }
+
+    #[test]
+    fn test_set_name_ip_and_commit() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        // NameOfStation and IpParameter, both permanent, then Control Stop
+        let raw_packet = get_set_request(
+            ServiceId::Set as u8,
+            &[
+                0x02, 0x02, 0x00, 0x08, 0x00, 0x01, b'd', b'e', b'v', b'i', b'c', b'e', 0x01, 0x02,
+                0x00, 0x0e, 0x00, 0x01, 0xc0, 0xa8, 0x00, 0x02, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x05, 0x02, 0x00, 0x02, 0x00, 0x00,
+            ],
+        );
+        let packet = handle_request(&mut pnet, &raw_packet);
+        let response = Dcp::from_bytes(packet.frame()).unwrap();
+
+        assert_eq!(response.blocks_iter().count(), 3);
+        for (block, (option, suboption)) in
+            response
+                .blocks_iter()
+                .zip([(0x02, 0x02), (0x01, 0x02), (0x05, 0x02)])
+        {
+            assert_eq!(
+                block.block,
+                Block::ControlResponse(ControlResponse {
+                    option,
+                    suboption,
+                    error: DcpBlockError::NoError,
+                })
+            );
+        }
+
+        // The commit waits for the conflict check of the new address
+        assert!(pnet.acd.is_probing());
+        assert_eq!(pnet.fspm_user_config.app.commits, 0);
+
+        pnet.run_scheduled_tasks(Ticks(1000));
+        pnet.run_scheduled_tasks(Ticks(3000));
+
+        let app = pnet.fspm_user_config.app;
+        assert_eq!(app.commits, 1);
+        assert_eq!(app.committed_name_length, 6);
+        assert_eq!(app.committed_ip, Ipv4Address::new(192, 168, 0, 2));
+        assert!(app.commit_permanent);
+
+        // A leading Stop still commits after the temporary name was set
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        let raw_packet = get_set_request(
+            ServiceId::Set as u8,
+            &[
+                0x05, 0x02, 0x00, 0x02, 0x00, 0x00, 0x02, 0x02, 0x00, 0x05, 0x00, 0x00, b'd', b'e',
+                b'v', 0x00,
+            ],
+        );
+        handle_request(&mut pnet, &raw_packet);
+
+        let app = pnet.fspm_user_config.app;
+        assert_eq!(app.commits, 1);
+        assert_eq!(app.committed_name_length, 3);
+        assert!(!app.commit_permanent);
+    }
+
+    #[test]
+    fn test_set_signal() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        // Control Signal, flash once
+        let raw_packet = get_set_request(
+            ServiceId::Set as u8,
+            &[0x05, 0x03, 0x00, 0x04, 0x00, 0x00, 0x01, 0x00],
+        );
+        let packet = handle_request(&mut pnet, &raw_packet);
+        let response = Dcp::from_bytes(packet.frame()).unwrap();
+        assert_eq!(
+            response.blocks_iter().next().unwrap().block,
+            Block::ControlResponse(ControlResponse {
+                option: 0x05,
+                suboption: 0x03,
+                error: DcpBlockError::NoError,
+            })
+        );
+
+        assert!(pnet.fspm_user_config.app.led_on);
+
+        pnet.run_scheduled_tasks(Ticks(500));
+        assert!(!pnet.fspm_user_config.app.led_on);
+
+        // The LED flashes for 3 seconds and is left off
+        for time in [1000, 1500, 2000, 2500, 3000, 10_000] {
+            pnet.run_scheduled_tasks(Ticks(time));
+        }
+        let app = pnet.fspm_user_config.app;
+        assert!(!app.led_on);
+        assert_eq!(app.led_changes, SIGNAL_TOGGLE_COUNT as usize);
+    }
+
+    #[test]
+    fn test_set_dhcp() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        let raw_packet = get_set_request(ServiceId::Set as u8, &FULL_IP_SUITE_SET);
+        handle_request(&mut pnet, &raw_packet);
+        pnet.run_scheduled_tasks(Ticks(1000));
+        pnet.run_scheduled_tasks(Ticks(3000));
+
+        // Enabling DHCP drops the static address
+        let raw_packet = get_set_request(
+            ServiceId::Set as u8,
+            &[0x03, 0xff, 0x00, 0x03, 0x00, 0x01, 0x01, 0x00],
+        );
+        let packet = handle_request(&mut pnet, &raw_packet);
+        let response = Dcp::from_bytes(packet.frame()).unwrap();
+
+        assert_eq!(
+            response.blocks[0].unwrap().block,
+            Block::ControlResponse(ControlResponse {
+                option: 0x03,
+                suboption: 0xff,
+                error: DcpBlockError::NoError,
+            })
+        );
+        assert_eq!(pnet.dhcp_state(), DhcpState::Requesting);
+        let ip_config = &pnet.fspm_user_config.interface_config.ip_config;
+        assert!(ip_config.enable_dhcp);
+        assert!(ip_config.ip_address.is_unspecified());
+
+        let lease = IpSuite {
+            ip_address: Ipv4Address::new(192, 168, 0, 7),
+            subnet_mask: Ipv4Address::new(255, 255, 255, 0),
+            gateway: Ipv4Address::UNSPECIFIED,
+            dns: None,
+        };
+        pnet.dhcp_lease(lease, Ticks(4000)).unwrap();
+        pnet.run_scheduled_tasks(Ticks(5000));
+        pnet.run_scheduled_tasks(Ticks(7000));
+
+        assert_eq!(pnet.dhcp_state(), DhcpState::Bound);
+        assert_eq!(
+            Dcp::ip_block(&pnet),
+            IpBlock::IpParameter(IpParameter {
+                ip_address: lease.ip_address,
+                subnet_mask: lease.subnet_mask,
+                gateway: lease.gateway,
+                block_info: IpParameterBlockInfo::IpSetViaDhcp,
+            })
+        );
+
+        // A static address disables DHCP again
+        let raw_packet = get_set_request(ServiceId::Set as u8, &FULL_IP_SUITE_SET);
+        handle_request(&mut pnet, &raw_packet);
+
+        assert_eq!(pnet.dhcp_state(), DhcpState::Disabled);
+        assert!(!pnet.fspm_user_config.interface_config.ip_config.enable_dhcp);
+        assert!(matches!(
+            pnet.dhcp_lease(lease, Ticks(8000)),
+            Err(crate::error::Error::DhcpDisabled)
+        ));
+    }
+
+    #[test]
+    fn test_set_invalid_name() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        let raw_packet = get_set_request(
+            ServiceId::Set as u8,
+            &[
+                0x02, 0x02, 0x00, 0x08, 0x00, 0x01, b'D', b'e', b'v', b'i', b'c', b'e',
+            ],
+        );
+        let packet = handle_request(&mut pnet, &raw_packet);
+        let response = Dcp::from_bytes(packet.frame()).unwrap();
+
+        assert_eq!(
+            response.blocks[0].unwrap().block,
+            Block::ControlResponse(ControlResponse {
+                option: 0x02,
+                suboption: 0x02,
+                error: DcpBlockError::SetNotPossible,
+            })
+        );
+        assert_eq!(pnet.fspm_user_config.station_name_len(), 0);
+    }
+
+    #[test]
+    fn test_set_reserved_qualifier_bits() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        let raw_packet = get_set_request(
+            ServiceId::Set as u8,
+            &[
+                0x02, 0x02, 0x00, 0x08, 0x00, 0x03, b'd', b'e', b'v', b'i', b'c', b'e',
+            ],
+        );
+        let packet = handle_request(&mut pnet, &raw_packet);
+        let response = Dcp::from_bytes(packet.frame()).unwrap();
+
+        assert_eq!(
+            response.blocks[0].unwrap().block,
+            Block::ControlResponse(ControlResponse {
+                option: 0x02,
+                suboption: 0x02,
+                error: DcpBlockError::SetNotPossible,
+            })
+        );
+        assert_eq!(pnet.fspm_user_config.station_name_len(), 0);
+    }
+
+    #[test]
+    fn test_set_full_ip_suite_zero_dns() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        pnet.fspm_user_config.interface_config.ip_config.dns = Some(Ipv4Address::new(1, 1, 1, 1));
+
+        let mut data = FULL_IP_SUITE_SET;
+        data[18..22].fill(0);
+        let raw_packet = get_set_request(ServiceId::Set as u8, &data);
+        handle_request(&mut pnet, &raw_packet);
+        pnet.run_scheduled_tasks(Ticks(1000));
+        pnet.run_scheduled_tasks(Ticks(3000));
+
+        assert_eq!(pnet.fspm_user_config.interface_config.ip_config.dns, None);
+    }
+
+    #[test]
+    fn test_new_identify_all() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        let request = Dcp::new_identify_all(
+            EthernetAddress::from_bytes(&[0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5]),
+            3,
+            1,
+        );
+        let mut buffer = [0; 64];
+        let length = request.encode_checked(&mut buffer).unwrap();
+
+        assert_eq!(
+            buffer[..length],
+            [
+                0x01, 0x0e, 0xcf, 0x00, 0x00, 0x00, 0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5, 0x88, 0x92,
+                0xfe, 0xfe, 0x05, 0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x01, 0x00, 0x04, 0xff, 0xff,
+                0x00, 0x00,
+            ]
+        );
+
+        let packet = handle_request(&mut pnet, &buffer[..length]);
+        let response = Dcp::from_bytes(packet.frame()).unwrap();
+        assert_eq!(response.frame_id, DcpFrameId::Response);
+        assert_eq!(response.header.service_type, ServiceType::Success);
+        assert_eq!(response.header.x_id, 3);
+    }
+
+    #[test]
+    fn test_invalid_service_type() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        let source = EthernetAddress::from_bytes(&[0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5]);
+        let mut buffer = [0; 64];
+
+        // An Identify request that claims to be a response
+        let mut request = Dcp::new_identify_all(source, 3, 0);
+        request.header.service_type = ServiceType::Success;
+        let length = request.encode_checked(&mut buffer).unwrap();
+        pnet.handle_frame(&buffer[..length], Ticks(0)).unwrap();
+
+        // Get and Set responses aren't answered either
+        let raw_packet = get_set_request(ServiceId::Get as u8, &[0x02, 0x02]);
+        let mut response = raw_packet;
+        response[17] = ServiceType::Success as u8;
+        pnet.handle_frame(&response, Ticks(0)).unwrap();
+
+        // Nor a Set on the Identify frame id
+        let mut identify = raw_packet;
+        identify[14..16].copy_from_slice(&[0xfe, 0xfe]);
+        identify[16] = ServiceId::Set as u8;
+        pnet.handle_frame(&identify, Ticks(0)).unwrap();
+
+        assert!(pnet.outgoing_packets.iter().all(|p| p.is_none()));
+        assert_eq!(pnet.stats().dcp_invalid_service, 3);
+    }
+
+    #[test]
+    fn test_foreign_destination() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        let foreign = [0x00, 0x00, 0x23, 0x53, 0x4e, 0x01];
+
+        // A Set of the name for another device
+        let mut raw_packet = get_set_request(
+            ServiceId::Set as u8,
+            &[0x02, 0x02, 0x00, 0x05, 0x00, 0x00, b'd', b'e', b'v', 0x00],
+        );
+        raw_packet[..6].copy_from_slice(&foreign);
+        let frame = EthernetFrame::new_checked(raw_packet).unwrap();
+        Dcp::handle_frame(&mut pnet, frame, Ticks(0));
+
+        // An Identify sent to another device
+        let source = EthernetAddress::from_bytes(&[0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5]);
+        let mut request = Dcp::new_identify_all(source, 3, 0);
+        request.destination = EthernetAddress(foreign);
+        let mut buffer = [0; 64];
+        let length = request.encode_checked(&mut buffer).unwrap();
+        let frame = EthernetFrame::new_checked(&buffer[..length]).unwrap();
+        Dcp::handle_frame(&mut pnet, frame, Ticks(0));
+
+        assert!(pnet.outgoing_packets.iter().all(|p| p.is_none()));
+        assert_eq!(pnet.fspm_user_config.station_name_len(), 0);
+        assert_eq!(pnet.stats().frames_not_for_us, 2);
+
+        // The same Set addressed to the device is applied
+        raw_packet[..6].copy_from_slice(&[0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe]);
+        handle_request(&mut pnet, &raw_packet);
+        assert_eq!(pnet.fspm_user_config.station_name_len(), 3);
+    }
+
+    #[test]
+    fn test_identify_multicast() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        let source = EthernetAddress::from_bytes(&[0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5]);
+
+        let mut request = Dcp::new_identify_all(source, 3, 0);
+        assert_eq!(request.destination.0, DCP_IDENTIFY_MULTICAST);
+        assert!(request.dst_is_identify());
+        assert!(!request.dst_is_hello());
+
+        let mut buffer = [0; 64];
+        let length = request.encode_checked(&mut buffer).unwrap();
+        let frame = EthernetFrame::new_checked(&buffer[..length]).unwrap();
+        let view = DcpView::parse(&frame).unwrap();
+        assert!(view.dst_is_identify());
+        assert!(!view.is_hello());
+
+        pnet.handle_frame(&buffer[..length], Ticks(0)).unwrap();
+        assert!(pnet.outgoing_packets[0].take().is_some());
+
+        // An Identify sent to the Hello group isn't answered
+        request.destination = EthernetAddress(DCP_HELLO_MULTICAST);
+        let length = request.encode_checked(&mut buffer).unwrap();
+        pnet.handle_frame(&buffer[..length], Ticks(0)).unwrap();
+        assert!(pnet.outgoing_packets.iter().all(|p| p.is_none()));
+    }
+
+    #[test]
+    fn test_device_options() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        let options = Dcp::device_options(&pnet).unwrap();
+        assert_eq!(
+            options.as_slice(),
+            [
+                (1, 1),
+                (1, 2),
+                (1, 3),
+                (2, 1),
+                (2, 2),
+                (2, 3),
+                (2, 4),
+                (2, 5),
+                (2, 7),
+                (3, 255),
+                (5, 1),
+                (5, 2),
+                (5, 3),
+            ]
+        );
+
+        pnet.fspm_user_config.send_dcp_hello = true;
+        let options = Dcp::device_options(&pnet).unwrap();
+        assert!(options.contains(6, 1));
+
+        // Every advertised option is in the Identify response
+        let response = Dcp::new(
+            EthernetAddress::from_bytes(&DCP_IDENTIFY_MULTICAST),
+            EthernetAddress::from_bytes(&[0x02, 0x12, 0x23, 0x53, 0x4e, 0xfa]),
+            DcpHeader::new(ServiceId::Identify, ServiceType::Success, 1, 0),
+            DcpFrameId::Hello,
+        )
+        .new_hello_response(&pnet);
+
+        let mut buffer = [0; MAX_FRAME_LENGTH];
+        let length = response.encode_checked(&mut buffer).unwrap();
+        let frame = EthernetFrame::new_checked(&buffer[..length]).unwrap();
+        let parsed = Dcp::parse(&frame).unwrap();
+
+        assert_eq!(
+            parsed.blocks[0].unwrap().block,
+            Block::DeviceProperties(DevicePropertiesBlock::DeviceOptions(options))
+        );
+        assert_eq!(parsed.blocks[0].unwrap().block_length, 30);
+    }
+
+    #[test]
+    fn test_hello_response() {
+        let pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        let dcp_hello = Dcp::new(
+            EthernetAddress::from_bytes(&DCP_IDENTIFY_MULTICAST),
+            EthernetAddress::from_bytes(&[0x02, 0x12, 0x23, 0x53, 0x4e, 0xfa]),
+            DcpHeader::new(ServiceId::Identify, ServiceType::Success, 1, 0),
+            DcpFrameId::Hello,
+        );
+
+        let dcp_response = dcp_hello.new_hello_response(&pnet);
+
+        assert_eq!(
+            dcp_response.destination,
+            EthernetAddress::from_bytes(&[0x02, 0x12, 0x23, 0x53, 0x4e, 0xfa])
+        );
+        assert_eq!(
+            dcp_response.source,
+            EthernetAddress::from_bytes(&[0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe])
+        );
+
+        assert_eq!(dcp_response.eth_type, EthType::Profinet);
+        assert_eq!(dcp_response.frame_id, DcpFrameId::Response);
+        assert_eq!(dcp_response.header.service_id, ServiceId::Identify);
+        assert_eq!(dcp_response.header.service_type, ServiceType::Success);
+
+        dcp_response.blocks_iter().for_each(|b| match b.block {
+            Block::Ip(ip) => match ip {
+                IpBlock::IpParameter(ip) => {
+                    assert_eq!(ip.block_info, IpParameterBlockInfo::IpNotSet);
+                    assert_eq!(ip.ip_address.0, [0, 0, 0, 0]);
+                    assert_eq!(ip.subnet_mask.0, [0, 0, 0, 0]);
+                    assert_eq!(ip.gateway.0, [0, 0, 0, 0])
+                }
+                _ => panic!("Response shouldn't contain anything but 'IpParameter' block"),
+            },
+            Block::All => panic!("Response shouldn't contain an 'ALL' block"),
+            _ => (),
+        })
+    }
+}
@@ -0,0 +1,48 @@
+// Flashing the signal LED when a controller asks the device to identify itself
+
+use super::Dcp;
+use crate::fspm::app::App;
+use crate::scheduler::{TaskCallback, TaskKind};
+use crate::time::Ticks;
+use crate::PNet;
+
+/// The LED toggles this often, flashing it with 1 Hz
+pub const SIGNAL_TOGGLE_INTERVAL_US: usize = 500_000;
+/// Toggles of one signal, 3 seconds of flashing that end with the LED off
+pub const SIGNAL_TOGGLE_COUNT: u8 = 6;
+
+impl Dcp<'_> {
+    /// Starts flashing the signal LED, a running signal starts over
+    pub fn start_signal<T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &mut PNet<T, U>,
+        current_time: Ticks,
+    ) {
+        pnet.scheduler
+            .cancel(|task| matches!(task, TaskKind::DcpSignal(_)));
+
+        Self::handle_signal(pnet, 0, current_time);
+    }
+
+    /// Scheduler callback, switches the LED for the given toggle of the signal
+    /// and schedules the next one
+    pub(crate) fn handle_signal<T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &mut PNet<T, U>,
+        toggle: u8,
+        current_time: Ticks,
+    ) {
+        let mut app = pnet.fspm_user_config.app;
+        app.signal_led_ind(pnet, toggle.is_multiple_of(2));
+        pnet.fspm_user_config.app = app;
+
+        if toggle + 1 < SIGNAL_TOGGLE_COUNT {
+            let config = &pnet.fspm_user_config;
+            let interval = Ticks::from_micros(SIGNAL_TOGGLE_INTERVAL_US, config.tick_us);
+            pnet.scheduler.add_task(
+                "dcp_signal",
+                interval,
+                TaskKind::DcpSignal(toggle + 1),
+                current_time,
+            );
+        }
+    }
+}
@@ -0,0 +1,215 @@
+// Borrowed view of a received DCP frame, blocks are parsed on demand
+
+use num_enum::TryFromPrimitive;
+use smoltcp::wire::EthernetAddress;
+
+use super::{
+    Block, DCPBlockFrame, DcpBlock, DcpFrameId, DcpHeader, DcpHeaderFrame, GetRequest,
+    ParseDcpError, ServiceId, ServiceType, DCP_HEADER_LENGTH_FIELD, DCP_HELLO_MULTICAST,
+    DCP_IDENTIFY_MULTICAST,
+};
+use crate::dcp::error::ParseDcpBlockError;
+use crate::ethernet::{EthType, EthernetFrame};
+
+/// Option, suboption and block length
+const BLOCK_HEADER_LENGTH: usize = 4;
+/// Get requests list bare option and suboption pairs
+const GET_REQUEST_LENGTH: usize = 2;
+
+pub struct DcpView<'a> {
+    pub destination: EthernetAddress,
+    pub source: EthernetAddress,
+    pub eth_type: EthType,
+    /// Tag control information, the frame is VLAN tagged when set
+    pub vlan_tci: Option<u16>,
+    pub frame_id: DcpFrameId,
+    pub header: DcpHeader,
+    /// The blocks as sent, limited to the data length of the header
+    data: &'a [u8],
+}
+
+impl<'a> DcpView<'a> {
+    /// Parses the DCP header, leaving the blocks in `frame` until they are iterated
+    pub fn parse<T: AsRef<[u8]>>(frame: &'a EthernetFrame<T>) -> Result<Self, ParseDcpError> {
+        Self::with_payload(frame, frame.payload())
+    }
+
+    /// Like `parse`, but the blocks are borrowed from `payload`, the payload
+    /// of `frame`, so they can outlive the frame itself
+    pub fn with_payload<T: AsRef<[u8]>>(
+        frame: &EthernetFrame<T>,
+        payload: &'a [u8],
+    ) -> Result<Self, ParseDcpError> {
+        let frame_id = DcpFrameId::try_from_primitive(frame.frame_id_u16())
+            .map_err(|_| ParseDcpError::FrameIdError)?;
+
+        let header_frame =
+            DcpHeaderFrame::new_checked(payload).map_err(ParseDcpError::HeaderError)?;
+        let header = DcpHeader::parse(&header_frame).map_err(ParseDcpError::HeaderError)?;
+
+        // The header is checked to fit the announced blocks
+        let payload = &payload[DCP_HEADER_LENGTH_FIELD..];
+        let data_length = header.data_length as usize;
+
+        Ok(Self {
+            destination: frame.dst_address(),
+            source: frame.src_address(),
+            eth_type: frame.eth_type(),
+            vlan_tci: frame.vlan_tci(),
+            frame_id,
+            header,
+            data: &payload[..data_length],
+        })
+    }
+
+    /// Whether the frame was sent to the Identify multicast group
+    pub fn dst_is_identify(&self) -> bool {
+        self.destination.0 == DCP_IDENTIFY_MULTICAST
+    }
+
+    /// Whether the frame was sent to the Hello multicast group
+    pub fn dst_is_hello(&self) -> bool {
+        self.destination.0 == DCP_HELLO_MULTICAST
+    }
+
+    /// Whether this is a Hello announcement of a device, not an Identify
+    pub fn is_hello(&self) -> bool {
+        self.dst_is_hello()
+            && self.frame_id == DcpFrameId::Hello
+            && self.header.service_id == ServiceId::Hello
+            && self.header.service_type == ServiceType::Request
+    }
+
+    /// Iterates over the blocks, parsing each one only when it is reached
+    pub fn blocks(&self) -> DcpBlocks<'a> {
+        DcpBlocks {
+            data: self.data,
+            is_get_request: self.header.service_id == ServiceId::Get
+                && self.header.service_type == ServiceType::Request,
+        }
+    }
+
+    /// Iterates over the unparsed blocks including their header, without the
+    /// padding. Not meaningful for Get requests, which have no block headers.
+    pub fn raw_blocks(&self) -> RawDcpBlocks<'a> {
+        RawDcpBlocks { data: self.data }
+    }
+}
+
+/// Iterator over the unparsed blocks of a `DcpView`
+pub struct RawDcpBlocks<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for RawDcpBlocks<'a> {
+    type Item = Result<&'a [u8], ParseDcpBlockError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Anything shorter is padding
+        if self.data.len() < BLOCK_HEADER_LENGTH {
+            return None;
+        }
+
+        let block_length =
+            DCPBlockFrame::new_unchecked(self.data).block_length() as usize + BLOCK_HEADER_LENGTH;
+        let Some(block) = self.data.get(..block_length) else {
+            self.data = &[];
+            return Some(Err(ParseDcpBlockError::InvalidBlockLength));
+        };
+
+        // Blocks with an odd length are followed by a padding byte
+        let next_block = block_length + block_length % 2;
+        self.data = self.data.get(next_block..).unwrap_or_default();
+
+        Some(Ok(block))
+    }
+}
+
+/// Iterator over the blocks of a `DcpView`
+pub struct DcpBlocks<'a> {
+    data: &'a [u8],
+    is_get_request: bool,
+}
+
+impl<'a> Iterator for DcpBlocks<'a> {
+    type Item = Result<DcpBlock<'a>, ParseDcpBlockError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_get_request {
+            if self.data.len() < GET_REQUEST_LENGTH {
+                return None;
+            }
+
+            let request = GetRequest::new(self.data);
+            self.data = &self.data[GET_REQUEST_LENGTH..];
+            return Some(Ok(DcpBlock::new(Block::GetRequest(request))));
+        }
+
+        let mut raw_blocks = RawDcpBlocks { data: self.data };
+        let block = raw_blocks.next()?;
+        self.data = raw_blocks.data;
+
+        Some(block.and_then(DcpBlock::parse_block))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dcp::{ControlSuboption, DevicePropertiesBlock, NameOfStation};
+
+    #[test]
+    fn test_view_blocks() {
+        let raw_packet = [
+            0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe, 0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5, 0x88, 0x92,
+            0xfe, 0xfd, 0x04, 0x00, 0x00, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00, 0x10, 0x02, 0x02,
+            0x00, 0x05, 0x00, 0x00, b'd', b'e', b'v', 0x00, 0x05, 0x02, 0x00, 0x02, 0x00, 0x01,
+        ];
+        let frame = EthernetFrame::new_checked(&raw_packet[..]).unwrap();
+
+        let view = DcpView::parse(&frame).unwrap();
+        assert_eq!(view.frame_id, DcpFrameId::GetSet);
+        assert_eq!(view.header.service_id, ServiceId::Set);
+
+        let mut blocks = view.blocks();
+        assert_eq!(
+            blocks.next().unwrap().unwrap().block,
+            Block::DeviceProperties(DevicePropertiesBlock::NameOfStation(
+                NameOfStation::from_str("dev")
+            ))
+        );
+        assert_eq!(
+            blocks.next().unwrap().unwrap().block,
+            Block::Control(ControlSuboption::Stop)
+        );
+        assert!(blocks.next().is_none());
+    }
+
+    #[test]
+    fn test_view_truncated_block() {
+        // The block claims 8 bytes of data but the frame ends after 2
+        let mut raw_packet = [
+            0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe, 0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5, 0x88, 0x92,
+            0xfe, 0xfd, 0x04, 0x00, 0x00, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00, 0x06, 0x02, 0x02,
+            0x00, 0x08, 0x00, 0x00,
+        ];
+        let frame = EthernetFrame::new_checked(&raw_packet[..]).unwrap();
+
+        let view = DcpView::parse(&frame).unwrap();
+        let mut blocks = view.blocks();
+
+        assert!(matches!(
+            blocks.next(),
+            Some(Err(ParseDcpBlockError::InvalidBlockLength))
+        ));
+        assert!(blocks.next().is_none());
+
+        // A header announcing more data than the frame holds is rejected up front
+        raw_packet[25] = 0x10;
+        let frame = EthernetFrame::new_checked(&raw_packet[..]).unwrap();
+        assert!(matches!(
+            DcpView::parse(&frame),
+            Err(ParseDcpError::HeaderError(_))
+        ));
+    }
+}
@@ -0,0 +1,82 @@
+// Address assignment by DHCP, switched on and off over DCP. The DHCP client
+// itself runs in the application, the stack tracks the state and applies leases.
+
+use crate::arp::{Acd, IpSuite};
+use crate::error::Error;
+use crate::fspm::app::App;
+use crate::scheduler::TaskCallback;
+use crate::time::Ticks;
+use crate::{Ipv4Address, PNet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DhcpState {
+    /// The address is set statically
+    #[default]
+    Disabled,
+    /// Waiting for the application to report a lease
+    Requesting,
+    /// The address of a lease is in use
+    Bound,
+}
+
+pub struct Dhcp;
+
+impl Dhcp {
+    /// Switches to DHCP. The static address is dropped until the application
+    /// reports a lease with `PNet::dhcp_lease`.
+    pub fn enable<T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &mut PNet<T, U>,
+        current_time: Ticks,
+    ) {
+        if pnet.dhcp_state != DhcpState::Disabled {
+            return;
+        }
+
+        pnet.fspm_user_config.interface_config.ip_config.enable_dhcp = true;
+        pnet.dhcp_state = DhcpState::Requesting;
+
+        // Also stops a static address waiting for its conflict check
+        let unassigned = IpSuite {
+            ip_address: Ipv4Address::UNSPECIFIED,
+            subnet_mask: Ipv4Address::UNSPECIFIED,
+            gateway: Ipv4Address::UNSPECIFIED,
+            dns: None,
+        };
+        Acd::start(pnet, unassigned, current_time);
+
+        let mut app = pnet.fspm_user_config.app;
+        app.dhcp_ind(pnet, true);
+        pnet.fspm_user_config.app = app;
+    }
+
+    /// Switches back to static addressing, keeping the current address
+    pub fn disable<T: App + Copy, U: TaskCallback + Copy>(pnet: &mut PNet<T, U>) {
+        if pnet.dhcp_state == DhcpState::Disabled {
+            return;
+        }
+
+        pnet.fspm_user_config.interface_config.ip_config.enable_dhcp = false;
+        pnet.dhcp_state = DhcpState::Disabled;
+
+        let mut app = pnet.fspm_user_config.app;
+        app.dhcp_ind(pnet, false);
+        pnet.fspm_user_config.app = app;
+    }
+
+    /// Uses the address of a lease once the conflict check passed
+    pub(crate) fn lease<T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &mut PNet<T, U>,
+        suite: IpSuite,
+        current_time: Ticks,
+    ) -> Result<(), Error> {
+        if pnet.dhcp_state == DhcpState::Disabled {
+            return Err(Error::DhcpDisabled);
+        }
+
+        pnet.dhcp_state = DhcpState::Bound;
+        Acd::start(pnet, suite, current_time);
+
+        Ok(())
+    }
+}
@@ -1,9 +1,25 @@
-use defmt::Format;
+use crate::{
+    dcp::{EncodeError, ParseDcpError},
+    ethernet::{EthernetError, RxError},
+    fspm::ConfigError,
+};
 
-// use crate::{dcp::ParseDcpError, ethernet::EthernetError};
-
-#[derive(Debug, Format)]
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Error {
-    // DcpError(ParseDcpError),
-    // EthernetError(EthernetError),
+    NotInitialised,
+    PacketTooLarge,
+    /// All slots of the outgoing queue are taken
+    QueueFull,
+    /// No free slot to join another multicast group
+    TooManyMulticastGroups,
+    /// A DHCP lease was reported while DHCP is disabled
+    DhcpDisabled,
+    ConfigError(ConfigError),
+    DcpError(ParseDcpError),
+    EncodeError(EncodeError),
+    /// Another response was built before this one was queued
+    StaleResponse,
+    EthernetError(EthernetError),
+    RxError(RxError),
 }
@@ -1,5 +1,4 @@
-use byteorder::{ByteOrder, NetworkEndian};
-use defmt::Format;
+use byteorder::{ByteOrder, LittleEndian, NetworkEndian};
 use num_enum::FromPrimitive;
 use smoltcp::wire::EthernetAddress;
 
@@ -7,9 +6,12 @@ use crate::field::{Field, Rest};
 
 #[derive(Debug, PartialEq, Clone, FromPrimitive)]
 #[repr(u16)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum EthType {
     Profinet = 0x8892,
     Vlan = 0x8100,
+    Arp = 0x0806,
+    Lldp = 0x88cc,
     #[num_enum(default)]
     Other,
 }
@@ -21,11 +23,75 @@ pub enum FrameId {
     Other,
     #[num_enum(alternatives = [0xfefd..0xfeff])]
     Dcp = 0xfefc,
+    AlarmHigh = 0xfc01,
+    AlarmLow = 0xfe01,
 }
 
-#[derive(Debug, Format)]
+/// What a PROFINET frame carries, decided by the range of its frame id
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FrameKind {
+    /// Isochronous cyclic data, 0x0100 to 0x7fff
+    RtClass3,
+    /// Cyclic data, 0x8000 to 0xbbff
+    RtClass2,
+    /// Cyclic data sent over UDP, 0xbc00 to 0xbfff
+    RtClassUdp,
+    /// Cyclic data, 0xc000 to 0xfbff
+    RtClass1,
+    /// Clock and time synchronisation
+    Ptcp,
+    AlarmHigh,
+    AlarmLow,
+    /// 0xfefc to 0xfeff
+    Dcp,
+    /// Reserved ids and protocols the stack doesn't handle
+    Other,
+}
+
+impl FrameId {
+    /// The kind of frame with the frame id `frame_id`
+    pub fn classify(frame_id: u16) -> FrameKind {
+        match frame_id {
+            0x0020..=0x0021 | 0x0080..=0x0081 => FrameKind::Ptcp,
+            0xff00..=0xff01 | 0xff20..=0xff21 | 0xff40..=0xff43 => FrameKind::Ptcp,
+            0x0100..=0x7fff => FrameKind::RtClass3,
+            0x8000..=0xbbff => FrameKind::RtClass2,
+            0xbc00..=0xbfff => FrameKind::RtClassUdp,
+            0xc000..=0xfbff => FrameKind::RtClass1,
+            0xfc01 => FrameKind::AlarmHigh,
+            0xfe01 => FrameKind::AlarmLow,
+            0xfefc..=0xfeff => FrameKind::Dcp,
+            _ => FrameKind::Other,
+        }
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum EthernetError {
     PacketParsingError,
+    TooShort,
+    /// The trailing frame check sequence doesn't match the frame
+    BadFcs,
+}
+
+/// Length of the CRC32 frame check sequence at the end of a frame
+pub const FCS_LENGTH: usize = 4;
+
+/// The IEEE 802.3 CRC32 of `data`, sent least significant byte first as the FCS
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffff_u32;
+
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+
+    !crc
 }
 
 #[derive(Debug)]
@@ -40,9 +106,15 @@ impl<T: AsRef<[u8]>> EthernetFrame<T> {
     pub(crate) const TYPE_FIELD: Field = 12..14;
     pub(crate) const FRAME_ID_FIELD: Field = 14..16;
     pub(crate) const PAYLOAD_FIELD: Rest = 16..;
+    pub(crate) const VLAN_TCI_FIELD: Field = 14..16;
     pub(crate) const VLAN_TYPE_FIELD: Field = 16..18;
     pub(crate) const VLAN_FRAME_ID: Field = 18..20;
     pub(crate) const VLAN_PAYLOAD_FIELD: Rest = 20..;
+    pub(crate) const ETH_PAYLOAD_FIELD: Rest = 14..;
+    pub(crate) const VLAN_ETH_PAYLOAD_FIELD: Rest = 18..;
+
+    /// Length of the shortest PDU following the frame id, the DCP header
+    pub(crate) const MIN_PDU_LENGTH: usize = 10;
 
     pub fn new_unchecked(buffer: T) -> Self {
         Self {
@@ -51,19 +123,34 @@ impl<T: AsRef<[u8]>> EthernetFrame<T> {
         }
     }
 
+    /// Parses a frame without a trailing FCS. The DMA checks and strips the
+    /// FCS of received frames, so frames from the transport use this mode.
     pub fn new_checked(buffer: T) -> Result<Self, EthernetError> {
         let mut packet = Self::new_unchecked(buffer);
         packet.check_len()?;
 
-        packet.is_vlan = packet.is_vlan();
+        packet.is_vlan = packet.outer_eth_type() == EthType::Vlan;
         Ok(packet)
     }
 
+    /// Checks that the buffer holds the Ethernet header, the (possibly VLAN shifted)
+    /// frame id and at least a DCP header worth of payload
     pub fn check_len(&self) -> Result<(), EthernetError> {
-        let len = self.buffer.as_ref().len();
+        let data = self.buffer.as_ref();
+        let len = data.len();
+
+        if len < Self::TYPE_FIELD.end {
+            return Err(EthernetError::TooShort);
+        }
+
+        let payload_start = if self.outer_eth_type() == EthType::Vlan {
+            Self::VLAN_PAYLOAD_FIELD.start
+        } else {
+            Self::PAYLOAD_FIELD.start
+        };
 
-        if len < Self::PAYLOAD_FIELD.start {
-            Err(EthernetError::PacketParsingError)
+        if len < payload_start + Self::MIN_PDU_LENGTH {
+            Err(EthernetError::TooShort)
         } else {
             Ok(())
         }
@@ -79,6 +166,13 @@ impl<T: AsRef<[u8]>> EthernetFrame<T> {
         EthernetAddress::from_bytes(&data[Self::SOURCE_FIELD])
     }
 
+    /// The EtherType directly after the source address, `EthType::Vlan` for tagged frames
+    fn outer_eth_type(&self) -> EthType {
+        let data = self.buffer.as_ref();
+        EthType::from(NetworkEndian::read_u16(&data[Self::TYPE_FIELD]))
+    }
+
+    /// The EtherType of the payload, for VLAN tagged frames this is the type after the tag
     pub fn eth_type(&self) -> EthType {
         let data = self.buffer.as_ref();
 
@@ -91,18 +185,45 @@ impl<T: AsRef<[u8]>> EthernetFrame<T> {
         EthType::from(raw)
     }
 
+    /// Whether the payload is PROFINET, for VLAN tagged frames the type after
+    /// the tag decides
     pub fn is_profinet(&self) -> bool {
         self.eth_type() == EthType::Profinet
     }
 
+    /// Whether the frame carries an 802.1Q tag
     pub fn is_vlan(&self) -> bool {
-        self.eth_type() == EthType::Vlan
+        self.is_vlan
+    }
+
+    /// The raw tag control information of the VLAN tag, `None` for untagged frames
+    pub fn vlan_tci(&self) -> Option<u16> {
+        if !self.is_vlan {
+            return None;
+        }
+
+        let data = self.buffer.as_ref();
+        Some(NetworkEndian::read_u16(&data[Self::VLAN_TCI_FIELD]))
+    }
+
+    /// The 3-bit priority code point of the VLAN tag, `None` for untagged frames
+    pub fn vlan_priority(&self) -> Option<u8> {
+        self.vlan_tci().map(|tci| (tci >> 13) as u8)
+    }
+
+    /// The 12-bit VLAN identifier, `None` for untagged frames
+    pub fn vlan_id(&self) -> Option<u16> {
+        self.vlan_tci().map(|tci| tci & 0x0fff)
     }
 
     pub fn frame_id(&self) -> FrameId {
         FrameId::from(self.frame_id_u16())
     }
 
+    pub fn frame_kind(&self) -> FrameKind {
+        FrameId::classify(self.frame_id_u16())
+    }
+
     pub fn frame_id_u16(&self) -> u16 {
         let data = self.buffer.as_ref();
 
@@ -122,4 +243,207 @@ impl<T: AsRef<[u8]>> EthernetFrame<T> {
             &data[Self::PAYLOAD_FIELD]
         }
     }
+
+    /// Everything after the EtherType, for protocols without a PROFINET frame id
+    pub fn eth_payload(&self) -> &[u8] {
+        let data = self.buffer.as_ref();
+
+        if self.is_vlan {
+            &data[Self::VLAN_ETH_PAYLOAD_FIELD]
+        } else {
+            &data[Self::ETH_PAYLOAD_FIELD]
+        }
+    }
+}
+
+impl<'a> EthernetFrame<&'a [u8]> {
+    /// Parses a frame that still ends in its FCS, as in captures and on
+    /// simulated transports. The FCS is validated and not part of the frame.
+    pub fn with_fcs(buffer: &'a [u8]) -> Result<Self, EthernetError> {
+        let Some(frame_length) = buffer.len().checked_sub(FCS_LENGTH) else {
+            return Err(EthernetError::TooShort);
+        };
+        let (frame, fcs) = buffer.split_at(frame_length);
+
+        if crc32(frame) != LittleEndian::read_u32(fcs) {
+            return Err(EthernetError::BadFcs);
+        }
+
+        Self::new_checked(frame)
+    }
+
+    /// Like `payload`, but borrowed from the buffer instead of the frame
+    pub fn buffer_payload(&self) -> &'a [u8] {
+        if self.is_vlan {
+            &self.buffer[Self::VLAN_PAYLOAD_FIELD]
+        } else {
+            &self.buffer[Self::PAYLOAD_FIELD]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_non_vlan() {
+        let raw_packet: [u8; 30] = [
+            0x01, 0x0e, 0xcf, 0x00, 0x00, 0x00, 0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5, 0x88, 0x92,
+            0xfe, 0xfe, 0x05, 0x00, 0x00, 0x00, 0x00, 0x05, 0x00, 0xc0, 0x00, 0x04, 0xff, 0xff,
+            0x00, 0x00,
+        ];
+
+        let frame = EthernetFrame::new_checked(raw_packet).unwrap();
+
+        assert!(!frame.is_vlan());
+        assert_eq!(frame.vlan_priority(), None);
+        assert_eq!(frame.vlan_id(), None);
+        assert!(frame.is_profinet());
+        assert_eq!(frame.eth_type(), EthType::Profinet);
+        assert_eq!(frame.frame_id_u16(), 0xfefe);
+        assert_eq!(frame.frame_id(), FrameId::Dcp);
+        assert_eq!(frame.payload()[..2], [0x05, 0x00]);
+    }
+
+    #[test]
+    fn test_vlan() {
+        let raw_packet = [
+            0x01, 0x0e, 0xcf, 0x00, 0x00, 0x00, 0xa8, 0x5e, 0x45, 0x15, 0x85, 0x46, 0x81, 0x00,
+            0x00, 0x00, 0x88, 0x92, 0xfe, 0xfe, 0x05, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01,
+            0x00, 0x04, 0xff, 0xff, 0x00, 0x00,
+        ];
+
+        let frame = EthernetFrame::new_checked(raw_packet).unwrap();
+
+        assert!(frame.is_vlan());
+        assert_eq!(frame.eth_type(), EthType::Profinet);
+        assert_eq!(
+            frame.src_address(),
+            EthernetAddress::from_bytes(&[0xa8, 0x5e, 0x45, 0x15, 0x85, 0x46])
+        );
+        assert_eq!(frame.frame_id_u16(), 0xfefe);
+        assert_eq!(frame.frame_id(), FrameId::Dcp);
+        assert_eq!(frame.payload()[..2], [0x05, 0x00]);
+        assert_eq!(frame.payload().len(), raw_packet.len() - 20);
+    }
+
+    #[test]
+    fn test_vlan_priority() {
+        let raw_packet = [
+            0x01, 0x0e, 0xcf, 0x00, 0x00, 0x00, 0xa8, 0x5e, 0x45, 0x15, 0x85, 0x46, 0x81, 0x00,
+            0xc0, 0x2a, 0x88, 0x92, 0xc0, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00,
+        ];
+
+        let frame = EthernetFrame::new_checked(raw_packet).unwrap();
+
+        assert_eq!(frame.vlan_priority(), Some(6));
+        assert_eq!(frame.vlan_id(), Some(42));
+        assert_eq!(frame.frame_id_u16(), 0xc001);
+    }
+
+    #[test]
+    fn test_is_profinet() {
+        let untagged = [
+            0x01, 0x0e, 0xcf, 0x00, 0x00, 0x00, 0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5, 0x88, 0x92,
+            0xfe, 0xfe, 0x05, 0x00, 0x00, 0x00, 0x00, 0x05, 0x00, 0xc0, 0x00, 0x04, 0xff, 0xff,
+            0x00, 0x00,
+        ];
+        let frame = EthernetFrame::new_checked(untagged).unwrap();
+        assert!(!frame.is_vlan());
+        assert!(frame.is_profinet());
+
+        let tagged = [
+            0x01, 0x0e, 0xcf, 0x00, 0x00, 0x00, 0xa8, 0x5e, 0x45, 0x15, 0x85, 0x46, 0x81, 0x00,
+            0x00, 0x00, 0x88, 0x92, 0xfe, 0xfe, 0x05, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01,
+            0x00, 0x04, 0xff, 0xff, 0x00, 0x00,
+        ];
+        let frame = EthernetFrame::new_checked(tagged).unwrap();
+        assert!(frame.is_vlan());
+        assert!(frame.is_profinet());
+
+        // Tagged IPv4
+        let mut tagged_ip = tagged;
+        tagged_ip[16..18].copy_from_slice(&[0x08, 0x00]);
+        let frame = EthernetFrame::new_checked(tagged_ip).unwrap();
+        assert!(!frame.is_profinet());
+
+        // Untagged IPv4
+        let mut untagged_ip = untagged;
+        untagged_ip[12..14].copy_from_slice(&[0x08, 0x00]);
+        let frame = EthernetFrame::new_checked(untagged_ip).unwrap();
+        assert!(!frame.is_profinet());
+    }
+
+    #[test]
+    fn test_classify_frame_id() {
+        assert_eq!(FrameId::classify(0x0000), FrameKind::Other);
+        assert_eq!(FrameId::classify(0x0100), FrameKind::RtClass3);
+        assert_eq!(FrameId::classify(0x7fff), FrameKind::RtClass3);
+        assert_eq!(FrameId::classify(0x8000), FrameKind::RtClass2);
+        assert_eq!(FrameId::classify(0xbc00), FrameKind::RtClassUdp);
+        assert_eq!(FrameId::classify(0xc000), FrameKind::RtClass1);
+        assert_eq!(FrameId::classify(0xfbff), FrameKind::RtClass1);
+        assert_eq!(FrameId::classify(0xfc01), FrameKind::AlarmHigh);
+        assert_eq!(FrameId::classify(0xfe01), FrameKind::AlarmLow);
+        assert_eq!(FrameId::classify(0xfefc), FrameKind::Dcp);
+        assert_eq!(FrameId::classify(0xfeff), FrameKind::Dcp);
+        assert_eq!(FrameId::classify(0x0080), FrameKind::Ptcp);
+        assert_eq!(FrameId::classify(0xff00), FrameKind::Ptcp);
+        assert_eq!(FrameId::classify(0xff43), FrameKind::Ptcp);
+        assert_eq!(FrameId::classify(0xff44), FrameKind::Other);
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn test_with_fcs() {
+        let mut raw_packet = [0; 64];
+        raw_packet[..30].copy_from_slice(&[
+            0x01, 0x0e, 0xcf, 0x00, 0x00, 0x00, 0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5, 0x88, 0x92,
+            0xfe, 0xfe, 0x05, 0x00, 0x00, 0x00, 0x00, 0x05, 0x00, 0xc0, 0x00, 0x04, 0xff, 0xff,
+            0x00, 0x00,
+        ]);
+        raw_packet[60..].copy_from_slice(&[0xa6, 0x62, 0x7f, 0xf3]);
+
+        let frame = EthernetFrame::with_fcs(&raw_packet[..]).unwrap();
+        assert_eq!(frame.frame_id_u16(), 0xfefe);
+        // The FCS is not part of the payload
+        assert_eq!(frame.payload().len(), 44);
+
+        let mut corrupted = raw_packet;
+        corrupted[20] ^= 0x01;
+        assert!(matches!(
+            EthernetFrame::with_fcs(&corrupted[..]),
+            Err(EthernetError::BadFcs)
+        ));
+
+        assert!(matches!(
+            EthernetFrame::with_fcs(&raw_packet[..3]),
+            Err(EthernetError::TooShort)
+        ));
+    }
+
+    #[test]
+    fn test_runt_frame() {
+        let raw_packet = [0x01, 0x0e, 0xcf, 0x00, 0x00, 0x00, 0x52, 0x54, 0x00, 0x8a];
+        assert!(matches!(
+            EthernetFrame::new_checked(raw_packet),
+            Err(EthernetError::TooShort)
+        ));
+
+        // A VLAN tagged frame needs 4 more bytes than an untagged one
+        let raw_packet = [
+            0x01, 0x0e, 0xcf, 0x00, 0x00, 0x00, 0xa8, 0x5e, 0x45, 0x15, 0x85, 0x46, 0x81, 0x00,
+            0x00, 0x00, 0x88, 0x92, 0xfe, 0xfe, 0x05, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01,
+        ];
+        assert!(matches!(
+            EthernetFrame::new_checked(raw_packet),
+            Err(EthernetError::TooShort)
+        ));
+    }
 }
@@ -1,10 +1,17 @@
+#[cfg(feature = "device")]
 pub use stm32f4xx_hal::gpio::*;
 
 pub mod frame;
+#[cfg(feature = "device")]
 pub mod setup;
+#[cfg(feature = "device")]
+pub mod transport;
 
 pub use frame::*;
+#[cfg(feature = "device")]
+pub use transport::*;
 
+#[cfg(feature = "device")]
 pub struct Gpio {
     pub gpioa: gpioa::Parts,
     pub gpiob: gpiob::Parts,
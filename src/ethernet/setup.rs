@@ -2,10 +2,10 @@ use stm32_eth::{hal::gpio::*, EthPins};
 
 use super::Gpio;
 
-pub fn setup_pins(
-    gpio: Gpio,
-) -> EthPins<PA1<Input>, PA7<Input>, PG11<Input>, PG13<Input>, PB13<Input>, PC4<Input>, PC5<Input>>
-{
+pub type EthernetPins =
+    EthPins<PA1<Input>, PA7<Input>, PG11<Input>, PG13<Input>, PB13<Input>, PC4<Input>, PC5<Input>>;
+
+pub fn setup_pins(gpio: Gpio) -> EthernetPins {
     let Gpio {
         gpioa,
         gpiob,
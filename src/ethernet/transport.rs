@@ -0,0 +1,102 @@
+#[cfg(test)]
+use std::collections::VecDeque;
+
+use stm32_eth::dma::EthernetDMA;
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TransportError {
+    /// The transmit ring had no free descriptor
+    Busy,
+}
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RxError {
+    /// No frame is waiting
+    WouldBlock,
+    /// The frame didn't fit into the buffer and was dropped
+    Truncated,
+    /// The DMA failed to receive the frame
+    DmaError,
+}
+
+/// Sends and receives raw Ethernet frames
+pub trait Transport {
+    /// Copies the next received frame into `buffer` and returns its length.
+    ///
+    /// Frames that don't fit into `buffer` are dropped.
+    fn receive(&mut self, buffer: &mut [u8]) -> Result<usize, RxError>;
+
+    fn transmit(&mut self, frame: &[u8]) -> Result<(), TransportError>;
+}
+
+impl Transport for EthernetDMA<'_, '_> {
+    fn receive(&mut self, buffer: &mut [u8]) -> Result<usize, RxError> {
+        let packet = self.recv_next(None).map_err(|e| match e {
+            stm32_eth::dma::RxError::WouldBlock => RxError::WouldBlock,
+            stm32_eth::dma::RxError::Truncated => RxError::Truncated,
+            stm32_eth::dma::RxError::DmaError => RxError::DmaError,
+        })?;
+        let length = packet.len();
+
+        buffer
+            .get_mut(..length)
+            .ok_or(RxError::Truncated)?
+            .copy_from_slice(&packet);
+        Ok(length)
+    }
+
+    fn transmit(&mut self, frame: &[u8]) -> Result<(), TransportError> {
+        self.send(frame.len(), None, |buf| buf.copy_from_slice(frame))
+            .map_err(|_| TransportError::Busy)
+    }
+}
+
+/// Host-side transport for driving the stack in tests
+#[cfg(test)]
+#[derive(Default)]
+pub struct MockDma {
+    /// Frames handed to the stack, oldest first
+    pub rx: VecDeque<Vec<u8>>,
+    /// Frames sent by the stack, oldest first
+    pub tx: VecDeque<Vec<u8>>,
+}
+
+#[cfg(test)]
+impl Transport for MockDma {
+    fn receive(&mut self, buffer: &mut [u8]) -> Result<usize, RxError> {
+        let frame = self.rx.pop_front().ok_or(RxError::WouldBlock)?;
+
+        buffer
+            .get_mut(..frame.len())
+            .ok_or(RxError::Truncated)?
+            .copy_from_slice(&frame);
+        Ok(frame.len())
+    }
+
+    fn transmit(&mut self, frame: &[u8]) -> Result<(), TransportError> {
+        self.tx.push_back(frame.to_vec());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_dma_receive() {
+        let mut dma = MockDma::default();
+        dma.rx.push_back(vec![0xaa; 60]);
+        dma.rx.push_back(vec![0xbb; 100]);
+
+        let mut buffer = [0; 64];
+        assert_eq!(dma.receive(&mut buffer), Ok(60));
+        assert_eq!(buffer[..60], [0xaa; 60]);
+
+        // Too long for the buffer
+        assert_eq!(dma.receive(&mut buffer), Err(RxError::Truncated));
+        assert_eq!(dma.receive(&mut buffer), Err(RxError::WouldBlock));
+    }
+}
@@ -1,4 +1,6 @@
-use crate::{scheduler::TaskCallback, PNet};
+use crate::{dcp::DiscoveredDevice, scheduler::TaskCallback, PNet};
+
+use super::IpConfig;
 
 pub enum EventValues {
     Abort,
@@ -8,6 +10,41 @@ pub enum EventValues {
     EventData,
 }
 
+/// ErrorCode of a failed record read
+pub const ERROR_CODE_READ: u8 = 0xde;
+/// ErrorCode of a failed record write
+pub const ERROR_CODE_WRITE: u8 = 0xdf;
+/// ErrorCode of a failed connect
+pub const ERROR_CODE_CONNECT: u8 = 0xdb;
+/// ErrorCode of a failed release
+pub const ERROR_CODE_RELEASE: u8 = 0xdd;
+/// ErrorDecode of record access errors
+pub const ERROR_DECODE_PNIORW: u8 = 0x80;
+/// ErrorDecode of errors of the context management
+pub const ERROR_DECODE_PNIO: u8 = 0x81;
+/// ErrorCode1 of PNIO, the IOCR block of a connect is faulty
+pub const ERROR_CODE_1_CONN_FAULTY_IOCR_BLOCK_REQ: u8 = 0x02;
+/// ErrorCode1 of PNIO, the context management RPC failed
+pub const ERROR_CODE_1_CMRPC: u8 = 0x40;
+/// ErrorCode2 of CMRPC, the arguments don't fit their buffer
+pub const ERROR_CODE_2_CMRPC_ARGS_LENGTH_INVALID: u8 = 0x00;
+/// ErrorCode2 of CMRPC, all ARs are in use
+pub const ERROR_CODE_2_CMRPC_OUT_OF_AR_RESOURCES: u8 = 0x04;
+/// ErrorCode2 of CMRPC, there is no AR with the given UUID
+pub const ERROR_CODE_2_CMRPC_AR_UUID_UNKNOWN: u8 = 0x05;
+/// ErrorCode2 of CMRPC, there are no free provider, consumer or alarm resources
+pub const ERROR_CODE_2_CMRPC_OUT_OF_PCA_RESOURCES: u8 = 0x07;
+/// ErrorCode1 of PNIORW, the index is unknown
+pub const ERROR_CODE_1_ACCESS_INVALID_INDEX: u8 = 0xb0;
+/// ErrorCode1 of PNIORW, the written record has the wrong length
+pub const ERROR_CODE_1_ACCESS_WRITE_LENGTH: u8 = 0xb1;
+/// ErrorCode1 of PNIORW, the record may not be accessed this way
+pub const ERROR_CODE_1_ACCESS_DENIED: u8 = 0xb6;
+/// ErrorCode1 of PNIORW, the written record is malformed
+pub const ERROR_CODE_1_ACCESS_INVALID_PARAMETER: u8 = 0xb8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct PnioStatus {
     pub error_code: u8,
     pub error_decode: u8,
@@ -15,6 +52,105 @@ pub struct PnioStatus {
     pub error_code_2: u8,
 }
 
+impl PnioStatus {
+    /// Status of a failed record read or write
+    pub fn record_error(error_code: u8, error_code_1: u8) -> Self {
+        Self {
+            error_code,
+            error_decode: ERROR_DECODE_PNIORW,
+            error_code_1,
+            error_code_2: 0,
+        }
+    }
+
+    /// The record index is unknown, `error_code` is `ERROR_CODE_READ` or `ERROR_CODE_WRITE`
+    pub fn invalid_index(error_code: u8) -> Self {
+        Self::record_error(error_code, ERROR_CODE_1_ACCESS_INVALID_INDEX)
+    }
+
+    /// The record may not be accessed this way, for example writing a read-only record
+    pub fn access_denied(error_code: u8) -> Self {
+        Self::record_error(error_code, ERROR_CODE_1_ACCESS_DENIED)
+    }
+
+    /// The written record doesn't have the expected length
+    pub fn write_length_error() -> Self {
+        Self::record_error(ERROR_CODE_WRITE, ERROR_CODE_1_ACCESS_WRITE_LENGTH)
+    }
+
+    /// The written record is malformed
+    pub fn invalid_parameter() -> Self {
+        Self::record_error(ERROR_CODE_WRITE, ERROR_CODE_1_ACCESS_INVALID_PARAMETER)
+    }
+
+    /// A connect was rejected because all ARs are in use
+    pub fn out_of_ar_resources() -> Self {
+        Self {
+            error_code: ERROR_CODE_CONNECT,
+            error_decode: ERROR_DECODE_PNIO,
+            error_code_1: ERROR_CODE_1_CMRPC,
+            error_code_2: ERROR_CODE_2_CMRPC_OUT_OF_AR_RESOURCES,
+        }
+    }
+
+    /// A connect was rejected because no frame ids are left for its IOCRs
+    pub fn out_of_cr_resources() -> Self {
+        Self {
+            error_code: ERROR_CODE_CONNECT,
+            error_decode: ERROR_DECODE_PNIO,
+            error_code_1: ERROR_CODE_1_CMRPC,
+            error_code_2: ERROR_CODE_2_CMRPC_OUT_OF_PCA_RESOURCES,
+        }
+    }
+
+    /// A connect was rejected because its response is longer than the
+    /// controller accepts
+    pub fn args_length_invalid() -> Self {
+        Self {
+            error_code: ERROR_CODE_CONNECT,
+            error_decode: ERROR_DECODE_PNIO,
+            error_code_1: ERROR_CODE_1_CMRPC,
+            error_code_2: ERROR_CODE_2_CMRPC_ARGS_LENGTH_INVALID,
+        }
+    }
+
+    /// A connect was rejected because of the field of an IOCR block with
+    /// the index `field`
+    pub fn faulty_iocr_block(field: u8) -> Self {
+        Self {
+            error_code: ERROR_CODE_CONNECT,
+            error_decode: ERROR_DECODE_PNIO,
+            error_code_1: ERROR_CODE_1_CONN_FAULTY_IOCR_BLOCK_REQ,
+            error_code_2: field,
+        }
+    }
+
+    /// The request refers to an AR that doesn't exist
+    pub fn ar_uuid_unknown(error_code: u8) -> Self {
+        Self {
+            error_code,
+            error_decode: ERROR_DECODE_PNIO,
+            error_code_1: ERROR_CODE_1_CMRPC,
+            error_code_2: ERROR_CODE_2_CMRPC_AR_UUID_UNKNOWN,
+        }
+    }
+
+    /// The status as sent on the wire
+    pub fn to_bytes(&self) -> [u8; 4] {
+        [
+            self.error_code,
+            self.error_decode,
+            self.error_code_1,
+            self.error_code_2,
+        ]
+    }
+
+    pub fn is_error(&self) -> bool {
+        *self != Self::default()
+    }
+}
+
+#[derive(Default)]
 pub struct EventResult {
     pub pnio_status: PnioStatus,
 }
@@ -28,6 +164,7 @@ pub enum ControlCommand {
     ReadyForRtc3,
 }
 
+#[derive(Clone, Copy)]
 pub struct AlarmArgument {
     pub api_id: usize,
     pub slot_number: usize,
@@ -37,6 +174,7 @@ pub struct AlarmArgument {
     pub alarm_specifier: AlarmSpecifier,
 }
 
+#[derive(Clone, Copy)]
 pub struct AlarmSpecifier {
     pub channel_diagnosis: bool,
     pub manufacturer_diagnosis: bool,
@@ -85,6 +223,9 @@ pub trait App {
         arep: usize,
         state: EventValues,
     );
+    /// Reads a record the stack doesn't handle itself into `read_data` and
+    /// returns its length. Failures are reported in `result.pnio_status`.
+    #[allow(clippy::too_many_arguments)]
     fn read_ind_callback<T: App + Copy, U: TaskCallback + Copy>(
         &mut self,
         pnet: &mut PNet<T, U>,
@@ -94,10 +235,12 @@ pub trait App {
         subslot: usize,
         idx: usize,
         sequence_number: usize,
-        read_data: usize,
-        read_length: usize,
-        result: EventResult,
-    );
+        read_data: &mut [u8],
+        result: &mut EventResult,
+    ) -> usize;
+    /// Writes a record the stack doesn't handle itself, like application
+    /// parameters. Failures are reported in `result.pnio_status`.
+    #[allow(clippy::too_many_arguments)]
     fn write_ind_callback<T: App + Copy, U: TaskCallback + Copy>(
         &mut self,
         pnet: &mut PNet<T, U>,
@@ -107,9 +250,8 @@ pub trait App {
         subslot: usize,
         idx: usize,
         sequence_number: usize,
-        write_length: usize,
-        write_data: usize,
-        result: EventResult,
+        write_data: &[u8],
+        result: &mut EventResult,
     );
     fn expect_module_ind_callback<T: App + Copy, U: TaskCallback + Copy>(
         &mut self,
@@ -158,4 +300,78 @@ pub trait App {
         pnet: &mut PNet<T, U>,
         led_state: bool,
     );
+    /// Called when the IP settings changed, for example after a DCP Set
+    fn ip_changed_ind<T: App + Copy, U: TaskCallback + Copy>(
+        &mut self,
+        _pnet: &mut PNet<T, U>,
+        _old: IpConfig,
+        _new: IpConfig,
+    ) {
+    }
+    /// Called when DHCP was enabled or disabled, for example by a DCP Set.
+    /// While enabled, leases are reported back with `PNet::dhcp_lease`.
+    fn dhcp_ind<T: App + Copy, U: TaskCallback + Copy>(
+        &mut self,
+        _pnet: &mut PNet<T, U>,
+        _enable: bool,
+    ) {
+    }
+    /// Called when a DCP Set ends its transaction with a Control Stop block,
+    /// after the other blocks of the request were applied. A new IP address is
+    /// in place by then, unless its conflict check found another host using it.
+    /// `permanent` is set if any of the blocks asked to be kept over a power cycle.
+    fn dcp_commit_ind<T: App + Copy, U: TaskCallback + Copy>(
+        &mut self,
+        _pnet: &mut PNet<T, U>,
+        _permanent: bool,
+    ) {
+    }
+    /// Called for every Identify response of another device, for applications
+    /// that look for devices like a controller does
+    fn dcp_response_ind<T: App + Copy, U: TaskCallback + Copy>(
+        &mut self,
+        _pnet: &mut PNet<T, U>,
+        _device: &DiscoveredDevice,
+    ) {
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pnio_status_bytes() {
+        assert_eq!(PnioStatus::default().to_bytes(), [0x00, 0x00, 0x00, 0x00]);
+        assert!(!PnioStatus::default().is_error());
+
+        assert_eq!(
+            PnioStatus::invalid_index(ERROR_CODE_READ).to_bytes(),
+            [0xde, 0x80, 0xb0, 0x00]
+        );
+        assert_eq!(
+            PnioStatus::access_denied(ERROR_CODE_WRITE).to_bytes(),
+            [0xdf, 0x80, 0xb6, 0x00]
+        );
+        assert_eq!(
+            PnioStatus::write_length_error().to_bytes(),
+            [0xdf, 0x80, 0xb1, 0x00]
+        );
+        assert_eq!(
+            PnioStatus::invalid_parameter().to_bytes(),
+            [0xdf, 0x80, 0xb8, 0x00]
+        );
+        assert_eq!(
+            PnioStatus::out_of_ar_resources().to_bytes(),
+            [0xdb, 0x81, 0x40, 0x04]
+        );
+        assert_eq!(
+            PnioStatus::out_of_cr_resources().to_bytes(),
+            [0xdb, 0x81, 0x40, 0x07]
+        );
+        assert_eq!(
+            PnioStatus::ar_uuid_unknown(ERROR_CODE_RELEASE).to_bytes(),
+            [0xdd, 0x81, 0x40, 0x05]
+        );
+    }
 }
@@ -0,0 +1,294 @@
+use smoltcp::wire::{EthernetAddress, Ipv4Address};
+
+use super::{
+    App, Config, ConfigError, DeviceIdConfig, InterfaceConfig, IpConfig, PortConfig, SlotTable,
+    IM0, IM1, IM2, IM3, IM4,
+};
+use crate::constants::{
+    DEFAULT_DCP_HELLO_COUNT, DEFAULT_DCP_HELLO_INTERVAL_US, DEFAULT_MAX_SEND_LATENCY_US,
+    DEFAULT_SEND_CLOCK_FACTOR, MAX_PHYSICAL_PORTS, MAX_PRODUCT_NAME_SIZE, MAX_STATION_NAME_SIZE,
+};
+
+/// Builds a [`Config`] from defaults, only the fields that differ need to be set
+///
+/// The defaults are a 1 ms tick, one physical port on `eth0`, empty I&M data
+/// and no station name or IP address.
+pub struct ConfigBuilder<T: App> {
+    config: Config<T>,
+}
+
+impl<T> ConfigBuilder<T>
+where
+    T: App + Copy,
+{
+    pub fn new(app: T) -> Self {
+        Self {
+            config: Config {
+                tick_us: 1000,
+                app,
+                im0: IM0 {
+                    vendor_id_hi: 0,
+                    vendor_id_lo: 0,
+                    order_id: [0; 20],
+                    serial_number: [0; 16],
+                    hw_rev: 0,
+                    sw_rev_prefx: 'V',
+                    sw_rev_functional_enhancment: 0,
+                    sw_rev_bug_fix: 0,
+                    sw_rev_internal_change: 0,
+                    revision_counter: 0,
+                    profile_id: 0,
+                    profile_specific_type: 0,
+                    version_major: 1,
+                    version_minor: 1,
+                    supported: 0,
+                },
+                im1: IM1 {
+                    tag_function: [0; 32],
+                    tag_location: [0; 22],
+                },
+                im2: IM2 { date: [0; 16] },
+                im3: IM3 {
+                    descriptor: [0; 54],
+                },
+                im4: IM4 { signature: [0; 54] },
+                device_id: DeviceIdConfig {
+                    vendor_id_hi: 0,
+                    vendor_id_lo: 0,
+                    device_id_hi: 0,
+                    device_id_lo: 0,
+                },
+                oem_device_id: DeviceIdConfig {
+                    vendor_id_hi: 0,
+                    vendor_id_lo: 0,
+                    device_id_hi: 0,
+                    device_id_lo: 0,
+                },
+                station_name: [0; MAX_STATION_NAME_SIZE],
+                product_name: [0; MAX_PRODUCT_NAME_SIZE],
+                min_data_exchange_interval: 32,
+                send_clock_factor: DEFAULT_SEND_CLOCK_FACTOR,
+                reduction_ratio: 1,
+                phase: 0,
+                send_dcp_hello: false,
+                dcp_hello_count: DEFAULT_DCP_HELLO_COUNT,
+                dcp_hello_interval_us: DEFAULT_DCP_HELLO_INTERVAL_US,
+                max_send_latency_us: DEFAULT_MAX_SEND_LATENCY_US,
+                num_physical_ports: 1,
+                use_qualified_diagnosis: false,
+                interface_config: InterfaceConfig {
+                    network_interface_name: "eth0",
+                    ip_config: IpConfig {
+                        mac_address: EthernetAddress([0; 6]),
+                        ip_address: Ipv4Address::UNSPECIFIED,
+                        subnet_mask: Ipv4Address::UNSPECIFIED,
+                        gateway: Ipv4Address::UNSPECIFIED,
+                        dns: None,
+                        enable_dhcp: false,
+                    },
+                    port_config: [const {
+                        PortConfig {
+                            netif_name: "eth0",
+                            default_mau_type: 0x10,
+                        }
+                    }; MAX_PHYSICAL_PORTS],
+                },
+                submodules: SlotTable::new(),
+            },
+        }
+    }
+
+    pub fn tick_us(mut self, tick_us: usize) -> Self {
+        self.config.tick_us = tick_us;
+        self
+    }
+
+    /// Sets the station name, names longer than `MAX_STATION_NAME_SIZE` are truncated
+    pub fn station_name(mut self, name: &str) -> Self {
+        let length = name.len().min(MAX_STATION_NAME_SIZE);
+        self.config.station_name = [0; MAX_STATION_NAME_SIZE];
+        self.config.station_name[..length].copy_from_slice(&name.as_bytes()[..length]);
+        self
+    }
+
+    /// Sets the product name, names longer than `MAX_PRODUCT_NAME_SIZE` are truncated
+    pub fn product_name(mut self, name: &str) -> Self {
+        let length = name.len().min(MAX_PRODUCT_NAME_SIZE);
+        self.config.product_name = [0; MAX_PRODUCT_NAME_SIZE];
+        self.config.product_name[..length].copy_from_slice(&name.as_bytes()[..length]);
+        self
+    }
+
+    /// Sets the vendor and device id, the I&M0 vendor id follows the device's
+    pub fn device_id(mut self, vendor_id: u16, device_id: u16) -> Self {
+        let [vendor_id_hi, vendor_id_lo] = vendor_id.to_be_bytes();
+        let [device_id_hi, device_id_lo] = device_id.to_be_bytes();
+
+        self.config.device_id = DeviceIdConfig {
+            vendor_id_hi,
+            vendor_id_lo,
+            device_id_hi,
+            device_id_lo,
+        };
+        self.config.im0.vendor_id_hi = vendor_id_hi;
+        self.config.im0.vendor_id_lo = vendor_id_lo;
+        self
+    }
+
+    pub fn oem_device_id(mut self, vendor_id: u16, device_id: u16) -> Self {
+        let [vendor_id_hi, vendor_id_lo] = vendor_id.to_be_bytes();
+        let [device_id_hi, device_id_lo] = device_id.to_be_bytes();
+
+        self.config.oem_device_id = DeviceIdConfig {
+            vendor_id_hi,
+            vendor_id_lo,
+            device_id_hi,
+            device_id_lo,
+        };
+        self
+    }
+
+    pub fn im0(mut self, im0: IM0) -> Self {
+        self.config.im0 = im0;
+        self
+    }
+
+    pub fn im1(mut self, im1: IM1) -> Self {
+        self.config.im1 = im1;
+        self
+    }
+
+    pub fn im2(mut self, im2: IM2) -> Self {
+        self.config.im2 = im2;
+        self
+    }
+
+    pub fn im3(mut self, im3: IM3) -> Self {
+        self.config.im3 = im3;
+        self
+    }
+
+    pub fn im4(mut self, im4: IM4) -> Self {
+        self.config.im4 = im4;
+        self
+    }
+
+    pub fn min_data_exchange_interval(mut self, interval: usize) -> Self {
+        self.config.min_data_exchange_interval = interval;
+        self
+    }
+
+    pub fn send_timing(mut self, send_clock_factor: u16, reduction_ratio: u16, phase: u16) -> Self {
+        self.config.send_clock_factor = send_clock_factor;
+        self.config.reduction_ratio = reduction_ratio;
+        self.config.phase = phase;
+        self
+    }
+
+    pub fn send_dcp_hello(mut self, send_dcp_hello: bool) -> Self {
+        self.config.send_dcp_hello = send_dcp_hello;
+        self
+    }
+
+    pub fn dcp_hello(mut self, count: usize, interval_us: usize) -> Self {
+        self.config.dcp_hello_count = count;
+        self.config.dcp_hello_interval_us = interval_us;
+        self
+    }
+
+    pub fn max_send_latency_us(mut self, max_send_latency_us: usize) -> Self {
+        self.config.max_send_latency_us = max_send_latency_us;
+        self
+    }
+
+    pub fn num_physical_ports(mut self, num_physical_ports: usize) -> Self {
+        self.config.num_physical_ports = num_physical_ports;
+        self
+    }
+
+    pub fn use_qualified_diagnosis(mut self, use_qualified_diagnosis: bool) -> Self {
+        self.config.use_qualified_diagnosis = use_qualified_diagnosis;
+        self
+    }
+
+    pub fn network_interface_name(mut self, name: &'static str) -> Self {
+        self.config.interface_config.network_interface_name = name;
+        self
+    }
+
+    pub fn mac_address(mut self, mac_address: EthernetAddress) -> Self {
+        self.config.interface_config.ip_config.mac_address = mac_address;
+        self
+    }
+
+    pub fn ip_config(mut self, ip_config: IpConfig) -> Self {
+        self.config.interface_config.ip_config = ip_config;
+        self
+    }
+
+    pub fn port_config(mut self, port_config: [PortConfig; MAX_PHYSICAL_PORTS]) -> Self {
+        self.config.interface_config.port_config = port_config;
+        self
+    }
+
+    pub fn submodules(mut self, submodules: SlotTable) -> Self {
+        self.config.submodules = submodules;
+        self
+    }
+
+    /// Checks the configuration and returns it if it is usable
+    pub fn build(self) -> Result<Config<T>, ConfigError> {
+        self.config.validate_config()?;
+        Ok(self.config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::TestApp;
+
+    use super::*;
+
+    #[test]
+    fn test_build_defaults() {
+        let config = ConfigBuilder::new(TestApp::default())
+            .station_name("rt-labs-dev")
+            .device_id(0x1337, 0x6969)
+            .build()
+            .unwrap();
+
+        assert_eq!(config.tick_us, 1000);
+        assert_eq!(config.num_physical_ports, 1);
+        assert_eq!(
+            &config.station_name[..config.station_name_len()],
+            b"rt-labs-dev"
+        );
+        assert_eq!(config.device_id.vendor_id_hi, 0x13);
+        assert_eq!(config.device_id.device_id_lo, 0x69);
+        assert_eq!(config.im0.vendor_id_lo, 0x37);
+    }
+
+    #[test]
+    fn test_build_invalid() {
+        let result = ConfigBuilder::new(TestApp::default()).tick_us(0).build();
+        assert_eq!(result.err(), Some(ConfigError::ZeroTickInterval));
+
+        let result = ConfigBuilder::new(TestApp::default())
+            .num_physical_ports(0)
+            .build();
+        assert_eq!(result.err(), Some(ConfigError::InvalidPortCount));
+
+        let result = ConfigBuilder::new(TestApp::default())
+            .min_data_exchange_interval(5000)
+            .build();
+        assert_eq!(
+            result.err(),
+            Some(ConfigError::DataExchangeIntervalTooLarge)
+        );
+
+        let result = ConfigBuilder::new(TestApp::default())
+            .send_timing(32, 4, 4)
+            .build();
+        assert_eq!(result.err(), Some(ConfigError::PhaseOutOfRange));
+    }
+}
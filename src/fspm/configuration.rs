@@ -1,4 +1,4 @@
-use smoltcp::wire::Ipv4Address;
+use smoltcp::wire::{EthernetAddress, Ipv4Address};
 
 use crate::constants::{
     MAX_LOCATION_SIZE, MAX_ORDER_ID_LENGTH, MAX_PHYSICAL_PORTS, MAX_SERIAL_NUMBER_LENGTH,
@@ -46,7 +46,7 @@ pub struct IM3 {
 
 #[derive(Clone)]
 pub struct IM4 {
-    pub signatire: [u8; 54],
+    pub signature: [u8; 54],
 }
 
 #[derive(Clone)]
@@ -57,14 +57,48 @@ pub struct DeviceIdConfig {
     pub device_id_lo: u8,
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub struct IpConfig {
+    pub mac_address: EthernetAddress,
     pub ip_address: Ipv4Address,
     pub subnet_mask: Ipv4Address,
     pub gateway: Ipv4Address,
+    /// DNS server, only set by a full IP suite
+    pub dns: Option<Ipv4Address>,
     pub enable_dhcp: bool,
 }
 
+impl IpConfig {
+    /// The router of the default route. There is none for a gateway of
+    /// 0.0.0.0, or if the device is its own gateway on a flat network.
+    pub fn default_route(&self) -> Option<Ipv4Address> {
+        if self.gateway.is_unspecified() || self.gateway == self.ip_address {
+            return None;
+        }
+
+        Some(self.gateway)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MaskError {
+    NonContiguous,
+}
+
+/// Converts a subnet mask like 255.255.255.0 to its prefix length
+pub fn subnet_mask_to_prefix(mask: Ipv4Address) -> Result<u8, MaskError> {
+    let mask = u32::from_be_bytes(mask.0);
+    let prefix = mask.leading_ones();
+
+    // All bits after the leading ones have to be zero
+    if mask.checked_shl(prefix).unwrap_or(0) != 0 {
+        return Err(MaskError::NonContiguous);
+    }
+
+    Ok(prefix as u8)
+}
+
 #[derive(Clone)]
 pub struct PortConfig {
     pub netif_name: &'static str,
@@ -77,3 +111,62 @@ pub struct InterfaceConfig {
     pub ip_config: IpConfig,
     pub port_config: [PortConfig; MAX_PHYSICAL_PORTS],
 }
+
+/// A submodule plugged into the device, identified by its API, slot and subslot
+#[derive(Clone, Copy, PartialEq)]
+pub struct SubmoduleConfig {
+    pub api: u32,
+    pub slot_number: u16,
+    pub subslot_number: u16,
+    pub module_ident: u32,
+    pub submodule_ident: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subnet_mask_to_prefix() {
+        assert_eq!(
+            subnet_mask_to_prefix(Ipv4Address::new(255, 255, 255, 0)),
+            Ok(24)
+        );
+        assert_eq!(
+            subnet_mask_to_prefix(Ipv4Address::new(255, 255, 0, 0)),
+            Ok(16)
+        );
+        assert_eq!(
+            subnet_mask_to_prefix(Ipv4Address::new(255, 255, 255, 252)),
+            Ok(30)
+        );
+        assert_eq!(
+            subnet_mask_to_prefix(Ipv4Address::new(255, 255, 255, 255)),
+            Ok(32)
+        );
+        assert_eq!(subnet_mask_to_prefix(Ipv4Address::UNSPECIFIED), Ok(0));
+        assert_eq!(
+            subnet_mask_to_prefix(Ipv4Address::new(255, 0, 255, 0)),
+            Err(MaskError::NonContiguous)
+        );
+    }
+
+    #[test]
+    fn test_default_route() {
+        let mut ip_config = IpConfig {
+            mac_address: EthernetAddress([0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe]),
+            ip_address: Ipv4Address::new(192, 168, 0, 2),
+            subnet_mask: Ipv4Address::new(255, 255, 255, 0),
+            gateway: Ipv4Address::new(192, 168, 0, 1),
+            dns: None,
+            enable_dhcp: false,
+        };
+        assert_eq!(ip_config.default_route(), Some(ip_config.gateway));
+
+        ip_config.gateway = Ipv4Address::UNSPECIFIED;
+        assert_eq!(ip_config.default_route(), None);
+
+        ip_config.gateway = ip_config.ip_address;
+        assert_eq!(ip_config.default_route(), None);
+    }
+}
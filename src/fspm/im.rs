@@ -0,0 +1,354 @@
+// Identification and maintenance records
+
+use crate::cmrpc::CmRpc;
+use crate::field::{Field, SmallField};
+use byteorder::{ByteOrder, NetworkEndian};
+
+use super::{IM0, IM1, IM2, IM3, IM4};
+
+pub const IM0_INDEX: u16 = 0xaff0;
+pub const IM1_INDEX: u16 = 0xaff1;
+pub const IM2_INDEX: u16 = 0xaff2;
+pub const IM3_INDEX: u16 = 0xaff3;
+pub const IM4_INDEX: u16 = 0xaff4;
+
+pub const BLOCK_TYPE_IM0: u16 = 0x0020;
+pub const BLOCK_TYPE_IM1: u16 = 0x0021;
+pub const BLOCK_TYPE_IM2: u16 = 0x0022;
+pub const BLOCK_TYPE_IM3: u16 = 0x0023;
+pub const BLOCK_TYPE_IM4: u16 = 0x0024;
+
+/// Bits of the I&M0 supported field that may be set, one for each of I&M1 to I&M4
+pub const IM_SUPPORTED_MASK: u16 = (1 << 1) | (1 << 2) | (1 << 3) | (1 << 4);
+
+/// Allowed prefixes of the software revision
+pub const SW_REV_PREFIXES: [char; 5] = ['V', 'R', 'P', 'U', 'T'];
+
+const BLOCK_TYPE_FIELD: Field = 0..2;
+const BLOCK_LENGTH_FIELD: Field = 2..4;
+const BLOCK_HEADER_LENGTH: usize = 6;
+
+// I&M0 content, relative to the end of the block header
+const VENDOR_ID_HIGH_FIELD: SmallField = 0;
+const VENDOR_ID_LOW_FIELD: SmallField = 1;
+const ORDER_ID_FIELD: Field = 2..22;
+const SERIAL_NUMBER_FIELD: Field = 22..38;
+const HW_REV_FIELD: Field = 38..40;
+const SW_REV_PREFIX_FIELD: SmallField = 40;
+const SW_REV_FUNCTIONAL_ENHANCEMENT_FIELD: SmallField = 41;
+const SW_REV_BUG_FIX_FIELD: SmallField = 42;
+const SW_REV_INTERNAL_CHANGE_FIELD: SmallField = 43;
+const REVISION_COUNTER_FIELD: Field = 44..46;
+const PROFILE_ID_FIELD: Field = 46..48;
+const PROFILE_SPECIFIC_TYPE_FIELD: Field = 48..50;
+const VERSION_MAJOR_FIELD: SmallField = 50;
+const VERSION_MINOR_FIELD: SmallField = 51;
+const SUPPORTED_FIELD: Field = 52..54;
+const IM0_LENGTH: usize = SUPPORTED_FIELD.end;
+
+// I&M1 content
+const TAG_FUNCTION_FIELD: Field = 0..32;
+const TAG_LOCATION_FIELD: Field = 32..54;
+const IM1_LENGTH: usize = TAG_LOCATION_FIELD.end;
+
+// I&M2 to I&M4 content
+const DATE_FIELD: Field = 0..16;
+const DESCRIPTOR_FIELD: Field = 0..54;
+const SIGNATURE_FIELD: Field = 0..54;
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ImError {
+    InvalidBlockType,
+    InvalidLength,
+    InvalidDate,
+}
+
+/// Checks the block header of a written record and returns its content
+fn record_content(buffer: &[u8], block_type: u16, length: usize) -> Result<&[u8], ImError> {
+    if buffer.len() != BLOCK_HEADER_LENGTH + length {
+        return Err(ImError::InvalidLength);
+    }
+
+    if NetworkEndian::read_u16(&buffer[BLOCK_TYPE_FIELD]) != block_type {
+        return Err(ImError::InvalidBlockType);
+    }
+
+    // The block length includes the two version bytes
+    if NetworkEndian::read_u16(&buffer[BLOCK_LENGTH_FIELD]) as usize != length + 2 {
+        return Err(ImError::InvalidLength);
+    }
+
+    Ok(&buffer[BLOCK_HEADER_LENGTH..])
+}
+
+/// Copies a visible string into `buffer`, replacing the zero padding with spaces
+fn write_visible_string(buffer: &mut [u8], value: &[u8]) {
+    for (dst, src) in buffer.iter_mut().zip(value) {
+        *dst = if *src == 0 { b' ' } else { *src };
+    }
+}
+
+pub fn encode_im0(im0: &IM0, buffer: &mut [u8]) -> usize {
+    let content = &mut buffer[BLOCK_HEADER_LENGTH..];
+
+    content[VENDOR_ID_HIGH_FIELD] = im0.vendor_id_hi;
+    content[VENDOR_ID_LOW_FIELD] = im0.vendor_id_lo;
+    write_visible_string(&mut content[ORDER_ID_FIELD], &im0.order_id);
+    write_visible_string(&mut content[SERIAL_NUMBER_FIELD], &im0.serial_number);
+    NetworkEndian::write_u16(&mut content[HW_REV_FIELD], im0.hw_rev);
+
+    if !SW_REV_PREFIXES.contains(&im0.sw_rev_prefx) {
+        warn!("Invalid software revision prefix {}", im0.sw_rev_prefx);
+    }
+
+    content[SW_REV_PREFIX_FIELD] = im0.sw_rev_prefx as u8;
+    content[SW_REV_FUNCTIONAL_ENHANCEMENT_FIELD] = im0.sw_rev_functional_enhancment;
+    content[SW_REV_BUG_FIX_FIELD] = im0.sw_rev_bug_fix;
+    content[SW_REV_INTERNAL_CHANGE_FIELD] = im0.sw_rev_internal_change;
+    NetworkEndian::write_u16(&mut content[REVISION_COUNTER_FIELD], im0.revision_counter);
+    NetworkEndian::write_u16(&mut content[PROFILE_ID_FIELD], im0.profile_id);
+    NetworkEndian::write_u16(
+        &mut content[PROFILE_SPECIFIC_TYPE_FIELD],
+        im0.profile_specific_type,
+    );
+    content[VERSION_MAJOR_FIELD] = im0.version_major;
+    content[VERSION_MINOR_FIELD] = im0.version_minor;
+    NetworkEndian::write_u16(&mut content[SUPPORTED_FIELD], im0.supported);
+
+    CmRpc::encode_block_header(buffer, BLOCK_TYPE_IM0, IM0_LENGTH) + IM0_LENGTH
+}
+
+pub fn encode_im1(im1: &IM1, buffer: &mut [u8]) -> usize {
+    let content = &mut buffer[BLOCK_HEADER_LENGTH..];
+
+    write_visible_string(&mut content[TAG_FUNCTION_FIELD], &im1.tag_function);
+    write_visible_string(&mut content[TAG_LOCATION_FIELD], &im1.tag_location);
+
+    CmRpc::encode_block_header(buffer, BLOCK_TYPE_IM1, IM1_LENGTH) + IM1_LENGTH
+}
+
+pub fn encode_im2(im2: &IM2, buffer: &mut [u8]) -> usize {
+    let content = &mut buffer[BLOCK_HEADER_LENGTH..];
+
+    write_visible_string(&mut content[DATE_FIELD], &im2.date);
+
+    CmRpc::encode_block_header(buffer, BLOCK_TYPE_IM2, DATE_FIELD.end) + DATE_FIELD.end
+}
+
+pub fn encode_im3(im3: &IM3, buffer: &mut [u8]) -> usize {
+    let content = &mut buffer[BLOCK_HEADER_LENGTH..];
+
+    write_visible_string(&mut content[DESCRIPTOR_FIELD], &im3.descriptor);
+
+    CmRpc::encode_block_header(buffer, BLOCK_TYPE_IM3, DESCRIPTOR_FIELD.end) + DESCRIPTOR_FIELD.end
+}
+
+/// The signature is an octet string, so it is copied without space padding
+pub fn encode_im4(im4: &IM4, buffer: &mut [u8]) -> usize {
+    let content = &mut buffer[BLOCK_HEADER_LENGTH..];
+
+    content[SIGNATURE_FIELD].copy_from_slice(&im4.signature);
+
+    CmRpc::encode_block_header(buffer, BLOCK_TYPE_IM4, SIGNATURE_FIELD.end) + SIGNATURE_FIELD.end
+}
+
+/// Applies a written I&M1 record, the record is rejected as a whole if it is malformed
+pub fn parse_im1_write(buffer: &[u8], im1: &mut IM1) -> Result<(), ImError> {
+    let content = record_content(buffer, BLOCK_TYPE_IM1, IM1_LENGTH)?;
+
+    im1.tag_function
+        .copy_from_slice(&content[TAG_FUNCTION_FIELD]);
+    im1.tag_location
+        .copy_from_slice(&content[TAG_LOCATION_FIELD]);
+
+    Ok(())
+}
+
+impl IM2 {
+    /// Stores `bytes` as the installation date if it has the format "YYYY-MM-DD HH:MM",
+    /// otherwise the previous date is kept
+    pub fn try_set_date(&mut self, bytes: &[u8]) -> Result<(), ImError> {
+        const FORMAT: &[u8; 16] = b"DDDD-DD-DD DD:DD";
+
+        if bytes.len() != FORMAT.len() {
+            return Err(ImError::InvalidDate);
+        }
+
+        let valid = bytes.iter().zip(FORMAT).all(|(b, f)| match f {
+            b'D' => b.is_ascii_digit(),
+            _ => b == f,
+        });
+
+        if !valid {
+            return Err(ImError::InvalidDate);
+        }
+
+        self.date.copy_from_slice(bytes);
+
+        Ok(())
+    }
+}
+
+pub fn parse_im2_write(buffer: &[u8], im2: &mut IM2) -> Result<(), ImError> {
+    let content = record_content(buffer, BLOCK_TYPE_IM2, DATE_FIELD.end)?;
+
+    im2.try_set_date(&content[DATE_FIELD])
+}
+
+pub fn parse_im3_write(buffer: &[u8], im3: &mut IM3) -> Result<(), ImError> {
+    let content = record_content(buffer, BLOCK_TYPE_IM3, DESCRIPTOR_FIELD.end)?;
+
+    im3.descriptor.copy_from_slice(&content[DESCRIPTOR_FIELD]);
+
+    Ok(())
+}
+
+pub fn parse_im4_write(buffer: &[u8], im4: &mut IM4) -> Result<(), ImError> {
+    let content = record_content(buffer, BLOCK_TYPE_IM4, SIGNATURE_FIELD.end)?;
+
+    im4.signature.copy_from_slice(&content[SIGNATURE_FIELD]);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::test_config;
+
+    use super::*;
+
+    #[test]
+    fn test_encode_im0() {
+        let mut im0 = test_config().im0;
+        im0.order_id[..4].copy_from_slice(b"6ES7");
+        im0.serial_number[..3].copy_from_slice(b"123");
+        im0.revision_counter = 2;
+        im0.supported = 0x1e;
+
+        let mut buffer = [0; 64];
+        let length = encode_im0(&im0, &mut buffer);
+
+        assert_eq!(length, 60);
+        assert_eq!(
+            buffer[..8],
+            [0x00, 0x20, 0x00, 0x38, 0x01, 0x00, 0x13, 0x37]
+        );
+        assert_eq!(&buffer[8..28], b"6ES7                ");
+        assert_eq!(&buffer[28..44], b"123             ");
+        assert_eq!(buffer[44..46], [0x00, 0x01]);
+        assert_eq!(buffer[46..50], [b'V', 0x00, 0x01, 0x00]);
+        assert_eq!(
+            buffer[50..60],
+            [0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x01, 0x01, 0x00, 0x1e]
+        );
+    }
+
+    #[test]
+    fn test_encode_im1() {
+        let mut im1 = test_config().im1;
+        im1.tag_function[..4].copy_from_slice(b"pump");
+
+        let mut buffer = [0; 64];
+        let length = encode_im1(&im1, &mut buffer);
+
+        assert_eq!(length, 60);
+        assert_eq!(buffer[..6], [0x00, 0x21, 0x00, 0x38, 0x01, 0x00]);
+        assert_eq!(&buffer[6..10], b"pump");
+        assert!(buffer[10..60].iter().all(|b| *b == b' '));
+    }
+
+    #[test]
+    fn test_encode_im4_keeps_zeros() {
+        let im4 = test_config().im4;
+
+        let mut buffer = [0xff; 64];
+        let length = encode_im4(&im4, &mut buffer);
+
+        assert_eq!(length, 60);
+        assert_eq!(buffer[..6], [0x00, 0x24, 0x00, 0x38, 0x01, 0x00]);
+        assert!(buffer[6..60].iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn test_im1_write_round_trip() {
+        let mut im1 = test_config().im1;
+        im1.tag_function[..4].copy_from_slice(b"pump");
+        im1.tag_location[..4].copy_from_slice(b"hall");
+
+        let mut buffer = [0; 64];
+        let length = encode_im1(&im1, &mut buffer);
+
+        let mut written = test_config().im1;
+        parse_im1_write(&buffer[..length], &mut written).unwrap();
+
+        assert_eq!(&written.tag_function[..5], b"pump ");
+        assert_eq!(&written.tag_location[..5], b"hall ");
+    }
+
+    #[test]
+    fn test_im_write_wrong_length() {
+        let mut im3 = test_config().im3;
+        im3.descriptor[0] = b'x';
+
+        let mut buffer = [0; 64];
+        let length = encode_im3(&im3, &mut buffer);
+
+        let mut written = test_config().im3;
+        assert_eq!(
+            parse_im3_write(&buffer[..length - 1], &mut written),
+            Err(ImError::InvalidLength)
+        );
+        assert_eq!(
+            parse_im4_write(&buffer[..length], &mut test_config().im4),
+            Err(ImError::InvalidBlockType)
+        );
+        assert_eq!(written.descriptor[0], 0);
+    }
+
+    #[test]
+    fn test_im2_write() {
+        let mut buffer = [0; 22];
+        CmRpc::encode_block_header(&mut buffer, BLOCK_TYPE_IM2, 16);
+        buffer[6..].copy_from_slice(b"2024-05-17 13:37");
+
+        let mut im2 = test_config().im2;
+        parse_im2_write(&buffer, &mut im2).unwrap();
+
+        assert_eq!(&im2.date, b"2024-05-17 13:37");
+    }
+
+    #[test]
+    fn test_im2_malformed_date() {
+        let mut im2 = test_config().im2;
+        im2.try_set_date(b"2024-05-17 13:37").unwrap();
+
+        let malformed: [&[u8]; 6] = [
+            b"2024/05/17 13:37",
+            b"2024-05-17T13:37",
+            b"2024-05-17 13-37",
+            b"20a4-05-17 13:37",
+            b"2024-05-17 13:3",
+            b"2024-05-17 13:370",
+        ];
+
+        for date in malformed {
+            assert_eq!(im2.try_set_date(date), Err(ImError::InvalidDate));
+            assert_eq!(&im2.date, b"2024-05-17 13:37");
+        }
+    }
+
+    #[test]
+    fn test_im2_write_malformed_date() {
+        let mut buffer = [0; 22];
+        CmRpc::encode_block_header(&mut buffer, BLOCK_TYPE_IM2, 16);
+        buffer[6..].copy_from_slice(b"17.05.2024 13:37");
+
+        let mut im2 = test_config().im2;
+        assert_eq!(
+            parse_im2_write(&buffer, &mut im2),
+            Err(ImError::InvalidDate)
+        );
+        assert_eq!(im2.date, [0; 16]);
+    }
+}
@@ -1,18 +1,44 @@
 pub mod app;
+mod builder;
 mod configuration;
-
-use app::*;
-use configuration::*;
+pub mod im;
+mod slot_table;
 
 use crate::{
-    constants::{MAX_PHYSICAL_PORTS, MAX_PRODUCT_NAME_SIZE, MAX_STATION_NAME_SIZE},
+    constants::{
+        MAX_PHYSICAL_PORTS, MAX_PRODUCT_NAME_SIZE, MAX_REDUCTION_RATIO, MAX_SEND_CLOCK_FACTOR,
+        MAX_STATION_NAME_SIZE, SEND_CLOCK_BASE_NS,
+    },
     scheduler::TaskCallback,
+    time::Ticks,
     PNet,
 };
+use app::*;
+pub use builder::*;
+pub use configuration::*;
+pub use slot_table::*;
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConfigError {
+    ZeroTickInterval,
+    EmptyInterfaceName,
+    InvalidPortCount,
+    ZeroDataExchangeInterval,
+    DataExchangeIntervalTooLarge,
+    InvalidImSupported,
+    /// No free entry is left in the `SlotTable`
+    TooManySubmodules,
+    InvalidSendClockFactor,
+    /// The reduction ratio is not a power of two up to `MAX_REDUCTION_RATIO`
+    InvalidReductionRatio,
+    /// The phase is not below the reduction ratio
+    PhaseOutOfRange,
+}
 
 #[derive(Clone)]
 pub struct Config<T: App> {
-    /// Tick interval in microseconds
+    /// Length of one `Ticks` in microseconds
     pub tick_us: usize,
     pub app: T,
 
@@ -29,58 +55,196 @@ pub struct Config<T: App> {
     pub product_name: [u8; MAX_PRODUCT_NAME_SIZE],
 
     pub min_data_exchange_interval: usize,
+    /// Send clock in units of 31.25 µs, 32 is a send clock of 1 ms
+    pub send_clock_factor: u16,
+    /// The PPM sends once every `reduction_ratio` send clocks
+    pub reduction_ratio: u16,
+    /// The send clock within the reduction ratio the PPM sends in, counted
+    /// from 0. Staggers the frames of several CRs.
+    pub phase: u16,
     pub send_dcp_hello: bool,
+    /// Number of Hello requests in the burst sent when `send_dcp_hello` is set
+    pub dcp_hello_count: usize,
+    /// Delay before the second Hello, doubled for each following one
+    pub dcp_hello_interval_us: usize,
+    /// Queued frames that are overdue by more than this many microseconds are
+    /// dropped instead of sent
+    pub max_send_latency_us: usize,
 
     pub num_physical_ports: usize,
     pub use_qualified_diagnosis: bool,
     pub interface_config: InterfaceConfig,
+
+    /// Submodules that are plugged into the device
+    pub submodules: SlotTable,
 }
 
 impl<T> Config<T>
 where
     T: App + Copy,
 {
-    pub fn init<U: TaskCallback + Copy>(mut self, pnet: &mut PNet<T, U>) {
-        self.validate_config();
+    pub fn init<U: TaskCallback + Copy>(
+        mut self,
+        pnet: &mut PNet<T, U>,
+    ) -> Result<(), ConfigError> {
+        self.validate_config()?;
+
         pnet.fspm_default_config = self.clone();
 
         self.app.signal_led_ind(pnet, false);
         pnet.fspm_user_config = self;
+
+        Ok(())
     }
 
-    fn validate_config(&self) {
-        let im_mask = 2 | 4 | 8 | 16;
+    /// Length of the station name, the remainder of `station_name` is zero padding
+    pub fn station_name_len(&self) -> usize {
+        self.station_name
+            .iter()
+            .position(|b| *b == 0)
+            .unwrap_or(MAX_STATION_NAME_SIZE)
+    }
 
+    /// Length of the product name, the remainder of `product_name` is zero padding
+    pub fn product_name_len(&self) -> usize {
+        self.product_name
+            .iter()
+            .position(|b| *b == 0)
+            .unwrap_or(MAX_PRODUCT_NAME_SIZE)
+    }
+
+    /// Time between two frames of the PPM, `send_clock_factor * reduction_ratio`
+    /// send clock units
+    pub fn ppm_send_interval(&self) -> Ticks {
+        let send_clocks = self.send_clock_factor as usize * self.reduction_ratio as usize;
+        Ticks::from_micros(send_clocks * SEND_CLOCK_BASE_NS / 1000, self.tick_us)
+    }
+
+    /// Delay of the first frame of the PPM, `phase` send clocks
+    pub fn ppm_send_offset(&self) -> Ticks {
+        let send_clocks = self.send_clock_factor as usize * self.phase as usize;
+        Ticks::from_micros(send_clocks * SEND_CLOCK_BASE_NS / 1000, self.tick_us)
+    }
+
+    pub(crate) fn validate_config(&self) -> Result<(), ConfigError> {
         if self.tick_us == 0 {
-            defmt::panic!("Tick interval must be more than 0.");
+            return Err(ConfigError::ZeroTickInterval);
         }
 
         if self.interface_config.network_interface_name.is_empty() {
-            defmt::panic!("Network interface must have a name");
+            return Err(ConfigError::EmptyInterfaceName);
         }
 
         if self.num_physical_ports == 0 || self.num_physical_ports > MAX_PHYSICAL_PORTS {
-            defmt::panic!(
+            error!(
                 "Wrong number of physical ports. Got {}, must be between 1 and {}",
-                self.num_physical_ports,
-                MAX_PHYSICAL_PORTS
+                self.num_physical_ports, MAX_PHYSICAL_PORTS
             );
+            return Err(ConfigError::InvalidPortCount);
         }
 
         if self.min_data_exchange_interval == 0 {
-            defmt::panic!("min_data_exchange_interval must be more than 0");
+            return Err(ConfigError::ZeroDataExchangeInterval);
         }
 
         if self.min_data_exchange_interval > 4096 {
-            defmt::panic!("min_data_exchange_interval is too large");
+            return Err(ConfigError::DataExchangeIntervalTooLarge);
+        }
+
+        if self.send_clock_factor == 0 || self.send_clock_factor > MAX_SEND_CLOCK_FACTOR {
+            return Err(ConfigError::InvalidSendClockFactor);
         }
 
-        if (self.im0.supported & im_mask) > 0 {
-            defmt::panic!(
-                "I&M supported setting is wrong. Got {}, must be {}",
+        if !self.reduction_ratio.is_power_of_two() || self.reduction_ratio > MAX_REDUCTION_RATIO {
+            return Err(ConfigError::InvalidReductionRatio);
+        }
+
+        if self.phase >= self.reduction_ratio {
+            return Err(ConfigError::PhaseOutOfRange);
+        }
+
+        if (self.im0.supported & !im::IM_SUPPORTED_MASK) > 0 {
+            error!(
+                "I&M supported setting is wrong. Got {}, only {} may be set",
                 self.im0.supported,
-                im_mask
+                im::IM_SUPPORTED_MASK
             );
+            return Err(ConfigError::InvalidImSupported);
         }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::test_config;
+
+    use super::*;
+
+    #[test]
+    fn test_validate_config() {
+        assert_eq!(test_config().validate_config(), Ok(()));
+
+        let mut config = test_config();
+        config.im0.supported = im::IM_SUPPORTED_MASK;
+        assert_eq!(config.validate_config(), Ok(()));
+
+        config.im0.supported = 1 << 5;
+        assert_eq!(
+            config.validate_config(),
+            Err(ConfigError::InvalidImSupported)
+        );
+
+        let mut config = test_config();
+        config.min_data_exchange_interval = 0;
+        assert_eq!(
+            config.validate_config(),
+            Err(ConfigError::ZeroDataExchangeInterval)
+        );
+    }
+
+    #[test]
+    fn test_validate_send_timing() {
+        let mut config = test_config();
+        config.reduction_ratio = 8;
+        config.phase = 7;
+        assert_eq!(config.validate_config(), Ok(()));
+
+        config.phase = 8;
+        assert_eq!(config.validate_config(), Err(ConfigError::PhaseOutOfRange));
+
+        config.phase = 0;
+        config.reduction_ratio = 6;
+        assert_eq!(
+            config.validate_config(),
+            Err(ConfigError::InvalidReductionRatio)
+        );
+
+        config.reduction_ratio = 1024;
+        assert_eq!(
+            config.validate_config(),
+            Err(ConfigError::InvalidReductionRatio)
+        );
+
+        config.reduction_ratio = 1;
+        config.send_clock_factor = 0;
+        assert_eq!(
+            config.validate_config(),
+            Err(ConfigError::InvalidSendClockFactor)
+        );
+    }
+
+    #[test]
+    fn test_ppm_send_timing() {
+        let mut config = test_config();
+        config.tick_us = 250;
+        config.send_clock_factor = 8;
+        config.reduction_ratio = 16;
+        config.phase = 3;
+
+        // 8 * 31.25 µs is a send clock of 250 µs
+        assert_eq!(config.ppm_send_interval(), Ticks(16));
+        assert_eq!(config.ppm_send_offset(), Ticks(3));
     }
 }
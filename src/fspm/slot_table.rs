@@ -0,0 +1,194 @@
+// The modules and submodules plugged into the device
+
+use crate::cmrpc::{ExpectedSubmodule, ModuleState, SubmoduleIdentInfo};
+use crate::constants::{MAX_SLOTS, MAX_SUBSLOTS};
+
+use super::{ConfigError, SubmoduleConfig};
+
+pub const MAX_PLUGGED_SUBMODULES: usize = MAX_SLOTS * MAX_SUBSLOTS;
+
+/// The plugged submodules, at most one per API, slot and subslot. All
+/// submodules in a slot belong to the same module.
+#[derive(Clone, Copy, PartialEq)]
+pub struct SlotTable {
+    submodules: [Option<SubmoduleConfig>; MAX_PLUGGED_SUBMODULES],
+}
+
+impl Default for SlotTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SlotTable {
+    pub const fn new() -> Self {
+        Self {
+            submodules: [None; MAX_PLUGGED_SUBMODULES],
+        }
+    }
+
+    /// Plugs `submodule`, replacing the submodule in its subslot
+    pub fn plug(&mut self, submodule: SubmoduleConfig) -> Result<(), ConfigError> {
+        let position = self
+            .submodules
+            .iter()
+            .position(|s| {
+                s.is_some_and(|s| {
+                    s.api == submodule.api
+                        && s.slot_number == submodule.slot_number
+                        && s.subslot_number == submodule.subslot_number
+                })
+            })
+            .or_else(|| self.submodules.iter().position(Option::is_none))
+            .ok_or(ConfigError::TooManySubmodules)?;
+        self.submodules[position] = Some(submodule);
+
+        Ok(())
+    }
+
+    /// Removes the submodule in the given subslot and returns it
+    pub fn pull(
+        &mut self,
+        api: u32,
+        slot_number: u16,
+        subslot_number: u16,
+    ) -> Option<SubmoduleConfig> {
+        self.submodules
+            .iter_mut()
+            .find(|s| {
+                s.is_some_and(|s| {
+                    s.api == api
+                        && s.slot_number == slot_number
+                        && s.subslot_number == subslot_number
+                })
+            })?
+            .take()
+    }
+
+    pub fn submodule(
+        &self,
+        api: u32,
+        slot_number: u16,
+        subslot_number: u16,
+    ) -> Option<&SubmoduleConfig> {
+        self.iter().find(|s| {
+            s.api == api && s.slot_number == slot_number && s.subslot_number == subslot_number
+        })
+    }
+
+    /// The ident of the module in the slot, `None` if the slot is empty
+    pub fn module_ident(&self, api: u32, slot_number: u16) -> Option<u32> {
+        self.iter()
+            .find(|s| s.api == api && s.slot_number == slot_number)
+            .map(|s| s.module_ident)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &SubmoduleConfig> + Clone {
+        self.submodules.iter().flatten()
+    }
+
+    /// Compares a submodule the controller expects against the plugged ones,
+    /// `None` if they match
+    pub fn diff(&self, expected: &ExpectedSubmodule) -> Option<(ModuleState, SubmoduleIdentInfo)> {
+        let Some(module_ident) = self.module_ident(expected.api, expected.slot_number) else {
+            return Some((ModuleState::NoModule, SubmoduleIdentInfo::NoSubmodule));
+        };
+
+        if module_ident != expected.module_ident {
+            return Some((ModuleState::WrongModule, SubmoduleIdentInfo::Wrong));
+        }
+
+        match self.submodule(expected.api, expected.slot_number, expected.subslot_number) {
+            None => Some((ModuleState::ProperModule, SubmoduleIdentInfo::NoSubmodule)),
+            Some(s) if s.submodule_ident != expected.submodule_ident => {
+                Some((ModuleState::ProperModule, SubmoduleIdentInfo::Wrong))
+            }
+            Some(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAP: SubmoduleConfig = SubmoduleConfig {
+        api: 0,
+        slot_number: 0,
+        subslot_number: 1,
+        module_ident: 0x01,
+        submodule_ident: 0x01,
+    };
+
+    fn expected(module_ident: u32, subslot_number: u16, submodule_ident: u32) -> ExpectedSubmodule {
+        ExpectedSubmodule {
+            api: 0,
+            slot_number: 0,
+            module_ident,
+            subslot_number,
+            submodule_ident,
+        }
+    }
+
+    #[test]
+    fn test_plug_and_pull() {
+        let mut slots = SlotTable::new();
+
+        slots.plug(DAP).unwrap();
+        slots
+            .plug(SubmoduleConfig {
+                submodule_ident: 0x02,
+                ..DAP
+            })
+            .unwrap();
+
+        // Plugging into the same subslot replaces the submodule
+        assert_eq!(slots.iter().count(), 1);
+        assert_eq!(slots.submodule(0, 0, 1).unwrap().submodule_ident, 0x02);
+        assert_eq!(slots.module_ident(0, 0), Some(0x01));
+
+        assert!(slots.pull(0, 0, 1).is_some());
+        assert!(slots.pull(0, 0, 1).is_none());
+        assert_eq!(slots.module_ident(0, 0), None);
+
+        for subslot_number in 0..MAX_PLUGGED_SUBMODULES as u16 {
+            slots
+                .plug(SubmoduleConfig {
+                    subslot_number,
+                    ..DAP
+                })
+                .unwrap();
+        }
+        assert_eq!(
+            slots.plug(SubmoduleConfig {
+                subslot_number: 0x8000,
+                ..DAP
+            }),
+            Err(ConfigError::TooManySubmodules)
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let mut slots = SlotTable::new();
+        assert_eq!(
+            slots.diff(&expected(0x01, 1, 0x01)),
+            Some((ModuleState::NoModule, SubmoduleIdentInfo::NoSubmodule))
+        );
+
+        slots.plug(DAP).unwrap();
+        assert_eq!(slots.diff(&expected(0x01, 1, 0x01)), None);
+        assert_eq!(
+            slots.diff(&expected(0x02, 1, 0x01)),
+            Some((ModuleState::WrongModule, SubmoduleIdentInfo::Wrong))
+        );
+        assert_eq!(
+            slots.diff(&expected(0x01, 1, 0x02)),
+            Some((ModuleState::ProperModule, SubmoduleIdentInfo::Wrong))
+        );
+        assert_eq!(
+            slots.diff(&expected(0x01, 2, 0x01)),
+            Some((ModuleState::ProperModule, SubmoduleIdentInfo::NoSubmodule))
+        );
+    }
+}
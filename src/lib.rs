@@ -1,19 +1,100 @@
 #![cfg_attr(not(test), no_std)]
 
-use fspm::{app::App, Config};
-use scheduler::{Scheduler, Task, TaskCallback};
-use smoltcp::{iface::SocketHandle, wire::EthernetAddress};
-use stm32_eth::{mac::EthernetMAC, Parts};
+#[cfg(feature = "device")]
+use core::cmp::Reverse;
 
-mod cmdev;
-mod cmrpc;
+#[cfg(feature = "device")]
+use alarm::{Alarm, AlarmPriority};
+#[cfg(feature = "device")]
+use arp::{Acd, IpSuite};
+#[cfg(feature = "device")]
+use cmdev::{AbortReason, Ar, ArStore, Arep, CmdevState, FrameIdAllocator};
+#[cfg(feature = "device")]
+use cmrpc::{CmRpc, ConnectRequest, IocrType, RecordError};
+#[cfg(feature = "device")]
+use constants::{
+    MAX_CR, MAX_FRAME_LENGTH, MAX_MULTICAST_GROUPS, MAX_RX_FRAMES_PER_POLL, MAX_STATION_NAME_SIZE,
+    MIN_FRAME_LENGTH,
+};
+#[cfg(feature = "device")]
+use cpm::Cpm;
+#[cfg(feature = "device")]
+use dhcp::{Dhcp, DhcpState};
+#[cfg(feature = "device")]
+use error::Error;
+#[cfg(all(test, feature = "device"))]
+use ethernet::MockDma;
+#[cfg(feature = "device")]
+use ethernet::{setup::setup_pins, EthType, EthernetFrame, FrameKind, Gpio, RxError, Transport};
+#[cfg(feature = "device")]
+use fspm::{
+    app::{App, EventResult, PnioStatus, ERROR_CODE_RELEASE},
+    subnet_mask_to_prefix, Config, IpConfig, SubmoduleConfig,
+};
+#[cfg(feature = "device")]
+use lldp::{Lldp, NeighborTable};
+#[cfg(feature = "device")]
+use ppm::Ppm;
+#[cfg(feature = "device")]
+use ptcp::SyncInfo;
+#[cfg(feature = "device")]
+use response::{Response, ResponseBuilder};
+#[cfg(feature = "device")]
+use scheduler::{Scheduler, Task, TaskCallback, TaskKind};
+#[cfg(feature = "device")]
+use smoltcp::{
+    iface::{Interface, SocketHandle},
+    time::Instant,
+    wire::Ipv4Cidr,
+};
+#[cfg(feature = "device")]
+use stats::{increment, PnetStats};
+#[cfg(feature = "device")]
+use stm32_eth::{
+    dma::{EthernetDMA, RxRingEntry, TxRingEntry},
+    hal::rcc::Clocks,
+    mac::EthernetMAC,
+    Parts, PartsIn,
+};
+#[cfg(feature = "device")]
+use time::Ticks;
+
+#[macro_use]
+#[cfg_attr(not(feature = "device"), allow(unused_macros))]
+mod log;
+
+#[cfg(feature = "device")]
+pub mod alarm;
+#[cfg(feature = "device")]
+pub mod arp;
+#[cfg(feature = "device")]
+pub mod cmdev;
+#[cfg(feature = "device")]
+pub mod cmrpc;
 pub mod constants;
-mod cpm;
-// mod dcp;
-mod error;
+#[cfg(feature = "device")]
+pub mod cpm;
+pub mod dcp;
+#[cfg(feature = "device")]
+pub mod dhcp;
+#[cfg(feature = "device")]
+pub mod error;
 pub mod ethernet;
-mod fspm;
+#[cfg(feature = "device")]
+pub mod fspm;
+#[cfg(feature = "device")]
+pub mod lldp;
+#[cfg(feature = "device")]
+pub mod ppm;
+#[cfg(feature = "device")]
+pub mod ptcp;
+#[cfg(feature = "device")]
+pub mod response;
+#[cfg(feature = "device")]
 pub mod scheduler;
+#[cfg(feature = "device")]
+pub mod stats;
+pub mod time;
 pub mod types;
 mod util;
 
@@ -23,38 +104,73 @@ mod field {
     pub type Rest = ::core::ops::RangeFrom<usize>;
 }
 
-// pub use dcp::*;
+pub use dcp::*;
+/// The address types of the public API, so applications don't need to depend on smoltcp
+pub use smoltcp::wire::{EthernetAddress, Ipv4Address};
 
+#[cfg(feature = "device")]
 #[derive(Clone, Copy)]
 pub struct OutgoingPacket {
-    pub data: [u8; 255],
+    pub data: [u8; MAX_FRAME_LENGTH],
+    /// Number of bytes of `data` that belong to the frame
     pub length: usize,
-    pub send_at: usize,
+    pub send_at: Ticks,
+    /// Queue order, breaks ties between packets with the same `send_at`
+    sequence: u32,
+}
+
+#[cfg(feature = "device")]
+impl OutgoingPacket {
+    pub fn frame(&self) -> &[u8] {
+        &self.data[..self.length]
+    }
 }
 
+#[cfg(feature = "device")]
+#[allow(dead_code)]
 pub struct PNet<'rx, 'tx, T: App + Copy, U: TaskCallback + Copy> {
     global_alarm_enable: bool,
 
     // CPM
     cpm_instance_count: u32,
+    cpm: [Option<Cpm>; MAX_CR],
 
     // PPM
     ppm_instance_count: u32,
+    ppm: [Option<Ppm>; MAX_CR],
 
     // DCP
     dcp_global_block_qualifier: u16,
     dcp_sam: EthernetAddress,
     dcp_delayed_response_waiting: bool,
+    dcp_hello: HelloState,
+
+    dcp_led_timeout: Option<Task<U>>,
+    dcp_sam_timeout: Option<Task<U>>,
+    dcp_identresp_timeout: Option<Task<U>>,
+
+    // Alarms
+    alarm: Alarm,
+
+    // ACD
+    acd: Acd,
+
+    // DHCP
+    dhcp_state: DhcpState,
 
-    dcp_led_timeout: Task<U>,
-    dcp_sam_timeout: Task<U>,
-    dcp_identresp_timeout: Task<U>,
+    // LLDP
+    lldp_neighbors: NeighborTable,
+
+    // PTCP
+    ptcp_sync: Option<SyncInfo>,
 
     // Scheduler
     scheduler: Scheduler<U>,
 
     // CMDEV
     cmdev_initialised: bool,
+    ars: ArStore,
+    frame_ids: FrameIdAllocator,
     // cmdev_device: pf_device_t,
 
     // CMNIA
@@ -79,213 +195,1404 @@ pub struct PNet<'rx, 'tx, T: App + Copy, U: TaskCallback + Copy> {
     //    bool cmina_commit_ip_suite;
     // config: Config,
     outgoing_packets: [Option<OutgoingPacket>; 8],
+    next_packet_sequence: u32,
+    response: ResponseBuilder,
+    stats: PnetStats,
+    multicast_groups: [Option<EthernetAddress>; MAX_MULTICAST_GROUPS],
     ethernet_parts: Option<Parts<'rx, 'tx, EthernetMAC>>,
+    /// Used instead of the DMA when set
+    #[cfg(test)]
+    mock_dma: Option<MockDma>,
+    interface: Option<Interface>,
     tcp_handle: SocketHandle,
     udp_handle: SocketHandle,
 }
 
+#[cfg(feature = "device")]
 impl<'rx, 'tx, T, U> PNet<'rx, 'tx, T, U>
 where
     T: App + Copy,
     U: TaskCallback + Copy,
 {
-    pub fn init(&mut self, config: Config<T>) {
-        config.init(self);
+    pub fn new(config: Config<T>) -> Self {
+        Self {
+            global_alarm_enable: false,
+            cpm_instance_count: 0,
+            cpm: [None; MAX_CR],
+            ppm_instance_count: 0,
+            ppm: [None; MAX_CR],
+            dcp_global_block_qualifier: 0,
+            dcp_sam: EthernetAddress::default(),
+            dcp_delayed_response_waiting: false,
+            dcp_hello: HelloState::new(),
+            dcp_led_timeout: None,
+            dcp_sam_timeout: None,
+            dcp_identresp_timeout: None,
+            alarm: Alarm::new(),
+            acd: Acd::new(),
+            dhcp_state: DhcpState::default(),
+            lldp_neighbors: NeighborTable::new(),
+            ptcp_sync: None,
+            scheduler: Scheduler::new(config.tick_us),
+            cmdev_initialised: false,
+            ars: ArStore::new(),
+            frame_ids: FrameIdAllocator::new(),
+            fspm_default_config: config.clone(),
+            fspm_user_config: config,
+            outgoing_packets: [None; 8],
+            next_packet_sequence: 0,
+            response: ResponseBuilder::new(),
+            stats: PnetStats::default(),
+            multicast_groups: [
+                Some(EthernetAddress(DCP_IDENTIFY_MULTICAST)),
+                Some(EthernetAddress(DCP_HELLO_MULTICAST)),
+                None,
+                None,
+            ],
+            ethernet_parts: None,
+            #[cfg(test)]
+            mock_dma: None,
+            interface: None,
+            tcp_handle: SocketHandle::default(),
+            udp_handle: SocketHandle::default(),
+        }
+    }
+
+    pub fn init(&mut self, config: Config<T>, current_time: Ticks) -> Result<(), Error> {
+        config.init(self).map_err(Error::ConfigError)?;
 
         self.cmdev_initialised = false;
+
+        Lldp::start(self, current_time);
+
+        if self.fspm_user_config.send_dcp_hello {
+            Dcp::start_hello(self, current_time);
+        }
+
+        Ok(())
+    }
+
+    pub fn init_ethernet(
+        &mut self,
+        ethernet: PartsIn,
+        clocks: Clocks,
+        gpio: Gpio,
+        rx_ring: &'rx mut [RxRingEntry; 2],
+        tx_ring: &'tx mut [TxRingEntry; 2],
+    ) {
+        info!("Enabling ethernet...");
+
+        let eth_pins = setup_pins(gpio);
+
+        let mut parts = stm32_eth::new(
+            ethernet,
+            &mut rx_ring[..],
+            &mut tx_ring[..],
+            clocks,
+            eth_pins,
+        )
+        .unwrap();
+        parts.dma.enable_interrupt();
+
+        let ip_config = self.fspm_user_config.interface_config.ip_config.clone();
+        let config = smoltcp::iface::Config::new(ip_config.mac_address.into());
+        let iface = Interface::new(config, &mut &mut parts.dma, Instant::ZERO);
+        self.interface = Some(iface);
+
+        self.update_interface();
+
+        info!(
+            "Enabled internet with IP and MAC: {}, {:x}",
+            ip_config.ip_address, ip_config.mac_address
+        );
+
+        self.ethernet_parts = Some(parts);
+    }
+
+    pub fn dma(&mut self) -> Result<&mut EthernetDMA<'rx, 'tx>, Error> {
+        match &mut self.ethernet_parts {
+            Some(parts) => Ok(&mut parts.dma),
+            None => Err(Error::NotInitialised),
+        }
+    }
+
+    /// The transport frames are sent and received through
+    fn transport(&mut self) -> Result<&mut dyn Transport, Error> {
+        #[cfg(test)]
+        if let Some(mock) = &mut self.mock_dma {
+            return Ok(mock);
+        }
+
+        match &mut self.ethernet_parts {
+            Some(parts) => Ok(&mut parts.dma),
+            None => Err(Error::NotInitialised),
+        }
+    }
+
+    /// Frame counters since startup
+    pub fn stats(&self) -> &PnetStats {
+        &self.stats
+    }
+
+    /// Whether frames sent to `mac` are meant for this device
+    pub fn accepts_destination(&self, mac: &EthernetAddress) -> bool {
+        *mac == self.fspm_user_config.interface_config.ip_config.mac_address
+            || self.multicast_groups.contains(&Some(*mac))
+    }
+
+    /// Accepts frames sent to the multicast group `mac` from now on
+    pub fn join_multicast_group(&mut self, mac: EthernetAddress) -> Result<(), Error> {
+        if self.multicast_groups.contains(&Some(mac)) {
+            return Ok(());
+        }
+
+        let slot = self
+            .multicast_groups
+            .iter_mut()
+            .find(|group| group.is_none())
+            .ok_or(Error::TooManyMulticastGroups)?;
+        *slot = Some(mac);
+
+        Ok(())
+    }
+
+    /// Stops accepting frames sent to the multicast group `mac`
+    pub fn leave_multicast_group(&mut self, mac: EthernetAddress) {
+        for group in self.multicast_groups.iter_mut() {
+            if *group == Some(mac) {
+                *group = None;
+            }
+        }
+    }
+
+    /// The IP settings currently in use
+    pub fn ip_config(&self) -> &IpConfig {
+        &self.fspm_user_config.interface_config.ip_config
+    }
+
+    /// Plugs a submodule, replacing the one already in its subslot
+    pub fn plug_submodule(&mut self, submodule: SubmoduleConfig) -> Result<(), Error> {
+        self.fspm_user_config
+            .submodules
+            .plug(submodule)
+            .map_err(Error::ConfigError)
+    }
+
+    /// Pulls the submodule in the given subslot, returning it if one was plugged
+    pub fn pull_submodule(
+        &mut self,
+        api: u32,
+        slot_number: u16,
+        subslot_number: u16,
+    ) -> Option<SubmoduleConfig> {
+        self.fspm_user_config
+            .submodules
+            .pull(api, slot_number, subslot_number)
+    }
+
+    pub fn update_interface(&mut self) {
+        let ip_config = &self.fspm_user_config.interface_config.ip_config;
+        let ip_address = ip_config.ip_address;
+
+        let prefix = match subnet_mask_to_prefix(ip_config.subnet_mask) {
+            Ok(prefix) => prefix,
+            Err(e) => {
+                error!("Invalid subnet mask {}: {}", ip_config.subnet_mask, e);
+                return;
+            }
+        };
+
+        if let Some(iface) = &mut self.interface {
+            iface.update_ip_addrs(|addr| {
+                addr.clear();
+                addr.push(smoltcp::wire::IpCidr::Ipv4(Ipv4Cidr::new(
+                    ip_address, prefix,
+                )))
+                .ok();
+            });
+
+            info!(
+                "Update ethernet interface with IP: {}/{}",
+                ip_address, prefix
+            );
+
+            match ip_config.default_route() {
+                Some(gateway) => {
+                    if iface.routes_mut().add_default_ipv4_route(gateway).is_err() {
+                        error!("Route table is full, no default route via {}", gateway);
+                    }
+                }
+                None => {
+                    iface.routes_mut().remove_default_ipv4_route();
+                }
+            }
+        } else {
+            error!("PNet not yet initialised, interface is None");
+        }
+    }
+
+    /// Changes the station name, for example to one derived from a serial number.
+    ///
+    /// The new name is announced with a burst of DCP Hellos if `send_dcp_hello` is set,
+    /// starting at `current_time`.
+    pub fn set_name_of_station(
+        &mut self,
+        name: &str,
+        current_time: Ticks,
+    ) -> Result<(), NameError> {
+        validate_station_name(name.as_bytes())?;
+
+        let config = &mut self.fspm_user_config;
+        config.station_name = [0; MAX_STATION_NAME_SIZE];
+        config.station_name[..name.len()].copy_from_slice(name.as_bytes());
+
+        if config.send_dcp_hello {
+            // The first one is sent with the next batch of queued frames
+            Dcp::start_hello(self, current_time);
+        }
+
+        Ok(())
+    }
+
+    /// Uses the address of a DHCP lease obtained by the application.
+    ///
+    /// Only accepted while DHCP is enabled, the address is checked for conflicts first.
+    pub fn dhcp_lease(&mut self, suite: IpSuite, current_time: Ticks) -> Result<(), Error> {
+        Dhcp::lease(self, suite, current_time)
+    }
+
+    pub fn dhcp_state(&self) -> DhcpState {
+        self.dhcp_state
+    }
+
+    /// Sets up an AR for a connect request and encodes the response body into
+    /// `buffer`. Returns the AREP of the new AR and the length of the response.
+    /// Every IOCR gets a frame id of its own, the response carries these ids.
+    /// The IOCRs the controller provides get a CPM whose watchdog aborts the
+    /// AR when their frames stop, the alarm CR opens the alarm channel.
+    ///
+    /// Fails with the status for the controller if an IOCR has invalid
+    /// watchdog parameters, all ARs are in use, no frame ids are left or the
+    /// response doesn't fit `buffer` and the controller's `args_maximum`.
+    pub fn connect(
+        &mut self,
+        request: &ConnectRequest,
+        buffer: &mut [u8],
+    ) -> Result<(Arep, usize), PnioStatus> {
+        for iocr in request.iocrs.iter().flatten() {
+            if let Err(status) = iocr.check_watchdog() {
+                warn!("Rejected connect, invalid IOCR watchdog");
+                return Err(status);
+            }
+        }
+
+        let Some(arep) = self.ars.allocate(Ar::new(&request.ar)) else {
+            warn!("Rejected connect, no free AR");
+            return Err(PnioStatus::out_of_ar_resources());
+        };
+
+        let mut request = *request;
+        for iocr in request.iocrs.iter_mut().flatten() {
+            let Some(frame_id) = self.frame_ids.allocate(arep, iocr.iocr_type) else {
+                warn!("Rejected connect, no free frame id");
+                self.free_ar(arep);
+                return Err(PnioStatus::out_of_cr_resources());
+            };
+            iocr.frame_id = frame_id;
+
+            if matches!(
+                iocr.iocr_type,
+                IocrType::Output | IocrType::MulticastConsumer
+            ) {
+                let cpm = Cpm::for_iocr(arep, iocr, self.fspm_user_config.tick_us);
+                if Cpm::start(self, cpm).is_none() {
+                    warn!("Rejected connect, no free CPM");
+                    self.free_ar(arep);
+                    return Err(PnioStatus::out_of_cr_resources());
+                }
+            }
+        }
+
+        // The application may plug the modules it is told about, before the
+        // expected submodules are compared against the plugged ones
+        let expected = request.expected_submodules.iter().flatten();
+        for (i, e) in expected.clone().enumerate() {
+            let announced = expected
+                .clone()
+                .take(i)
+                .any(|other| other.api == e.api && other.slot_number == e.slot_number);
+            let plugged = self
+                .fspm_user_config
+                .submodules
+                .module_ident(e.api, e.slot_number)
+                .is_some();
+            if announced || plugged {
+                continue;
+            }
+
+            let mut app = self.fspm_user_config.app;
+            app.expect_module_ind_callback(
+                self,
+                e.api as usize,
+                e.slot_number as usize,
+                e.module_ident as usize,
+            );
+            self.fspm_user_config.app = app;
+        }
+
+        let length = match CmRpc::build_connect_response(&request, &self.fspm_user_config, buffer) {
+            Ok(length) => length,
+            Err(e) => {
+                warn!("Rejected connect, response doesn't fit: {}", e);
+                self.free_ar(arep);
+                return Err(PnioStatus::args_length_invalid());
+            }
+        };
+        if let Some(ar) = self.ars.get_mut(arep) {
+            ar.state = CmdevState::ConnectResp;
+        }
+
+        if let Some(alarm_cr) = &request.alarm_cr {
+            Alarm::start(self, arep, request.ar.cm_initiator_mac, alarm_cr);
+        }
+
+        let mut app = self.fspm_user_config.app;
+        app.connect_ind_callback(self, arep, EventResult::default());
+        self.fspm_user_config.app = app;
+
+        Ok((arep, length))
+    }
+
+    /// The state of the AR of `arep`, `None` if there is no such AR.
+    ///
+    /// Process data is only exchanged in `CmdevState::DataExchange`.
+    pub fn ar_state(&self, arep: Arep) -> Option<CmdevState> {
+        self.ars.get(arep).map(|ar| ar.state)
+    }
+
+    /// Whether any AR exchanges process data with its controller
+    pub fn is_connected(&self) -> bool {
+        self.ars
+            .iter()
+            .any(|ar| ar.state == CmdevState::DataExchange)
+    }
+
+    /// Ends the AR of `arep` on an RPC release from the controller
+    pub fn release(&mut self, arep: Arep) -> Result<(), PnioStatus> {
+        if self.ars.get(arep).is_none() {
+            return Err(PnioStatus::ar_uuid_unknown(ERROR_CODE_RELEASE));
+        }
+        self.abort_ar(arep, AbortReason::Released);
+
+        Ok(())
+    }
+
+    /// Ends the AR of `arep`: frees its resources with `free_ar` and tells
+    /// the application with `App::release_ind_callback`. Does nothing if
+    /// there is no such AR.
+    pub fn abort_ar(&mut self, arep: Arep, reason: AbortReason) {
+        let Some(ar) = self.ars.get_mut(arep) else {
+            debug!("No AR {} to abort", arep);
+            return;
+        };
+        info!("Aborting AR {}: {}", arep, reason);
+        ar.state = CmdevState::Abort;
+
+        self.free_ar(arep);
+
+        let mut app = self.fspm_user_config.app;
+        app.release_ind_callback(self, arep, EventResult::default());
+        self.fspm_user_config.app = app;
+    }
+
+    /// Frees everything `connect` set up for the AR of `arep`: its CPMs and
+    /// their scheduled watchdogs, its alarm channel, its frame ids and its slot
+    fn free_ar(&mut self, arep: Arep) {
+        Cpm::stop(self, arep);
+        Alarm::stop(self, arep);
+        self.frame_ids.release(arep);
+        self.ars.release(arep);
+    }
+
+    /// Reads the record with the given index into `buffer` and returns its length.
+    ///
+    /// Records the stack doesn't know are read from the application with
+    /// `App::read_ind_callback`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn read_record(
+        &mut self,
+        arep: usize,
+        api: usize,
+        slot: usize,
+        subslot: usize,
+        index: u16,
+        sequence_number: usize,
+        buffer: &mut [u8],
+    ) -> Result<usize, RecordError> {
+        match CmRpc::read_record(&self.fspm_user_config, index, buffer) {
+            Err(RecordError::InvalidIndex) => (),
+            result => return result,
+        }
+
+        let mut result = EventResult::default();
+        let mut app = self.fspm_user_config.app;
+        let length = app.read_ind_callback(
+            self,
+            arep,
+            api,
+            slot,
+            subslot,
+            index as usize,
+            sequence_number,
+            buffer,
+            &mut result,
+        );
+        self.fspm_user_config.app = app;
+
+        if result.pnio_status.is_error() {
+            return Err(RecordError::Application(result.pnio_status));
+        }
+
+        Ok(length)
+    }
+
+    /// Writes the record with the given index.
+    ///
+    /// I&M records are handled by the stack, all other records, like the
+    /// parameters of the application, are passed to `App::write_ind_callback`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn write_record(
+        &mut self,
+        arep: usize,
+        api: usize,
+        slot: usize,
+        subslot: usize,
+        index: u16,
+        sequence_number: usize,
+        data: &[u8],
+    ) -> Result<(), RecordError> {
+        match CmRpc::write_record(&mut self.fspm_user_config, index, data) {
+            Err(RecordError::InvalidIndex) => (),
+            result => return result,
+        }
+
+        let mut result = EventResult::default();
+        let mut app = self.fspm_user_config.app;
+        app.write_ind_callback(
+            self,
+            arep,
+            api,
+            slot,
+            subslot,
+            index as usize,
+            sequence_number,
+            data,
+            &mut result,
+        );
+        self.fspm_user_config.app = app;
+
+        if result.pnio_status.is_error() {
+            return Err(RecordError::Application(result.pnio_status));
+        }
+
+        Ok(())
+    }
+
+    pub fn handle_periodic(&mut self, current_timestamp: Ticks) {
+        self.poll(current_timestamp);
+        self.send_queued_packets(current_timestamp);
+        self.run_scheduled_tasks(current_timestamp);
+    }
+
+    /// Handles the frames waiting in the receive ring, at most
+    /// `MAX_RX_FRAMES_PER_POLL` of them. Returns the number of frames handled.
+    pub fn poll(&mut self, current_timestamp: Ticks) -> usize {
+        let mut packet_buf = [0; MAX_FRAME_LENGTH];
+
+        for handled in 0..MAX_RX_FRAMES_PER_POLL {
+            let Ok(transport) = self.transport() else {
+                return handled;
+            };
+            let packet_len = match transport.receive(&mut packet_buf) {
+                Ok(packet_len) => packet_len,
+                Err(RxError::WouldBlock) => return handled,
+                Err(e) => {
+                    debug!("Failed to receive packet: {}", e);
+                    continue;
+                }
+            };
+            increment(&mut self.stats.frames_received);
+
+            if let Err(e) = self.handle_frame(&packet_buf[..packet_len], current_timestamp) {
+                debug!("Dropped incoming packet: {}", e);
+            }
+        }
+
+        MAX_RX_FRAMES_PER_POLL
+    }
+
+    pub fn handle_incoming_packet(&mut self, current_timestamp: Ticks) -> Result<(), Error> {
+        let mut packet_buf = [0; MAX_FRAME_LENGTH];
+
+        let packet_len = match self.transport()?.receive(&mut packet_buf) {
+            Ok(packet_len) => packet_len,
+            Err(RxError::WouldBlock) => return Ok(()),
+            Err(e) => return Err(Error::RxError(e)),
+        };
+        increment(&mut self.stats.frames_received);
+
+        self.handle_frame(&packet_buf[..packet_len], current_timestamp)
+    }
+
+    /// Dispatches a received Ethernet frame to the protocol it belongs to
+    pub fn handle_frame(&mut self, packet: &[u8], current_timestamp: Ticks) -> Result<(), Error> {
+        let frame_in = EthernetFrame::new_checked(packet).map_err(Error::EthernetError)?;
+
+        match frame_in.eth_type() {
+            EthType::Arp => {
+                Acd::handle_frame(self, &frame_in);
+                arp::handle_request(self, &frame_in, current_timestamp);
+                return Ok(());
+            }
+            EthType::Lldp => {
+                Lldp::handle_frame(self, packet, current_timestamp);
+                return Ok(());
+            }
+            _ => (),
+        }
+
+        if !self.accepts_destination(&frame_in.dst_address()) {
+            increment(&mut self.stats.frames_not_for_us);
+            return Ok(());
+        }
+
+        if !frame_in.is_profinet() {
+            debug!("Packet is not Profinet");
+            return Ok(());
+        }
+
+        match frame_in.frame_kind() {
+            FrameKind::Dcp => {
+                debug!("Packet Frame ID is DCP");
+                increment(&mut self.stats.dcp_received);
+                Dcp::handle_frame(self, frame_in, current_timestamp);
+            }
+            FrameKind::AlarmHigh | FrameKind::AlarmLow => {
+                let priority = if frame_in.frame_kind() == FrameKind::AlarmHigh {
+                    AlarmPriority::High
+                } else {
+                    AlarmPriority::Low
+                };
+
+                match alarm::parse_ack(frame_in.payload()) {
+                    Ok(ack) => Alarm::handle_ack(self, priority, ack, current_timestamp),
+                    Err(e) => debug!("Dropped alarm frame: {}", e),
+                }
+            }
+            FrameKind::RtClass1
+            | FrameKind::RtClass2
+            | FrameKind::RtClass3
+            | FrameKind::RtClassUdp => {
+                trace!("Cyclic frame {:x}", frame_in.frame_id_u16());
+                increment(&mut self.stats.cyclic_received);
+                Cpm::handle_frame(self, frame_in.frame_id_u16(), current_timestamp);
+            }
+            FrameKind::Ptcp => {
+                increment(&mut self.stats.ptcp_received);
+                ptcp::handle_frame(self, &frame_in, current_timestamp);
+            }
+            FrameKind::Other => debug!("Frame ID {:x} is not handled", frame_in.frame_id_u16()),
+        }
+
+        Ok(())
+    }
+
+    /// Queues `frame` to be sent once `send_at` has passed.
+    ///
+    /// Frames shorter than `MIN_FRAME_LENGTH` are padded with zeros.
+    /// Frames longer than `MAX_FRAME_LENGTH` are rejected instead of being truncated,
+    /// and `Error::QueueFull` is returned when no slot is free.
+    pub fn queue_packet(&mut self, frame: &[u8], send_at: Ticks) -> Result<(), Error> {
+        if frame.len() > MAX_FRAME_LENGTH {
+            return Err(Error::PacketTooLarge);
+        }
+
+        let slot = self.free_packet_slot()?;
+        let mut data = [0; MAX_FRAME_LENGTH];
+        data[..frame.len()].copy_from_slice(frame);
+        self.store_packet(slot, data, frame.len(), send_at);
+
+        Ok(())
+    }
+
+    /// Queues a response built with the `ResponseBuilder` of the stack, like
+    /// `queue_packet`. The builder may be reused as soon as this returns.
+    pub(crate) fn queue_response(
+        &mut self,
+        response: Response,
+        send_at: Ticks,
+    ) -> Result<(), Error> {
+        let slot = self.free_packet_slot()?;
+        let mut data = [0; MAX_FRAME_LENGTH];
+        self.response.copy_frame(response, &mut data)?;
+        self.store_packet(slot, data, response.length, send_at);
+
+        Ok(())
+    }
+
+    fn free_packet_slot(&mut self) -> Result<usize, Error> {
+        let slot = self.outgoing_packets.iter().position(Option::is_none);
+        if slot.is_none() {
+            increment(&mut self.stats.queue_full);
+        }
+
+        slot.ok_or(Error::QueueFull)
+    }
+
+    fn store_packet(
+        &mut self,
+        slot: usize,
+        data: [u8; MAX_FRAME_LENGTH],
+        length: usize,
+        send_at: Ticks,
+    ) {
+        self.outgoing_packets[slot] = Some(OutgoingPacket {
+            data,
+            length: length.max(MIN_FRAME_LENGTH),
+            send_at,
+            sequence: self.next_packet_sequence,
+        });
+        self.next_packet_sequence = self.next_packet_sequence.wrapping_add(1);
+        increment(&mut self.stats.packets_queued);
+    }
+
+    /// Sends all packets whose `send_at` has passed, earliest deadline first.
+    ///
+    /// Packets with the same `send_at` are sent in the order they were queued.
+    /// Sending stops at the first failure so the order is kept on the next call.
+    /// Packets overdue by more than `max_send_latency_us` are dropped.
+    pub fn send_queued_packets(&mut self, current_timestamp: Ticks) {
+        let config = &self.fspm_user_config;
+        let max_latency = Ticks::from_micros(config.max_send_latency_us, config.tick_us);
+
+        for slot in self.outgoing_packets.iter_mut() {
+            if slot.is_some_and(|p| current_timestamp.since(p.send_at) > max_latency) {
+                debug!("Dropping stale packet");
+                *slot = None;
+                increment(&mut self.stats.dropped_stale);
+            }
+        }
+
+        while let Some(i) = self.next_due_packet(current_timestamp) {
+            let Some(packet) = self.outgoing_packets[i] else {
+                return;
+            };
+
+            let Ok(transport) = self.transport() else {
+                return;
+            };
+
+            match transport.transmit(packet.frame()) {
+                Ok(()) => {
+                    debug!("Successfully sent out packet");
+                    increment(&mut self.stats.packets_sent);
+                    self.outgoing_packets[i] = None
+                }
+                Err(e) => {
+                    error!("Failed sending packet: {}", e);
+                    increment(&mut self.stats.send_errors);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Index of the queued packet that is due next at `current_timestamp`
+    fn next_due_packet(&self, current_timestamp: Ticks) -> Option<usize> {
+        self.outgoing_packets
+            .iter()
+            .enumerate()
+            .filter_map(|(i, slot)| slot.as_ref().map(|p| (i, p)))
+            .filter(|(_, p)| p.send_at.is_reached(current_timestamp))
+            .max_by_key(|(_, p)| (current_timestamp.since(p.send_at), Reverse(p.sequence)))
+            .map(|(i, _)| i)
+    }
+
+    /// Runs all scheduled tasks that are due at `current_time`
+    pub fn run_scheduled_tasks(&mut self, current_time: Ticks) {
+        while let Some(task) = self.scheduler.next_due(current_time) {
+            trace!("Running scheduled task {}", task.name);
+
+            match task.task {
+                TaskKind::PpmSend(cr) => Ppm::handle_send(self, cr, current_time),
+                TaskKind::CpmWatchdog(cr) => Cpm::handle_watchdog(self, cr, current_time),
+                TaskKind::AcdProbe => Acd::handle_probe(self, current_time),
+                TaskKind::AlarmRetransmit(priority, send_seq_num) => {
+                    Alarm::handle_retransmit(self, priority, send_seq_num, current_time)
+                }
+                TaskKind::LldpSend(port) => Lldp::handle_send(self, port, current_time),
+                TaskKind::LldpAge => Lldp::handle_age(self, current_time),
+                TaskKind::DcpHello(burst) => Dcp::handle_hello(self, burst, current_time),
+                TaskKind::DcpSignal(toggle) => Dcp::handle_signal(self, toggle, current_time),
+                TaskKind::App(mut callback) => callback.callback(self),
+            }
+        }
+    }
+
+    pub(crate) fn send_frame(&mut self, frame: &[u8]) {
+        let Ok(transport) = self.transport() else {
+            error!("PNet not initialised, ethernet_parts is None");
+            return;
+        };
+
+        match transport.transmit(frame) {
+            Ok(()) => increment(&mut self.stats.packets_sent),
+            Err(e) => {
+                error!("Failed sending packet: {}", e);
+                increment(&mut self.stats.send_errors);
+            }
+        }
     }
 }
 
-// impl<'rx, 'tx, T> PNet<'rx, 'tx, T>
-// where
-//     T: App,
-// {
-//     pub fn new(config: Config) -> Self {
-//         let mut sockets = [SocketStorage::EMPTY];
-//         let mut sockets = SocketSet::new(&mut sockets[..]);
-
-//         let mut tcp_rx_buffer = [0; 1024];
-//         let mut tcp_tx_buffer = [0; 1024];
-//         let tcp_socket = TcpSocket::new(
-//             TcpSocketBuffer::new(tcp_rx_buffer.as_mut_slice()),
-//             TcpSocketBuffer::new(tcp_tx_buffer.as_mut_slice()),
-//         );
-
-//         let mut udp_rx_metadata_buf = [PacketMetadata::EMPTY; 4];
-//         let mut udp_rx_data_buf = [0; 1024];
-//         let mut udp_tx_metadata_buf = [PacketMetadata::EMPTY; 4];
-//         let mut udp_tx_data_buf = [0; 1024];
-
-//         let udp_rx_buffer = UdpPacketBuffer::new(
-//             udp_rx_metadata_buf.as_mut_slice(),
-//             udp_rx_data_buf.as_mut_slice(),
-//         );
-//         let upd_tx_buffer = UdpPacketBuffer::new(
-//             udp_tx_metadata_buf.as_mut_slice(),
-//             udp_tx_data_buf.as_mut_slice(),
-//         );
-//         let udp_socket = UdpSocket::new(udp_rx_buffer, upd_tx_buffer);
-
-//         let tcp_handle = sockets.add(tcp_socket);
-//         let udp_handle = sockets.add(udp_socket);
-
-//         // Self {
-//         //     config,
-//         //     outgoing_packets: [None; 8],
-//         //     ethernet_parts: None,
-//         //     tcp_handle,
-//         //     udp_handle,
-//         //     global_alarm_enable: false,
-//         // }
-
-//         todo!()
-//     }
-
-//     pub fn init(
-//         &mut self,
-//         ethernet: PartsIn,
-//         clocks: Clocks,
-//         gpio: Gpio,
-//         rx_ring: &'rx mut [RxRingEntry; 2],
-//         tx_ring: &'tx mut [TxRingEntry; 2],
-//     ) {
-//         defmt::info!("Enabling ethernet...");
-
-//         let eth_pins = setup_pins(gpio);
-
-//         let mut parts = stm32_eth::new(
-//             ethernet,
-//             &mut rx_ring[..],
-//             &mut tx_ring[..],
-//             clocks,
-//             eth_pins,
-//         )
-//         .unwrap();
-//         parts.dma.enable_interrupt();
-
-//         let config = smoltcp::iface::Config::new(self.config.ip_config.mac_address.into());
-//         let iface = smoltcp::iface::Interface::new(config, &mut &mut parts.dma, Instant::ZERO);
-//         self.config.interface = Some(iface);
-
-//         self.update_interface();
-
-//         defmt::info!(
-//             "Enabled internet with IP and MAC: {}, {:x}",
-//             self.config.ip_config.ip_address,
-//             self.config.ip_config.mac_address
-//         );
-
-//         self.ethernet_parts = Some(parts);
-//     }
-
-//     pub fn dma(&mut self) -> &mut EthernetDMA<'rx, 'tx> {
-//         if let Some(parts) = &mut self.ethernet_parts {
-//             &mut parts.dma
-//         } else {
-//             panic!("PNet not initialised, ethernet_parts is None");
-//         }
-//     }
-
-//     pub fn update_interface(&mut self) {
-//         if let Some(iface) = &mut self.config.interface {
-//             iface.update_ip_addrs(|addr| {
-//                 addr.clear();
-//                 addr.push(smoltcp::wire::IpCidr::Ipv4(Ipv4Cidr::new(
-//                     self.config.ip_config.ip_address,
-//                     24,
-//                 )))
-//                 .ok();
-//             });
-
-//             defmt::info!(
-//                 "Update ethernet interface with IP: {}",
-//                 self.config.ip_config.ip_address
-//             );
-//         } else {
-//             panic!("PNet not yet initialised, interface is None");
-//         }
-//     }
-
-//     pub fn handle_periodic(&mut self, current_timestamp: usize) {
-//         // defmt::debug!("Handling incoming packets");
-//         let _ = self.handle_incoming_packet(current_timestamp);
-
-//         self.send_queued_packets(current_timestamp);
-//     }
-
-//     pub fn handle_incoming_packet(&mut self, current_timestamp: usize) -> Result<(), Error> {
-//         let mut packet_buf = [0; 1024];
-
-//         match self.dma().recv_next(None) {
-//             Ok(p) => {
-//                 let packet_len = p.len();
-//                 packet_buf[0..packet_len].copy_from_slice(&*p)
-//             }
-//             Err(_) => return Ok(()),
-//         };
-
-//         let frame_in =
-//             EthernetFrame::new_checked(&packet_buf).map_err(|e| Error::EthernetError(e))?;
-
-//         if frame_in.dst_address().0 != self.config.ip_config.mac_address.0
-//             && frame_in.dst_address().0 != DCP_MAC_HELLO_ADDRESS
-//         {
-//             // defmt::debug!(
-//             //     "Packet was not meant for us, dst_address: {}",
-//             //     frame_in.dst_address()
-//             // );
-//             return Ok(());
-//         }
-
-//         if !frame_in.is_profinet() {
-//             defmt::debug!("Packet is not Profinet");
-//             return Ok(());
-//         }
-
-//         let frame_id = frame_in.frame_id();
-
-//         match frame_id {
-//             FrameId::Dcp => {
-//                 defmt::debug!("Packet Frame ID is DCP");
-//                 Dcp::handle_frame(self, frame_in, current_timestamp);
-//             }
-//             FrameId::Other => defmt::debug!("Packet Frame ID is not DCP"),
-//         }
-
-//         Ok(())
-//     }
-
-//     pub fn queue_packet(&mut self, data: [u8; 255], send_at: usize) {
-//         let packet_out = OutgoingPacket {
-//             data,
-//             length: data.len(),
-//             send_at,
-//         };
-
-//         for i in 0..self.outgoing_packets.len() {
-//             if let None = self.outgoing_packets[i] {
-//                 self.outgoing_packets[i] = Some(packet_out);
-//                 break;
-//             }
-//         }
-//     }
-
-//     pub fn send_queued_packets(&mut self, current_timestamp: usize) {
-//         for i in 0..self.outgoing_packets.len() {
-//             if let Some(p) = self.outgoing_packets[i] {
-//                 if current_timestamp >= p.send_at {
-//                     match self
-//                         .dma()
-//                         .send(p.length, None, |buf| buf.copy_from_slice(&p.data))
-//                     {
-//                         Ok(_) => {
-//                             defmt::debug!("Successfully sent out packet");
-//                             self.outgoing_packets[i] = None
-//                         }
-//                         Err(_) => defmt::error!("Failed sending packet"),
-//                     }
-//                 }
-//             }
-//         }
-//     }
-// }
+#[cfg(all(test, feature = "device"))]
+mod tests {
+    use crate::cmrpc::IocrRequest;
+    use crate::fspm::app::{PnioStatus, ERROR_CODE_READ, ERROR_CODE_WRITE};
+    use crate::fspm::im::IM0_INDEX;
+    use crate::fspm::ConfigError;
+    use crate::util::{
+        test_config, test_connect_request, test_interface, TestTask, TEST_APP_RECORD,
+        TEST_APP_RECORD_INDEX,
+    };
+    use smoltcp::wire::IpAddress;
+
+    use super::*;
+
+    #[test]
+    fn test_queue_packet() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        pnet.queue_packet(&[0xaa; 60], Ticks(10)).unwrap();
+
+        let packet = pnet.outgoing_packets[0].as_ref().unwrap();
+        assert_eq!(packet.length, 60);
+        assert_eq!(packet.frame(), [0xaa; 60]);
+        assert_eq!(packet.send_at, Ticks(10));
+    }
+
+    #[test]
+    fn test_queue_short_packet_is_padded() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        pnet.queue_packet(&[0xaa; 26], Ticks(10)).unwrap();
+
+        let packet = pnet.outgoing_packets[0].as_ref().unwrap();
+        assert_eq!(packet.length, MIN_FRAME_LENGTH);
+        assert_eq!(packet.frame()[..26], [0xaa; 26]);
+        assert!(packet.frame()[26..].iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn test_send_queued_packets_in_deadline_order() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        pnet.mock_dma = Some(MockDma::default());
+
+        pnet.queue_packet(&[0xaa; 60], Ticks(30)).unwrap();
+        pnet.queue_packet(&[0xbb; 60], Ticks(10)).unwrap();
+        pnet.queue_packet(&[0xcc; 60], Ticks(20)).unwrap();
+        pnet.queue_packet(&[0xdd; 60], Ticks(10)).unwrap();
+
+        // Refill the freed first slot, it must still go after the older packets
+        pnet.outgoing_packets[0] = None;
+        pnet.queue_packet(&[0xee; 60], Ticks(10)).unwrap();
+
+        pnet.send_queued_packets(Ticks(20));
+
+        let sent: Vec<u8> = pnet
+            .mock_dma
+            .as_ref()
+            .unwrap()
+            .tx
+            .iter()
+            .map(|frame| frame[0])
+            .collect();
+        assert_eq!(sent, [0xbb, 0xdd, 0xee, 0xcc]);
+    }
+
+    #[test]
+    fn test_send_queued_packets_across_wrap() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        pnet.mock_dma = Some(MockDma::default());
+
+        pnet.queue_packet(&[0xaa; 60], Ticks(2)).unwrap();
+        pnet.queue_packet(&[0xbb; 60], Ticks(usize::MAX - 1))
+            .unwrap();
+        pnet.queue_packet(&[0xcc; 60], Ticks(10)).unwrap();
+
+        pnet.send_queued_packets(Ticks(usize::MAX));
+        pnet.send_queued_packets(Ticks(2));
+
+        let sent: Vec<u8> = pnet
+            .mock_dma
+            .as_ref()
+            .unwrap()
+            .tx
+            .iter()
+            .map(|frame| frame[0])
+            .collect();
+        assert_eq!(sent, [0xbb, 0xaa]);
+        assert_eq!(pnet.stats().dropped_stale, 0);
+    }
+
+    #[test]
+    fn test_drop_stale_packets() {
+        let mut config = test_config();
+        config.max_send_latency_us = 100_000;
+        let mut pnet: PNet<_, TestTask> = PNet::new(config);
+        pnet.mock_dma = Some(MockDma::default());
+
+        pnet.queue_packet(&[0xaa; 60], Ticks(0)).unwrap();
+        pnet.queue_packet(&[0xbb; 60], Ticks(50)).unwrap();
+
+        // 100 ticks of 1 ms after the second deadline
+        pnet.send_queued_packets(Ticks(150));
+
+        let tx = &pnet.mock_dma.as_ref().unwrap().tx;
+        assert_eq!(tx.len(), 1);
+        assert_eq!(tx[0][0], 0xbb);
+        assert_eq!(pnet.stats().dropped_stale, 1);
+        assert_eq!(pnet.stats().packets_sent, 1);
+    }
+
+    #[test]
+    fn test_queue_oversized_packet() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        let result = pnet.queue_packet(&[0; MAX_FRAME_LENGTH + 1], Ticks(0));
+
+        assert!(matches!(result, Err(Error::PacketTooLarge)));
+        assert!(pnet.outgoing_packets.iter().all(|p| p.is_none()));
+    }
+
+    fn default_route<T: App + Copy>(pnet: &mut PNet<T, TestTask>) -> Option<IpAddress> {
+        let mut router = None;
+        let iface = pnet.interface.as_mut().unwrap();
+        iface
+            .routes_mut()
+            .update(|routes| router = routes.first().map(|route| route.via_router));
+
+        router
+    }
+
+    fn set_ip<T: App + Copy>(pnet: &mut PNet<T, TestTask>, gateway: Ipv4Address) {
+        let ip_config = &mut pnet.fspm_user_config.interface_config.ip_config;
+        ip_config.ip_address = Ipv4Address::new(192, 168, 0, 2);
+        ip_config.subnet_mask = Ipv4Address::new(255, 255, 255, 0);
+        ip_config.gateway = gateway;
+
+        pnet.update_interface();
+    }
+
+    #[test]
+    fn test_update_interface_gateway_route() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        pnet.interface = Some(test_interface());
+
+        set_ip(&mut pnet, Ipv4Address::new(192, 168, 0, 1));
+        assert_eq!(
+            pnet.interface.as_ref().unwrap().ipv4_addr(),
+            Some(Ipv4Address::new(192, 168, 0, 2))
+        );
+        assert_eq!(
+            default_route(&mut pnet),
+            Some(IpAddress::v4(192, 168, 0, 1))
+        );
+
+        // No gateway removes the route again
+        set_ip(&mut pnet, Ipv4Address::UNSPECIFIED);
+        assert_eq!(default_route(&mut pnet), None);
+        assert_eq!(pnet.ip_config().gateway, Ipv4Address::UNSPECIFIED);
+    }
+
+    #[test]
+    fn test_update_interface_gateway_changed() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        pnet.interface = Some(test_interface());
+
+        set_ip(&mut pnet, Ipv4Address::new(192, 168, 0, 1));
+        set_ip(&mut pnet, Ipv4Address::new(192, 168, 0, 254));
+
+        // The new gateway replaces the old default route
+        let mut routes_count = 0;
+        let iface = pnet.interface.as_mut().unwrap();
+        iface
+            .routes_mut()
+            .update(|routes| routes_count = routes.len());
+        assert_eq!(routes_count, 1);
+        assert_eq!(
+            default_route(&mut pnet),
+            Some(IpAddress::v4(192, 168, 0, 254))
+        );
+    }
+
+    #[test]
+    fn test_update_interface_self_gateway() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        pnet.interface = Some(test_interface());
+
+        set_ip(&mut pnet, Ipv4Address::new(192, 168, 0, 1));
+        set_ip(&mut pnet, Ipv4Address::new(192, 168, 0, 2));
+
+        // The gateway is stored, but no route through the device itself is installed
+        assert_eq!(default_route(&mut pnet), None);
+        assert_eq!(pnet.ip_config().gateway, Ipv4Address::new(192, 168, 0, 2));
+    }
+
+    #[test]
+    fn test_init_invalid_config() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        let mut config = test_config();
+        config.interface_config.network_interface_name = "";
+
+        assert!(matches!(
+            pnet.init(config, Ticks(0)),
+            Err(Error::ConfigError(ConfigError::EmptyInterfaceName))
+        ));
+    }
+
+    #[test]
+    fn test_set_name_of_station() {
+        let mut config = test_config();
+        config.send_dcp_hello = true;
+        let mut pnet: PNet<_, TestTask> = PNet::new(config);
+
+        pnet.set_name_of_station("device-1", Ticks(0)).unwrap();
+
+        let config = &pnet.fspm_user_config;
+        assert_eq!(
+            &config.station_name[..config.station_name_len()],
+            b"device-1"
+        );
+
+        // The new name is announced in a Hello
+        let hello = pnet.outgoing_packets[0].as_ref().unwrap();
+        let frame = EthernetFrame::new_checked(hello.frame()).unwrap();
+        let dcp = Dcp::parse(&frame).unwrap();
+        assert_eq!(dcp.frame_id, DcpFrameId::Hello);
+        assert_eq!(dcp.destination.0, DCP_HELLO_MULTICAST);
+        assert_eq!(
+            dcp.blocks_iter().next().unwrap().block,
+            Block::DeviceProperties(DevicePropertiesBlock::NameOfStation(
+                NameOfStation::from_str("device-1")
+            ))
+        );
+    }
+
+    #[test]
+    fn test_set_name_of_station_after_boot() {
+        let mut config = test_config();
+        config.send_dcp_hello = true;
+        config.dcp_hello_count = 2;
+        config.dcp_hello_interval_us = 100_000;
+        let mut pnet: PNet<_, TestTask> = PNet::new(config);
+        pnet.mock_dma = Some(MockDma::default());
+
+        // Renamed long after boot, the Hello isn't dropped as stale
+        let now = Ticks(usize::MAX / 2 + 10_000);
+        pnet.set_name_of_station("device-1", now).unwrap();
+        pnet.send_queued_packets(now);
+
+        let sent = pnet.mock_dma.as_mut().unwrap().tx.pop_front().unwrap();
+        assert_eq!(sent[..6], DCP_HELLO_MULTICAST);
+        assert_eq!(pnet.stats.dropped_stale, 0);
+
+        // The next Hello follows one interval later
+        pnet.run_scheduled_tasks(now + Ticks(99));
+        assert!(pnet.outgoing_packets.iter().all(Option::is_none));
+        pnet.run_scheduled_tasks(now + Ticks(100));
+        pnet.send_queued_packets(now + Ticks(100));
+        let sent = pnet.mock_dma.as_mut().unwrap().tx.pop_front().unwrap();
+        assert_eq!(sent[..6], DCP_HELLO_MULTICAST);
+    }
+
+    #[test]
+    fn test_set_invalid_name_of_station() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        assert_eq!(
+            pnet.set_name_of_station("Device 1", Ticks(0)),
+            Err(NameError::InvalidCharacter)
+        );
+        assert_eq!(pnet.fspm_user_config.station_name_len(), 0);
+        assert!(pnet.outgoing_packets[0].is_none());
+    }
+
+    #[test]
+    fn test_hello_response_through_transport() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        pnet.mock_dma = Some(MockDma::default());
+
+        // Identify All request from the controller
+        let mut request = vec![
+            0x01, 0x0e, 0xcf, 0x00, 0x00, 0x00, 0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5, 0x88, 0x92,
+            0xfe, 0xfe, 0x05, 0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x01, 0x00, 0x04, 0xff, 0xff,
+            0x00, 0x00,
+        ];
+        request.resize(60, 0);
+        pnet.mock_dma.as_mut().unwrap().rx.push_back(request);
+
+        pnet.handle_periodic(Ticks(0));
+
+        let response = pnet.mock_dma.as_mut().unwrap().tx.pop_front().unwrap();
+        let frame = EthernetFrame::new_checked(&response[..]).unwrap();
+        let dcp = Dcp::parse(&frame).unwrap();
+
+        assert_eq!(
+            dcp.destination,
+            EthernetAddress::from_bytes(&[0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5])
+        );
+        assert_eq!(dcp.frame_id, DcpFrameId::Response);
+        assert_eq!(dcp.header.service_id, ServiceId::Identify);
+        assert_eq!(dcp.header.service_type, ServiceType::Success);
+        assert_eq!(dcp.header.x_id, 3);
+        assert!(pnet.outgoing_packets.iter().all(|p| p.is_none()));
+
+        // Nothing after the encoded blocks is transmitted: Ethernet header,
+        // frame id and DCP header followed by the blocks
+        assert_eq!(
+            response.len(),
+            14 + 2 + 10 + dcp.header.data_length as usize
+        );
+    }
+
+    #[test]
+    fn test_send_only_frame_length() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        pnet.mock_dma = Some(MockDma::default());
+
+        pnet.queue_packet(&[0xaa; 64], Ticks(0)).unwrap();
+        // Leftovers in the slot past the frame
+        pnet.outgoing_packets[0].as_mut().unwrap().data[64..].fill(0xbb);
+
+        pnet.send_queued_packets(Ticks(0));
+
+        let sent = pnet.mock_dma.as_mut().unwrap().tx.pop_front().unwrap();
+        assert_eq!(sent, [0xaa; 64]);
+    }
+
+    #[test]
+    fn test_poll_drains_receive_ring() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        pnet.mock_dma = Some(MockDma::default());
+
+        // Frames for another station
+        let frame = [
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5, 0x88, 0x92,
+        ];
+        let dma = pnet.mock_dma.as_mut().unwrap();
+        for _ in 0..MAX_RX_FRAMES_PER_POLL + 2 {
+            dma.rx.push_back(frame.to_vec());
+        }
+
+        assert_eq!(pnet.poll(Ticks(0)), MAX_RX_FRAMES_PER_POLL);
+        assert_eq!(pnet.poll(Ticks(0)), 2);
+        assert_eq!(pnet.poll(Ticks(0)), 0);
+        assert_eq!(
+            pnet.stats().frames_received as usize,
+            MAX_RX_FRAMES_PER_POLL + 2
+        );
+    }
+
+    #[test]
+    fn test_connect_and_release() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        let request = test_connect_request();
+        let mut buffer = [0; 1024];
+
+        let (arep, length) = pnet.connect(&request, &mut buffer).unwrap();
+        assert!(length > 0);
+        assert_eq!(pnet.ars.get(arep).unwrap().state, CmdevState::ConnectResp);
+        // The IOCR block responses carry the allocated frame ids
+        assert_eq!(buffer[64..66], [0xc0, 0x00]);
+        assert_eq!(buffer[76..78], [0xc0, 0x01]);
+
+        // The only AR is taken
+        assert_eq!(
+            pnet.connect(&request, &mut buffer),
+            Err(PnioStatus::out_of_ar_resources())
+        );
+
+        pnet.release(arep).unwrap();
+        assert!(!pnet.frame_ids.in_use(0xc000));
+        assert_eq!(
+            pnet.release(arep),
+            Err(PnioStatus::ar_uuid_unknown(ERROR_CODE_RELEASE))
+        );
+        assert!(pnet.connect(&request, &mut buffer).is_ok());
+    }
+
+    #[test]
+    fn test_ar_state() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        let mut buffer = [0; 1024];
+
+        let (arep, _) = pnet.connect(&test_connect_request(), &mut buffer).unwrap();
+        assert_eq!(pnet.ar_state(arep), Some(CmdevState::ConnectResp));
+        assert!(!pnet.is_connected());
+
+        pnet.ars.get_mut(arep).unwrap().state = CmdevState::DataExchange;
+        assert!(pnet.is_connected());
+
+        pnet.release(arep).unwrap();
+        assert_eq!(pnet.ar_state(arep), None);
+        assert!(!pnet.is_connected());
+    }
+
+    #[test]
+    fn test_watchdog_aborts_ar() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        let mut buffer = [0; 1024];
+
+        let (arep, _) = pnet.connect(&test_connect_request(), &mut buffer).unwrap();
+        assert_eq!(pnet.cpm_instance_count, 1);
+
+        // The controller's output CR got frame id 0xc001, its data hold time
+        // is 3 * 32 * 32 send clocks or 96 ms
+        let mut frame = [0; 60];
+        frame[0..6].copy_from_slice(&[0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe]);
+        frame[12..14].copy_from_slice(&[0x88, 0x92]);
+        frame[14..16].copy_from_slice(&[0xc0, 0x01]);
+
+        // The watchdog only starts with the first frame
+        pnet.run_scheduled_tasks(Ticks(1000));
+        assert!(pnet.ars.get(arep).is_some());
+
+        pnet.handle_frame(&frame, Ticks(1000)).unwrap();
+        pnet.handle_frame(&frame, Ticks(1050)).unwrap();
+        pnet.run_scheduled_tasks(Ticks(1096));
+        assert_eq!(pnet.ar_state(arep), Some(CmdevState::ConnectResp));
+
+        pnet.run_scheduled_tasks(Ticks(1146));
+        assert_eq!(pnet.ar_state(arep), None);
+        assert_eq!(pnet.fspm_user_config.app.releases, 1);
+        assert!(!pnet.frame_ids.in_use(0xc000));
+        assert!(!pnet.frame_ids.in_use(0xc001));
+        assert_eq!(pnet.cpm_instance_count, 0);
+
+        // The AREP is free for the next connect
+        assert!(pnet.connect(&test_connect_request(), &mut buffer).is_ok());
+    }
+
+    #[test]
+    fn test_connect_checks_watchdog() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        let mut buffer = [0; 1024];
+        let watchdog = PnioStatus::faulty_iocr_block(IocrRequest::WATCHDOG_FACTOR_FIELD);
+        let data_hold = PnioStatus::faulty_iocr_block(IocrRequest::DATA_HOLD_FACTOR_FIELD);
+
+        for (watchdog_factor, data_hold_factor, status) in [
+            (0, 3, watchdog),
+            (0x1e01, 0x1e01, watchdog),
+            (3, 0, data_hold),
+            (3, 2, data_hold),
+            // 60 * 32 * 32 send clocks are 1.92 s
+            (3, 61, data_hold),
+        ] {
+            let mut request = test_connect_request();
+            let iocr = request.iocrs[1].as_mut().unwrap();
+            iocr.watchdog_factor = watchdog_factor;
+            iocr.data_hold_factor = data_hold_factor;
+
+            assert_eq!(pnet.connect(&request, &mut buffer), Err(status));
+            assert_eq!(pnet.ars.iter().count(), 0);
+        }
+
+        let mut request = test_connect_request();
+        request.iocrs[1].as_mut().unwrap().data_hold_factor = 60;
+        pnet.connect(&request, &mut buffer).unwrap();
+        assert_eq!(pnet.cpm[0].unwrap().data_hold_time, Ticks(1920));
+    }
+
+    #[test]
+    fn test_connect_expects_missing_modules() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        let request = test_connect_request();
+        let mut buffer = [0; 1024];
+
+        let (arep, _) = pnet.connect(&request, &mut buffer).unwrap();
+        assert_eq!(pnet.fspm_user_config.app.modules_expected, 2);
+        pnet.release(arep).unwrap();
+
+        pnet.plug_submodule(SubmoduleConfig {
+            api: 0,
+            slot_number: 0,
+            subslot_number: 1,
+            module_ident: 0x01,
+            submodule_ident: 0x01,
+        })
+        .unwrap();
+        pnet.connect(&request, &mut buffer).unwrap();
+        // Only the module of slot 1 is still missing
+        assert_eq!(pnet.fspm_user_config.app.modules_expected, 3);
+
+        assert_eq!(pnet.pull_submodule(0, 0, 1).unwrap().module_ident, 0x01);
+        assert!(pnet.pull_submodule(0, 0, 1).is_none());
+    }
+
+    #[test]
+    fn test_read_record() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        let mut buffer = [0; 64];
+
+        // Handled by the stack
+        let length = pnet
+            .read_record(1, 0, 0, 1, IM0_INDEX, 0, &mut buffer)
+            .unwrap();
+        assert_eq!(length, 60);
+
+        // Handled by the application
+        let length = pnet
+            .read_record(1, 0, 1, 1, TEST_APP_RECORD_INDEX, 0, &mut buffer)
+            .unwrap();
+        assert_eq!(buffer[..length], TEST_APP_RECORD);
+
+        assert_eq!(
+            pnet.read_record(1, 0, 1, 1, 0x0200, 0, &mut buffer),
+            Err(RecordError::Application(PnioStatus::invalid_index(
+                ERROR_CODE_READ
+            )))
+        );
+    }
+
+    #[test]
+    fn test_write_record() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        pnet.write_record(1, 0, 1, 1, TEST_APP_RECORD_INDEX, 0, &[0; 8])
+            .unwrap();
+        assert_eq!(pnet.fspm_user_config.app.records_written, 1);
+        assert_eq!(pnet.fspm_user_config.app.last_record_length, 8);
+
+        let error = pnet
+            .write_record(1, 0, 0, 1, IM0_INDEX, 0, &[0; 60])
+            .unwrap_err();
+        assert_eq!(error, RecordError::ReadOnly);
+        assert_eq!(
+            error.pnio_status(ERROR_CODE_WRITE),
+            PnioStatus::access_denied(ERROR_CODE_WRITE)
+        );
+        assert_eq!(pnet.fspm_user_config.app.records_written, 1);
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        pnet.mock_dma = Some(MockDma::default());
+
+        let mut identify = vec![
+            0x01, 0x0e, 0xcf, 0x00, 0x00, 0x00, 0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5, 0x88, 0x92,
+            0xfe, 0xfe, 0x05, 0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x01, 0x00, 0x04, 0xff, 0xff,
+            0x00, 0x00,
+        ];
+        identify.resize(60, 0);
+
+        // Addressed to another station
+        let mut other = identify.clone();
+        other[..6].copy_from_slice(&[0x00, 0x00, 0x23, 0x53, 0x4e, 0x01]);
+
+        // Unknown service id
+        let mut invalid = identify.clone();
+        invalid[16] = 0xff;
+
+        let dma = pnet.mock_dma.as_mut().unwrap();
+        dma.rx.extend([identify, other, invalid]);
+
+        for _ in 0..3 {
+            pnet.handle_periodic(Ticks(0));
+        }
+
+        assert_eq!(
+            *pnet.stats(),
+            PnetStats {
+                frames_received: 3,
+                frames_not_for_us: 1,
+                dcp_received: 2,
+                dcp_parsed: 1,
+                dcp_parse_errors: 1,
+                packets_queued: 1,
+                packets_sent: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_queue_full() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        for _ in 0..8 {
+            pnet.queue_packet(&[0; 60], Ticks(0)).unwrap();
+        }
+
+        assert!(matches!(
+            pnet.queue_packet(&[0; 60], Ticks(0)),
+            Err(Error::QueueFull)
+        ));
+        assert_eq!(pnet.stats().packets_queued, 8);
+        assert_eq!(pnet.stats().queue_full, 1);
+    }
+
+    #[test]
+    fn test_accepts_destination() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        let group = EthernetAddress([0x01, 0x0e, 0xcf, 0x00, 0x01, 0x01]);
+
+        assert!(pnet.accepts_destination(&EthernetAddress([0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe])));
+        assert!(pnet.accepts_destination(&EthernetAddress(DCP_IDENTIFY_MULTICAST)));
+        assert!(pnet.accepts_destination(&EthernetAddress(DCP_HELLO_MULTICAST)));
+        assert!(!pnet.accepts_destination(&EthernetAddress([0x00, 0x00, 0x23, 0x53, 0x4e, 0x01])));
+        assert!(!pnet.accepts_destination(&group));
+
+        pnet.join_multicast_group(group).unwrap();
+        assert!(pnet.accepts_destination(&group));
+
+        pnet.leave_multicast_group(group);
+        assert!(!pnet.accepts_destination(&group));
+    }
+
+    #[test]
+    fn test_too_many_multicast_groups() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        pnet.join_multicast_group(EthernetAddress([0x01, 0x0e, 0xcf, 0x00, 0x01, 0x01]))
+            .unwrap();
+        pnet.join_multicast_group(EthernetAddress([0x01, 0x0e, 0xcf, 0x00, 0x01, 0x02]))
+            .unwrap();
+
+        // Joining again is a no-op
+        pnet.join_multicast_group(EthernetAddress([0x01, 0x0e, 0xcf, 0x00, 0x01, 0x02]))
+            .unwrap();
+
+        assert!(matches!(
+            pnet.join_multicast_group(EthernetAddress([0x01, 0x0e, 0xcf, 0x00, 0x01, 0x03])),
+            Err(Error::TooManyMulticastGroups)
+        ));
+    }
+
+    #[test]
+    fn test_handle_runt_frame() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        let result = pnet.handle_frame(
+            &[0x01, 0x0e, 0xcf, 0x00, 0x00, 0x00, 0x52, 0x54, 0x00, 0x8a],
+            Ticks(0),
+        );
+
+        assert!(matches!(
+            result,
+            Err(Error::EthernetError(ethernet::EthernetError::TooShort))
+        ));
+    }
+}
@@ -0,0 +1,489 @@
+// Link layer discovery protocol
+
+use byteorder::{ByteOrder, NetworkEndian};
+use num_enum::TryFromPrimitive;
+
+use crate::constants::MAX_FRAME_LENGTH;
+use crate::ethernet::{EthType, EthernetFrame};
+use crate::field::{Field, Rest};
+use crate::fspm::{app::App, Config};
+use crate::scheduler::{TaskCallback, TaskKind};
+use crate::time::Ticks;
+use crate::PNet;
+
+mod neighbor;
+
+pub use neighbor::*;
+
+pub const LLDP_MULTICAST_ADDRESS: [u8; 6] = [0x01, 0x80, 0xc2, 0x00, 0x00, 0x0e];
+
+/// Time between two LLDP frames of a port in microseconds
+pub const LLDP_TX_INTERVAL_US: usize = 5_000_000;
+/// Time to live advertised to neighbors in seconds
+pub const LLDP_TTL: u16 = 20;
+/// Time between two checks for expired neighbors in microseconds
+pub const LLDP_AGE_INTERVAL_US: usize = 1_000_000;
+
+/// Organizationally unique identifier of the PROFIBUS & PROFINET International
+pub const PNIO_OUI: [u8; 3] = [0x00, 0x0e, 0xcf];
+
+const DESTINATION_FIELD: Field = 0..6;
+const SOURCE_FIELD: Field = 6..12;
+const TYPE_FIELD: Field = 12..14;
+const PAYLOAD_FIELD: Rest = 14..;
+
+const TLV_HEADER_LENGTH: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, TryFromPrimitive)]
+#[repr(u8)]
+pub enum TlvType {
+    End = 0,
+    ChassisId = 1,
+    PortId = 2,
+    TimeToLive = 3,
+    OrganizationSpecific = 127,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum ChassisIdSubtype {
+    MacAddress = 4,
+    Local = 7,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum PortIdSubtype {
+    Local = 7,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum PnioSubtype {
+    Delay = 1,
+    PortStatus = 2,
+}
+
+/// Writes the header of a TLV with the given value length into `buffer`
+fn write_tlv_header(buffer: &mut [u8], tlv_type: TlvType, length: usize) {
+    NetworkEndian::write_u16(
+        &mut buffer[..TLV_HEADER_LENGTH],
+        ((tlv_type as u16) << 9) | (length as u16 & 0x01ff),
+    );
+}
+
+/// Writes a TLV whose value starts with a subtype byte and returns its total length
+fn write_subtype_tlv(buffer: &mut [u8], tlv_type: TlvType, subtype: u8, value: &[u8]) -> usize {
+    write_tlv_header(buffer, tlv_type, value.len() + 1);
+    buffer[TLV_HEADER_LENGTH] = subtype;
+    buffer[TLV_HEADER_LENGTH + 1..TLV_HEADER_LENGTH + 1 + value.len()].copy_from_slice(value);
+
+    TLV_HEADER_LENGTH + 1 + value.len()
+}
+
+/// Writes a PROFINET organization specific TLV and returns its total length
+fn write_pnio_tlv(buffer: &mut [u8], subtype: PnioSubtype, value: &[u8]) -> usize {
+    let value_start = TLV_HEADER_LENGTH + PNIO_OUI.len() + 1;
+
+    write_tlv_header(
+        buffer,
+        TlvType::OrganizationSpecific,
+        value_start - TLV_HEADER_LENGTH + value.len(),
+    );
+    buffer[TLV_HEADER_LENGTH..TLV_HEADER_LENGTH + PNIO_OUI.len()].copy_from_slice(&PNIO_OUI);
+    buffer[value_start - 1] = subtype as u8;
+    buffer[value_start..value_start + value.len()].copy_from_slice(value);
+
+    value_start + value.len()
+}
+
+/// Writes the port id of `port`, the station name followed by `.port-001` for
+/// the first port, into `buffer` and returns its length
+fn port_id<T: App + Copy>(config: &Config<T>, port: usize, buffer: &mut [u8]) -> usize {
+    let name_length = config.station_name_len();
+    let mut offset = 0;
+
+    if name_length > 0 {
+        buffer[..name_length].copy_from_slice(&config.station_name[..name_length]);
+        buffer[name_length] = b'.';
+        offset = name_length + 1;
+    }
+
+    buffer[offset..offset + 5].copy_from_slice(b"port-");
+
+    let number = (port + 1) % 1000;
+    buffer[offset + 5] = b'0' + (number / 100) as u8;
+    buffer[offset + 6] = b'0' + (number / 10 % 10) as u8;
+    buffer[offset + 7] = b'0' + (number % 10) as u8;
+
+    offset + 8
+}
+
+/// Builds the LLDP frame advertised on `port` and returns the written length.
+///
+/// The chassis id is the station name, or the MAC address while the device has
+/// no name. `buffer` must be at least `MAX_FRAME_LENGTH` bytes long.
+pub fn build_frame<T: App + Copy>(config: &Config<T>, port: usize, buffer: &mut [u8]) -> usize {
+    let mac_address = config.interface_config.ip_config.mac_address;
+
+    buffer[DESTINATION_FIELD].copy_from_slice(&LLDP_MULTICAST_ADDRESS);
+    buffer[SOURCE_FIELD].copy_from_slice(mac_address.as_bytes());
+    NetworkEndian::write_u16(&mut buffer[TYPE_FIELD], EthType::Lldp as u16);
+
+    let payload = &mut buffer[PAYLOAD_FIELD];
+    let mut offset = 0;
+
+    let name_length = config.station_name_len();
+    offset += if name_length > 0 {
+        write_subtype_tlv(
+            &mut payload[offset..],
+            TlvType::ChassisId,
+            ChassisIdSubtype::Local as u8,
+            &config.station_name[..name_length],
+        )
+    } else {
+        write_subtype_tlv(
+            &mut payload[offset..],
+            TlvType::ChassisId,
+            ChassisIdSubtype::MacAddress as u8,
+            mac_address.as_bytes(),
+        )
+    };
+
+    let mut port_id_buffer = [0; 255];
+    let port_id_length = port_id(config, port, &mut port_id_buffer);
+    offset += write_subtype_tlv(
+        &mut payload[offset..],
+        TlvType::PortId,
+        PortIdSubtype::Local as u8,
+        &port_id_buffer[..port_id_length],
+    );
+
+    write_tlv_header(&mut payload[offset..], TlvType::TimeToLive, 2);
+    NetworkEndian::write_u16(&mut payload[offset + TLV_HEADER_LENGTH..], LLDP_TTL);
+    offset += TLV_HEADER_LENGTH + 2;
+
+    // Rx/Tx delays of the port and the cable delay, all unknown
+    offset += write_pnio_tlv(&mut payload[offset..], PnioSubtype::Delay, &[0; 20]);
+
+    // RT_CLASS_2 and RT_CLASS_3 are both off
+    offset += write_pnio_tlv(&mut payload[offset..], PnioSubtype::PortStatus, &[0; 4]);
+
+    write_tlv_header(&mut payload[offset..], TlvType::End, 0);
+    offset += TLV_HEADER_LENGTH;
+
+    PAYLOAD_FIELD.start + offset
+}
+
+/// Parses the chassis id, port id and TTL of a received LLDP frame.
+///
+/// Unknown TLVs are skipped, parsing stops at the end TLV or at a TLV that
+/// claims more bytes than the frame holds.
+pub fn parse_frame(buffer: &[u8]) -> Result<LldpNeighbor, LldpError> {
+    let frame = EthernetFrame::new_checked(buffer).map_err(|_| LldpError::TooShort)?;
+
+    if frame.eth_type() != EthType::Lldp {
+        return Err(LldpError::NotLldp);
+    }
+
+    let payload = frame.eth_payload();
+
+    let mut neighbor = LldpNeighbor {
+        chassis_id_subtype: 0,
+        chassis_id: [0; MAX_LLDP_ID_LENGTH],
+        chassis_id_length: 0,
+        port_id_subtype: 0,
+        port_id: [0; MAX_LLDP_ID_LENGTH],
+        port_id_length: 0,
+        ttl: 0,
+        last_seen: Ticks::ZERO,
+    };
+    let mut ttl = None;
+    let mut offset = 0;
+
+    while offset + TLV_HEADER_LENGTH <= payload.len() {
+        let header = NetworkEndian::read_u16(&payload[offset..offset + TLV_HEADER_LENGTH]);
+        let length = (header & 0x01ff) as usize;
+        let value_start = offset + TLV_HEADER_LENGTH;
+
+        if value_start + length > payload.len() {
+            debug!("LLDP TLV is truncated");
+            break;
+        }
+
+        let value = &payload[value_start..value_start + length];
+        offset = value_start + length;
+
+        match TlvType::try_from_primitive((header >> 9) as u8) {
+            Ok(TlvType::End) => break,
+            Ok(TlvType::ChassisId) => {
+                if value.len() < 2 {
+                    return Err(LldpError::InvalidTlv);
+                }
+                let id = &value[1..value.len().min(MAX_LLDP_ID_LENGTH + 1)];
+                neighbor.chassis_id_subtype = value[0];
+                neighbor.chassis_id[..id.len()].copy_from_slice(id);
+                neighbor.chassis_id_length = id.len();
+            }
+            Ok(TlvType::PortId) => {
+                if value.len() < 2 {
+                    return Err(LldpError::InvalidTlv);
+                }
+                let id = &value[1..value.len().min(MAX_LLDP_ID_LENGTH + 1)];
+                neighbor.port_id_subtype = value[0];
+                neighbor.port_id[..id.len()].copy_from_slice(id);
+                neighbor.port_id_length = id.len();
+            }
+            Ok(TlvType::TimeToLive) => {
+                if value.len() != 2 {
+                    return Err(LldpError::InvalidTlv);
+                }
+                ttl = Some(NetworkEndian::read_u16(value));
+            }
+            _ => (),
+        }
+    }
+
+    if neighbor.chassis_id_length == 0 {
+        return Err(LldpError::MissingChassisId);
+    }
+
+    if neighbor.port_id_length == 0 {
+        return Err(LldpError::MissingPortId);
+    }
+
+    neighbor.ttl = ttl.ok_or(LldpError::MissingTtl)?;
+
+    Ok(neighbor)
+}
+
+pub struct Lldp;
+
+impl Lldp {
+    /// Schedules the first LLDP frame of every port and the neighbor aging
+    pub fn start<T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &mut PNet<T, U>,
+        current_time: Ticks,
+    ) {
+        for port in 0..pnet.fspm_user_config.num_physical_ports {
+            pnet.scheduler.add_task(
+                "lldp_send",
+                Ticks::ZERO,
+                TaskKind::LldpSend(port),
+                current_time,
+            );
+        }
+
+        pnet.scheduler.add_task(
+            "lldp_age",
+            Ticks::from_micros(LLDP_AGE_INTERVAL_US, pnet.fspm_user_config.tick_us),
+            TaskKind::LldpAge,
+            current_time,
+        );
+    }
+
+    /// Records the sender of a received LLDP frame in the neighbor table
+    pub(crate) fn handle_frame<T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &mut PNet<T, U>,
+        buffer: &[u8],
+        current_time: Ticks,
+    ) {
+        match parse_frame(buffer) {
+            Ok(neighbor) => pnet.lldp_neighbors.update(neighbor, current_time),
+            Err(e) => debug!("Failed to parse LLDP frame: {}", e),
+        }
+    }
+
+    /// Scheduler callback, drops expired neighbors and reschedules itself
+    pub(crate) fn handle_age<T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &mut PNet<T, U>,
+        current_time: Ticks,
+    ) {
+        let tick_us = pnet.fspm_user_config.tick_us;
+        pnet.lldp_neighbors.age(current_time, tick_us);
+
+        pnet.scheduler.add_task(
+            "lldp_age",
+            Ticks::from_micros(LLDP_AGE_INTERVAL_US, tick_us),
+            TaskKind::LldpAge,
+            current_time,
+        );
+    }
+
+    /// Scheduler callback, sends the LLDP frame of `port` and reschedules itself
+    pub(crate) fn handle_send<T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &mut PNet<T, U>,
+        port: usize,
+        current_time: Ticks,
+    ) {
+        let mut buffer = [0; MAX_FRAME_LENGTH];
+        let length = build_frame(&pnet.fspm_user_config, port, &mut buffer);
+
+        if let Err(e) = pnet.queue_packet(&buffer[..length], current_time) {
+            error!("Failed to queue LLDP frame: {}", e);
+        }
+
+        pnet.scheduler.add_task(
+            "lldp_send",
+            Ticks::from_micros(LLDP_TX_INTERVAL_US, pnet.fspm_user_config.tick_us),
+            TaskKind::LldpSend(port),
+            current_time,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::{test_config, TestTask};
+
+    use super::*;
+
+    #[test]
+    fn test_build_frame() {
+        let mut config = test_config();
+        config.station_name[..3].copy_from_slice(b"dev");
+
+        let mut buffer = [0; MAX_FRAME_LENGTH];
+        let length = build_frame(&config, 0, &mut buffer);
+
+        assert_eq!(length, 77);
+        assert_eq!(
+            buffer[..14],
+            [0x01, 0x80, 0xc2, 0x00, 0x00, 0x0e, 0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe, 0x88, 0xcc]
+        );
+
+        // Chassis id, port id and TTL
+        assert_eq!(buffer[14..20], [0x02, 0x04, 0x07, b'd', b'e', b'v']);
+        assert_eq!(buffer[20..23], [0x04, 0x0d, 0x07]);
+        assert_eq!(&buffer[23..35], b"dev.port-001");
+        assert_eq!(buffer[35..39], [0x06, 0x02, 0x00, 0x14]);
+
+        // PNIO delay and port status
+        assert_eq!(buffer[39..45], [0xfe, 0x18, 0x00, 0x0e, 0xcf, 0x01]);
+        assert_eq!(buffer[65..71], [0xfe, 0x08, 0x00, 0x0e, 0xcf, 0x02]);
+
+        // End of LLDPDU
+        assert_eq!(buffer[75..77], [0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_build_frame_without_name() {
+        let config = test_config();
+
+        let mut buffer = [0; MAX_FRAME_LENGTH];
+        build_frame(&config, 1, &mut buffer);
+
+        assert_eq!(
+            buffer[14..23],
+            [0x02, 0x07, 0x04, 0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe]
+        );
+        assert_eq!(&buffer[26..34], b"port-002");
+    }
+
+    fn test_lldp_frame(name: &[u8], ttl: u16) -> [u8; MAX_FRAME_LENGTH] {
+        let mut config = test_config();
+        config.station_name[..name.len()].copy_from_slice(name);
+
+        let mut buffer = [0; MAX_FRAME_LENGTH];
+        build_frame(&config, 0, &mut buffer);
+
+        // The TTL value follows the chassis id and the `<name>.port-001` port id
+        NetworkEndian::write_u16(&mut buffer[31 + 2 * name.len()..], ttl);
+
+        buffer
+    }
+
+    #[test]
+    fn test_parse_frame() {
+        let buffer = test_lldp_frame(b"dev", LLDP_TTL);
+        let neighbor = parse_frame(&buffer).unwrap();
+
+        assert_eq!(neighbor.chassis_id_subtype, ChassisIdSubtype::Local as u8);
+        assert_eq!(neighbor.chassis_id(), b"dev");
+        assert_eq!(neighbor.port_id_subtype, PortIdSubtype::Local as u8);
+        assert_eq!(neighbor.port_id(), b"dev.port-001");
+        assert_eq!(neighbor.ttl, LLDP_TTL);
+    }
+
+    #[test]
+    fn test_parse_skips_unknown_tlv() {
+        let mut buffer = [0; 64];
+        buffer[..14].copy_from_slice(&[
+            0x01, 0x80, 0xc2, 0x00, 0x00, 0x0e, 0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5, 0x88, 0xcc,
+        ]);
+        // A system name TLV unknown to the parser, chassis id, port id, TTL,
+        // a PNIO port status TLV and the end TLV
+        buffer[14..40].copy_from_slice(&[
+            0x0a, 0x03, b'a', b'b', b'c', 0x02, 0x02, 0x07, b'c', 0x04, 0x02, 0x07, b'p', 0x06,
+            0x02, 0x00, 0x78, 0xfe, 0x05, 0x00, 0x0e, 0xcf, 0x02, 0x00, 0x00, 0x00,
+        ]);
+
+        let neighbor = parse_frame(&buffer).unwrap();
+
+        assert_eq!(neighbor.chassis_id(), b"c");
+        assert_eq!(neighbor.port_id(), b"p");
+        assert_eq!(neighbor.ttl, 120);
+    }
+
+    #[test]
+    fn test_parse_truncated_tlv() {
+        let mut buffer = [0; 30];
+        buffer[..14].copy_from_slice(&[
+            0x01, 0x80, 0xc2, 0x00, 0x00, 0x0e, 0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5, 0x88, 0xcc,
+        ]);
+        // Chassis id followed by a port id claiming more bytes than the frame holds
+        buffer[14..22].copy_from_slice(&[0x02, 0x02, 0x07, b'c', 0x04, 0x40, 0x07, b'p']);
+
+        assert!(matches!(
+            parse_frame(&buffer),
+            Err(LldpError::MissingPortId)
+        ));
+    }
+
+    #[test]
+    fn test_neighbor_table_aging() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        let buffer = test_lldp_frame(b"peer", 2);
+        pnet.handle_frame(&buffer, Ticks(0)).unwrap();
+        assert_eq!(pnet.lldp_neighbors.iter().count(), 1);
+
+        // Refreshing the same peer does not add a second entry
+        pnet.handle_frame(&buffer, Ticks(1000)).unwrap();
+        assert_eq!(pnet.lldp_neighbors.iter().count(), 1);
+
+        Lldp::handle_age(&mut pnet, Ticks(2999));
+        assert_eq!(pnet.lldp_neighbors.iter().count(), 1);
+
+        Lldp::handle_age(&mut pnet, Ticks(3000));
+        assert_eq!(pnet.lldp_neighbors.iter().count(), 0);
+    }
+
+    #[test]
+    fn test_neighbor_shutdown() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        pnet.handle_frame(&test_lldp_frame(b"peer", 20), Ticks(0))
+            .unwrap();
+        pnet.handle_frame(&test_lldp_frame(b"peer", 0), Ticks(10))
+            .unwrap();
+
+        assert_eq!(pnet.lldp_neighbors.iter().count(), 0);
+    }
+
+    #[test]
+    fn test_periodic_send() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+
+        Lldp::start(&mut pnet, Ticks(0));
+        pnet.run_scheduled_tasks(Ticks(0));
+        assert!(pnet.outgoing_packets[0].is_some());
+        assert!(pnet.outgoing_packets[1].is_none());
+
+        pnet.run_scheduled_tasks(Ticks(4999));
+        assert!(pnet.outgoing_packets[1].is_none());
+
+        pnet.run_scheduled_tasks(Ticks(5000));
+        assert!(pnet.outgoing_packets[1].is_some());
+    }
+}
@@ -0,0 +1,120 @@
+use crate::constants::MAX_PHYSICAL_PORTS;
+use crate::time::Ticks;
+
+/// Longest chassis or port id an LLDP TLV can carry
+pub const MAX_LLDP_ID_LENGTH: usize = 255;
+/// Each port has one direct neighbor, the spare entry covers a neighbor being replaced
+pub const MAX_LLDP_NEIGHBORS: usize = MAX_PHYSICAL_PORTS + 1;
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LldpError {
+    TooShort,
+    NotLldp,
+    MissingChassisId,
+    MissingPortId,
+    MissingTtl,
+    InvalidTlv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LldpNeighbor {
+    pub chassis_id_subtype: u8,
+    pub chassis_id: [u8; MAX_LLDP_ID_LENGTH],
+    pub chassis_id_length: usize,
+    pub port_id_subtype: u8,
+    pub port_id: [u8; MAX_LLDP_ID_LENGTH],
+    pub port_id_length: usize,
+    /// Time to live in seconds
+    pub ttl: u16,
+    /// Time the last LLDP frame was received in scheduler ticks
+    pub last_seen: Ticks,
+}
+
+impl LldpNeighbor {
+    pub fn chassis_id(&self) -> &[u8] {
+        &self.chassis_id[..self.chassis_id_length]
+    }
+
+    pub fn port_id(&self) -> &[u8] {
+        &self.port_id[..self.port_id_length]
+    }
+
+    fn is_same_peer(&self, other: &LldpNeighbor) -> bool {
+        self.chassis_id() == other.chassis_id() && self.port_id() == other.port_id()
+    }
+
+    fn is_expired(&self, current_time: Ticks, tick_us: usize) -> bool {
+        let ttl_ticks = Ticks::from_micros(self.ttl as usize * 1_000_000, tick_us);
+        current_time.since(self.last_seen) >= ttl_ticks
+    }
+}
+
+pub struct NeighborTable {
+    neighbors: [Option<LldpNeighbor>; MAX_LLDP_NEIGHBORS],
+}
+
+impl Default for NeighborTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NeighborTable {
+    pub const fn new() -> Self {
+        Self {
+            neighbors: [None; MAX_LLDP_NEIGHBORS],
+        }
+    }
+
+    /// Adds or refreshes a neighbor, replacing the least recently seen one when full
+    pub fn update(&mut self, mut neighbor: LldpNeighbor, current_time: Ticks) {
+        neighbor.last_seen = current_time;
+
+        // A TTL of zero announces the neighbor is going away
+        if neighbor.ttl == 0 {
+            self.remove(&neighbor);
+            return;
+        }
+
+        let index = self
+            .neighbors
+            .iter()
+            .position(|n| n.is_some_and(|n| n.is_same_peer(&neighbor)))
+            .or_else(|| self.neighbors.iter().position(|n| n.is_none()))
+            .unwrap_or_else(|| {
+                let mut oldest = 0;
+                for (i, n) in self.neighbors.iter().enumerate() {
+                    if let (Some(n), Some(o)) = (n, &self.neighbors[oldest]) {
+                        if current_time.since(n.last_seen) > current_time.since(o.last_seen) {
+                            oldest = i;
+                        }
+                    }
+                }
+                oldest
+            });
+
+        self.neighbors[index] = Some(neighbor);
+    }
+
+    fn remove(&mut self, neighbor: &LldpNeighbor) {
+        for slot in self.neighbors.iter_mut() {
+            if slot.is_some_and(|n| n.is_same_peer(neighbor)) {
+                *slot = None;
+            }
+        }
+    }
+
+    /// Drops all neighbors whose TTL ran out
+    pub fn age(&mut self, current_time: Ticks, tick_us: usize) {
+        for slot in self.neighbors.iter_mut() {
+            if slot.is_some_and(|n| n.is_expired(current_time, tick_us)) {
+                *slot = None;
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &LldpNeighbor> {
+        self.neighbors.iter().flatten()
+    }
+}
@@ -0,0 +1,53 @@
+// Logging macros that forward to defmt when the `defmt` feature is enabled and
+// compile to nothing otherwise. The arguments are still borrowed so values that
+// are only used for logging don't trigger unused warnings.
+
+#[cfg(feature = "defmt")]
+macro_rules! trace {
+    ($($arg:tt)*) => { defmt::trace!($($arg)*) };
+}
+
+#[cfg(feature = "defmt")]
+macro_rules! debug {
+    ($($arg:tt)*) => { defmt::debug!($($arg)*) };
+}
+
+#[cfg(feature = "defmt")]
+macro_rules! info {
+    ($($arg:tt)*) => { defmt::info!($($arg)*) };
+}
+
+#[cfg(feature = "defmt")]
+macro_rules! warn {
+    ($($arg:tt)*) => { defmt::warn!($($arg)*) };
+}
+
+#[cfg(feature = "defmt")]
+macro_rules! error {
+    ($($arg:tt)*) => { defmt::error!($($arg)*) };
+}
+
+#[cfg(not(feature = "defmt"))]
+macro_rules! trace {
+    ($fmt:literal $(, $arg:expr)* $(,)?) => {{ let _ = $fmt; $( let _ = &$arg; )* }};
+}
+
+#[cfg(not(feature = "defmt"))]
+macro_rules! debug {
+    ($fmt:literal $(, $arg:expr)* $(,)?) => {{ let _ = $fmt; $( let _ = &$arg; )* }};
+}
+
+#[cfg(not(feature = "defmt"))]
+macro_rules! info {
+    ($fmt:literal $(, $arg:expr)* $(,)?) => {{ let _ = $fmt; $( let _ = &$arg; )* }};
+}
+
+#[cfg(not(feature = "defmt"))]
+macro_rules! warn {
+    ($fmt:literal $(, $arg:expr)* $(,)?) => {{ let _ = $fmt; $( let _ = &$arg; )* }};
+}
+
+#[cfg(not(feature = "defmt"))]
+macro_rules! error {
+    ($fmt:literal $(, $arg:expr)* $(,)?) => {{ let _ = $fmt; $( let _ = &$arg; )* }};
+}
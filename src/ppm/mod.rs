@@ -0,0 +1,437 @@
+// Provider protocol machine
+
+use byteorder::{ByteOrder, NetworkEndian};
+use smoltcp::wire::EthernetAddress;
+
+use crate::ethernet::EthType;
+use crate::field::{Field, Rest, SmallField};
+use crate::fspm::{app::App, Config};
+use crate::scheduler::{TaskCallback, TaskKind};
+use crate::time::Ticks;
+use crate::PNet;
+
+pub const MAX_C_SDU_LENGTH: usize = 1440;
+pub const MIN_C_SDU_LENGTH: usize = 40;
+
+/// Priority used in the VLAN tag of RT_CLASS_1 frames
+pub const RT_VLAN_PRIORITY: u16 = 6;
+
+pub const DATA_STATUS_STATE_PRIMARY: u8 = 1 << 0;
+pub const DATA_STATUS_REDUNDANCY: u8 = 1 << 1;
+pub const DATA_STATUS_DATA_VALID: u8 = 1 << 2;
+pub const DATA_STATUS_PROVIDER_STATE_RUN: u8 = 1 << 4;
+pub const DATA_STATUS_STATION_PROBLEM_NORMAL: u8 = 1 << 5;
+pub const DATA_STATUS_IGNORE: u8 = 1 << 7;
+
+pub const IOXS_GOOD: u8 = 0x80;
+pub const IOXS_BAD: u8 = 0x00;
+
+const DESTINATION_FIELD: Field = 0..6;
+const SOURCE_FIELD: Field = 6..12;
+const VLAN_TYPE_FIELD: Field = 12..14;
+const VLAN_TCI_FIELD: Field = 14..16;
+const TYPE_FIELD: Field = 16..18;
+const FRAME_ID_FIELD: Field = 18..20;
+const C_SDU_FIELD: Rest = 20..;
+
+// Offsets of the APDU status, relative to the end of the C_SDU
+const CYCLE_COUNTER_FIELD: Field = 0..2;
+const DATA_STATUS_FIELD: SmallField = 2;
+const TRANSFER_STATUS_FIELD: SmallField = 3;
+const APDU_STATUS_LENGTH: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PPMState {
+    WStart,
+    Run,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PpmError {
+    InvalidCsduLength,
+    DataOutOfBounds,
+    /// There is no CR with the given index
+    InvalidCr,
+}
+
+/// Location of one IO data object (a submodule's data and its IOPS) inside the C_SDU
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IoDataObject {
+    pub data_offset: usize,
+    pub data_length: usize,
+    pub iops_offset: usize,
+}
+
+#[derive(Clone, Copy)]
+pub struct Ppm {
+    pub state: PPMState,
+    pub frame_id: u16,
+    pub destination: EthernetAddress,
+    pub source: EthernetAddress,
+    pub send_interval: Ticks,
+    /// Delay of the first frame after `start`, see `Config::ppm_send_offset`
+    pub send_offset: Ticks,
+
+    c_sdu: [u8; MAX_C_SDU_LENGTH],
+    c_sdu_length: usize,
+
+    cycle_counter: u16,
+    data_status: u8,
+    transfer_status: u8,
+}
+
+impl Ppm {
+    pub fn new(
+        frame_id: u16,
+        destination: EthernetAddress,
+        source: EthernetAddress,
+        c_sdu_length: usize,
+        send_interval: Ticks,
+    ) -> Result<Self, PpmError> {
+        if c_sdu_length > MAX_C_SDU_LENGTH {
+            return Err(PpmError::InvalidCsduLength);
+        }
+
+        Ok(Self {
+            state: PPMState::WStart,
+            frame_id,
+            destination,
+            source,
+            send_interval,
+            send_offset: Ticks::ZERO,
+            c_sdu: [0; MAX_C_SDU_LENGTH],
+            c_sdu_length,
+            cycle_counter: 0,
+            data_status: DATA_STATUS_STATE_PRIMARY
+                | DATA_STATUS_DATA_VALID
+                | DATA_STATUS_PROVIDER_STATE_RUN
+                | DATA_STATUS_STATION_PROBLEM_NORMAL,
+            transfer_status: 0,
+        })
+    }
+
+    /// Copies the process data of one IO data object and its IOPS into the C_SDU
+    pub fn set_data(
+        &mut self,
+        object: &IoDataObject,
+        data: &[u8],
+        iops: u8,
+    ) -> Result<(), PpmError> {
+        if data.len() != object.data_length
+            || object.data_offset + object.data_length > self.c_sdu_length
+            || object.iops_offset >= self.c_sdu_length
+        {
+            return Err(PpmError::DataOutOfBounds);
+        }
+
+        self.c_sdu[object.data_offset..object.data_offset + object.data_length]
+            .copy_from_slice(data);
+        self.c_sdu[object.iops_offset] = iops;
+
+        Ok(())
+    }
+
+    /// Sets the IOCS the device reports for data it consumes
+    pub fn set_iocs(&mut self, iocs_offset: usize, iocs: u8) -> Result<(), PpmError> {
+        if iocs_offset >= self.c_sdu_length {
+            return Err(PpmError::DataOutOfBounds);
+        }
+
+        self.c_sdu[iocs_offset] = iocs;
+
+        Ok(())
+    }
+
+    pub fn cycle_counter(&self) -> u16 {
+        self.cycle_counter
+    }
+
+    pub fn data_status(&self) -> u8 {
+        self.data_status
+    }
+
+    /// The C_SDU is padded to the minimum length required by an Ethernet frame
+    fn padded_c_sdu_length(&self) -> usize {
+        self.c_sdu_length.max(MIN_C_SDU_LENGTH)
+    }
+
+    /// Number of bytes `build_frame` writes
+    pub fn frame_length(&self) -> usize {
+        C_SDU_FIELD.start + self.padded_c_sdu_length() + APDU_STATUS_LENGTH
+    }
+
+    /// Builds the next cyclic RT frame into `buffer` and returns the written length.
+    ///
+    /// The cycle counter is advanced on every call. `buffer` must be at least
+    /// `frame_length()` bytes long.
+    pub fn build_frame(&mut self, buffer: &mut [u8]) -> usize {
+        self.cycle_counter = self.cycle_counter.wrapping_add(1);
+
+        buffer[DESTINATION_FIELD].copy_from_slice(self.destination.as_bytes());
+        buffer[SOURCE_FIELD].copy_from_slice(self.source.as_bytes());
+        NetworkEndian::write_u16(&mut buffer[VLAN_TYPE_FIELD], EthType::Vlan as u16);
+        NetworkEndian::write_u16(&mut buffer[VLAN_TCI_FIELD], RT_VLAN_PRIORITY << 13);
+        NetworkEndian::write_u16(&mut buffer[TYPE_FIELD], EthType::Profinet as u16);
+        NetworkEndian::write_u16(&mut buffer[FRAME_ID_FIELD], self.frame_id);
+
+        let c_sdu_end = C_SDU_FIELD.start + self.padded_c_sdu_length();
+        let c_sdu = &mut buffer[C_SDU_FIELD.start..c_sdu_end];
+        c_sdu.fill(0);
+        c_sdu[..self.c_sdu_length].copy_from_slice(&self.c_sdu[..self.c_sdu_length]);
+
+        let status = &mut buffer[c_sdu_end..c_sdu_end + APDU_STATUS_LENGTH];
+        NetworkEndian::write_u16(&mut status[CYCLE_COUNTER_FIELD], self.cycle_counter);
+        status[DATA_STATUS_FIELD] = self.data_status;
+        status[TRANSFER_STATUS_FIELD] = self.transfer_status;
+
+        self.frame_length()
+    }
+
+    /// A PPM sending with the timing of `config`
+    pub fn with_config<T: App + Copy>(
+        frame_id: u16,
+        destination: EthernetAddress,
+        c_sdu_length: usize,
+        config: &Config<T>,
+    ) -> Result<Self, PpmError> {
+        let mut ppm = Self::new(
+            frame_id,
+            destination,
+            config.interface_config.ip_config.mac_address,
+            c_sdu_length,
+            config.ppm_send_interval(),
+        )?;
+        ppm.send_offset = config.ppm_send_offset();
+
+        Ok(ppm)
+    }
+
+    /// Activates the PPM for the given CR and schedules its first cyclic send
+    /// `send_offset` from now. A PPM already running for the CR is replaced.
+    pub fn start<T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &mut PNet<T, U>,
+        cr: usize,
+        mut ppm: Ppm,
+        current_time: Ticks,
+    ) -> Result<(), PpmError> {
+        if cr >= pnet.ppm.len() {
+            return Err(PpmError::InvalidCr);
+        }
+        Self::stop(pnet, cr);
+
+        ppm.state = PPMState::Run;
+        let send_offset = ppm.send_offset;
+
+        pnet.ppm[cr] = Some(ppm);
+        pnet.ppm_instance_count += 1;
+
+        pnet.scheduler
+            .add_task("ppm_send", send_offset, TaskKind::PpmSend(cr), current_time);
+
+        Ok(())
+    }
+
+    /// Stops the PPM for the given CR and cancels its pending send
+    pub fn stop<T: App + Copy, U: TaskCallback + Copy>(pnet: &mut PNet<T, U>, cr: usize) {
+        let Some(slot) = pnet.ppm.get_mut(cr) else {
+            return;
+        };
+
+        if slot.take().is_some() {
+            pnet.ppm_instance_count -= 1;
+            pnet.scheduler
+                .cancel(|task| matches!(task, TaskKind::PpmSend(c) if *c == cr));
+        }
+    }
+
+    /// Scheduler callback, sends one frame and reschedules itself
+    pub(crate) fn handle_send<T: App + Copy, U: TaskCallback + Copy>(
+        pnet: &mut PNet<T, U>,
+        cr: usize,
+        current_time: Ticks,
+    ) {
+        let Some(ppm) = &mut pnet.ppm[cr] else {
+            return;
+        };
+
+        if ppm.state != PPMState::Run {
+            return;
+        }
+
+        let mut buffer = [0; MAX_C_SDU_LENGTH + C_SDU_FIELD.start + APDU_STATUS_LENGTH];
+        let length = ppm.build_frame(&mut buffer);
+        let send_interval = ppm.send_interval;
+
+        pnet.send_frame(&buffer[..length]);
+
+        pnet.scheduler.add_task(
+            "ppm_send",
+            send_interval,
+            TaskKind::PpmSend(cr),
+            current_time,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::MAX_CR;
+    use crate::ethernet::MockDma;
+    use crate::util::{test_config, TestTask};
+
+    fn test_ppm(c_sdu_length: usize) -> Ppm {
+        Ppm::new(
+            0xc001,
+            EthernetAddress::from_bytes(&[0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5]),
+            EthernetAddress::from_bytes(&[0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe]),
+            c_sdu_length,
+            Ticks(1000),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_build_frame() {
+        let mut ppm = test_ppm(4);
+        let object = IoDataObject {
+            data_offset: 0,
+            data_length: 2,
+            iops_offset: 2,
+        };
+
+        ppm.set_data(&object, &[0x12, 0x34], IOXS_GOOD).unwrap();
+        ppm.set_iocs(3, IOXS_GOOD).unwrap();
+
+        let mut buffer = [0; 128];
+        let length = ppm.build_frame(&mut buffer);
+
+        assert_eq!(length, 64);
+        assert_eq!(length, ppm.frame_length());
+        assert_eq!(
+            buffer[..24],
+            [
+                0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5, 0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe, 0x81, 0x00,
+                0xc0, 0x00, 0x88, 0x92, 0xc0, 0x01, 0x12, 0x34, 0x80, 0x80
+            ]
+        );
+        assert!(buffer[24..60].iter().all(|b| *b == 0));
+        assert_eq!(buffer[60..64], [0x00, 0x01, 0x35, 0x00]);
+    }
+
+    #[test]
+    fn test_cycle_counter_wraps() {
+        let mut ppm = test_ppm(MIN_C_SDU_LENGTH);
+        ppm.cycle_counter = u16::MAX - 1;
+
+        let mut buffer = [0; 128];
+        ppm.build_frame(&mut buffer);
+        assert_eq!(ppm.cycle_counter(), u16::MAX);
+
+        let length = ppm.build_frame(&mut buffer);
+        assert_eq!(ppm.cycle_counter(), 0);
+        assert_eq!(buffer[length - 4..length - 2], [0, 0]);
+    }
+
+    #[test]
+    fn test_data_status_bits() {
+        let ppm = test_ppm(MIN_C_SDU_LENGTH);
+        let status = ppm.data_status();
+
+        assert_ne!(status & DATA_STATUS_STATE_PRIMARY, 0);
+        assert_ne!(status & DATA_STATUS_DATA_VALID, 0);
+        assert_ne!(status & DATA_STATUS_PROVIDER_STATE_RUN, 0);
+        assert_eq!(status & DATA_STATUS_IGNORE, 0);
+    }
+
+    #[test]
+    fn test_set_data_out_of_bounds() {
+        let mut ppm = test_ppm(4);
+        let object = IoDataObject {
+            data_offset: 3,
+            data_length: 2,
+            iops_offset: 5,
+        };
+
+        assert!(ppm.set_data(&object, &[1, 2], IOXS_GOOD).is_err());
+        assert!(Ppm::new(
+            1,
+            ppm.destination,
+            ppm.source,
+            MAX_C_SDU_LENGTH + 1,
+            Ticks(1)
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_send_timing_from_config() {
+        let mut config = test_config();
+        config.send_clock_factor = 32;
+        config.reduction_ratio = 4;
+        config.phase = 2;
+
+        let ppm = Ppm::with_config(
+            0xc001,
+            EthernetAddress::from_bytes(&[0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5]),
+            MIN_C_SDU_LENGTH,
+            &config,
+        )
+        .unwrap();
+        assert_eq!(ppm.send_interval, Ticks(4));
+        assert_eq!(ppm.send_offset, Ticks(2));
+
+        let mut pnet: PNet<_, TestTask> = PNet::new(config);
+        pnet.mock_dma = Some(MockDma::default());
+        Ppm::start(&mut pnet, 0, ppm, Ticks(0)).unwrap();
+
+        let sent = |pnet: &PNet<_, TestTask>| pnet.mock_dma.as_ref().unwrap().tx.len();
+
+        pnet.run_scheduled_tasks(Ticks(1));
+        assert_eq!(sent(&pnet), 0);
+        pnet.run_scheduled_tasks(Ticks(2));
+        assert_eq!(sent(&pnet), 1);
+        pnet.run_scheduled_tasks(Ticks(5));
+        assert_eq!(sent(&pnet), 1);
+        pnet.run_scheduled_tasks(Ticks(6));
+        assert_eq!(sent(&pnet), 2);
+    }
+
+    #[test]
+    fn test_restart_ppm() {
+        let mut config = test_config();
+        config.send_clock_factor = 32;
+        config.reduction_ratio = 4;
+        let ppm = Ppm::with_config(
+            0xc001,
+            EthernetAddress::from_bytes(&[0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5]),
+            MIN_C_SDU_LENGTH,
+            &config,
+        )
+        .unwrap();
+
+        let mut pnet: PNet<_, TestTask> = PNet::new(config);
+        pnet.mock_dma = Some(MockDma::default());
+        let sent = |pnet: &PNet<_, TestTask>| pnet.mock_dma.as_ref().unwrap().tx.len();
+
+        // Started twice, and stopped and started again before the send fired
+        Ppm::start(&mut pnet, 0, ppm, Ticks(0)).unwrap();
+        Ppm::start(&mut pnet, 0, ppm, Ticks(0)).unwrap();
+        Ppm::stop(&mut pnet, 0);
+        Ppm::start(&mut pnet, 0, ppm, Ticks(0)).unwrap();
+        assert_eq!(pnet.ppm_instance_count, 1);
+
+        // One frame per send interval
+        for (time, frames) in [(0, 1), (4, 2), (8, 3), (12, 4)] {
+            pnet.run_scheduled_tasks(Ticks(time));
+            assert_eq!(sent(&pnet), frames);
+        }
+
+        assert!(matches!(
+            Ppm::start(&mut pnet, MAX_CR, ppm, Ticks(12)),
+            Err(PpmError::InvalidCr)
+        ));
+    }
+}
@@ -0,0 +1,281 @@
+// Precision transparent clock protocol (PTCP), recognises the sync frames of
+// the sync master. Time synchronisation itself is not implemented yet.
+
+use byteorder::{ByteOrder, NetworkEndian};
+use smoltcp::wire::EthernetAddress;
+
+use crate::ethernet::EthernetFrame;
+use crate::field::Field;
+use crate::fspm::app::App;
+use crate::scheduler::TaskCallback;
+use crate::time::Ticks;
+use crate::PNet;
+
+/// Destination of the RTSync frames of clock synchronisation
+pub const PTCP_MAC_SYNC_ADDRESS: [u8; 6] = [0x01, 0x0e, 0xcf, 0x00, 0x04, 0x00];
+
+// Frame ids of clock synchronisation, time synchronisation uses the next id
+pub const FRAME_ID_SYNC_FOLLOW_UP: u16 = 0x0020;
+pub const FRAME_ID_SYNC: u16 = 0x0080;
+pub const FRAME_ID_ANNOUNCE: u16 = 0xff00;
+pub const FRAME_ID_FOLLOW_UP: u16 = 0xff20;
+pub const FRAME_ID_DELAY_REQUEST: u16 = 0xff40;
+pub const FRAME_ID_DELAY_RESPONSE_FOLLOW_UP: u16 = 0xff41;
+pub const FRAME_ID_DELAY_FOLLOW_UP_RESPONSE: u16 = 0xff42;
+pub const FRAME_ID_DELAY_RESPONSE: u16 = 0xff43;
+
+const TLV_TYPE_END: u8 = 0x00;
+const TLV_TYPE_SUBDOMAIN: u8 = 0x01;
+const TLV_TYPE_TIME: u8 = 0x02;
+
+// PTCP header, relative to the end of the frame id
+const SEQUENCE_ID_FIELD: Field = 16..18;
+const HEADER_LENGTH: usize = 20;
+
+// TLVs, relative to the start of their value
+const MASTER_SOURCE_ADDRESS_FIELD: Field = 0..6;
+const SUBDOMAIN_UUID_FIELD: Field = 6..22;
+const EPOCH_FIELD: Field = 0..2;
+const SECONDS_FIELD: Field = 2..6;
+const NANOSECONDS_FIELD: Field = 6..10;
+const TLV_HEADER_LENGTH: usize = 2;
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PtcpError {
+    TooShort,
+    /// A sync or announce frame without a subdomain TLV
+    NoSubdomain,
+}
+
+/// The PTCP frame types, told apart by their frame id
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PtcpFrameType {
+    Sync,
+    SyncWithFollowUp,
+    Announce,
+    FollowUp,
+    DelayRequest,
+    DelayResponse,
+}
+
+impl PtcpFrameType {
+    /// The frame type of `frame_id`, `None` if it isn't a PTCP frame
+    pub fn from_frame_id(frame_id: u16) -> Option<Self> {
+        match frame_id {
+            0x0020..=0x0021 => Some(Self::SyncWithFollowUp),
+            0x0080..=0x0081 => Some(Self::Sync),
+            0xff00..=0xff01 => Some(Self::Announce),
+            0xff20..=0xff21 => Some(Self::FollowUp),
+            FRAME_ID_DELAY_REQUEST => Some(Self::DelayRequest),
+            FRAME_ID_DELAY_RESPONSE_FOLLOW_UP..=FRAME_ID_DELAY_RESPONSE => {
+                Some(Self::DelayResponse)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// What the sync master announced in its last sync or announce frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SyncInfo {
+    pub master_mac: EthernetAddress,
+    pub subdomain_uuid: [u8; 16],
+    pub sequence_id: u16,
+    /// Time of the master, only sent in sync frames of time synchronisation
+    pub epoch: u16,
+    pub seconds: u32,
+    pub nanoseconds: u32,
+    pub received_at: Ticks,
+}
+
+/// Parses the PTCP header and TLVs following the frame id of a sync or
+/// announce frame
+pub fn parse_sync(buffer: &[u8], received_at: Ticks) -> Result<SyncInfo, PtcpError> {
+    if buffer.len() < HEADER_LENGTH {
+        return Err(PtcpError::TooShort);
+    }
+
+    let mut info = SyncInfo {
+        master_mac: EthernetAddress::default(),
+        subdomain_uuid: [0; 16],
+        sequence_id: NetworkEndian::read_u16(&buffer[SEQUENCE_ID_FIELD]),
+        epoch: 0,
+        seconds: 0,
+        nanoseconds: 0,
+        received_at,
+    };
+    let mut has_subdomain = false;
+
+    let mut offset = HEADER_LENGTH;
+    while offset + TLV_HEADER_LENGTH <= buffer.len() {
+        let tlv_header = NetworkEndian::read_u16(&buffer[offset..offset + TLV_HEADER_LENGTH]);
+        let tlv_type = (tlv_header >> 9) as u8;
+        let length = (tlv_header & 0x01ff) as usize;
+
+        let value_start = offset + TLV_HEADER_LENGTH;
+        let value = buffer
+            .get(value_start..value_start + length)
+            .ok_or(PtcpError::TooShort)?;
+
+        match tlv_type {
+            TLV_TYPE_END => break,
+            TLV_TYPE_SUBDOMAIN if length >= SUBDOMAIN_UUID_FIELD.end => {
+                info.master_mac = EthernetAddress::from_bytes(&value[MASTER_SOURCE_ADDRESS_FIELD]);
+                info.subdomain_uuid
+                    .copy_from_slice(&value[SUBDOMAIN_UUID_FIELD]);
+                has_subdomain = true;
+            }
+            TLV_TYPE_TIME if length >= NANOSECONDS_FIELD.end => {
+                info.epoch = NetworkEndian::read_u16(&value[EPOCH_FIELD]);
+                info.seconds = NetworkEndian::read_u32(&value[SECONDS_FIELD]);
+                info.nanoseconds = NetworkEndian::read_u32(&value[NANOSECONDS_FIELD]);
+            }
+            // Other TLVs aren't needed to follow the master
+            _ => (),
+        }
+
+        offset = value_start + length;
+    }
+
+    if !has_subdomain {
+        return Err(PtcpError::NoSubdomain);
+    }
+
+    Ok(info)
+}
+
+/// Handles a received PTCP frame. Sync and announce frames update the sync
+/// info of the master, all other frames are ignored for now.
+pub(crate) fn handle_frame<T: App + Copy, U: TaskCallback + Copy>(
+    pnet: &mut PNet<T, U>,
+    frame: &EthernetFrame<&[u8]>,
+    current_time: Ticks,
+) {
+    let frame_id = frame.frame_id_u16();
+
+    match PtcpFrameType::from_frame_id(frame_id) {
+        Some(PtcpFrameType::Sync | PtcpFrameType::Announce) => {
+            match parse_sync(frame.payload(), current_time) {
+                Ok(info) => pnet.ptcp_sync = Some(info),
+                Err(e) => debug!("Dropped PTCP frame: {}", e),
+            }
+        }
+        Some(frame_type) => trace!("Ignored PTCP frame {}", frame_type),
+        None => trace!("Ignored PTCP frame id {:x}", frame_id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethernet::EthType;
+    use crate::util::{test_config, TestTask};
+
+    use super::*;
+
+    const MASTER_MAC: [u8; 6] = [0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5];
+    const SUBDOMAIN_UUID: [u8; 16] = [
+        0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff,
+        0x00,
+    ];
+
+    fn sync_frame(frame_id: u16) -> [u8; 72] {
+        let mut buffer = [0; 72];
+        buffer[0..6].copy_from_slice(&PTCP_MAC_SYNC_ADDRESS);
+        buffer[6..12].copy_from_slice(&MASTER_MAC);
+        NetworkEndian::write_u16(&mut buffer[12..14], EthType::Profinet as u16);
+        NetworkEndian::write_u16(&mut buffer[14..16], frame_id);
+
+        // Header, relative to the end of the frame id
+        let ptcp = &mut buffer[16..];
+        NetworkEndian::write_u16(&mut ptcp[SEQUENCE_ID_FIELD], 42);
+
+        // Subdomain TLV
+        NetworkEndian::write_u16(&mut ptcp[20..22], (1 << 9) | 22);
+        ptcp[22..28].copy_from_slice(&MASTER_MAC);
+        ptcp[28..44].copy_from_slice(&SUBDOMAIN_UUID);
+
+        // Time TLV
+        NetworkEndian::write_u16(&mut ptcp[44..46], (2 << 9) | 10);
+        NetworkEndian::write_u16(&mut ptcp[46..48], 1);
+        NetworkEndian::write_u32(&mut ptcp[48..52], 1000);
+        NetworkEndian::write_u32(&mut ptcp[52..56], 500);
+
+        // End TLV is all zeros
+
+        buffer
+    }
+
+    #[test]
+    fn test_frame_type() {
+        assert_eq!(
+            PtcpFrameType::from_frame_id(0x0080),
+            Some(PtcpFrameType::Sync)
+        );
+        assert_eq!(
+            PtcpFrameType::from_frame_id(0x0081),
+            Some(PtcpFrameType::Sync)
+        );
+        assert_eq!(
+            PtcpFrameType::from_frame_id(0xff01),
+            Some(PtcpFrameType::Announce)
+        );
+        assert_eq!(
+            PtcpFrameType::from_frame_id(0xff43),
+            Some(PtcpFrameType::DelayResponse)
+        );
+        assert_eq!(PtcpFrameType::from_frame_id(0xff44), None);
+        assert_eq!(PtcpFrameType::from_frame_id(0xc000), None);
+    }
+
+    #[test]
+    fn test_parse_sync() {
+        let frame = sync_frame(FRAME_ID_SYNC);
+        let info = parse_sync(&frame[16..], Ticks(7)).unwrap();
+
+        assert_eq!(info.master_mac, EthernetAddress(MASTER_MAC));
+        assert_eq!(info.subdomain_uuid, SUBDOMAIN_UUID);
+        assert_eq!(info.sequence_id, 42);
+        assert_eq!(info.epoch, 1);
+        assert_eq!(info.seconds, 1000);
+        assert_eq!(info.nanoseconds, 500);
+        assert_eq!(info.received_at, Ticks(7));
+
+        assert_eq!(
+            parse_sync(&frame[16..30], Ticks(7)),
+            Err(PtcpError::TooShort)
+        );
+        assert_eq!(parse_sync(&[0; 24], Ticks(7)), Err(PtcpError::NoSubdomain));
+    }
+
+    #[test]
+    fn test_handle_sync() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        pnet.join_multicast_group(EthernetAddress(PTCP_MAC_SYNC_ADDRESS))
+            .unwrap();
+
+        pnet.handle_frame(&sync_frame(FRAME_ID_SYNC), Ticks(3))
+            .unwrap();
+
+        let info = pnet.ptcp_sync.unwrap();
+        assert_eq!(info.master_mac, EthernetAddress(MASTER_MAC));
+        assert_eq!(info.received_at, Ticks(3));
+        assert_eq!(pnet.stats.ptcp_received, 1);
+    }
+
+    #[test]
+    fn test_other_ptcp_frames_ignored() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        pnet.join_multicast_group(EthernetAddress(PTCP_MAC_SYNC_ADDRESS))
+            .unwrap();
+
+        pnet.handle_frame(&sync_frame(FRAME_ID_FOLLOW_UP), Ticks(3))
+            .unwrap();
+        pnet.handle_frame(&sync_frame(FRAME_ID_DELAY_REQUEST), Ticks(3))
+            .unwrap();
+
+        assert_eq!(pnet.ptcp_sync, None);
+        assert_eq!(pnet.stats.ptcp_received, 2);
+    }
+}
@@ -0,0 +1,135 @@
+// One buffer the stack encodes its responses into, instead of a zeroed
+// buffer on the stack for every frame
+
+use core::cell::{Cell, RefCell};
+
+use crate::constants::MAX_FRAME_LENGTH;
+use crate::dcp::Dcp;
+use crate::error::Error;
+
+/// A response encoded by a `ResponseBuilder`, queued with `PNet::queue_response`
+#[must_use]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Response {
+    pub length: usize,
+    generation: u32,
+}
+
+/// Reuses one frame buffer for every response the stack sends.
+///
+/// Responses often borrow the `PNet` that owns the builder, the station name
+/// for example, so building only needs a shared reference. Queueing copies the
+/// frame out of the buffer, a response built over one that wasn't queued yet
+/// makes the older one stale instead of changing its bytes.
+pub struct ResponseBuilder {
+    buffer: RefCell<[u8; MAX_FRAME_LENGTH]>,
+    /// Bytes the last response wrote, only these are cleared for the next one
+    used: Cell<usize>,
+    generation: Cell<u32>,
+}
+
+impl Default for ResponseBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ResponseBuilder {
+    pub const fn new() -> Self {
+        Self {
+            buffer: RefCell::new([0; MAX_FRAME_LENGTH]),
+            used: Cell::new(0),
+            generation: Cell::new(0),
+        }
+    }
+
+    /// Encodes `dcp` as the next response
+    pub fn build_dcp<const N: usize>(&self, dcp: &Dcp<'_, N>) -> Result<Response, Error> {
+        let mut buffer = self.buffer.borrow_mut();
+        buffer[..self.used.get()].fill(0);
+
+        let length = dcp
+            .encode_checked(&mut buffer[..])
+            .map_err(Error::EncodeError)?;
+        self.used.set(length);
+
+        let generation = self.generation.get().wrapping_add(1);
+        self.generation.set(generation);
+
+        Ok(Response { length, generation })
+    }
+
+    /// Copies the bytes of `response` into `buffer`, fails with
+    /// `Error::StaleResponse` if another response was built since
+    pub(crate) fn copy_frame(&self, response: Response, buffer: &mut [u8]) -> Result<(), Error> {
+        if response.generation != self.generation.get() {
+            return Err(Error::StaleResponse);
+        }
+
+        buffer[..response.length].copy_from_slice(&self.buffer.borrow()[..response.length]);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use smoltcp::wire::EthernetAddress;
+
+    use super::*;
+    use crate::constants::MIN_FRAME_LENGTH;
+    use crate::dcp::*;
+    use crate::time::Ticks;
+    use crate::util::{test_config, TestTask};
+    use crate::PNet;
+
+    fn identify_response(name: &str) -> Dcp<'_> {
+        let mut dcp = Dcp::new(
+            EthernetAddress([0x02, 0, 0, 0, 0, 1]),
+            EthernetAddress([0x02, 0, 0, 0, 0, 2]),
+            DcpHeader::new(ServiceId::Identify, ServiceType::Success, 1, 0),
+            DcpFrameId::Response,
+        );
+        dcp.add_block(DcpBlock::new(Block::DeviceProperties(
+            DevicePropertiesBlock::NameOfStation(NameOfStation::from_str(name)),
+        )));
+
+        dcp
+    }
+
+    #[test]
+    fn test_reuse_buffer() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        let long = identify_response("a-much-longer-name-of-station");
+        let short = identify_response("short");
+
+        let response = pnet.response.build_dcp(&long).unwrap();
+        pnet.queue_response(response, Ticks(0)).unwrap();
+        let response = pnet.response.build_dcp(&short).unwrap();
+        pnet.queue_response(response, Ticks(0)).unwrap();
+
+        // The short frame doesn't carry bytes of the long one
+        let mut expected = [0; MAX_FRAME_LENGTH];
+        let length = short.encode_checked(&mut expected).unwrap();
+        let packet = pnet.outgoing_packets[1].unwrap();
+        assert_eq!(packet.frame(), &expected[..length.max(MIN_FRAME_LENGTH)]);
+        assert_eq!(pnet.response.buffer.borrow()[length..], expected[length..]);
+    }
+
+    #[test]
+    fn test_stale_response() {
+        let mut pnet: PNet<_, TestTask> = PNet::new(test_config());
+        let dcp = identify_response("device");
+
+        let first = pnet.response.build_dcp(&dcp).unwrap();
+        let second = pnet.response.build_dcp(&dcp).unwrap();
+
+        assert!(matches!(
+            pnet.queue_response(first, Ticks(0)),
+            Err(Error::StaleResponse)
+        ));
+        pnet.queue_response(second, Ticks(0)).unwrap();
+        assert_eq!(pnet.outgoing_packets.iter().flatten().count(), 1);
+    }
+}
@@ -1,18 +1,64 @@
-use crate::{constants::MAX_SCHEDULER_TASKS, fspm::app::App, PNet};
+use crate::time::Ticks;
+use crate::{alarm::AlarmPriority, constants::MAX_SCHEDULER_TASKS, fspm::app::App, PNet};
+
+/// What a scheduled task does when it fires.
+///
+/// Stack-internal timeouts get their own variant so the stack can schedule
+/// them without knowing the application's callback type.
+#[derive(Clone, Copy)]
+pub enum TaskKind<T: TaskCallback + Copy> {
+    /// Send the next cyclic frame of the PPM instance with the given CR index
+    PpmSend(usize),
+    /// Check the data hold timer of the CPM instance with the given CR index
+    CpmWatchdog(usize),
+    /// Send the next ARP probe of the address conflict detection
+    AcdProbe,
+    /// Resend the alarm of the given priority if the DATA PDU with the given
+    /// sequence number is still unacknowledged
+    AlarmRetransmit(AlarmPriority, u16),
+    /// Send the LLDP frame of the port with the given index
+    LldpSend(usize),
+    /// Drop LLDP neighbors whose TTL expired
+    LldpAge,
+    /// Send the next Hello of the DCP Hello burst with the given id
+    DcpHello(u16),
+    /// Switch the signal LED for the given toggle of a DCP Signal
+    DcpSignal(u8),
+    App(T),
+}
 
 #[derive(Clone, Copy)]
 pub struct Task<T: TaskCallback + Copy> {
-    name: &'static str,
-    run_at: usize,
-    task: T,
+    pub(crate) name: &'static str,
+    pub(crate) run_at: Ticks,
+    pub(crate) task: TaskKind<T>,
+    /// Order of `add_task` calls, breaks ties between tasks with the same `run_at`
+    sequence: u32,
+}
+
+impl<T: TaskCallback + Copy> Task<T> {
+    /// Whether the task fires before `other`. The deadlines are compared by
+    /// their wrapping difference, like `Ticks::is_reached` does.
+    fn runs_before(&self, other: &Task<T>) -> bool {
+        let difference = self.run_at.0.wrapping_sub(other.run_at.0) as isize;
+        difference < 0
+            || (difference == 0 && (self.sequence.wrapping_sub(other.sequence) as i32) < 0)
+    }
 }
 
 pub trait TaskCallback {
     fn callback<T: App + Copy, U: TaskCallback + Copy>(&mut self, pnet: &mut PNet<T, U>);
 }
 
+/// Runs tasks once their deadline has passed.
+///
+/// The tasks are kept in a binary min-heap on `run_at`, so finding the next
+/// due task only looks at the root and taking it costs O(log n).
 pub struct Scheduler<T: TaskCallback + Copy> {
+    /// The heap lives in `tasks[..len]`, the rest is `None`
     tasks: [Option<Task<T>>; MAX_SCHEDULER_TASKS],
+    len: usize,
+    next_sequence: u32,
 }
 
 impl<T> Scheduler<T>
@@ -21,39 +67,176 @@ where
 {
     pub fn new(tick_interval: usize) -> Self {
         if tick_interval == 0 {
-            defmt::panic!("Tick interval must be more than 0");
+            panic!("Tick interval must be more than 0");
         }
 
         Self {
             tasks: [None; MAX_SCHEDULER_TASKS],
+            len: 0,
+            next_sequence: 0,
+        }
+    }
+
+    pub fn add_task(
+        &mut self,
+        name: &'static str,
+        delay: Ticks,
+        callback: TaskKind<T>,
+        current_time: Ticks,
+    ) {
+        if self.len == MAX_SCHEDULER_TASKS {
+            error!("Scheduler is full, dropping task {}", name);
+            return;
+        }
+
+        self.tasks[self.len] = Some(Task {
+            name,
+            run_at: current_time + delay,
+            task: callback,
+            sequence: self.next_sequence,
+        });
+        self.next_sequence = self.next_sequence.wrapping_add(1);
+        self.len += 1;
+        self.sift_up(self.len - 1);
+    }
+
+    /// Drops all pending tasks for which `predicate` holds
+    pub fn cancel(&mut self, predicate: impl Fn(&TaskKind<T>) -> bool) {
+        let mut kept = 0;
+        for i in 0..self.len {
+            let task = self.tasks[i].take();
+            if task.is_some_and(|task| !predicate(&task.task)) {
+                self.tasks[kept] = task;
+                kept += 1;
+            }
+        }
+        self.len = kept;
+
+        for i in (0..self.len / 2).rev() {
+            self.sift_down(i);
+        }
+    }
+
+    /// Removes and returns the next task that is due at `current_time`.
+    ///
+    /// The task is handed back instead of being run in place, because running
+    /// it needs a mutable borrow of the `PNet` that owns this scheduler.
+    pub fn next_due(&mut self, current_time: Ticks) -> Option<Task<T>> {
+        let first = self.tasks[0]?;
+        if !first.run_at.is_reached(current_time) {
+            return None;
+        }
+
+        self.len -= 1;
+        self.tasks.swap(0, self.len);
+        let task = self.tasks[self.len].take();
+        self.sift_down(0);
+
+        task
+    }
+
+    fn runs_before(&self, a: usize, b: usize) -> bool {
+        match (&self.tasks[a], &self.tasks[b]) {
+            (Some(a), Some(b)) => a.runs_before(b),
+            _ => false,
         }
     }
 
-    pub fn add_task(&mut self, name: &'static str, delay: usize, callback: T, current_time: usize) {
-        for i in 0..MAX_SCHEDULER_TASKS {
-            match self.tasks[i] {
-                None => {
-                    let new_task = Task {
-                        name,
-                        run_at: current_time + delay,
-                        task: callback,
-                    };
+    fn sift_up(&mut self, mut i: usize) {
+        while i > 0 {
+            let parent = (i - 1) / 2;
+            if !self.runs_before(i, parent) {
+                break;
+            }
+            self.tasks.swap(i, parent);
+            i = parent;
+        }
+    }
 
-                    self.tasks[i] = Some(new_task);
+    fn sift_down(&mut self, mut i: usize) {
+        loop {
+            let mut first = i;
+            for child in [2 * i + 1, 2 * i + 2] {
+                if child < self.len && self.runs_before(child, first) {
+                    first = child;
                 }
-                _ => (),
             }
+            if first == i {
+                break;
+            }
+            self.tasks.swap(i, first);
+            i = first;
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::TestTask;
+
+    #[test]
+    fn test_next_due_across_wrap() {
+        let mut scheduler: Scheduler<TestTask> = Scheduler::new(1);
+        let before_wrap = Ticks(usize::MAX - 5);
+
+        scheduler.add_task("wrap", Ticks(10), TaskKind::LldpAge, before_wrap);
+
+        assert!(scheduler.next_due(before_wrap).is_none());
+        assert!(scheduler.next_due(Ticks(usize::MAX)).is_none());
+        assert!(scheduler.next_due(Ticks(3)).is_none());
+        assert_eq!(scheduler.next_due(Ticks(4)).unwrap().run_at, Ticks(4));
+        assert!(scheduler.next_due(Ticks(5)).is_none());
+    }
+
+    #[test]
+    fn test_cancel() {
+        let mut scheduler: Scheduler<TestTask> = Scheduler::new(1);
+
+        scheduler.add_task("first", Ticks(1), TaskKind::CpmWatchdog(0), Ticks(0));
+        scheduler.add_task("second", Ticks(1), TaskKind::CpmWatchdog(1), Ticks(0));
+        scheduler.cancel(|task| matches!(task, TaskKind::CpmWatchdog(0)));
+
+        let task = scheduler.next_due(Ticks(1)).unwrap();
+        assert!(matches!(task.task, TaskKind::CpmWatchdog(1)));
+        assert!(scheduler.next_due(Ticks(1)).is_none());
+    }
+
+    #[test]
+    fn test_fire_order() {
+        let mut scheduler: Scheduler<TestTask> = Scheduler::new(1);
+        let mut state: u32 = 0x2545_f491;
+        let mut now = Ticks(usize::MAX - 500);
+        let mut fired = 0;
 
-    pub fn tick<U: App + Copy>(&mut self, pnet: &mut PNet<U, T>, current_time: usize) {
-        for i in 0..MAX_SCHEDULER_TASKS {
-            if let Some(task) = &mut self.tasks[i] {
-                if current_time >= task.run_at {
-                    task.task.callback(pnet);
-                    self.tasks[i] = None;
+        // Keep the scheduler full of tasks with random delays while advancing
+        // the time past the wrap of the counter, every task has to fire in
+        // deadline order with ties in the order they were added
+        for round in 0..1000 {
+            while scheduler.len < MAX_SCHEDULER_TASKS {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                let delay = Ticks(state as usize % 20);
+                scheduler.add_task("random", delay, TaskKind::LldpSend(round), now);
+            }
+
+            now += Ticks(3);
+            let mut last: Option<Task<TestTask>> = None;
+            while let Some(task) = scheduler.next_due(now) {
+                assert!(task.run_at.is_reached(now));
+                if let Some(last) = last {
+                    assert!(last.runs_before(&task));
                 }
+                last = Some(task);
+                fired += 1;
+            }
+
+            for task in scheduler.tasks[..scheduler.len].iter().flatten() {
+                assert!(!task.run_at.is_reached(now));
             }
         }
+
+        assert!(fired > 1000);
     }
 }
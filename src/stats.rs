@@ -0,0 +1,36 @@
+// Frame counters for field debugging. All counters wrap around on overflow.
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PnetStats {
+    /// Frames read from the transport
+    pub frames_received: u32,
+    /// Frames addressed to another station
+    pub frames_not_for_us: u32,
+    /// DCP frames addressed to this station or the DCP multicast address
+    pub dcp_received: u32,
+    /// DCP frames that were parsed successfully
+    pub dcp_parsed: u32,
+    /// DCP frames that failed to parse
+    pub dcp_parse_errors: u32,
+    /// DCP frames dropped because their service doesn't fit the frame id
+    pub dcp_invalid_service: u32,
+    /// Cyclic RT frames
+    pub cyclic_received: u32,
+    /// PTCP clock and time synchronisation frames
+    pub ptcp_received: u32,
+    /// Frames put into the outgoing queue
+    pub packets_queued: u32,
+    /// Frames dropped because the outgoing queue was full
+    pub queue_full: u32,
+    /// Frames dropped because they were overdue by more than `max_send_latency_us`
+    pub dropped_stale: u32,
+    /// Frames handed to the transport
+    pub packets_sent: u32,
+    /// Frames the transport failed to send
+    pub send_errors: u32,
+}
+
+pub(crate) fn increment(counter: &mut u32) {
+    *counter = counter.wrapping_add(1);
+}
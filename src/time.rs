@@ -0,0 +1,103 @@
+// Time as counted by the stack
+
+use core::ops::{Add, AddAssign, Sub};
+
+/// A point in time or a duration in scheduler ticks.
+///
+/// A tick lasts `Config::tick_us` microseconds. Timestamps passed to the stack,
+/// task deadlines and the send times of queued packets are all counted in
+/// ticks, intervals given in microseconds are converted with `from_micros`.
+///
+/// The tick counter may wrap around, on 32 bit targets a microsecond counter
+/// does so after about 71 minutes. Points in time are therefore compared with
+/// `is_reached` and `since`, which look at the wrapping difference, and not
+/// with `<` or `>`. This works as long as the compared points are less than
+/// half the counter range apart.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Ticks(pub usize);
+
+impl Ticks {
+    pub const ZERO: Ticks = Ticks(0);
+
+    /// The whole ticks in `micros` microseconds
+    pub const fn from_micros(micros: usize, tick_us: usize) -> Self {
+        Self(micros / tick_us)
+    }
+
+    pub const fn to_micros(self, tick_us: usize) -> usize {
+        self.0 * tick_us
+    }
+
+    /// Whether the point in time `self` has come at `now`
+    pub const fn is_reached(self, now: Ticks) -> bool {
+        now.0.wrapping_sub(self.0) as isize >= 0
+    }
+
+    /// The time that passed from `earlier` to `self`, zero if `earlier` is
+    /// still ahead
+    pub const fn since(self, earlier: Ticks) -> Ticks {
+        let difference = self.0.wrapping_sub(earlier.0) as isize;
+        if difference < 0 {
+            Ticks::ZERO
+        } else {
+            Ticks(difference as usize)
+        }
+    }
+}
+
+impl From<usize> for Ticks {
+    fn from(ticks: usize) -> Self {
+        Self(ticks)
+    }
+}
+
+impl Add for Ticks {
+    type Output = Ticks;
+
+    fn add(self, rhs: Ticks) -> Ticks {
+        Ticks(self.0.wrapping_add(rhs.0))
+    }
+}
+
+impl AddAssign for Ticks {
+    fn add_assign(&mut self, rhs: Ticks) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Ticks {
+    type Output = Ticks;
+
+    fn sub(self, rhs: Ticks) -> Ticks {
+        Ticks(self.0.wrapping_sub(rhs.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_micros_conversion() {
+        assert_eq!(Ticks::from_micros(1_000_000, 1000), Ticks(1000));
+        assert_eq!(Ticks::from_micros(1500, 1000), Ticks(1));
+        assert_eq!(Ticks(5).to_micros(1000), 5000);
+        assert_eq!(Ticks(3) + Ticks(5), Ticks(8));
+    }
+
+    #[test]
+    fn test_compare_across_wrap() {
+        let before_wrap = Ticks(usize::MAX - 5);
+        let deadline = before_wrap + Ticks(10);
+        assert_eq!(deadline, Ticks(4));
+
+        assert!(!deadline.is_reached(before_wrap));
+        assert!(!deadline.is_reached(Ticks(usize::MAX)));
+        assert!(deadline.is_reached(Ticks(4)));
+        assert!(deadline.is_reached(Ticks(5)));
+
+        assert_eq!(Ticks(4).since(before_wrap), Ticks(10));
+        assert_eq!(before_wrap.since(Ticks(4)), Ticks::ZERO);
+    }
+}
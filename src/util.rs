@@ -1,4 +1,82 @@
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WriteError {
+    OutOfBounds,
+}
+
+/// Writes big endian values into a buffer while keeping track of the position.
+///
+/// The `put_*` methods panic when the buffer is too short, like slice indexing
+/// does. The `try_put_*` methods leave the buffer untouched and return an error
+/// instead.
+pub(crate) struct ByteWriter<'a> {
+    buffer: &'a mut [u8],
+    position: usize,
+}
+
+impl<'a> ByteWriter<'a> {
+    pub fn new(buffer: &'a mut [u8]) -> Self {
+        Self {
+            buffer,
+            position: 0,
+        }
+    }
+
+    /// Number of bytes written so far
+    pub fn written(&self) -> usize {
+        self.position
+    }
+
+    fn reserve(&mut self, length: usize) -> Result<&mut [u8], WriteError> {
+        let end = self.position + length;
+        if end > self.buffer.len() {
+            return Err(WriteError::OutOfBounds);
+        }
+
+        let field = &mut self.buffer[self.position..end];
+        self.position = end;
+        Ok(field)
+    }
+
+    pub fn try_put_u8(&mut self, value: u8) -> Result<&mut Self, WriteError> {
+        self.reserve(1)?[0] = value;
+        Ok(self)
+    }
+
+    pub fn try_put_u16_be(&mut self, value: u16) -> Result<&mut Self, WriteError> {
+        self.reserve(2)?.copy_from_slice(&value.to_be_bytes());
+        Ok(self)
+    }
+
+    pub fn try_put_u32_be(&mut self, value: u32) -> Result<&mut Self, WriteError> {
+        self.reserve(4)?.copy_from_slice(&value.to_be_bytes());
+        Ok(self)
+    }
+
+    pub fn try_put_slice(&mut self, data: &[u8]) -> Result<&mut Self, WriteError> {
+        self.reserve(data.len())?.copy_from_slice(data);
+        Ok(self)
+    }
+
+    pub fn put_u8(&mut self, value: u8) -> &mut Self {
+        self.try_put_u8(value).expect("buffer too short")
+    }
+
+    pub fn put_u16_be(&mut self, value: u16) -> &mut Self {
+        self.try_put_u16_be(value).expect("buffer too short")
+    }
+
+    pub fn put_u32_be(&mut self, value: u32) -> &mut Self {
+        self.try_put_u32_be(value).expect("buffer too short")
+    }
+
+    pub fn put_slice(&mut self, data: &[u8]) -> &mut Self {
+        self.try_put_slice(data).expect("buffer too short")
+    }
+}
+
 #[cfg(test)]
+#[allow(dead_code)]
 pub fn print_hexdump(buf: &[u8]) -> String {
     let mut string = String::new();
 
@@ -11,8 +89,482 @@ pub fn print_hexdump(buf: &[u8]) -> String {
             .iter()
             .for_each(|c| string.push_str(&format!("{:0>2x} ", c)));
 
-        string.push_str("\n");
+        string.push('\n');
     });
 
     string
 }
+
+#[cfg(all(test, feature = "device"))]
+pub use test_app::*;
+
+#[cfg(all(test, feature = "device"))]
+mod test_app {
+    use smoltcp::iface::Interface;
+    use smoltcp::phy::{Device, DeviceCapabilities, RxToken, TxToken};
+    use smoltcp::time::Instant;
+    use smoltcp::wire::{EthernetAddress, Ipv4Address};
+
+    use crate::cmrpc::{
+        AlarmCrRequest, ArRequest, ConnectRequest, ExpectedSubmodule, IocrRequest, IocrType,
+        MAX_EXPECTED_SUBMODULES,
+    };
+    use crate::constants::{
+        DEFAULT_DCP_HELLO_COUNT, DEFAULT_DCP_HELLO_INTERVAL_US, DEFAULT_MAX_SEND_LATENCY_US,
+        DEFAULT_SEND_CLOCK_FACTOR, MAX_STATION_NAME_SIZE,
+    };
+    use crate::dcp::DiscoveredDevice;
+    use crate::fspm::app::*;
+    use crate::fspm::*;
+    use crate::scheduler::TaskCallback;
+    use crate::PNet;
+
+    /// A device that never sends or receives, for an `Interface` in tests
+    pub struct NullDevice;
+
+    pub enum NullToken {}
+
+    impl RxToken for NullToken {
+        fn consume<R, F: FnOnce(&mut [u8]) -> R>(self, _: F) -> R {
+            match self {}
+        }
+    }
+
+    impl TxToken for NullToken {
+        fn consume<R, F: FnOnce(&mut [u8]) -> R>(self, _: usize, _: F) -> R {
+            match self {}
+        }
+    }
+
+    impl Device for NullDevice {
+        type RxToken<'a> = NullToken;
+        type TxToken<'a> = NullToken;
+
+        fn receive(&mut self, _: Instant) -> Option<(NullToken, NullToken)> {
+            None
+        }
+
+        fn transmit(&mut self, _: Instant) -> Option<NullToken> {
+            None
+        }
+
+        fn capabilities(&self) -> DeviceCapabilities {
+            DeviceCapabilities::default()
+        }
+    }
+
+    /// An interface with the MAC address of `test_config`
+    pub fn test_interface() -> Interface {
+        let mac_address = EthernetAddress([0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe]);
+        let config = smoltcp::iface::Config::new(mac_address.into());
+
+        Interface::new(config, &mut NullDevice, Instant::ZERO)
+    }
+
+    /// A scheduler task that does nothing
+    #[derive(Clone, Copy)]
+    pub struct TestTask;
+
+    impl TaskCallback for TestTask {
+        fn callback<T: App + Copy, U: TaskCallback + Copy>(&mut self, _: &mut PNet<T, U>) {}
+    }
+
+    /// The only record the test application can read and write
+    pub const TEST_APP_RECORD_INDEX: u16 = 0x0100;
+    pub const TEST_APP_RECORD: [u8; 4] = [0xde, 0xad, 0xbe, 0xef];
+
+    /// An application that ignores every callback except for counting IP
+    /// changes and expected modules, and accessing `TEST_APP_RECORD_INDEX`
+    #[derive(Clone, Copy, Default)]
+    pub struct TestApp {
+        pub ip_changes: usize,
+        pub records_written: usize,
+        pub last_record_length: usize,
+        pub modules_expected: usize,
+        pub releases: usize,
+        pub devices_discovered: usize,
+        pub commits: usize,
+        /// Length of the station name when the last commit was signalled
+        pub committed_name_length: usize,
+        /// IP address when the last commit was signalled
+        pub committed_ip: Ipv4Address,
+        pub commit_permanent: bool,
+        pub led_on: bool,
+        pub led_changes: usize,
+    }
+
+    impl App for TestApp {
+        fn connect_ind_callback<T: App + Copy, U: TaskCallback + Copy>(
+            &mut self,
+            _: &mut PNet<T, U>,
+            _: usize,
+            _: EventResult,
+        ) {
+        }
+        fn release_ind_callback<T: App + Copy, U: TaskCallback + Copy>(
+            &mut self,
+            _: &mut PNet<T, U>,
+            _: usize,
+            _: EventResult,
+        ) {
+            self.releases += 1;
+        }
+        fn dcontrol_ind_callback<T: App + Copy, U: TaskCallback + Copy>(
+            &mut self,
+            _: &mut PNet<T, U>,
+            _: usize,
+            _: ControlCommand,
+            _: EventResult,
+        ) {
+        }
+        fn sm_released_ind_callback<T: App + Copy, U: TaskCallback + Copy>(
+            &mut self,
+            _: &mut PNet<T, U>,
+            _: usize,
+            _: usize,
+            _: usize,
+            _: usize,
+            _: EventResult,
+        ) {
+        }
+        fn ccontrol_cnf_callback<T: App + Copy, U: TaskCallback + Copy>(
+            &mut self,
+            _: &mut PNet<T, U>,
+            _: usize,
+            _: EventResult,
+        ) {
+        }
+        fn state_ind_callback<T: App + Copy, U: TaskCallback + Copy>(
+            &mut self,
+            _: &mut PNet<T, U>,
+            _: usize,
+            _: EventValues,
+        ) {
+        }
+        fn read_ind_callback<T: App + Copy, U: TaskCallback + Copy>(
+            &mut self,
+            _: &mut PNet<T, U>,
+            _: usize,
+            _: usize,
+            _: usize,
+            _: usize,
+            idx: usize,
+            _: usize,
+            read_data: &mut [u8],
+            result: &mut EventResult,
+        ) -> usize {
+            if idx != TEST_APP_RECORD_INDEX as usize {
+                result.pnio_status = PnioStatus::invalid_index(ERROR_CODE_READ);
+                return 0;
+            }
+
+            read_data[..TEST_APP_RECORD.len()].copy_from_slice(&TEST_APP_RECORD);
+            TEST_APP_RECORD.len()
+        }
+        fn write_ind_callback<T: App + Copy, U: TaskCallback + Copy>(
+            &mut self,
+            _: &mut PNet<T, U>,
+            _: usize,
+            _: usize,
+            _: usize,
+            _: usize,
+            idx: usize,
+            _: usize,
+            write_data: &[u8],
+            result: &mut EventResult,
+        ) {
+            if idx != TEST_APP_RECORD_INDEX as usize {
+                result.pnio_status = PnioStatus::invalid_index(ERROR_CODE_WRITE);
+                return;
+            }
+
+            self.records_written += 1;
+            self.last_record_length = write_data.len();
+        }
+        fn expect_module_ind_callback<T: App + Copy, U: TaskCallback + Copy>(
+            &mut self,
+            _: &mut PNet<T, U>,
+            _: usize,
+            _: usize,
+            _: usize,
+        ) {
+            self.modules_expected += 1;
+        }
+        fn new_data_status_ind_callback<T: App + Copy, U: TaskCallback + Copy>(
+            &mut self,
+            _: &mut PNet<T, U>,
+            _: usize,
+            _: usize,
+            _: usize,
+            _: usize,
+        ) {
+        }
+        fn alarm_ind_callback<T: App + Copy, U: TaskCallback + Copy>(
+            &mut self,
+            _: &mut PNet<T, U>,
+            _: usize,
+            _: AlarmArgument,
+            _: usize,
+            _: usize,
+            _: usize,
+        ) {
+        }
+        fn alarm_cnf_callback<T: App + Copy, U: TaskCallback + Copy>(
+            &mut self,
+            _: &mut PNet<T, U>,
+            _: usize,
+            _: PnioStatus,
+        ) {
+        }
+        fn alarm_ack_cnf_callback<T: App + Copy, U: TaskCallback + Copy>(
+            &mut self,
+            _: &mut PNet<T, U>,
+            _: usize,
+            _: usize,
+        ) {
+        }
+        fn reset_ind_callback<T: App + Copy, U: TaskCallback + Copy>(
+            &mut self,
+            _: &mut PNet<T, U>,
+            _: bool,
+            _: usize,
+        ) {
+        }
+        fn signal_led_ind<T: App + Copy, U: TaskCallback + Copy>(
+            &mut self,
+            _: &mut PNet<T, U>,
+            led_state: bool,
+        ) {
+            self.led_on = led_state;
+            self.led_changes += 1;
+        }
+        fn ip_changed_ind<T: App + Copy, U: TaskCallback + Copy>(
+            &mut self,
+            _: &mut PNet<T, U>,
+            _: IpConfig,
+            _: IpConfig,
+        ) {
+            self.ip_changes += 1;
+        }
+        fn dcp_response_ind<T: App + Copy, U: TaskCallback + Copy>(
+            &mut self,
+            _: &mut PNet<T, U>,
+            _: &DiscoveredDevice,
+        ) {
+            self.devices_discovered += 1;
+        }
+        fn dcp_commit_ind<T: App + Copy, U: TaskCallback + Copy>(
+            &mut self,
+            pnet: &mut PNet<T, U>,
+            permanent: bool,
+        ) {
+            self.commits += 1;
+            self.committed_name_length = pnet.fspm_user_config.station_name_len();
+            self.committed_ip = pnet.fspm_user_config.interface_config.ip_config.ip_address;
+            self.commit_permanent = permanent;
+        }
+    }
+
+    /// A connect request for an input and an output CR, expecting submodules in slot 0 and 1
+    pub fn test_connect_request() -> ConnectRequest {
+        let mut expected_submodules = [None; MAX_EXPECTED_SUBMODULES];
+        expected_submodules[0] = Some(ExpectedSubmodule {
+            api: 0,
+            slot_number: 0,
+            module_ident: 0x01,
+            subslot_number: 1,
+            submodule_ident: 0x01,
+        });
+        expected_submodules[1] = Some(ExpectedSubmodule {
+            api: 0,
+            slot_number: 1,
+            module_ident: 0x30,
+            subslot_number: 1,
+            submodule_ident: 0x01,
+        });
+
+        ConnectRequest {
+            args_maximum: 1024,
+            ar: ArRequest {
+                ar_type: 1,
+                ar_uuid: [0xaa; 16],
+                session_key: 0x1234,
+                cm_initiator_mac: EthernetAddress([0x52, 0x54, 0x00, 0x8a, 0x3b, 0xa5]),
+                cm_initiator_object_uuid: [0; 16],
+                ar_properties: 0,
+                cm_initiator_activity_timeout_factor: 600,
+                cm_initiator_udp_rt_port: 0x8892,
+            },
+            iocrs: [
+                Some(IocrRequest {
+                    iocr_type: IocrType::Input,
+                    iocr_reference: 1,
+                    frame_id: 0xc001,
+                    data_length: 40,
+                    send_clock_factor: 32,
+                    reduction_ratio: 32,
+                    phase: 1,
+                    watchdog_factor: 3,
+                    data_hold_factor: 3,
+                }),
+                Some(IocrRequest {
+                    iocr_type: IocrType::Output,
+                    iocr_reference: 2,
+                    frame_id: 0xc002,
+                    data_length: 40,
+                    send_clock_factor: 32,
+                    reduction_ratio: 32,
+                    phase: 1,
+                    watchdog_factor: 3,
+                    data_hold_factor: 3,
+                }),
+            ],
+            alarm_cr: Some(AlarmCrRequest {
+                alarm_cr_type: 1,
+                rta_timeout_factor: 1,
+                rta_retries: 3,
+                local_alarm_reference: 3,
+                max_alarm_data_length: 200,
+            }),
+            expected_submodules,
+        }
+    }
+
+    pub fn test_config() -> Config<TestApp> {
+        Config {
+            tick_us: 1000,
+            app: TestApp::default(),
+            im0: IM0 {
+                vendor_id_hi: 0x13,
+                vendor_id_lo: 0x37,
+                order_id: [0; 20],
+                serial_number: [0; 16],
+                hw_rev: 1,
+                sw_rev_prefx: 'V',
+                sw_rev_functional_enhancment: 0,
+                sw_rev_bug_fix: 1,
+                sw_rev_internal_change: 0,
+                revision_counter: 0,
+                profile_id: 0,
+                profile_specific_type: 0,
+                version_major: 1,
+                version_minor: 1,
+                supported: 0,
+            },
+            im1: IM1 {
+                tag_function: [0; 32],
+                tag_location: [0; 22],
+            },
+            im2: IM2 { date: [0; 16] },
+            im3: IM3 {
+                descriptor: [0; 54],
+            },
+            im4: IM4 { signature: [0; 54] },
+            device_id: DeviceIdConfig {
+                vendor_id_hi: 0x13,
+                vendor_id_lo: 0x37,
+                device_id_hi: 0x69,
+                device_id_lo: 0x69,
+            },
+            oem_device_id: DeviceIdConfig {
+                vendor_id_hi: 0,
+                vendor_id_lo: 0,
+                device_id_hi: 0,
+                device_id_lo: 0,
+            },
+            station_name: [0; MAX_STATION_NAME_SIZE],
+            product_name: [0; 25],
+            min_data_exchange_interval: 32,
+            send_clock_factor: DEFAULT_SEND_CLOCK_FACTOR,
+            reduction_ratio: 1,
+            phase: 0,
+            send_dcp_hello: false,
+            dcp_hello_count: DEFAULT_DCP_HELLO_COUNT,
+            dcp_hello_interval_us: DEFAULT_DCP_HELLO_INTERVAL_US,
+            max_send_latency_us: DEFAULT_MAX_SEND_LATENCY_US,
+            num_physical_ports: 1,
+            use_qualified_diagnosis: false,
+            interface_config: InterfaceConfig {
+                network_interface_name: "eth0",
+                ip_config: IpConfig {
+                    mac_address: EthernetAddress([0x00, 0x00, 0x23, 0x53, 0x4e, 0xfe]),
+                    ip_address: Ipv4Address::UNSPECIFIED,
+                    subnet_mask: Ipv4Address::UNSPECIFIED,
+                    gateway: Ipv4Address::UNSPECIFIED,
+                    dns: None,
+                    enable_dhcp: false,
+                },
+                port_config: [PortConfig {
+                    netif_name: "eth0",
+                    default_mau_type: 0x10,
+                }],
+            },
+            submodules: SlotTable::new(),
+        }
+    }
+}
+
+/// Link-time stubs for running the tests on the host, where there is no defmt
+/// transport and no Cortex-M core
+#[cfg(test)]
+mod host {
+    #[cfg(feature = "defmt")]
+    #[defmt::global_logger]
+    struct Logger;
+
+    #[cfg(feature = "defmt")]
+    unsafe impl defmt::Logger for Logger {
+        fn acquire() {}
+        unsafe fn flush() {}
+        unsafe fn release() {}
+        unsafe fn write(_bytes: &[u8]) {}
+    }
+
+    #[cfg(feature = "defmt")]
+    #[defmt::panic_handler]
+    fn panic() -> ! {
+        core::panic!("defmt panic")
+    }
+
+    #[cfg(feature = "defmt")]
+    defmt::timestamp!("{=u8}", 0);
+
+    #[no_mangle]
+    extern "C" fn __delay(_cycles: u32) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_writer() {
+        let mut buffer = [0; 10];
+        let mut writer = ByteWriter::new(&mut buffer);
+
+        writer
+            .put_u8(0x01)
+            .put_u16_be(0x0203)
+            .put_u32_be(0x04050607)
+            .put_slice(&[0x08, 0x09]);
+
+        assert_eq!(writer.written(), 9);
+        assert_eq!(buffer, [1, 2, 3, 4, 5, 6, 7, 8, 9, 0]);
+    }
+
+    #[test]
+    fn test_byte_writer_out_of_bounds() {
+        let mut buffer = [0; 3];
+        let mut writer = ByteWriter::new(&mut buffer);
+
+        writer.put_u16_be(0x0102);
+        assert!(matches!(
+            writer.try_put_u16_be(0x0304),
+            Err(WriteError::OutOfBounds)
+        ));
+        assert_eq!(writer.written(), 2);
+
+        writer.try_put_u8(0x03).unwrap();
+        assert_eq!(buffer, [1, 2, 3]);
+    }
+}